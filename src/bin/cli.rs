@@ -3,10 +3,13 @@
 // AgarthaSoftware - 2024
 
 use clap::Parser;
-use std::{env, path::PathBuf};
+use std::{env, path::PathBuf, process::ExitCode};
 use wormhole::{
-    commands::{self, cli_commands::Cli},
-    error::CliResult,
+    commands::{
+        self,
+        cli_commands::{Cli, CliArgs},
+    },
+    error::CliResponse,
 };
 
 fn get_config_path() -> PathBuf {
@@ -35,7 +38,7 @@ fn get_args(args: Vec<String>) -> (String, Vec<String>) {
     return (ip, cli_args);
 }
 
-fn main() -> CliResult<()> {
+fn main() -> ExitCode {
     env_logger::init();
 
     // Recover all arguments
@@ -45,44 +48,49 @@ fn main() -> CliResult<()> {
     log::trace!("Starting cli on {}", ip);
     log::trace!("cli args: {:?}", cli_args);
 
-    let status = match Cli::parse_from(cli_args) {
+    let CliArgs { format, command } = CliArgs::parse_from(cli_args);
+    let status = match command {
         Cli::Start(args) => commands::cli::start(ip, args),
         Cli::Stop(args) => commands::cli::stop(ip, args),
         Cli::Template(args) => {
-            println!("creating network {:?}", args.name.clone());
-            commands::cli::templates(&args.path, &args.name)
+            log::info!("creating network {:?}", args.name.clone());
+            commands::cli::templates(&args.path, &args.name, args.secret)
         }
         Cli::New(args) => {
-            println!("creating pod");
+            log::info!("creating pod");
             commands::cli::new(ip, args)
         }
         Cli::Remove(args) => {
-            println!("removing pod");
+            log::info!("removing pod");
             commands::cli::remove(ip, args)
         }
-        Cli::Inspect => {
-            log::warn!("inspecting pod");
-            todo!("inspect");
-        }
+        Cli::Inspect(args) => commands::cli::inspect(ip, args),
         Cli::GetHosts(args) => commands::cli::get_hosts(ip, args),
         Cli::Tree(args) => commands::cli::tree(ip, args),
+        Cli::Pin(args) => commands::cli::pin(ip, args),
+        Cli::Unpin(args) => commands::cli::unpin(ip, args),
+        Cli::Invite(args) => commands::cli::invite(ip, args),
         Cli::Apply(args) => {
             log::warn!("reloading pod");
             commands::cli::apply(ip, args)
         }
         Cli::Restore(args) => {
-            println!("retore a specific file config");
+            log::info!("retore a specific file config");
             commands::cli::restore(ip, args)
         }
-        Cli::Interrupt => {
-            log::warn!("interrupt command");
-            todo!("interrupt");
-        }
-    };
-    if let Err(e) = &status {
-        log::error!("CLI: error reported: {e}");
-    } else {
-        log::info!("CLI: no error reported")
+        Cli::Interrupt => commands::cli::interrupt(ip),
     };
-    status
+    // every outcome goes through the same printer, so `--format json` always gets JSON
+    let response = status.unwrap_or_else(CliResponse::from);
+    commands::cli::print_response(&response, format);
+    match response {
+        CliResponse::Success { .. } => {
+            log::info!("CLI: no error reported");
+            ExitCode::SUCCESS
+        }
+        CliResponse::Error { code, message } => {
+            log::error!("CLI: error reported: {code}: {message}");
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -29,17 +29,21 @@ use tokio_tungstenite::{accept_async, WebSocketStream};
 #[cfg(target_os = "windows")]
 use winfsp::winfsp_init;
 use wormhole::commands::{self, cli_commands::Cli};
+use wormhole::config::registry::{PodRegistry, RegisteredPod};
 use wormhole::config::types::Config;
 use wormhole::config::LocalConfig;
-use wormhole::error::{CliError, CliSuccess, WhError, WhResult};
+use wormhole::data::metrics::serve_metrics;
+use wormhole::error::{CliData, CliError, CliResponse, CliSuccess, WhError, WhResult};
 use wormhole::network::ip::IpP;
 use wormhole::pods::pod::Pod;
+use wormhole::pods::whpath::WhPath;
 
 type CliTcpWriter =
     SplitSink<WebSocketStream<tokio::net::TcpStream>, tokio_tungstenite::tungstenite::Message>;
 
 async fn handle_cli_command(
     pods: &mut HashMap<String, Pod>,
+    registry: &mut PodRegistry,
     command: Cli,
     mut writer: CliTcpWriter,
 ) {
@@ -47,41 +51,90 @@ async fn handle_cli_command(
         Cli::New(pod_args) => match commands::service::new(pod_args).await {
             Ok(pod) => {
                 let name = pod.get_name().to_string();
+                register_pod(registry, &name, &pod);
                 pods.insert(name.clone(), pod);
                 Ok(CliSuccess::WithData {
                     message: String::from("Pod created with success"),
-                    data: name,
+                    data: CliData::Pod(name),
                 })
             }
             Err(e) => Err(e),
         },
-        Cli::Start(pod_args) => commands::service::start(pod_args).await,
+        Cli::Start(pod_args) => {
+            let mount_point = if pod_args.name == "." {
+                Some(pod_args.path.clone())
+            } else {
+                registry
+                    .get(&pod_args.name)
+                    .map(|pod| WhPath::from(&pod.path))
+            };
+            match mount_point {
+                Some(mount_point)
+                    if pods.contains_key(&pod_args.name)
+                        || pods
+                            .values()
+                            .any(|pod| pod.get_mount_point() == &mount_point) =>
+                {
+                    Err(CliError::Message {
+                        reason: format!("A pod is already running at {mount_point}"),
+                    })
+                }
+                Some(mount_point) => match commands::service::start(mount_point).await {
+                    Ok(pod) => {
+                        let name = pod.get_name().to_string();
+                        register_pod(registry, &name, &pod);
+                        pods.insert(name.clone(), pod);
+                        Ok(CliSuccess::WithData {
+                            message: String::from("Pod started with success"),
+                            data: CliData::Pod(name),
+                        })
+                    }
+                    Err(e) => Err(e),
+                },
+                None => Err(CliError::PodNotFound),
+            }
+        }
         Cli::Stop(pod_args) => {
-            if let Some(pod) = pods.remove(&pod_args.name) {
+            let name = if pod_args.name == "." {
+                pods.iter()
+                    .find(|(_, pod)| pod.get_mount_point() == &pod_args.path)
+                    .map(|(name, _)| name.clone())
+            } else {
+                Some(pod_args.name.clone())
+            };
+            if let Some((name, pod)) = name.and_then(|name| pods.remove_entry(&name)) {
+                // a pod stopped by hand stays stopped after a reboot
+                registry.set_autostart(&name, false);
+                save_registry(registry);
                 commands::service::stop(pod).await
             } else {
-                log::warn!("(TODO) Stopping a pod by path is not yet implemented");
-                Err(CliError::PodRemovalFailed {
-                    name: pod_args.name,
-                })
+                Err(CliError::PodNotFound)
             }
         }
         Cli::Remove(remove_arg) => {
             let opt = if remove_arg.name != "." {
-                pods.remove(&remove_arg.name)
+                pods.remove_entry(&remove_arg.name)
             } else if remove_arg.path.inner != "." {
                 let key_to_remove = pods
                     .iter()
                     .find(|(_, pod)| pod.get_mount_point() == &remove_arg.path)
                     .map(|(key, _)| key.clone());
 
-                key_to_remove.and_then(|key| pods.remove(&key))
+                key_to_remove.and_then(|key| pods.remove_entry(&key))
             } else {
                 log::error!("No pod name nor path were provided by RemovePod command");
                 None
             };
-            if let Some(pod) = opt {
-                commands::service::remove(remove_arg, pod).await
+            if let Some((name, pod)) = opt {
+                let removed = commands::service::remove(remove_arg, pod).await;
+                if removed.is_ok() {
+                    registry.unregister(&name);
+                } else {
+                    // the pod is stopped but its folder may still be started again
+                    registry.set_autostart(&name, false);
+                }
+                save_registry(registry);
+                removed
             } else {
                 Err(CliError::PodRemovalFailed {
                     name: remove_arg.name,
@@ -161,6 +214,8 @@ async fn handle_cli_command(
             match res {
                 Ok(Some((new_name, old_name))) => {
                     if let Some(pod) = pods.remove(&old_name) {
+                        registry.rename(&old_name, &new_name);
+                        save_registry(registry);
                         pods.insert(new_name, pod);
                         Ok(CliSuccess::Message("tt".to_owned()))
                     } else {
@@ -175,12 +230,26 @@ async fn handle_cli_command(
                 Err(err) => Err(err),
             }
         }
+        Cli::Inspect(args) => {
+            let opt_pod = if args.name == "." {
+                pods.values()
+                    .find(|pod| pod.get_mount_point() == &args.path)
+            } else {
+                pods.get(&args.name)
+            };
+            if let Some(pod) = opt_pod {
+                commands::service::inspect(pod)
+            } else {
+                Err(CliError::PodNotFound)
+            }
+        }
+        Cli::Interrupt => commands::service::interrupt(pods).await,
         Cli::GetHosts(args) => {
             if let Some(pod) = pods.get(&args.name) {
                 match pod.get_file_hosts(args.path) {
                     Ok(hosts) => Ok(CliSuccess::WithData {
                         message: "Hosts:".to_owned(),
-                        data: format!("{:?}", hosts),
+                        data: CliData::Hosts(hosts),
                     }),
                     Err(error) => Err(CliError::PodInfoError { source: error }),
                 }
@@ -193,7 +262,7 @@ async fn handle_cli_command(
                 match pod.get_file_tree_and_hosts(args.path) {
                     Ok(tree) => Ok(CliSuccess::WithData {
                         message: "File tree and hosts per file:".to_owned(),
-                        data: tree.to_string(),
+                        data: CliData::Tree(tree),
                     }),
                     Err(error) => Err(CliError::PodInfoError { source: error }),
                 }
@@ -201,15 +270,61 @@ async fn handle_cli_command(
                 Err(CliError::PodNotFound)
             }
         }
+        Cli::Pin(args) => match pods.get(&args.name) {
+            Some(pod) => commands::service::pin(pod, args.path).await,
+            None => Err(CliError::PodNotFound),
+        },
+        Cli::Unpin(args) => match pods.get(&args.name) {
+            Some(pod) => commands::service::unpin(pod, args.path),
+            None => Err(CliError::PodNotFound),
+        },
+        Cli::Invite(args) => match pods.get(&args.name) {
+            Some(pod) => commands::service::invite(pod, args),
+            None => Err(CliError::PodNotFound),
+        },
         _ => Err(CliError::InvalidCommand),
     };
-    let string_output = response_command.map_or_else(|e| e.to_string(), |a| a.to_string());
-    match writer.send(Message::Text(string_output)).await {
+    let output = match serde_json::to_string(&CliResponse::from(response_command)) {
+        Ok(output) => output,
+        Err(e) => {
+            log::error!("Can't serialize the answer to cli: {e}");
+            return;
+        }
+    };
+    match writer.send(Message::Text(output)).await {
         Ok(()) => log::debug!("Sent answer to cli"),
         Err(err) => log::error!("Message can't send to cli: {}", err),
     }
 }
 
+/// Records a running pod in the registry, to start it again when the service boots
+fn register_pod(registry: &mut PodRegistry, name: &str, pod: &Pod) {
+    match RegisteredPod::from_pod(name, pod, true) {
+        Ok(registered) => registry.register(registered),
+        Err(e) => log::error!("Can't register the pod {name}: {e}"),
+    }
+    save_registry(registry);
+}
+
+fn save_registry(registry: &PodRegistry) {
+    if let Err(e) = registry.save() {
+        log::error!("Can't save the pod registry: {e}");
+    }
+}
+
+/// Brings back the pods running when the service last stopped
+async fn autostart_pods(pods: &mut HashMap<String, Pod>, registry: &PodRegistry) {
+    for registered in registry.pods.iter().filter(|pod| pod.autostart) {
+        match commands::service::start(WhPath::from(&registered.path)).await {
+            Ok(pod) => {
+                log::info!("Started pod {} at {}", registered.name, registered.path);
+                pods.insert(registered.name.clone(), pod);
+            }
+            Err(e) => log::error!("Can't start the pod {}: {e}", registered.name),
+        }
+    }
+}
+
 async fn get_cli_command(stream: tokio::net::TcpStream) -> WhResult<(Cli, CliTcpWriter)> {
     // Accept the TCP stream as a WebSocket stream
     let ws_stream = match accept_async(stream).await {
@@ -257,6 +372,7 @@ async fn get_cli_command(stream: tokio::net::TcpStream) -> WhResult<(Cli, CliTcp
 /// Listens for CLI calls and launch one tcp instance per cli command
 async fn start_cli_listener(
     pods: &mut HashMap<String, Pod>,
+    registry: &mut PodRegistry,
     mut ip: IpP,
     mut interrupt_rx: UnboundedReceiver<()>,
 ) {
@@ -286,11 +402,17 @@ async fn start_cli_listener(
                 continue;
             }
         };
-        handle_cli_command(pods, command, writer).await;
+        let interrupt = matches!(command, Cli::Interrupt);
+        handle_cli_command(pods, registry, command, writer).await;
+        if interrupt {
+            break;
+        }
     }
 }
 
 const DEFAULT_ADDRESS: &str = "127.0.0.1:8081";
+/// Address of the optional Prometheus endpoint
+const METRICS_ADDRESS_VAR: &str = "WORMHOLE_METRICS_ADDRESS";
 
 #[tokio::main]
 async fn main() {
@@ -298,7 +420,10 @@ async fn main() {
     let mut pods: HashMap<String, Pod> = HashMap::new();
 
     if env::args().any(|arg| arg == "-h" || arg == "--help") {
-        println!("Usage: wormholed <IP>\n\nIP is the node address, default at {DEFAULT_ADDRESS}");
+        println!(
+            "Usage: wormholed <IP>\n\nIP is the node address, default at {DEFAULT_ADDRESS}\n\
+            Set {METRICS_ADDRESS_VAR} to an address to serve the metrics there"
+        );
         return;
     }
 
@@ -322,24 +447,55 @@ async fn main() {
             return;
         }
     };
+    let signal_handle = tokio::spawn(signal_watchdog(interrupt_tx.clone()));
     let terminal_handle = tokio::spawn(terminal_watchdog(interrupt_tx));
-    let cli_airport = start_cli_listener(&mut pods, ip, interrupt_rx);
+    let metrics_handle = env::var(METRICS_ADDRESS_VAR)
+        .ok()
+        .map(|address| tokio::spawn(serve_metrics(address)));
+    let mut registry = PodRegistry::load(&PodRegistry::default_dir());
+    autostart_pods(&mut pods, &registry).await;
+    let cli_airport = start_cli_listener(&mut pods, &mut registry, ip, interrupt_rx);
     log::trace!("Starting service on {}", ip_string);
     log::info!("Started");
 
     cli_airport.await;
     terminal_handle.abort();
+    signal_handle.abort();
+    if let Some(handle) = metrics_handle {
+        handle.abort();
+    }
 
     log::info!("Stopping");
-    for (name, pod) in pods.into_iter() {
-        match pod.stop().await {
-            Ok(()) => log::info!("Stopped pod {name}"),
-            Err(e) => log::error!("Pod {name} can't be stopped: {e}"),
-        }
+    if let Err(e) = commands::service::interrupt(&mut pods).await {
+        log::error!("{e}");
     }
     log::info!("Stopped");
 }
 
+/// Asks the service to stop on SIGINT or SIGTERM
+async fn signal_watchdog(tx: UnboundedSender<()>) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => (),
+                _ = terminate.recv() => (),
+            },
+            Err(e) => {
+                log::error!("Can't listen to SIGTERM: {e}");
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+
+    log::info!("Signal received, stopping");
+    let _ = tx.send(());
+}
+
 // NOTE - old watchdog brought here for debug purposes
 pub async fn terminal_watchdog(tx: UnboundedSender<()>) {
     let mut stdin = tokio::io::stdin();
@@ -4,7 +4,7 @@ use tokio::runtime::Runtime;
 
 use crate::{
     commands::cli_commands::{Cli, PodConf},
-    error::{CliError, CliResult},
+    error::{CliError, CliResponse, CliResult},
     pods::{
         arbo::{GLOBAL_CONFIG_FNAME, LOCAL_CONFIG_FNAME},
         whpath::WhPath,
@@ -13,7 +13,7 @@ use crate::{
 
 use super::cli_messager;
 
-pub fn apply(ip: &str, mut args: PodConf) -> CliResult<()> {
+pub fn apply(ip: &str, mut args: PodConf) -> CliResult<CliResponse> {
     let files_name = vec![LOCAL_CONFIG_FNAME, GLOBAL_CONFIG_FNAME];
 
     for file in args.files.clone() {
@@ -38,6 +38,5 @@ pub fn apply(ip: &str, mut args: PodConf) -> CliResult<()> {
             path: args.path,
             files: args.files,
         }),
-    ))?;
-    Ok(())
+    ))
 }
@@ -6,12 +6,12 @@ use tokio::runtime::Runtime;
 
 use crate::{
     commands::cli_commands::{Cli, GetHostsArgs},
-    error::CliResult,
+    error::{CliResponse, CliResult},
 };
 
 use super::cli_messager;
 
-pub fn get_hosts(ip: &str, args: GetHostsArgs) -> CliResult<()> {
+pub fn get_hosts(ip: &str, args: GetHostsArgs) -> CliResult<CliResponse> {
     let rt = Runtime::new().unwrap();
     rt.block_on(cli_messager(ip, Cli::GetHosts(args)))
 }
@@ -0,0 +1,26 @@
+use std::env;
+
+use tokio::runtime::Runtime;
+
+use crate::{
+    commands::cli_commands::{Cli, InspectArgs},
+    error::{CliResponse, CliResult},
+    pods::whpath::WhPath,
+};
+
+use super::cli_messager;
+
+pub fn inspect(ip: &str, mut args: InspectArgs) -> CliResult<CliResponse> {
+    if args.name == "." {
+        let p = env::current_dir()?;
+        let path = WhPath::from(&p.display().to_string());
+        args.path = if args.path.inner != "." {
+            path.join(&args.path)
+        } else {
+            path
+        }
+    }
+
+    let rt = Runtime::new().unwrap();
+    rt.block_on(cli_messager(ip, Cli::Inspect(args)))
+}
@@ -0,0 +1,13 @@
+use tokio::runtime::Runtime;
+
+use crate::{
+    commands::cli_commands::Cli,
+    error::{CliResponse, CliResult},
+};
+
+use super::cli_messager;
+
+pub fn interrupt(ip: &str) -> CliResult<CliResponse> {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(cli_messager(ip, Cli::Interrupt))
+}
@@ -2,9 +2,14 @@ use futures_util::sink::SinkExt;
 use futures_util::TryStreamExt;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 
-use crate::{commands::cli_commands::Cli, error::CliResult};
+use crate::{
+    commands::cli_commands::{Cli, Format},
+    error::{CliError, CliResponse, CliResult},
+};
 
-pub async fn cli_messager(ip: &str, cli: Cli) -> CliResult<()> {
+/// Sends a command to the service and returns its answer
+pub async fn cli_messager(ip: &str, cli: Cli) -> CliResult<CliResponse> {
+    let mut response = String::new();
     let (mut ws_stream, _) = connect_async(format!("ws://{}", ip)).await?;
     log::info!("Service connected at ws://{ip}");
 
@@ -13,13 +18,27 @@ pub async fn cli_messager(ip: &str, cli: Cli) -> CliResult<()> {
 
     while let Ok(Some(msg)) = ws_stream.try_next().await {
         if msg.is_text() {
-            let response = msg.to_text()?;
-            log::info!("Service anwser: {response}");
+            response = msg.to_text()?.to_owned();
             break;
         }
     }
 
     ws_stream.close(None).await?;
     log::info!("Connection closed");
-    Ok(())
+
+    serde_json::from_str(&response).map_err(|e| CliError::Message {
+        reason: format!("Unreadable answer from the service: {e}"),
+    })
+}
+
+/// Prints the outcome of a command in the asked format, errors of the CLI itself included
+pub fn print_response(response: &CliResponse, format: Format) {
+    match (format, response) {
+        (Format::Text, CliResponse::Success { .. }) => println!("{response}"),
+        (Format::Text, CliResponse::Error { .. }) => eprintln!("{response}"),
+        (Format::Json, _) => match serde_json::to_string_pretty(response) {
+            Ok(json) => println!("{json}"),
+            Err(e) => eprintln!("Can't format the answer as JSON: {e}"),
+        },
+    }
 }
@@ -1,7 +1,11 @@
 mod apply;
 mod get_hosts;
+mod inspect;
+mod interrupt;
+mod invite;
 mod message;
 mod new;
+mod pin;
 mod register;
 mod remove;
 mod restore;
@@ -12,8 +16,12 @@ mod tree;
 
 pub use apply::apply;
 pub use get_hosts::get_hosts;
-pub use message::cli_messager;
+pub use inspect::inspect;
+pub use interrupt::interrupt;
+pub use invite::invite;
+pub use message::{cli_messager, print_response};
 pub use new::new;
+pub use pin::{pin, unpin};
 pub use register::register;
 pub use remove::remove;
 pub use restore::restore;
@@ -13,7 +13,7 @@ use crate::{
         default_local_config,
     },
     config::{types::Config, LocalConfig},
-    error::{CliError, CliResult},
+    error::{CliError, CliResponse, CliResult},
     pods::{
         arbo::{GLOBAL_CONFIG_FNAME, LOCAL_CONFIG_FNAME},
         whpath::WhPath,
@@ -54,7 +54,7 @@ fn is_new_wh_file_config(path: &WhPath) -> CliResult<()> {
 }
 
 //FIXME - Error id name of the pod not check (can be already exist)
-pub fn new(ip: &str, mut args: PodArgs) -> CliResult<()> {
+pub fn new(ip: &str, mut args: PodArgs) -> CliResult<CliResponse> {
     if args.path.inner == "." {
         args.path = WhPath::from(&env::current_dir()?.display().to_string());
     }
@@ -69,7 +69,7 @@ pub fn new(ip: &str, mut args: PodArgs) -> CliResult<()> {
             url: args.url,
             ip: args.ip,
             additional_hosts: args.additional_hosts,
+            token: args.token,
         }),
-    ))?;
-    Ok(())
+    ))
 }
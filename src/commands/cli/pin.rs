@@ -0,0 +1,18 @@
+use tokio::runtime::Runtime;
+
+use crate::{
+    commands::cli_commands::{Cli, PinArgs},
+    error::{CliResponse, CliResult},
+};
+
+use super::cli_messager;
+
+pub fn pin(ip: &str, args: PinArgs) -> CliResult<CliResponse> {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(cli_messager(ip, Cli::Pin(args)))
+}
+
+pub fn unpin(ip: &str, args: PinArgs) -> CliResult<CliResponse> {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(cli_messager(ip, Cli::Unpin(args)))
+}
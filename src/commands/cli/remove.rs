@@ -5,13 +5,13 @@
 use tokio::runtime::Runtime;
 
 use crate::commands::cli_commands::{Cli, RemoveArgs};
-use crate::error::CliResult;
+use crate::error::{CliResponse, CliResult};
 use crate::pods::whpath::WhPath;
 use std::env;
 
 use super::cli_messager;
 
-pub fn remove(ip: &str, mut args: RemoveArgs) -> CliResult<()> {
+pub fn remove(ip: &str, mut args: RemoveArgs) -> CliResult<CliResponse> {
     if args.name == "." {
         let p = env::current_dir()?;
         let path = WhPath::from(&p.display().to_string());
@@ -7,13 +7,13 @@ use crate::{
         Cli::{self},
         StatusPodArgs,
     },
-    error::CliResult,
+    error::{CliResponse, CliResult},
     pods::whpath::WhPath,
 };
 
 use super::cli_messager;
 
-pub fn start(ip: &str, mut start_args: StatusPodArgs) -> CliResult<()> {
+pub fn start(ip: &str, mut start_args: StatusPodArgs) -> CliResult<CliResponse> {
     if start_args.name == "." {
         let p = env::current_dir()?;
         let path = WhPath::from(&p.display().to_string());
@@ -5,17 +5,30 @@ use std::fs;
 
 use crate::commands::{default_global_config, default_local_config};
 use crate::config::types::Config;
-use crate::error::CliResult;
+use crate::error::{CliError, CliResponse, CliResult, CliSuccess};
+use crate::network::secure::generate_network_key;
 use crate::pods::arbo::{GLOBAL_CONFIG_FNAME, LOCAL_CONFIG_FNAME};
 use crate::pods::whpath::WhPath;
 
-#[must_use]
-pub fn templates(path: &WhPath, name: &str) -> CliResult<()> {
-    let global_config = default_global_config();
+pub fn templates(path: &WhPath, name: &str, secret: bool) -> CliResult<CliResponse> {
+    let mut global_config = default_global_config();
     let local_config = default_local_config(name);
     path.clone().set_absolute();
     fs::read_dir(path.inner.clone()).map(|_| ())?;
+
+    if secret {
+        let key = generate_network_key().map_err(|e| CliError::Message {
+            reason: e.to_string(),
+        })?;
+        // the key never leaves the configuration, pods join with single-use invitations
+        global_config.security.network_key = Some(key);
+    }
     local_config.write(path.join(LOCAL_CONFIG_FNAME).inner)?;
     global_config.write(path.join(GLOBAL_CONFIG_FNAME).inner)?;
-    Ok(())
+    Ok(CliResponse::from(Ok(CliSuccess::Message(if secret {
+        "Template created with a network key, invite pods with `invite <pod name>` once the pod is up"
+            .to_owned()
+    } else {
+        "Template created".to_owned()
+    }))))
 }
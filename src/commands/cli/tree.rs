@@ -6,12 +6,12 @@ use tokio::runtime::Runtime;
 
 use crate::{
     commands::cli_commands::{Cli, TreeArgs},
-    error::CliResult,
+    error::{CliResponse, CliResult},
 };
 
 use super::cli_messager;
 
-pub fn tree(ip: &str, args: TreeArgs) -> CliResult<()> {
+pub fn tree(ip: &str, args: TreeArgs) -> CliResult<CliResponse> {
     let rt = Runtime::new().unwrap();
     rt.block_on(cli_messager(ip, Cli::Tree(args)))
 }
@@ -2,12 +2,30 @@ use crate::pods::{
     arbo::{GLOBAL_CONFIG_FNAME, LOCAL_CONFIG_FNAME},
     whpath::WhPath,
 };
-use clap::{Args, Parser, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Parser, Serialize, Deserialize)] // requires `derive` feature
+#[derive(Debug, Parser)] // requires `derive` feature
 #[command(name = "wormhole")]
 #[command(bin_name = "wormhole")]
+pub struct CliArgs {
+    /// How the answers of the service are printed
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+    #[command(subcommand)]
+    pub command: Cli,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+#[clap(rename_all = "lower")]
+pub enum Format {
+    /// Human readable text
+    Text,
+    /// The answer of the service as sent, for scripts
+    Json,
+}
+
+#[derive(Debug, Subcommand, Serialize, Deserialize)]
 pub enum Cli {
     /// Start the service
     Start(StatusPodArgs),
@@ -18,18 +36,24 @@ pub enum Cli {
     /// Create a new pod and join a network if he have peers in arguments or create a new network
     New(PodArgs),
     /// Inspect a pod with its configuration, connections, etc
-    Inspect,
+    Inspect(InspectArgs),
     /// Get hosts for a specific file
     GetHosts(GetHostsArgs),
     /// Tree the folder structure from the given path and show hosts for each file
     Tree(TreeArgs),
+    /// Always keep a copy of a file, or of every file under a directory, on the pod
+    Pin(PinArgs),
+    /// Let the copies of a pinned path be dropped when the disk is short of space
+    Unpin(PinArgs),
+    /// Issue a single-use invitation to the network of a pod, or manage the pending ones
+    Invite(InviteArgs),
     /// Remove a pod from its network
     Remove(RemoveArgs),
     /// Apply a new configuration to a pod
     Apply(PodConf),
     /// Restore many or a specifique file configuration  
     Restore(PodConf),
-    /// Stops the service
+    /// Stop every pod cleanly, then the service
     Interrupt,
 }
 
@@ -66,6 +90,39 @@ pub struct TreeArgs {
     pub path: WhPath,
 }
 
+#[derive(Debug, clap::Args, Serialize, Deserialize)]
+#[command(version, about, long_about = None)]
+pub struct PinArgs {
+    /// Name of the pod
+    pub name: String,
+    /// File or directory path from the root of the wh folder
+    pub path: WhPath,
+}
+
+#[derive(Debug, clap::Args, Serialize, Deserialize)]
+#[command(version, about, long_about = None)]
+pub struct InviteArgs {
+    /// Name of the pod
+    pub name: String,
+    /// List the ids of the pending invitations instead of issuing one
+    #[arg(long, conflicts_with = "revoke")]
+    pub list: bool,
+    /// Revoke the pending invitation with this id instead of issuing one
+    #[arg(long)]
+    pub revoke: Option<String>,
+}
+
+#[derive(Debug, clap::Args, Serialize, Deserialize)]
+#[command(version, about, long_about = None)]
+pub struct InspectArgs {
+    /// Name of the pod, if '.' the pod is found from its path
+    #[arg(long, short, default_value = ".")]
+    pub name: String,
+    /// Path of the pod, used only if the name is '.'
+    #[arg(long, short = 'C', default_value = ".")]
+    pub path: WhPath,
+}
+
 #[derive(Debug, clap::Args, Serialize, Deserialize)]
 #[command(version, about, long_about = None)]
 pub struct PodArgs {
@@ -83,6 +140,9 @@ pub struct PodArgs {
     /// Additional hosts to try to join from as a backup
     #[arg(long, short)]
     pub additional_hosts: Option<Vec<String>>,
+    /// Invitation token issued by the pod at the url with `invite`
+    #[arg(long, short)]
+    pub token: Option<String>,
 }
 
 #[derive(Debug, clap::Args, Serialize, Deserialize)]
@@ -105,6 +165,9 @@ pub struct TemplateArg {
     /// Change to DIRECTORY before doing anything
     #[arg(long, short = 'C', default_value = ".")]
     pub path: WhPath,
+    /// Generate a secret that pods will need to join the network
+    #[arg(long, short)]
+    pub secret: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ValueEnum)]
@@ -117,7 +180,8 @@ pub enum Mode {
     /// and clone all data from the network into the folder where the pod was
     /// making this folder into a real folder
     Clone,
-    /// Remove the pod from the network and delete any data that was stored in the pod
+    /// Remove the pod from the network and delete any data that was stored in the pod,
+    /// once the other pods took the files only it was hosting
     Clean,
     /// Remove this pod from the network without distributing its data to other nodes
     Take,
@@ -1,5 +1,7 @@
 use crate::config::{
-    types::{GeneralGlobalConfig, RedundancyConfig},
+    types::{
+        ConflictConfig, GeneralGlobalConfig, PlacementConfig, RedundancyConfig, SecurityConfig,
+    },
     GlobalConfig,
 };
 
@@ -10,6 +12,13 @@ pub fn default_global_config() -> GlobalConfig {
             ignore_paths: Vec::new(),
             pods_names: Vec::new(),
         },
-        redundancy: RedundancyConfig { number: 2 },
+        redundancy: RedundancyConfig {
+            number: 2,
+            policies: Vec::new(),
+            placement: PlacementConfig::default(),
+            erasure: None,
+        },
+        security: SecurityConfig::default(),
+        conflict: ConflictConfig::default(),
     };
 }
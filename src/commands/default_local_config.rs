@@ -1,10 +1,16 @@
-use crate::config::{types::GeneralLocalConfig, LocalConfig};
+use crate::config::{
+    types::{CacheLocalConfig, GeneralLocalConfig, StorageLocalConfig},
+    LocalConfig,
+};
 
 pub fn default_local_config(name: &str) -> LocalConfig {
     return LocalConfig {
         general: GeneralLocalConfig {
             name: name.to_string(),
             address: "0.0.0.0:8081".to_string(),
+            failure_domain: None,
         },
+        cache: CacheLocalConfig::default(),
+        storage: StorageLocalConfig::default(),
     };
 }
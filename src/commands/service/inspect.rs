@@ -0,0 +1,11 @@
+use crate::{
+    error::{CliData, CliResult, CliSuccess},
+    pods::pod::Pod,
+};
+
+pub fn inspect(pod: &Pod) -> CliResult<CliSuccess> {
+    Ok(CliSuccess::WithData {
+        message: "Pod report:".to_owned(),
+        data: CliData::Inspect(Box::new(pod.inspect()?)),
+    })
+}
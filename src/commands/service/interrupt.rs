@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+
+use crate::{
+    error::{CliError, CliResult, CliSuccess},
+    pods::pod::Pod,
+};
+
+/// Stops every pod, one after another, before the service quits
+pub async fn interrupt(pods: &mut HashMap<String, Pod>) -> CliResult<CliSuccess> {
+    let mut names: Vec<String> = pods.keys().cloned().collect();
+    names.sort();
+    let mut failed = Vec::new();
+
+    for name in names {
+        let Some(pod) = pods.remove(&name) else {
+            continue;
+        };
+        match pod.stop().await {
+            Ok(()) => log::info!("Stopped pod {name}"),
+            Err(e) => {
+                log::error!("Pod {name} can't be stopped: {e}");
+                failed.push(format!("{name}: {e}"));
+            }
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(CliSuccess::Message("Every pod was stopped.".to_owned()))
+    } else {
+        Err(CliError::Message {
+            reason: format!("Some pods weren't stopped cleanly:\n{}", failed.join("\n")),
+        })
+    }
+}
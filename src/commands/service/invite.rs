@@ -0,0 +1,32 @@
+use crate::{
+    commands::cli_commands::InviteArgs,
+    config::{types::Config, LocalConfig},
+    error::{CliResult, CliSuccess},
+    pods::pod::Pod,
+};
+
+pub fn invite(pod: &Pod, args: InviteArgs) -> CliResult<CliSuccess> {
+    if let Some(id) = args.revoke {
+        pod.revoke_invite(&id)?;
+        return Ok(CliSuccess::Message(format!("Invitation {id} revoked")));
+    }
+    if args.list {
+        let pending = pod.pending_invites()?;
+        return Ok(CliSuccess::Message(if pending.is_empty() {
+            "No pending invitation".to_owned()
+        } else {
+            format!("Pending invitations:\n{}", pending.join("\n"))
+        }));
+    }
+
+    let token = pod.invite()?;
+    let address = LocalConfig::read_lock(&pod.local_config, "service::invite")?
+        .general
+        .address
+        .clone();
+    Ok(CliSuccess::Message(format!(
+        "Invitation {} (single use, keep it secret): {token}\n\
+        A pod can join with `new <name> --url {address} --token {token}`",
+        token.id()
+    )))
+}
@@ -1,12 +1,20 @@
 mod apply;
+mod inspect;
+mod interrupt;
+mod invite;
 mod new;
+mod pin;
 mod remove;
 mod restore;
 mod start;
 mod stop;
 
 pub use apply::apply;
+pub use inspect::inspect;
+pub use interrupt::interrupt;
+pub use invite::invite;
 pub use new::new;
+pub use pin::{pin, unpin};
 pub use remove::remove;
 pub use restore::restore;
 pub use start::start;
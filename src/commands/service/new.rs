@@ -2,14 +2,18 @@ use crate::{
     commands::{cli_commands::PodArgs, default_global_config, default_local_config},
     config::{types::Config, GlobalConfig, LocalConfig},
     error::{CliError, CliResult},
-    network::server::Server,
+    network::{
+        invite::{join_network, InviteToken},
+        secure::StaticKey,
+        server::Server,
+    },
     pods::{
-        arbo::{GLOBAL_CONFIG_FNAME, LOCAL_CONFIG_FNAME},
+        arbo::{GLOBAL_CONFIG_FNAME, LOCAL_CONFIG_FNAME, STATIC_KEY_FNAME},
         pod::Pod,
         whpath::WhPath,
     },
 };
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 
 pub async fn new(args: PodArgs) -> CliResult<Pod> {
     let (global_config, local_config, server, mount_point) = pod_value(&args).await?;
@@ -63,11 +67,30 @@ async fn pod_value(args: &PodArgs) -> CliResult<(GlobalConfig, LocalConfig, Arc<
     let global_path = args.path.clone().join(GLOBAL_CONFIG_FNAME).inner;
     let global_config: GlobalConfig =
         GlobalConfig::read(global_path).unwrap_or(default_global_config());
-    let global_config = add_hosts(
+    let mut global_config = add_hosts(
         global_config,
         args.url.clone().unwrap_or("".to_string()),
         args.additional_hosts.clone().unwrap_or(vec![]),
     );
+    if let Some(token) = &args.token {
+        let url = args.url.as_ref().ok_or(CliError::InvalidArgument {
+            arg: "token without url".to_owned(),
+        })?;
+        let key = join(url, token, &args.path).await?;
+        global_config.security.network_key = Some(hex::encode(key));
+    }
 
     Ok((global_config, local_config, server, args.path.clone()))
 }
+
+/// Trades the invitation `token` for the network key with the pod at `url`
+async fn join(url: &str, token: &str, path: &WhPath) -> CliResult<[u8; 32]> {
+    let token = InviteToken::parse(token)?;
+    // the pod keeps the static key it joined with
+    let static_key = StaticKey::load_or_create(Path::new(&path.join(STATIC_KEY_FNAME).inner))
+        .map_err(|e| CliError::Message {
+            reason: e.to_string(),
+        })?;
+    let (mut stream, _) = tokio_tungstenite::connect_async(format!("ws://{url}")).await?;
+    Ok(join_network(&mut stream, &token, &static_key).await?)
+}
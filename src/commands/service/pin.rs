@@ -0,0 +1,21 @@
+use crate::{
+    error::{CliResult, CliSuccess},
+    pods::{pod::Pod, whpath::WhPath},
+};
+
+pub async fn pin(pod: &Pod, path: WhPath) -> CliResult<CliSuccess> {
+    let not_pulled = pod.pin(path.clone()).await?;
+    if not_pulled.is_empty() {
+        Ok(CliSuccess::Message(format!("{path} pinned")))
+    } else {
+        Ok(CliSuccess::Message(format!(
+            "{path} pinned, {} files couldn't be pulled yet and will be tried again",
+            not_pulled.len()
+        )))
+    }
+}
+
+pub fn unpin(pod: &Pod, path: WhPath) -> CliResult<CliSuccess> {
+    pod.unpin(path.clone())?;
+    Ok(CliSuccess::Message(format!("{path} unpinned")))
+}
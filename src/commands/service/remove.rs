@@ -1,22 +1,112 @@
+use std::{fs, io, path::Path};
+
 use crate::{
     commands::cli_commands::{Mode, RemoveArgs},
     error::{CliError, CliResult, CliSuccess},
-    pods::pod::Pod,
+    pods::{
+        arbo::{
+            ARBO_FILE_FNAME, GLOBAL_CONFIG_FNAME, INVITES_FNAME, JOURNAL_FNAME, LOCAL_CONFIG_FNAME,
+            SHARDS_DIR, STATIC_KEY_FNAME,
+        },
+        pod::Pod,
+        whpath::WhPath,
+    },
 };
 
-pub async fn remove(args: RemoveArgs, _pod: Pod) -> CliResult<CliSuccess> {
+pub async fn remove(args: RemoveArgs, pod: Pod) -> CliResult<CliSuccess> {
+    let mount_point = pod.get_mount_point().clone();
+
     match args.mode {
-        Mode::Simple => Err(CliError::Unimplemented {
-            arg: "Mode Simple".into(),
-        }),
-        Mode::Clone => Err(CliError::Unimplemented {
-            arg: "Mode Clone".into(),
-        }),
-        Mode::Clean => Err(CliError::Unimplemented {
-            arg: "Mode Clean".into(),
-        }),
-        Mode::Take => Err(CliError::Unimplemented {
-            arg: "Mode Take".into(),
-        }),
+        Mode::Simple => {
+            let not_taken = pod.leave(true).await?;
+            let message = format!("Pod removed, the files it hosted are left in {mount_point}");
+            if !not_taken.is_empty() {
+                // the pod is gone all the same, only the network lost these files
+                return Ok(CliSuccess::WithWarnings {
+                    message,
+                    warnings: not_taken
+                        .iter()
+                        .map(|path| format!("No other pod took {path}"))
+                        .collect(),
+                });
+            }
+            Ok(CliSuccess::Message(message))
+        }
+        Mode::Clone => {
+            let not_pulled = pod.pull_every_file().await?;
+            pod.leave(true).await?;
+            if !not_pulled.is_empty() {
+                // kept as a pod so it can be started again to retry
+                return Err(CliError::Message {
+                    reason: format!(
+                        "Pod stopped but not removed, these files couldn't be pulled:\n{}",
+                        list(&not_pulled)
+                    ),
+                });
+            }
+            remove_pod_files(&mount_point)?;
+            Ok(CliSuccess::Message(format!(
+                "Pod removed, every file was cloned into {mount_point}"
+            )))
+        }
+        Mode::Clean => {
+            let not_taken = pod.leave(true).await?;
+            if !not_taken.is_empty() {
+                return Err(CliError::Message {
+                    reason: format!(
+                        "Pod stopped but its data was kept, no other pod took these files:\n{}",
+                        list(&not_taken)
+                    ),
+                });
+            }
+            for entry in fs::read_dir(&mount_point.inner)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    fs::remove_dir_all(path)?;
+                } else {
+                    fs::remove_file(path)?;
+                }
+            }
+            Ok(CliSuccess::Message(format!(
+                "Pod removed and {mount_point} emptied"
+            )))
+        }
+        Mode::Take => {
+            let taken = pod.leave(false).await?;
+            remove_pod_files(&mount_point)?;
+            Ok(CliSuccess::Message(format!(
+                "Pod removed, {} files only it hosted were taken out of the network",
+                taken.len()
+            )))
+        }
+    }
+}
+
+/// Removes what made the folder a pod, leaving a regular folder
+fn remove_pod_files(mount_point: &WhPath) -> io::Result<()> {
+    for file in [
+        ARBO_FILE_FNAME,
+        JOURNAL_FNAME,
+        STATIC_KEY_FNAME,
+        INVITES_FNAME,
+        LOCAL_CONFIG_FNAME,
+        GLOBAL_CONFIG_FNAME,
+    ] {
+        match fs::remove_file(Path::new(&mount_point.join(file).inner)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => (),
+        }
+    }
+    match fs::remove_dir_all(Path::new(&mount_point.join(SHARDS_DIR).inner)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
     }
 }
+
+fn list(paths: &[WhPath]) -> String {
+    paths
+        .iter()
+        .map(|path| path.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
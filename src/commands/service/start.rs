@@ -1,12 +1,41 @@
+use std::sync::Arc;
+
 use crate::{
-    commands::cli_commands::StatusPodArgs,
-    error::{CliResult, CliSuccess},
+    commands::default_global_config,
+    config::{types::Config, GlobalConfig, LocalConfig},
+    error::{CliError, CliResult},
+    network::server::Server,
+    pods::{
+        arbo::{GLOBAL_CONFIG_FNAME, LOCAL_CONFIG_FNAME},
+        pod::Pod,
+        whpath::WhPath,
+    },
 };
 
-pub async fn start(start_args: StatusPodArgs) -> CliResult<CliSuccess> {
-    let name = start_args.name.clone();
-    Ok(CliSuccess::WithData {
-        message: String::from("Pod start: "),
-        data: name,
-    })
+/// Brings back a stopped pod from the configuration and arbo saved in its folder
+pub async fn start(mount_point: WhPath) -> CliResult<Pod> {
+    let local_path = mount_point.clone().join(LOCAL_CONFIG_FNAME).inner;
+    let local_config =
+        LocalConfig::read(&local_path).map_err(|_| CliError::InvalidConfig { file: local_path })?;
+    let global_path = mount_point.clone().join(GLOBAL_CONFIG_FNAME).inner;
+    // a pod saved without its global configuration gets the default one, a broken one is refused
+    let global_config = if std::path::Path::new(&global_path).exists() {
+        GlobalConfig::read(&global_path)
+            .map_err(|_| CliError::InvalidConfig { file: global_path })?
+    } else {
+        log::warn!("No global configuration at {global_path}, the default one is used");
+        default_global_config()
+    };
+    let server = Arc::new(Server::setup(&local_config.general.address).await?);
+
+    Pod::new(
+        local_config.general.name.clone(),
+        global_config,
+        local_config.clone(),
+        mount_point,
+        server,
+        local_config.general.address,
+    )
+    .await
+    .map_err(|e| CliError::PodCreationFailed { reason: e })
 }
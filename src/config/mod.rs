@@ -1,4 +1,5 @@
 pub mod parser;
+pub mod registry;
 pub mod types;
 
 pub use parser::parse_toml_file;
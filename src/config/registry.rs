@@ -0,0 +1,114 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{types::Config, LocalConfig},
+    error::WhResult,
+    network::message::Address,
+    pods::pod::Pod,
+};
+
+/// File of the service's directory listing its pods
+pub const REGISTRY_FNAME: &str = "pods.toml";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RegisteredPod {
+    pub name: String,
+    /// Mount point of the pod
+    pub path: String,
+    pub address: Address,
+    /// Started again when the service boots
+    pub autostart: bool,
+}
+
+impl RegisteredPod {
+    pub fn from_pod(name: &str, pod: &Pod, autostart: bool) -> WhResult<Self> {
+        Ok(Self {
+            name: name.to_owned(),
+            path: pod.get_mount_point().inner.clone(),
+            address: LocalConfig::read_lock(&pod.local_config, "RegisteredPod::from_pod")?
+                .general
+                .address
+                .clone(),
+            autostart,
+        })
+    }
+}
+
+/// Pods known by the service, kept on disk so they come back after a restart
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PodRegistry {
+    #[serde(default)]
+    pub pods: Vec<RegisteredPod>,
+    #[serde(skip)]
+    file: PathBuf,
+}
+
+impl PodRegistry {
+    /// Directory of the service's own files, `$WORMHOLE_CONFIG_DIR` or `~/.config/wormhole`
+    pub fn default_dir() -> PathBuf {
+        if let Ok(dir) = env::var("WORMHOLE_CONFIG_DIR") {
+            return PathBuf::from(dir);
+        }
+        env::var("HOME")
+            .map(|home| PathBuf::from(home).join(".config/wormhole"))
+            .unwrap_or_else(|_| PathBuf::from(".config/wormhole"))
+    }
+
+    /// Reads the registry of a directory, empty if there is none yet
+    pub fn load(dir: &Path) -> Self {
+        let file = dir.join(REGISTRY_FNAME);
+        let mut registry = if file.exists() {
+            Self::read(&file).unwrap_or_else(|e| {
+                log::error!("Can't read the pod registry {}: {e}", file.display());
+                Self::default()
+            })
+        } else {
+            Self::default()
+        };
+        registry.file = file;
+        registry
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        self.write(&self.file)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RegisteredPod> {
+        self.pods.iter().find(|pod| pod.name == name)
+    }
+
+    pub fn find_by_path(&self, path: &str) -> Option<&RegisteredPod> {
+        self.pods.iter().find(|pod| pod.path == path)
+    }
+
+    /// Adds a pod, replacing the one with the same name or path
+    pub fn register(&mut self, pod: RegisteredPod) {
+        self.pods
+            .retain(|known| known.name != pod.name && known.path != pod.path);
+        self.pods.push(pod);
+    }
+
+    pub fn unregister(&mut self, name: &str) {
+        self.pods.retain(|pod| pod.name != name);
+    }
+
+    pub fn set_autostart(&mut self, name: &str, autostart: bool) {
+        if let Some(pod) = self.pods.iter_mut().find(|pod| pod.name == name) {
+            pod.autostart = autostart;
+        }
+    }
+
+    pub fn rename(&mut self, old_name: &str, new_name: &str) {
+        if let Some(pod) = self.pods.iter_mut().find(|pod| pod.name == old_name) {
+            pod.name = new_name.to_owned();
+        }
+    }
+}
@@ -1,10 +1,12 @@
 use std::{fs, path::Path, str, sync::Arc};
 
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
     error::{CliError, WhError, WhResult},
+    network::secure::{parse_network_key, parse_public_key, NetworkKey, PublicKey, SecureError},
     pods::arbo::LOCK_TIMEOUT,
 };
 
@@ -35,9 +37,8 @@ pub trait Config: Serialize + DeserializeOwned {
         conf: &'a Arc<RwLock<T>>,
         called_from: &'a str,
     ) -> WhResult<RwLockReadGuard<'a, T>> {
-        conf.try_read_for(LOCK_TIMEOUT).ok_or(WhError::WouldBlock {
-            called_from: called_from.to_owned(),
-        })
+        conf.try_read_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block(called_from))
     }
 
     #[must_use]
@@ -45,9 +46,8 @@ pub trait Config: Serialize + DeserializeOwned {
         conf: &'a Arc<RwLock<T>>,
         called_from: &'a str,
     ) -> WhResult<RwLockWriteGuard<'a, T>> {
-        conf.try_write_for(LOCK_TIMEOUT).ok_or(WhError::WouldBlock {
-            called_from: called_from.to_owned(),
-        })
+        conf.try_write_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block(called_from))
     }
 }
 
@@ -56,17 +56,96 @@ impl<T: Serialize + DeserializeOwned> Config for T {}
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct LocalConfig {
     pub general: GeneralLocalConfig,
+    #[serde(default)]
+    pub cache: CacheLocalConfig,
+    #[serde(default)]
+    pub storage: StorageLocalConfig,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct GeneralLocalConfig {
     pub name: String,
     pub address: String,
+    /// Pods sharing a failure domain (machine, rack, site...) may fail together,
+    /// the copies of a file are spread over different ones
+    #[serde(default)]
+    pub failure_domain: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CacheLocalConfig {
+    /// Size (in Mo) of the remote file blocks kept in memory, 0 disables the cache
+    pub size: u64,
+}
+
+impl Default for CacheLocalConfig {
+    fn default() -> Self {
+        Self { size: 64 }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct StorageLocalConfig {
+    /// Space left (in Mo, on the disk or under the quota) under which the least recently used copies
+    /// are dropped, 0 never drops them. Under the quota, at most a tenth of it is kept free.
+    pub min_free_space: u64,
+    /// Paths (from the root of the pod) always kept on this pod, set by `wormhole pin`
+    pub pinned: Vec<String>,
+    /// Space (in Mo) the copies and shards held by this pod may take, no limit if unset
+    pub quota: Option<u64>,
+}
+
+impl Default for StorageLocalConfig {
+    fn default() -> Self {
+        Self {
+            min_free_space: 1024,
+            pinned: Vec::new(),
+            quota: None,
+        }
+    }
+}
+
+impl StorageLocalConfig {
+    /// Tells if the file at `path` (from the root of the pod) or one of its parents is pinned
+    pub fn is_pinned(&self, path: &str) -> bool {
+        let path = path.trim_start_matches("./").trim_matches('/');
+        self.pinned.iter().any(|pinned| {
+            let pinned = pinned.trim_start_matches("./").trim_matches('/');
+            pinned.is_empty()
+                || path
+                    .strip_prefix(pinned)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+
+    /// Adds `path` to the pinned paths, returns false if it already was
+    pub fn pin(&mut self, path: &str) -> bool {
+        let path = format!("/{}", path.trim_start_matches("./").trim_matches('/'));
+        if self.pinned.contains(&path) {
+            return false;
+        }
+        self.pinned.push(path);
+        true
+    }
+
+    /// Removes `path` from the pinned paths, returns false if it wasn't
+    pub fn unpin(&mut self, path: &str) -> bool {
+        let path = format!("/{}", path.trim_start_matches("./").trim_matches('/'));
+        let count = self.pinned.len();
+        self.pinned.retain(|pinned| *pinned != path);
+        count != self.pinned.len()
+    }
 }
 
 impl LocalConfig {
     pub fn constructor(&mut self, local: Self) -> Result<(), CliError> {
         self.general.name = local.general.name;
+        self.general.failure_domain = local.general.failure_domain;
+        self.cache = local.cache;
+        // pinned paths are only changed by `wormhole pin` and `wormhole unpin`
+        self.storage.min_free_space = local.storage.min_free_space;
+        self.storage.quota = local.storage.quota;
         if local.general.address != self.general.address {
             log::warn!("Local Config: Impossible to modify an ip address");
             return Err(CliError::Unimplemented {
@@ -81,18 +160,209 @@ impl LocalConfig {
 pub struct GlobalConfig {
     pub general: GeneralGlobalConfig,
     pub redundancy: RedundancyConfig,
+    #[serde(default)]
+    pub security: SecurityConfig,
+    #[serde(default)]
+    pub conflict: ConflictConfig,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct GeneralGlobalConfig {
     pub peers: Vec<String>,
-    pub ignore_paths: Vec<String>, //FIXME - What is this ???
+    /// Gitignore-style patterns (from the root of the pod) of the paths kept on the pod
+    /// that created them, never broadcast nor replicated.
+    /// A file is checked when created or indexed, moving it later doesn't change that.
+    pub ignore_paths: Vec<String>,
     pub pods_names: Vec<String>,
 }
 
+impl GeneralGlobalConfig {
+    pub fn ignore_rules(&self) -> IgnoreRules {
+        IgnoreRules::new(&self.ignore_paths)
+    }
+}
+
+/// Compiled [GeneralGlobalConfig::ignore_paths]
+#[derive(Debug, Clone)]
+pub struct IgnoreRules(Gitignore);
+
+impl IgnoreRules {
+    /// Invalid patterns are logged and skipped
+    pub fn new(patterns: &[String]) -> Self {
+        Self(compile_patterns(patterns, "Ignored path"))
+    }
+
+    /// Tells if `path` (from the root of the pod) or one of its parents is ignored
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        matches_patterns(&self.0, path, is_dir)
+    }
+}
+
+/// Gitignore-style `patterns` rooted at the root of the pod, `what` names them in the logs
+fn compile_patterns(patterns: &[String], what: &str) -> Gitignore {
+    let mut builder = GitignoreBuilder::new("/");
+    for pattern in patterns {
+        if let Err(e) = builder.add_line(None, pattern) {
+            log::warn!("{what} \"{pattern}\" can't be used: {e}");
+        }
+    }
+    builder.build().unwrap_or_else(|e| {
+        log::warn!("{what}s can't be used: {e}");
+        Gitignore::empty()
+    })
+}
+
+fn matches_patterns(patterns: &Gitignore, path: &str, is_dir: bool) -> bool {
+    let path = path.trim_start_matches("./").trim_start_matches('/');
+    !path.is_empty()
+        && patterns
+            .matched_path_or_any_parents(path, is_dir)
+            .is_ignore()
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct RedundancyConfig {
+    /// Copies kept of a file no policy applies to
     pub number: u64,
+    /// Copies kept of the files under some paths, the last matching policy wins.
+    /// Overridden by the `user.wormhole.redundancy` xattr, see [crate::pods::network::redundancy]
+    #[serde(default)]
+    pub policies: Vec<RedundancyPolicy>,
+    #[serde(default)]
+    pub placement: PlacementConfig,
+    /// Files split in data and parity shards instead of being copied, see [crate::pods::network::erasure]
+    #[serde(default)]
+    pub erasure: Option<ErasureConfig>,
+}
+
+impl RedundancyConfig {
+    pub fn targets(&self) -> RedundancyTargets {
+        RedundancyTargets {
+            number: self.number,
+            policies: self
+                .policies
+                .iter()
+                .map(|policy| {
+                    (
+                        compile_patterns(std::slice::from_ref(&policy.path), "Redundancy policy"),
+                        policy.number,
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RedundancyPolicy {
+    /// Gitignore-style pattern (from the root of the pod) of the files concerned
+    pub path: String,
+    /// Copies kept of these files, 0 or 1 to never copy them
+    pub number: u64,
+}
+
+/// Compiled [RedundancyConfig::policies]
+#[derive(Debug, Clone)]
+pub struct RedundancyTargets {
+    number: u64,
+    policies: Vec<(Gitignore, u64)>,
+}
+
+impl RedundancyTargets {
+    /// Copies wanted of the file at `path` (from the root of the pod)
+    pub fn target(&self, path: &str) -> u64 {
+        self.policies
+            .iter()
+            .rev()
+            .find(|(patterns, _)| matches_patterns(patterns, path, false))
+            .map_or(self.number, |(_, number)| *number)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct PlacementConfig {
+    /// Space (in Mo) a pod must keep free after receiving a copy
+    pub min_free_space: u64,
+    /// Avoids putting two copies of a file in the same failure domain while others are available
+    pub spread_failure_domains: bool,
+}
+
+impl Default for PlacementConfig {
+    fn default() -> Self {
+        Self {
+            min_free_space: 512,
+            spread_failure_domains: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ErasureConfig {
+    /// Number of shards the file is split in, it can be read back from any `data_shards` shards
+    pub data_shards: usize,
+    /// Number of shards that can be lost without losing the file
+    pub parity_shards: usize,
+    /// Directories (from the root of the pod) whose files are erasure coded, every file if empty
+    #[serde(default)]
+    pub paths: Vec<String>,
+}
+
+impl ErasureConfig {
+    /// Tells if the file at `path` (from the root of the pod) is erasure coded
+    pub fn applies_to(&self, path: &str) -> bool {
+        let path = path.trim_matches('/');
+        self.paths.is_empty()
+            || self.paths.iter().any(|dir| {
+                let dir = dir.trim_matches('/');
+                dir.is_empty()
+                    || path
+                        .strip_prefix(dir)
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SecurityConfig {
+    /// Secret (hex) every pod must know to connect to the network
+    pub network_key: Option<String>,
+    /// Public keys (hex) of the pods allowed to link, shown by `wormhole inspect`.
+    /// If empty, any pod knowing the network key is. Without either, no link is accepted.
+    #[serde(default)]
+    pub trusted_peers: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ConflictConfig {
+    /// How a file written concurrently on several pods is settled
+    pub policy: ConflictPolicy,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// The losing version is kept next to the file, as `<name>.conflict-<pod address>`
+    #[default]
+    KeepBoth,
+    /// The losing version is dropped
+    LastWriterWins,
+}
+
+impl SecurityConfig {
+    pub fn network_key(&self) -> Result<Option<NetworkKey>, SecureError> {
+        self.network_key
+            .as_ref()
+            .map(|key| parse_network_key(key))
+            .transpose()
+    }
+
+    pub fn trusted_peers(&self) -> Result<Vec<PublicKey>, SecureError> {
+        self.trusted_peers
+            .iter()
+            .map(|key| parse_public_key(key))
+            .collect()
+    }
 }
 
 impl GlobalConfig {
@@ -106,6 +376,11 @@ impl GlobalConfig {
             });
         }
         self.redundancy.number = global.redundancy.number;
+        self.redundancy.policies = global.redundancy.policies;
+        self.redundancy.placement = global.redundancy.placement;
+        self.redundancy.erasure = global.redundancy.erasure;
+        self.security = global.security;
+        self.conflict = global.conflict;
 
         Ok(())
     }
@@ -0,0 +1,107 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{GlobalConfig, LocalConfig},
+    network::{message::Address, peer_ipc::PeerState},
+    pods::network::gossip::NodeStatus,
+};
+
+const MO: u64 = 1024 * 1024;
+
+/// Peer as seen by the inspected pod
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PeerInspect {
+    pub address: Address,
+    pub state: PeerState,
+    /// Last status gossiped by the peer, None if it didn't reach this pod yet
+    pub status: Option<NodeStatus>,
+}
+
+/// Answer of `wormhole inspect`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PodInspect {
+    pub name: String,
+    pub mount_point: String,
+    pub address: Address,
+    /// To add to the trusted peers of the network, see [crate::config::types::SecurityConfig]
+    pub public_key: String,
+    pub peers: Vec<PeerInspect>,
+    /// Disk, load and hosted files of the pod itself
+    pub status: NodeStatus,
+    pub inodes: usize,
+    /// Default target number of hosts per file, some paths may have another one
+    pub redundancy: u64,
+    /// Files with fewer hosts than the target, or with lost shards
+    pub under_replicated: usize,
+    pub global_config: GlobalConfig,
+    pub local_config: LocalConfig,
+}
+
+impl fmt::Display for PodInspect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Pod {}", self.name)?;
+        writeln!(f, "  mount point: {}", self.mount_point)?;
+        writeln!(f, "  address: {}", self.address)?;
+        writeln!(f, "  public key: {}", self.public_key)?;
+        writeln!(
+            f,
+            "  version {}, up for {}s",
+            self.status.version, self.status.uptime
+        )?;
+        writeln!(f, "  disk: {}", disk_usage(&self.status))?;
+        writeln!(
+            f,
+            "  files: {} inodes, {} hosted here, {} under their redundancy target (default: {} hosts)",
+            self.inodes, self.status.hosted_files, self.under_replicated, self.redundancy
+        )?;
+        writeln!(f, "  transfers running: {}", self.status.load)?;
+
+        writeln!(f, "\nPeers ({}):", self.peers.len())?;
+        for peer in &self.peers {
+            match &peer.status {
+                Some(status) => writeln!(
+                    f,
+                    "  {} {:?}, version {}, disk: {}, {} files, {} transfers",
+                    peer.address,
+                    peer.state,
+                    status.version,
+                    disk_usage(status),
+                    status.hosted_files,
+                    status.load
+                )?,
+                None => writeln!(f, "  {} {:?}, no status yet", peer.address, peer.state)?,
+            }
+        }
+
+        writeln!(f, "\nLocal configuration:")?;
+        writeln!(f, "{}", to_toml(&self.local_config))?;
+        writeln!(f, "Global configuration:")?;
+        write!(f, "{}", to_toml(&self.global_config))
+    }
+}
+
+fn disk_usage(status: &NodeStatus) -> String {
+    let disk = match (status.free_space, status.total_space) {
+        (Some(free), Some(total)) => format!(
+            "{} Mo used of {} Mo ({} Mo free)",
+            total.saturating_sub(free) / MO,
+            total / MO,
+            free / MO
+        ),
+        _ => "unknown".to_owned(),
+    };
+    match status.quota {
+        Some(quota) => format!(
+            "{disk}, {} Mo held of a {} Mo quota",
+            status.used_space / MO,
+            quota / MO
+        ),
+        None => disk,
+    }
+}
+
+fn to_toml<T: Serialize>(config: &T) -> String {
+    toml::to_string(config).unwrap_or_else(|e| format!("<can't be displayed: {e}>"))
+}
@@ -0,0 +1,306 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock,
+    },
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::network::message::Address;
+
+/// Upper bounds, in seconds, of the buckets of the latency histograms
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1., 5., 10., 30., 60.,
+];
+
+/// Counters and gauges of the service, shared by all its pods
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+#[derive(Debug, Default, Clone)]
+struct Histogram {
+    /// Observations per bucket, not cumulated
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|le| seconds <= *le) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulated = 0;
+        for (le, count) in LATENCY_BUCKETS.iter().zip(self.buckets) {
+            cumulated += count;
+            let _ = writeln!(out, "{name}_bucket{{{labels}le=\"{le}\"}} {cumulated}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{{labels}le=\"+Inf\"}} {}", self.count);
+        let labels = match labels.trim_end_matches(',') {
+            "" => String::new(),
+            labels => format!("{{{labels}}}"),
+        };
+        let _ = writeln!(out, "{name}_sum{labels} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{labels} {}", self.count);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// By [crate::network::message::MessageContent::kind]
+    messages_sent: Mutex<BTreeMap<&'static str, u64>>,
+    messages_received: Mutex<BTreeMap<&'static str, u64>>,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    pulls: Mutex<Histogram>,
+    pull_failures: AtomicU64,
+    /// By pod address
+    redundancy_queue: Mutex<BTreeMap<Address, i64>>,
+    /// By pod address, as last counted by its redundancy worker
+    under_replicated: Mutex<BTreeMap<Address, u64>>,
+    fuse_ops: Mutex<BTreeMap<&'static str, Histogram>>,
+    lock_timeouts: AtomicU64,
+}
+
+/// Records the duration of a FUSE operation when dropped
+pub struct FuseOpTimer {
+    op: &'static str,
+    started: Instant,
+}
+
+impl Drop for FuseOpTimer {
+    fn drop(&mut self) {
+        METRICS
+            .fuse_ops
+            .lock()
+            .entry(self.op)
+            .or_default()
+            .observe(self.started.elapsed());
+    }
+}
+
+impl Metrics {
+    pub fn message_sent(&self, kind: &'static str, bytes: usize) {
+        *self.messages_sent.lock().entry(kind).or_default() += 1;
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn message_received(&self, kind: &'static str, bytes: usize) {
+        *self.messages_received.lock().entry(kind).or_default() += 1;
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// A pull went through, successfully or not, in `duration`
+    pub fn pull_done(&self, duration: Duration, succeeded: bool) {
+        self.pulls.lock().observe(duration);
+        if !succeeded {
+            self.pull_failed();
+        }
+    }
+
+    /// A pull couldn't even be asked to a host
+    pub fn pull_failed(&self) {
+        self.pull_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn redundancy_queued(&self, pod: &Address) {
+        *self.redundancy_queue.lock().entry(pod.clone()).or_default() += 1;
+    }
+
+    pub fn redundancy_dequeued(&self, pod: &Address) {
+        // the worker of a stopped pod may still drain its queue, its gauge isn't brought back
+        if let Some(depth) = self.redundancy_queue.lock().get_mut(pod) {
+            *depth = (*depth - 1).max(0);
+        }
+    }
+
+    pub fn set_under_replicated(&self, pod: &Address, files: usize) {
+        self.under_replicated
+            .lock()
+            .insert(pod.clone(), files as u64);
+    }
+
+    /// Drops the gauges of a stopped pod
+    pub fn forget_pod(&self, pod: &Address) {
+        self.redundancy_queue.lock().remove(pod);
+        self.under_replicated.lock().remove(pod);
+    }
+
+    pub fn fuse_op(&self, op: &'static str) -> FuseOpTimer {
+        FuseOpTimer {
+            op,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn lock_timed_out(&self) {
+        self.lock_timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "wormhole_messages_sent_total",
+            "counter",
+            "Messages sent to peers, by kind",
+        );
+        for (kind, count) in self.messages_sent.lock().iter() {
+            let _ = writeln!(
+                out,
+                "wormhole_messages_sent_total{{kind=\"{kind}\"}} {count}"
+            );
+        }
+        header(
+            &mut out,
+            "wormhole_messages_received_total",
+            "counter",
+            "Messages received from peers, by kind",
+        );
+        for (kind, count) in self.messages_received.lock().iter() {
+            let _ = writeln!(
+                out,
+                "wormhole_messages_received_total{{kind=\"{kind}\"}} {count}"
+            );
+        }
+        counter(
+            &mut out,
+            "wormhole_sent_bytes_total",
+            "Bytes sent to peers, encryption included",
+            self.bytes_sent.load(Ordering::Relaxed),
+        );
+        counter(
+            &mut out,
+            "wormhole_received_bytes_total",
+            "Bytes received from peers, encryption included",
+            self.bytes_received.load(Ordering::Relaxed),
+        );
+
+        header(
+            &mut out,
+            "wormhole_pull_duration_seconds",
+            "histogram",
+            "Time taken to pull a whole file",
+        );
+        self.pulls
+            .lock()
+            .render(&mut out, "wormhole_pull_duration_seconds", "");
+        counter(
+            &mut out,
+            "wormhole_pull_failures_total",
+            "Pulls that didn't bring the file",
+            self.pull_failures.load(Ordering::Relaxed),
+        );
+
+        header(
+            &mut out,
+            "wormhole_redundancy_queue_depth",
+            "gauge",
+            "Requests waiting for the redundancy worker, by pod",
+        );
+        for (pod, depth) in self.redundancy_queue.lock().iter() {
+            let _ = writeln!(
+                out,
+                "wormhole_redundancy_queue_depth{{pod=\"{pod}\"}} {depth}"
+            );
+        }
+        header(
+            &mut out,
+            "wormhole_under_replicated_files",
+            "gauge",
+            "Files with fewer hosts than the redundancy target, by pod",
+        );
+        for (pod, files) in self.under_replicated.lock().iter() {
+            let _ = writeln!(
+                out,
+                "wormhole_under_replicated_files{{pod=\"{pod}\"}} {files}"
+            );
+        }
+
+        header(
+            &mut out,
+            "wormhole_fuse_op_duration_seconds",
+            "histogram",
+            "Time taken by the FUSE operations, by operation",
+        );
+        for (op, histogram) in self.fuse_ops.lock().iter() {
+            histogram.render(
+                &mut out,
+                "wormhole_fuse_op_duration_seconds",
+                &format!("op=\"{op}\","),
+            );
+        }
+
+        counter(
+            &mut out,
+            "wormhole_lock_timeouts_total",
+            "Locks not acquired in time",
+            self.lock_timeouts.load(Ordering::Relaxed),
+        );
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    header(out, name, "counter", help);
+    let _ = writeln!(out, "{name} {value}");
+}
+
+/// Serves [METRICS] over HTTP at `address`, on any path, until the task is aborted
+pub async fn serve_metrics(address: String) {
+    let listener = match TcpListener::bind(&address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            log::error!("Can't serve the metrics on {address}: {e}");
+            return;
+        }
+    };
+    log::info!("Serving the metrics on http://{address}/metrics");
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(answer_scrape(stream));
+            }
+            Err(e) => log::warn!("Metrics: can't accept a connection: {e}"),
+        }
+    }
+}
+
+async fn answer_scrape(mut stream: TcpStream) {
+    // the request itself doesn't matter, it only has to be read before answering
+    let mut request = [0; 1024];
+    if let Err(e) = stream.read(&mut request).await {
+        log::debug!("Metrics: can't read the request: {e}");
+        return;
+    }
+    let body = METRICS.render();
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    if let Err(e) = stream.write_all(response.as_bytes()).await {
+        log::debug!("Metrics: can't answer the scrape: {e}");
+    }
+}
@@ -1,2 +1,4 @@
+pub mod inspect;
 pub mod metadata;
+pub mod metrics;
 pub mod tree_hosts;
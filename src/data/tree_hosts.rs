@@ -1,27 +1,54 @@
 use std::fmt;
 
-use crate::{
-    network::message::Address,
-    pods::{arbo::InodeId, whpath::WhPath},
-};
+use serde::{Deserialize, Serialize};
 
-pub type TreeLine = (u8, InodeId, WhPath, Vec<Address>); // (indentation_level, ino, path, hosts)
+use crate::{network::message::Address, pods::arbo::InodeId};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TreeLine {
+    pub indentation: u8,
+    pub ino: InodeId,
+    pub path: String,
+    pub hosts: Vec<Address>,
+    /// Hosts wanted for this file, see [crate::pods::network::redundancy::target_redundancy]
+    pub target: u64,
+    /// Fewer hosts than the target or lost shards, ignored files never lack any
+    pub lacking: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CliHostTree {
     pub lines: Vec<TreeLine>,
+    /// Number of hosts wanted for the files without a policy or an xattr of their own
+    pub redundancy: u64,
+}
+
+impl CliHostTree {
+    /// Number of listed files under their redundancy target, see [crate::pods::network::redundancy::under_replicated]
+    pub fn under_replicated(&self) -> usize {
+        self.lines.iter().filter(|line| line.lacking).count()
+    }
 }
 
 impl fmt::Display for CliHostTree {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut output = String::new();
-        for (indent, ino, path, hosts) in &self.lines {
+        for line in &self.lines {
             output.push_str(&format!(
-                "{}[{ino}] {}    ->    ({}) {:?}\n",
-                generate_indentation(*indent),
-                path,
-                hosts.len(),
-                hosts
+                "{}[{}] {}    ->    ({}/{}) {:?}\n",
+                generate_indentation(line.indentation),
+                line.ino,
+                line.path,
+                line.hosts.len(),
+                line.target,
+                line.hosts
             ));
         }
+        output.push_str(&format!(
+            "{} files under their redundancy target (default: {} hosts)\n",
+            self.under_replicated(),
+            self.redundancy
+        ));
         write!(f, "{output}")
     }
 }
@@ -1,6 +1,12 @@
 use custom_error::custom_error;
+use serde::{Deserialize, Serialize};
 use std::{fmt, io};
 
+use crate::data::inspect::PodInspect;
+use crate::data::metrics::METRICS;
+use crate::data::tree_hosts::CliHostTree;
+use crate::network::invite::InviteError;
+use crate::network::message::Address;
 use crate::pods::pod::PodInfoError;
 use crate::pods::pod::PodStopError;
 use bincode;
@@ -15,6 +21,14 @@ custom_error! {pub WhError
 }
 
 impl WhError {
+    /// Lock not acquired within [crate::pods::arbo::LOCK_TIMEOUT], counted in the metrics
+    pub fn would_block(called_from: &str) -> Self {
+        METRICS.lock_timed_out();
+        WhError::WouldBlock {
+            called_from: called_from.to_owned(),
+        }
+    }
+
     pub fn to_libc(&self) -> i32 {
         match self {
             WhError::InodeNotFound => libc::ENOENT,
@@ -39,6 +53,7 @@ custom_error! {pub CliError
     PodInfoError{source: PodInfoError} = "{source}",
     PodStopError{source: PodStopError} = "{source}",
     WhError{source: WhError} = "{source}",
+    InviteError{source: InviteError} = "{source}",
 
     FileConfigName{name: String} = "This isn't a valid configuration's file: {name}",
 
@@ -54,12 +69,43 @@ custom_error! {pub CliError
     Message{reason: String} = "{reason}",
 }
 
+impl CliError {
+    /// Stable name of the error, for the scripts reading the json output
+    pub fn code(&self) -> &'static str {
+        match self {
+            CliError::BoxError { .. } => "internal",
+            CliError::BincodeError => "serialization",
+            CliError::TungsteniteError => "websocket",
+            CliError::IoError { .. } => "io",
+            CliError::PodNotFound => "pod_not_found",
+            CliError::PodInfoError { .. } => "pod_info",
+            CliError::PodStopError { .. } => "pod_stop",
+            CliError::WhError { .. } => "internal",
+            CliError::InviteError { .. } => "invite",
+            CliError::FileConfigName { .. } => "invalid_config_name",
+            CliError::PodCreationFailed { .. } => "pod_creation_failed",
+            CliError::PodRemovalFailed { .. } => "pod_removal_failed",
+            CliError::InvalidConfig { .. } => "invalid_config",
+            CliError::InvalidCommand => "invalid_command",
+            CliError::InvalidArgument { .. } => "invalid_argument",
+            CliError::Unimplemented { .. } => "unimplemented",
+            CliError::Server { .. } => "server",
+            CliError::Message { .. } => "failed",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum CliSuccess {
     /// Success with a simple message
     Message(String),
     /// Success with a message and additional data
-    WithData { message: String, data: String },
+    WithData { message: String, data: CliData },
+    /// Success with a message and what the user should still look at
+    WithWarnings {
+        message: String,
+        warnings: Vec<String>,
+    },
 }
 
 impl fmt::Display for CliSuccess {
@@ -69,6 +115,100 @@ impl fmt::Display for CliSuccess {
             CliSuccess::WithData { message, data } => {
                 write!(f, "{} - Data:\n{}\n", message, data)
             }
+            CliSuccess::WithWarnings { message, warnings } => {
+                write!(f, "{}\n{}", message, warnings.join("\n"))
+            }
+        }
+    }
+}
+
+/// Data answered by the service along with a [CliSuccess]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CliData {
+    /// Name of the pod created or started
+    Pod(String),
+    Hosts(Vec<Address>),
+    Tree(CliHostTree),
+    Inspect(Box<PodInspect>),
+}
+
+impl fmt::Display for CliData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliData::Pod(name) => write!(f, "{name}"),
+            CliData::Hosts(hosts) => write!(f, "{hosts:?}"),
+            CliData::Tree(tree) => write!(f, "{tree}"),
+            CliData::Inspect(report) => write!(f, "{report}"),
+        }
+    }
+}
+
+/// Answer of the service to a cli command, as sent to the cli
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum CliResponse {
+    Success {
+        message: String,
+        data: Option<CliData>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        warnings: Vec<String>,
+    },
+    Error {
+        code: String,
+        message: String,
+    },
+}
+
+impl From<CliResult<CliSuccess>> for CliResponse {
+    fn from(result: CliResult<CliSuccess>) -> Self {
+        match result {
+            Ok(CliSuccess::Message(message)) => CliResponse::Success {
+                message,
+                data: None,
+                warnings: Vec::new(),
+            },
+            Ok(CliSuccess::WithData { message, data }) => CliResponse::Success {
+                message,
+                data: Some(data),
+                warnings: Vec::new(),
+            },
+            Ok(CliSuccess::WithWarnings { message, warnings }) => CliResponse::Success {
+                message,
+                data: None,
+                warnings,
+            },
+            Err(e) => CliResponse::Error {
+                code: e.code().to_owned(),
+                message: e.to_string(),
+            },
+        }
+    }
+}
+
+impl From<CliError> for CliResponse {
+    fn from(error: CliError) -> Self {
+        CliResponse::from(Err(error))
+    }
+}
+
+impl fmt::Display for CliResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliResponse::Success {
+                message,
+                data,
+                warnings,
+            } => {
+                write!(f, "{message}")?;
+                if let Some(data) = data {
+                    write!(f, "\n{data}")?;
+                }
+                warnings
+                    .iter()
+                    .try_for_each(|warning| write!(f, "\n{warning}"))
+            }
+            CliResponse::Error { message, .. } => write!(f, "{message}"),
         }
     }
 }
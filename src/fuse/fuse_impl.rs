@@ -1,13 +1,16 @@
+use crate::data::metrics::METRICS;
 use crate::fuse::linux_attrs::time_or_now_to_system_time;
 use crate::fuse::linux_mknod::filetype_from_mode;
 use crate::pods::filesystem::attrs::SetAttrError;
 use crate::pods::filesystem::file_handle::{AccessMode, OpenFlags};
 use crate::pods::filesystem::fs_interface::{FsInterface, SimpleFileType};
 // use crate::pods::filesystem::make_inode::CreateError;
+use crate::pods::filesystem::links::LinkError;
 use crate::pods::filesystem::make_inode::MakeInodeError;
 use crate::pods::filesystem::open::{check_permissions, OpenError};
 use crate::pods::filesystem::read::ReadError;
 
+use crate::pods::filesystem::quota::QuotaError;
 use crate::pods::filesystem::remove_inode::RemoveFileError;
 use crate::pods::filesystem::rename::RenameError;
 use crate::pods::filesystem::write::WriteError;
@@ -21,6 +24,7 @@ use fuser::{
 use libc::{EIO, ENOENT, XATTR_CREATE, XATTR_REPLACE};
 use std::ffi::OsStr;
 use std::io;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
@@ -36,6 +40,7 @@ impl Filesystem for FuseController {
     // READING
 
     fn lookup(&mut self, req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let _timer = METRICS.fuse_op("lookup");
         match self
             .fs_interface
             .get_entry_from_name(parent, name.to_string_lossy().to_string())
@@ -50,6 +55,7 @@ impl Filesystem for FuseController {
     }
 
     fn getattr(&mut self, req: &Request, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        let _timer = METRICS.fuse_op("getattr");
         let attrs = self.fs_interface.get_inode_attributes(ino);
 
         match attrs {
@@ -79,6 +85,7 @@ impl Filesystem for FuseController {
         flags: Option<u32>,
         reply: ReplyAttr,
     ) {
+        let _timer = METRICS.fuse_op("setattr");
         match self.fs_interface.setattr(
             ino,
             mode,
@@ -116,6 +123,7 @@ impl Filesystem for FuseController {
         size: u32,
         reply: ReplyXattr,
     ) {
+        let _timer = METRICS.fuse_op("getxattr");
         let attr = self
             .fs_interface
             .get_inode_xattr(ino, &name.to_string_lossy().to_string());
@@ -149,6 +157,7 @@ impl Filesystem for FuseController {
         _position: u32, // Postion undocumented
         reply: ReplyEmpty,
     ) {
+        let _timer = METRICS.fuse_op("setxattr");
         // As we follow linux implementation in spirit, data size limit at 64kb
         if data.len() > 64000 {
             return reply.error(libc::ENOSPC);
@@ -197,6 +206,7 @@ impl Filesystem for FuseController {
         name: &OsStr,
         reply: ReplyEmpty,
     ) {
+        let _timer = METRICS.fuse_op("removexattr");
         match self
             .fs_interface
             .network_interface
@@ -208,6 +218,7 @@ impl Filesystem for FuseController {
     }
 
     fn listxattr(&mut self, _req: &Request<'_>, ino: u64, size: u32, reply: ReplyXattr) {
+        let _timer = METRICS.fuse_op("listxattr");
         match self.fs_interface.list_inode_xattr(ino) {
             Ok(keys) => {
                 let mut bytes = vec![];
@@ -243,6 +254,7 @@ impl Filesystem for FuseController {
         _lock: Option<u64>,
         reply: ReplyData,
     ) {
+        let _timer = METRICS.fuse_op("read");
         let mut buf = vec![];
         buf.resize(size as usize, 0);
         match self.fs_interface.read_file(
@@ -281,6 +293,7 @@ impl Filesystem for FuseController {
         offset: i64,
         mut reply: ReplyDirectory,
     ) {
+        let _timer = METRICS.fuse_op("readdir");
         let entries = match self.fs_interface.read_dir(ino) {
             Ok(entries) => entries,
             Err(e) => {
@@ -304,6 +317,19 @@ impl Filesystem for FuseController {
         reply.ok();
     }
 
+    fn readlink(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyData) {
+        let _timer = METRICS.fuse_op("readlink");
+        match self.fs_interface.read_link(ino) {
+            Ok(target) => reply.data(target.as_bytes()),
+            Err(LinkError::WhError { source }) => reply.error(source.to_libc()),
+            Err(LinkError::NotASymlink) => reply.error(libc::EINVAL),
+            Err(err) => {
+                log::error!("readlink: {err}");
+                reply.error(EIO)
+            }
+        }
+    }
+
     // ^ READING
 
     // WRITING
@@ -315,24 +341,26 @@ impl Filesystem for FuseController {
         name: &OsStr,
         mode: u32,
         _umask: u32,
-        _rdev: u32,
+        rdev: u32,
         reply: ReplyEntry,
     ) {
+        let _timer = METRICS.fuse_op("mknod");
         let permissions = mode as u16;
         let kind = match filetype_from_mode(mode) {
             Some(kind) => kind,
             None => {
-                // If it's not a file or a directory it's not yet supported
+                // symlinks are made through [Filesystem::symlink]
                 reply.error(libc::EPERM);
                 return;
             }
         };
 
-        match self.fs_interface.make_inode(
+        match self.fs_interface.make_special(
             parent,
             name.to_string_lossy().to_string(),
             permissions,
             kind,
+            rdev,
         ) {
             Ok(node) => reply.entry(&TTL, &node.meta.with_ids(req.uid(), req.gid()), 0),
             Err(MakeInodeError::LocalCreationFailed { io }) => {
@@ -345,6 +373,7 @@ impl Filesystem for FuseController {
             Err(MakeInodeError::ParentNotFound) => reply.error(libc::ENOENT),
             Err(MakeInodeError::ParentNotFolder) => reply.error(libc::ENOTDIR),
             Err(MakeInodeError::ProtectedNameIsFolder) => reply.error(libc::EISDIR),
+            Err(MakeInodeError::SymlinkWithoutTarget) => reply.error(libc::EINVAL),
         }
         //todo when persmissions are added reply.error(libc::EACCES)
     }
@@ -358,6 +387,7 @@ impl Filesystem for FuseController {
         _umask: u32,
         reply: ReplyEntry,
     ) {
+        let _timer = METRICS.fuse_op("mkdir");
         match self.fs_interface.make_inode(
             parent,
             name.to_string_lossy().to_string(),
@@ -375,10 +405,73 @@ impl Filesystem for FuseController {
             Err(MakeInodeError::ParentNotFound) => reply.error(libc::ENOENT),
             Err(MakeInodeError::ParentNotFolder) => reply.error(libc::ENOTDIR),
             Err(MakeInodeError::ProtectedNameIsFolder) => reply.error(libc::EISDIR),
+            Err(MakeInodeError::SymlinkWithoutTarget) => reply.error(libc::EINVAL),
+        }
+    }
+
+    fn symlink(
+        &mut self,
+        req: &Request<'_>,
+        parent: u64,
+        link_name: &OsStr,
+        target: &Path,
+        reply: ReplyEntry,
+    ) {
+        let _timer = METRICS.fuse_op("symlink");
+        match self.fs_interface.make_symlink(
+            parent,
+            link_name.to_string_lossy().to_string(),
+            target.to_string_lossy().to_string(),
+        ) {
+            Ok(node) => reply.entry(&TTL, &node.meta.with_ids(req.uid(), req.gid()), 0),
+            Err(MakeInodeError::LocalCreationFailed { io }) => {
+                reply.error(io.raw_os_error().expect(
+                    "Local creation error should always be the underling libc::open os error",
+                ))
+            }
+            Err(MakeInodeError::WhError { source }) => reply.error(source.to_libc()),
+            Err(MakeInodeError::AlreadyExist) => reply.error(libc::EEXIST),
+            Err(MakeInodeError::ParentNotFound) => reply.error(libc::ENOENT),
+            Err(MakeInodeError::ParentNotFolder) => reply.error(libc::ENOTDIR),
+            Err(MakeInodeError::ProtectedNameIsFolder) => reply.error(libc::EISDIR),
+            Err(MakeInodeError::SymlinkWithoutTarget) => reply.error(libc::EINVAL),
+        }
+    }
+
+    fn link(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        newparent: u64,
+        newname: &OsStr,
+        reply: ReplyEntry,
+    ) {
+        let _timer = METRICS.fuse_op("link");
+        match self.fs_interface.make_hard_link(
+            ino,
+            newparent,
+            newname.to_string_lossy().to_string(),
+        ) {
+            Ok(node) => reply.entry(&TTL, &node.meta.with_ids(req.uid(), req.gid()), 0),
+            Err(LinkError::WhError { source }) => reply.error(source.to_libc()),
+            Err(LinkError::NotAFile) => reply.error(libc::EPERM),
+            Err(LinkError::NotASymlink) => reply.error(libc::EINVAL),
+            Err(LinkError::MakeInode { source }) => match source {
+                MakeInodeError::AlreadyExist => reply.error(libc::EEXIST),
+                MakeInodeError::ParentNotFound => reply.error(libc::ENOENT),
+                MakeInodeError::ParentNotFolder => reply.error(libc::ENOTDIR),
+                MakeInodeError::ProtectedNameIsFolder => reply.error(libc::EPERM),
+                MakeInodeError::WhError { source } => reply.error(source.to_libc()),
+                err => {
+                    log::error!("link: {err}");
+                    reply.error(EIO)
+                }
+            },
         }
     }
 
     fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        let _timer = METRICS.fuse_op("unlink");
         match self.fs_interface.fuse_remove_inode(parent, name) {
             Ok(()) => reply.ok(),
             Err(RemoveFileError::WhError { source }) => reply.error(source.to_libc()),
@@ -388,10 +481,15 @@ impl Filesystem for FuseController {
                 ))
             }
             Err(RemoveFileError::NonEmpty) => reply.error(libc::ENOTEMPTY),
+            Err(RemoveFileError::LinkPromotionFailed { source }) => {
+                log::error!("{source}");
+                reply.error(EIO)
+            }
         }
     }
 
     fn rmdir(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        let _timer = METRICS.fuse_op("rmdir");
         match self.fs_interface.fuse_remove_inode(parent, name) {
             Ok(()) => reply.ok(),
             Err(RemoveFileError::WhError { source }) => reply.error(source.to_libc()),
@@ -401,6 +499,10 @@ impl Filesystem for FuseController {
                 ))
             }
             Err(RemoveFileError::NonEmpty) => reply.error(libc::ENOTEMPTY),
+            Err(RemoveFileError::LinkPromotionFailed { source }) => {
+                log::error!("{source}");
+                reply.error(EIO)
+            }
         }
     }
 
@@ -414,6 +516,7 @@ impl Filesystem for FuseController {
         flags: u32,
         reply: fuser::ReplyEmpty,
     ) {
+        let _timer = METRICS.fuse_op("rename");
         match self
             .fs_interface
             .rename(
@@ -453,10 +556,13 @@ impl Filesystem for FuseController {
                 io.raw_os_error()
                     .expect("Local read error should always be the underling os error"),
             ),
+            Err(RenameError::SymlinkWithoutTarget) => reply.error(libc::EINVAL),
+            Err(RenameError::CrossesIgnoredPaths) => reply.error(libc::EXDEV),
         }
     }
 
     fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: fuser::ReplyOpen) {
+        let _timer = METRICS.fuse_op("open");
         match AccessMode::from_libc(flags).and_then(|access| {
             self.fs_interface
                 .open(ino, OpenFlags::from_libc(flags), access)
@@ -481,6 +587,7 @@ impl Filesystem for FuseController {
         _lock_owner: Option<u64>,
         reply: fuser::ReplyWrite,
     ) {
+        let _timer = METRICS.fuse_op("write");
         let offset = offset
             .try_into()
             .expect("fuser write: can't convert i64 to u64");
@@ -493,10 +600,14 @@ impl Filesystem for FuseController {
             ),
             Err(WriteError::WhError { source }) => reply.error(source.to_libc()),
             Err(WriteError::LocalWriteFailed { io }) => {
-                reply.error(io.raw_os_error().expect(
-                    "Local creation error should always be the underling libc::open os error",
-                ))
+                reply.error(io.raw_os_error().unwrap_or(libc::EIO))
             }
+            Err(WriteError::Quota {
+                source: QuotaError::WhError { source },
+            }) => reply.error(source.to_libc()),
+            Err(WriteError::Quota {
+                source: QuotaError::NoSpace { .. },
+            }) => reply.error(libc::ENOSPC),
             Err(WriteError::NoFileHandle) => reply.error(libc::EBADFD), // Shouldn't happend
             //According to the man EBADF if the fd is not a valid file descriptor or is not open for writing.
             Err(WriteError::NoWritePermission) => reply.error(libc::EBADF), // Shouldn't happend, write not call with wrong perms, already stopped
@@ -585,20 +696,22 @@ impl Filesystem for FuseController {
     fn release(
         &mut self,
         _req: &Request<'_>,
-        _ino: u64,
+        ino: u64,
         file_handle: u64,
         _flags: i32,
         _lock_owner: Option<u64>,
         _flush: bool,
         reply: fuser::ReplyEmpty,
     ) {
-        match self.fs_interface.release(file_handle) {
+        let _timer = METRICS.fuse_op("release");
+        match self.fs_interface.release(ino, file_handle) {
             Ok(()) => reply.ok(),
             Err(err) => reply.error(err.to_libc()),
         }
     }
 
     fn access(&mut self, _req: &Request<'_>, ino: u64, mask: i32, reply: ReplyEmpty) {
+        let _timer = METRICS.fuse_op("access");
         let meta = match self.fs_interface.n_get_inode_attributes(ino) {
             Ok(meta) => meta,
             Err(err) => {
@@ -6,10 +6,7 @@ use crate::pods::{arbo::Metadata, filesystem::fs_interface::SimpleFileType};
 
 impl Into<FileType> for SimpleFileType {
     fn into(self) -> FileType {
-        match self {
-            SimpleFileType::File => FileType::RegularFile,
-            SimpleFileType::Directory => FileType::Directory,
-        }
+        (&self).into()
     }
 }
 
@@ -18,6 +15,11 @@ impl Into<FileType> for &SimpleFileType {
         match self {
             SimpleFileType::File => FileType::RegularFile,
             SimpleFileType::Directory => FileType::Directory,
+            SimpleFileType::Symlink => FileType::Symlink,
+            SimpleFileType::NamedPipe => FileType::NamedPipe,
+            SimpleFileType::CharDevice => FileType::CharDevice,
+            SimpleFileType::BlockDevice => FileType::BlockDevice,
+            SimpleFileType::Socket => FileType::Socket,
         }
     }
 }
@@ -27,11 +29,11 @@ impl Into<SimpleFileType> for FileType {
         match self {
             FileType::RegularFile => SimpleFileType::File,
             FileType::Directory => SimpleFileType::Directory,
-            FileType::NamedPipe => todo!("file type not supported"),
-            FileType::CharDevice => todo!("file type not supported"),
-            FileType::BlockDevice => todo!("file type not supported"),
-            FileType::Symlink => todo!("file type not supported"),
-            FileType::Socket => todo!("file type not supported"),
+            FileType::NamedPipe => SimpleFileType::NamedPipe,
+            FileType::CharDevice => SimpleFileType::CharDevice,
+            FileType::BlockDevice => SimpleFileType::BlockDevice,
+            FileType::Symlink => SimpleFileType::Symlink,
+            FileType::Socket => SimpleFileType::Socket,
         }
     }
 }
@@ -86,6 +88,7 @@ impl Into<Metadata> for FileAttr {
             rdev: self.rdev,
             flags: self.flags,
             blksize: self.blksize,
+            hash: None,
         }
     }
 }
@@ -22,5 +22,17 @@ pub fn filetype_from_mode(mode: u32) -> Option<SimpleFileType> {
     if file_type == libc::S_IFDIR {
         return Some(SimpleFileType::Directory);
     }
+    if file_type == libc::S_IFIFO {
+        return Some(SimpleFileType::NamedPipe);
+    }
+    if file_type == libc::S_IFCHR {
+        return Some(SimpleFileType::CharDevice);
+    }
+    if file_type == libc::S_IFBLK {
+        return Some(SimpleFileType::BlockDevice);
+    }
+    if file_type == libc::S_IFSOCK {
+        return Some(SimpleFileType::Socket);
+    }
     return None;
 }
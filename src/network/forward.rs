@@ -1,46 +1,119 @@
-use futures_util::SinkExt;
-use futures_util::{stream::SplitStream, Sink, StreamExt};
-use std::fmt::Debug;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
-use tokio_tungstenite::tungstenite::Message;
-
-use crate::error::WhError;
-use crate::network::message::MessageContent;
-
-use super::message::{FromNetworkMessage, MessageAndStatus};
-
-pub async fn forward_receiver_to_write<T>(
-    mut write: T,
-    rx: &mut UnboundedReceiver<MessageAndStatus>,
-) where
-    T: Sink<Message> + Unpin,
-    <T as Sink<Message>>::Error: Debug,
-{
-    while let Some((message, status_tx)) = rx.recv().await {
-        let serialized = bincode::serialize(&message).unwrap();
-        let sent = write.send(Message::binary(serialized)).await;
-
-        status_tx.inspect(|tx| {
-            let _ = tx.send(sent.map_err(|_| WhError::NetworkDied {
-                called_from: "forward_receiver_to_write".to_string(),
-            }));
-        });
-    }
-}
-
-pub async fn forward_read_to_sender<
-    T: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>>,
->(
-    mut read: SplitStream<T>,
-    tx: UnboundedSender<FromNetworkMessage>,
-    address: String,
-) {
-    while let Ok(Message::Binary(message)) = read.next().await.unwrap() {
-        let deserialized: MessageContent = bincode::deserialize(&message).unwrap();
-        tx.send(FromNetworkMessage {
-            origin: address.clone(),
-            content: deserialized,
-        })
-        .unwrap();
-    }
-}
+use futures_util::SinkExt;
+use futures_util::{stream::SplitStream, Sink, StreamExt};
+use std::fmt::Debug;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::data::metrics::METRICS;
+use crate::error::WhError;
+use crate::network::message::MessageContent;
+
+use super::message::{FromNetworkMessage, MessageAndStatus};
+use super::peer_ipc::{Liveness, HEARTBEAT_INTERVAL};
+use super::secure::{Decryptor, Encryptor};
+
+pub async fn forward_receiver_to_write<T>(
+    mut write: T,
+    rx: &mut UnboundedReceiver<MessageAndStatus>,
+    mut encryptor: Encryptor,
+) where
+    T: Sink<Message> + Unpin,
+    <T as Sink<Message>>::Error: Debug,
+{
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+
+    loop {
+        let (message, status_tx) = tokio::select! {
+            message = rx.recv() => match message {
+                Some(message) => message,
+                None => return,
+            },
+            // answered by a pong, proving the link alive to both sides
+            _ = heartbeat.tick() => {
+                if write.send(Message::Ping(Vec::new())).await.is_err() {
+                    return;
+                }
+                continue;
+            }
+        };
+        let serialized = bincode::serialize(&message).unwrap();
+        let sent = match encryptor.seal(&serialized) {
+            Ok(sealed) => {
+                let bytes = sealed.len();
+                write
+                    .send(Message::binary(sealed))
+                    .await
+                    .map(|_| METRICS.message_sent(message.kind(), bytes))
+                    .map_err(|_| ())
+            }
+            Err(e) => {
+                log::error!("Can't encrypt message {message}: {e}");
+                Err(())
+            }
+        };
+
+        let broken = sent.is_err();
+        status_tx.inspect(|tx| {
+            let _ = tx.send(sent.map_err(|_| WhError::NetworkDied {
+                called_from: "forward_receiver_to_write".to_string(),
+            }));
+        });
+        if broken {
+            return;
+        }
+    }
+}
+
+pub async fn forward_read_to_sender<
+    T: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>>,
+>(
+    mut read: SplitStream<T>,
+    tx: UnboundedSender<FromNetworkMessage>,
+    address: String,
+    mut decryptor: Decryptor,
+    liveness: &Liveness,
+) {
+    loop {
+        let message = match read.next().await {
+            Some(Ok(message)) => message,
+            Some(Err(e)) => {
+                log::warn!("Link to {address} broke: {e}");
+                return;
+            }
+            None => return,
+        };
+        liveness.seen();
+        let message = match message {
+            Message::Binary(message) => message,
+            Message::Close(_) => return,
+            // pings are answered by tungstenite itself
+            _ => continue,
+        };
+        let sealed_len = message.len();
+        // a message that can't be decrypted doesn't come from the trusted peer, the link is dropped
+        let message = match decryptor.open(&message) {
+            Ok(message) => message,
+            Err(e) => {
+                log::error!("Refusing message from {address}: {e}. Closing the link.");
+                return;
+            }
+        };
+        let deserialized = match bincode::deserialize::<MessageContent>(&message) {
+            Ok(content) => {
+                METRICS.message_received(content.kind(), sealed_len);
+                content
+            }
+            Err(e) => {
+                log::error!("Invalid message from {address}: {e}");
+                continue;
+            }
+        };
+        let forwarded = tx.send(FromNetworkMessage {
+            origin: address.clone(),
+            content: deserialized,
+        });
+        if forwarded.is_err() {
+            return; // the pod is stopping
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use custom_error::custom_error;
+use futures_util::{Sink, SinkExt, Stream};
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use tokio_tungstenite::tungstenite::{self, Message};
+
+use crate::{error::WhError, pods::arbo::LOCK_TIMEOUT};
+
+use super::secure::{
+    handshake, parse_public_key, random_key, receive, send, LinkSecurity, NetworkKey, PublicKey,
+    SecureError, StaticKey, HANDSHAKE_TIMEOUT, JOIN_HELLO,
+};
+
+/// Length of the ids naming the pending invitations
+const ID_LEN: usize = 8;
+
+custom_error! {pub InviteError
+    WhError{source: WhError} = "{source}",
+    Io{source: io::Error} = "Can't save the invitations: {source}",
+    Secure{source: SecureError} = "{source}",
+    InvalidToken = "An invitation token must be 128 hexadecimal characters",
+    UnknownInvite{id: String} = "No pending invitation has the id {id}",
+    ShortInviteId{id: String} = "{id} is shorter than the ids listed for the invitations",
+    AmbiguousInvite{id: String} = "Several pending invitations have an id starting with {id}",
+    NoNetworkKey = "This network has no key to share, invitations can't be issued",
+}
+
+/// What a pod needs to join a network: the public key of the pod that invited it,
+/// and a secret only used once
+#[derive(Clone, PartialEq)]
+pub struct InviteToken {
+    pub issuer: PublicKey,
+    secret: [u8; 32],
+}
+
+impl fmt::Display for InviteToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            hex::encode(self.issuer),
+            hex::encode(self.secret)
+        )
+    }
+}
+
+impl fmt::Debug for InviteToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "InviteToken({})", invite_id(&self.secret))
+    }
+}
+
+impl InviteToken {
+    pub fn parse(token: &str) -> Result<Self, InviteError> {
+        if token.len() != 128 {
+            return Err(InviteError::InvalidToken);
+        }
+        let (issuer, secret) = token.split_at(64);
+        Ok(Self {
+            issuer: parse_public_key(issuer).map_err(|_| InviteError::InvalidToken)?,
+            secret: hex::decode(secret)
+                .ok()
+                .and_then(|secret| secret.try_into().ok())
+                .ok_or(InviteError::InvalidToken)?,
+        })
+    }
+
+    pub fn id(&self) -> String {
+        invite_id(&self.secret)
+    }
+}
+
+fn secret_hash(secret: &[u8]) -> String {
+    hex::encode(Sha256::digest(secret))
+}
+
+fn invite_id(secret: &[u8]) -> String {
+    secret_hash(secret)[..ID_LEN].to_owned()
+}
+
+/// Invitations issued by a pod and not used yet
+///
+/// Only the hashes of their secrets are kept, one per line in the file of the pod.
+#[derive(Debug, Default)]
+pub struct Invites {
+    path: Option<PathBuf>,
+    pending: Mutex<Vec<String>>,
+}
+
+impl Invites {
+    /// Reads the invitations saved at `path`, where they will be kept
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let pending = match fs::read_to_string(path) {
+            Ok(saved) => saved.lines().map(str::to_owned).collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            path: Some(path.to_owned()),
+            pending: Mutex::new(pending),
+        })
+    }
+
+    fn save(&self, pending: &[String]) -> io::Result<()> {
+        match &self.path {
+            Some(path) => fs::write(
+                path,
+                pending
+                    .iter()
+                    .map(|hash| hash.clone() + "\n")
+                    .collect::<String>(),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Issues an invitation to the network of the pod identified by `issuer`
+    pub fn issue(&self, issuer: PublicKey) -> Result<InviteToken, InviteError> {
+        let token = InviteToken {
+            issuer,
+            secret: random_key()?,
+        };
+        let mut pending = self
+            .pending
+            .try_lock_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Invites::issue"))?;
+        pending.push(secret_hash(&token.secret));
+        self.save(&pending)?;
+        Ok(token)
+    }
+
+    /// Ids of the pending invitations, whole when their first [ID_LEN] characters are shared
+    pub fn list(&self) -> Result<Vec<String>, InviteError> {
+        let pending = self
+            .pending
+            .try_lock_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Invites::list"))?;
+        Ok(pending
+            .iter()
+            .map(|hash| {
+                let id = &hash[..ID_LEN];
+                match pending.iter().filter(|other| other.starts_with(id)).count() {
+                    1 => id.to_owned(),
+                    _ => hash.clone(),
+                }
+            })
+            .collect())
+    }
+
+    /// Revokes the pending invitation whose id starts with `id`
+    ///
+    /// `id` must be at least as long as the listed ids and match a single invitation.
+    pub fn revoke(&self, id: &str) -> Result<(), InviteError> {
+        if id.len() < ID_LEN {
+            return Err(InviteError::ShortInviteId { id: id.to_owned() });
+        }
+        let mut pending = self
+            .pending
+            .try_lock_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Invites::revoke"))?;
+        let mut matching = pending
+            .iter()
+            .enumerate()
+            .filter(|(_, hash)| hash.starts_with(id));
+        let index = match (matching.next(), matching.next()) {
+            (Some((index, _)), None) => index,
+            (None, _) => return Err(InviteError::UnknownInvite { id: id.to_owned() }),
+            (Some(_), Some(_)) => return Err(InviteError::AmbiguousInvite { id: id.to_owned() }),
+        };
+        pending.remove(index);
+        self.save(&pending)?;
+        Ok(())
+    }
+
+    /// Uses up the invitation whose secret is `secret`, false if it isn't pending
+    pub fn consume(&self, secret: &[u8]) -> Result<bool, InviteError> {
+        let hash = secret_hash(secret);
+        let mut pending = self
+            .pending
+            .try_lock_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Invites::consume"))?;
+        let Some(index) = pending.iter().position(|pending| *pending == hash) else {
+            return Ok(false);
+        };
+        pending.remove(index);
+        self.save(&pending)?;
+        Ok(true)
+    }
+}
+
+/// Asks the pod that issued `token` for the network key, using up the invitation.
+///
+/// The issuer is authenticated by its static key, so the token is only ever shown to it.
+pub async fn join_network<S>(
+    stream: &mut S,
+    token: &InviteToken,
+    static_key: &StaticKey,
+) -> Result<NetworkKey, InviteError>
+where
+    S: Stream<Item = Result<Message, tungstenite::Error>>
+        + Sink<Message, Error = tungstenite::Error>
+        + Unpin,
+{
+    let security = LinkSecurity {
+        network_key: None,
+        static_key: static_key.clone(),
+        trusted_peers: vec![token.issuer],
+    };
+    let answer = tokio::time::timeout(HANDSHAKE_TIMEOUT, async {
+        stream
+            .send(Message::text(JOIN_HELLO))
+            .await
+            .map_err(Box::new)?;
+        let (mut encryptor, mut decryptor) = handshake(stream, &security, true).await?;
+        send(stream, &encryptor.seal(&token.secret)?).await?;
+        decryptor.open(&receive(stream).await?)
+    })
+    .await
+    .map_err(|_| SecureError::Timeout)??;
+
+    // an empty answer is a refusal
+    Ok(answer.try_into().map_err(|_| SecureError::InviteRefused)?)
+}
+
+/// Answers a pod joining with an invitation, giving it the network key if the invitation is pending
+pub(super) async fn serve_join<S>(
+    stream: &mut S,
+    security: &LinkSecurity,
+    invites: &Invites,
+) -> Result<(), SecureError>
+where
+    S: Stream<Item = Result<Message, tungstenite::Error>>
+        + Sink<Message, Error = tungstenite::Error>
+        + Unpin,
+{
+    // the joining pod isn't known yet, the invitation is what it proves
+    let join_security = LinkSecurity {
+        network_key: None,
+        static_key: security.static_key.clone(),
+        trusted_peers: Vec::new(),
+    };
+    let (mut encryptor, mut decryptor) = handshake(stream, &join_security, false).await?;
+    let secret = decryptor.open(&receive(stream).await?)?;
+
+    let accepted = match invites.consume(&secret) {
+        Ok(accepted) => accepted,
+        Err(e) => {
+            log::error!("Can't check an invitation: {e}");
+            false
+        }
+    };
+    let answer = match (&security.network_key, accepted) {
+        (Some(key), true) => key.to_vec(),
+        _ => Vec::new(),
+    };
+    send(stream, &encryptor.seal(&answer)?).await?;
+    if answer.is_empty() {
+        Err(SecureError::InviteRefused)
+    } else {
+        Ok(())
+    }
+}
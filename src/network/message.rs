@@ -1,7 +1,4 @@
-use std::{
-    fmt::{self, Debug},
-    sync::Arc,
-};
+use std::fmt::{self, Debug};
 
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
@@ -9,7 +6,15 @@ use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
     error::WhResult,
-    pods::arbo::{ArboIndex, Inode, InodeId, Metadata},
+    pods::{
+        arbo::{ArboDigests, ArboIndex, Inode, InodeId, Metadata},
+        filesystem::conflict::VersionVector,
+        network::{
+            erasure::ShardLayout,
+            gossip::GossipEntry,
+            journal::{JournalEntry, JournalFrontier},
+        },
+    },
 };
 
 /// Message Content
@@ -20,47 +25,96 @@ pub enum MessageContent {
     Register(Address),
     Remove(InodeId),
     Inode(Inode),
-    RequestFile(InodeId, Address),
-    PullAnswer(InodeId, Vec<u8>),
-    RedundancyFile(InodeId, Arc<Vec<u8>>),
+    /// Inode, requesting peer, offset to resume from
+    RequestFile(InodeId, Address, u64),
+    PullAnswer(FileChunk),
+    RedundancyFile(FileChunk),
+    /// Inode, end offset of the received data, accepted
+    ChunkAck(InodeId, u64, bool),
+    /// Inode, offset, length
+    RequestRange(InodeId, u64, u64),
+    /// Inode, offset, data (None if the host can't read it)
+    RangeAnswer(InodeId, u64, Option<Vec<u8>>),
     /// Parent, New Parent, Name, New Name, overwrite
     Rename(InodeId, InodeId, String, String, bool),
     EditHosts(InodeId, Vec<Address>),
-    RevokeFile(InodeId, Address, Metadata),
+    /// Inode, writer, new metadata, new version
+    RevokeFile(InodeId, Address, Metadata, VersionVector),
     AddHosts(InodeId, Vec<Address>),
     RemoveHosts(InodeId, Vec<Address>),
     EditMetadata(InodeId, Metadata),
     SetXAttr(InodeId, String, Vec<u8>),
     RemoveXAttr(InodeId, String),
     RequestFs,
+    /// Asks for the subtrees that changed since a saved arbo, see [crate::pods::arbo::Arbo::diff_from]
+    RequestFsDiff(ArboDigests),
     Disconnect(Address),
+    /// Mutation made by a pod, see [crate::pods::network::journal]
+    Journal(JournalEntry),
+    /// Asks a pod for its journal entries made after this sequence number
+    RequestJournal(u64),
+    JournalEntries(Vec<JournalEntry>),
+    /// Status of the pods known by the sender, see [crate::pods::network::gossip]
+    Gossip(Vec<GossipEntry>),
+    /// Shard index, part of a shard given to the receiver to hold, acknowledged with a [MessageContent::ChunkAck]
+    StoreShard(usize, FileChunk),
+    /// Inode, shard index
+    RequestShard(InodeId, usize),
+    /// Shard index, part of a shard asked with a [MessageContent::RequestShard]
+    ShardAnswer(usize, FileChunk),
+    /// Inode, shard index, answered when the pod can't send the shard asked
+    ShardMissing(InodeId, usize),
+    /// Inode, shard indexes, stored by the receiver but not used in the end
+    DropShards(InodeId, Vec<usize>),
+    /// Where the shards of a file are, None once the file isn't erasure coded anymore
+    EditShards(InodeId, Option<ShardLayout>),
 
     // (Arbo, peers, global_config)
     FsAnswer(FileSystemSerialized, Vec<Address>, Vec<u8>),
 }
 
-impl fmt::Display for MessageContent {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let name = match self {
+impl MessageContent {
+    /// Name of the variant, without its content
+    pub fn kind(&self) -> &'static str {
+        match self {
             MessageContent::Register(_) => "Register",
             MessageContent::Remove(_) => "Remove",
             MessageContent::Inode(_) => "Inode",
-            MessageContent::RequestFile(_, _) => "RequestFile",
-            MessageContent::PullAnswer(_, _) => "PullAnswer",
+            MessageContent::RequestFile(_, _, _) => "RequestFile",
+            MessageContent::PullAnswer(_) => "PullAnswer",
+            MessageContent::ChunkAck(_, _, _) => "ChunkAck",
+            MessageContent::RequestRange(_, _, _) => "RequestRange",
+            MessageContent::RangeAnswer(_, _, _) => "RangeAnswer",
             MessageContent::Rename(_, _, _, _, _) => "Rename",
             MessageContent::EditHosts(_, _) => "EditHosts",
-            MessageContent::RevokeFile(_, _, _) => "RevokeFile",
+            MessageContent::RevokeFile(_, _, _, _) => "RevokeFile",
             MessageContent::AddHosts(_, _) => "AddHosts",
             MessageContent::RemoveHosts(_, _) => "RemoveHosts",
             MessageContent::EditMetadata(_, _) => "EditMetadata",
             MessageContent::SetXAttr(_, _, _) => "SetXAttr",
             MessageContent::RemoveXAttr(_, _) => "RemoveXAttr",
             MessageContent::RequestFs => "RequestFs",
+            MessageContent::RequestFsDiff(_) => "RequestFsDiff",
             MessageContent::FsAnswer(_, _, _) => "FsAnswer",
-            MessageContent::RedundancyFile(_, _) => "RedundancyFile",
+            MessageContent::RedundancyFile(_) => "RedundancyFile",
             MessageContent::Disconnect(_) => "Disconnect",
-        };
-        write!(f, "{}", name)
+            MessageContent::Journal(_) => "Journal",
+            MessageContent::RequestJournal(_) => "RequestJournal",
+            MessageContent::JournalEntries(_) => "JournalEntries",
+            MessageContent::Gossip(_) => "Gossip",
+            MessageContent::StoreShard(_, _) => "StoreShard",
+            MessageContent::RequestShard(_, _) => "RequestShard",
+            MessageContent::ShardAnswer(_, _) => "ShardAnswer",
+            MessageContent::ShardMissing(_, _) => "ShardMissing",
+            MessageContent::DropShards(_, _) => "DropShards",
+            MessageContent::EditShards(_, _) => "EditShards",
+        }
+    }
+}
+
+impl fmt::Display for MessageContent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind())
     }
 }
 
@@ -76,22 +130,41 @@ impl fmt::Debug for MessageContent {
                 match inode.entry {
                     crate::pods::arbo::FsEntry::File(_) => 'f',
                     crate::pods::arbo::FsEntry::Directory(_) => 'd',
+                    crate::pods::arbo::FsEntry::Symlink(_) => 'l',
+                    crate::pods::arbo::FsEntry::HardLink(_) => 'h',
+                    crate::pods::arbo::FsEntry::Special(_) => 's',
                 }
             ),
-            MessageContent::RedundancyFile(id, _) => write!(f, "RedundancyFile({id}, <bin>)"),
+            MessageContent::RedundancyFile(chunk) => write!(f, "RedundancyFile({chunk:?})"),
             MessageContent::FsAnswer(_, peers, _) => write!(f, "FsAnswer(<bin>, {peers:?}, <bin>"),
-            MessageContent::PullAnswer(id, _) => write!(f, "PullAnswer({id}, <bin>)"),
+            MessageContent::PullAnswer(chunk) => write!(f, "PullAnswer({chunk:?})"),
+            MessageContent::ChunkAck(id, offset, accepted) => {
+                write!(f, "ChunkAck({id}, {offset}, accepted: {accepted})")
+            }
+            MessageContent::RequestRange(id, offset, len) => {
+                write!(f, "RequestRange({id}, {offset}..{})", offset + len)
+            }
+            MessageContent::RangeAnswer(id, offset, data) => match data {
+                Some(data) => write!(
+                    f,
+                    "RangeAnswer({id}, {offset}..{}, <bin>)",
+                    offset + data.len() as u64
+                ),
+                None => write!(f, "RangeAnswer({id}, {offset}, unreadable)"),
+            },
             MessageContent::Register(address) => write!(f, "Register({address})"),
             MessageContent::Remove(id) => write!(f, "Remove({id})"),
-            MessageContent::RequestFile(id, y) => write!(f, "RequestFile({id}, {y})"),
+            MessageContent::RequestFile(id, y, offset) => {
+                write!(f, "RequestFile({id}, {y}, from: {offset})")
+            }
             MessageContent::Rename(parent, new_parent, name, new_name, overwrite) => write!(
                 f,
                 "Rename(parent: {}, new_parent: {}, name: {}, new_name: {}, overwrite: {})",
                 parent, new_parent, name, new_name, overwrite
             ),
             MessageContent::EditHosts(id, hosts) => write!(f, "EditHosts({id}, {hosts:?})"),
-            MessageContent::RevokeFile(id, address, _) => {
-                write!(f, "RevokeFile({id}, {address}, <metadata>)")
+            MessageContent::RevokeFile(id, address, _, version) => {
+                write!(f, "RevokeFile({id}, {address}, <metadata>, {version:?})")
             }
             MessageContent::AddHosts(id, hosts) => write!(f, "AddHosts({id}, {hosts:?})"),
             MessageContent::RemoveHosts(id, hosts) => write!(f, "RemoveHosts({id}, {hosts:?})"),
@@ -105,11 +178,99 @@ impl fmt::Debug for MessageContent {
             ),
             MessageContent::RemoveXAttr(id, name) => write!(f, "RemoveXAttr({id}, {name})"),
             MessageContent::RequestFs => write!(f, "RequestFs"),
+            MessageContent::RequestFsDiff(digests) => {
+                write!(f, "RequestFsDiff(<{} directories>)", digests.len())
+            }
             MessageContent::Disconnect(address) => write!(f, "Disconnect({address})"),
+            MessageContent::Journal(entry) => write!(
+                f,
+                "Journal({}, {}, {:?})",
+                entry.origin, entry.seq, entry.content
+            ),
+            MessageContent::RequestJournal(seq) => write!(f, "RequestJournal(after: {seq})"),
+            MessageContent::JournalEntries(entries) => match (entries.first(), entries.last()) {
+                (Some(first), Some(last)) => write!(
+                    f,
+                    "JournalEntries({}, {}..={})",
+                    first.origin, first.seq, last.seq
+                ),
+                _ => write!(f, "JournalEntries(<empty>)"),
+            },
+            MessageContent::Gossip(entries) => write!(f, "Gossip(<{} pods>)", entries.len()),
+            MessageContent::StoreShard(index, chunk) => {
+                write!(f, "StoreShard({index}, {chunk:?})")
+            }
+            MessageContent::RequestShard(id, index) => write!(f, "RequestShard({id}, {index})"),
+            MessageContent::ShardAnswer(index, chunk) => {
+                write!(f, "ShardAnswer({index}, {chunk:?})")
+            }
+            MessageContent::ShardMissing(id, index) => write!(f, "ShardMissing({id}, {index})"),
+            MessageContent::DropShards(id, indexes) => write!(f, "DropShards({id}, {indexes:?})"),
+            MessageContent::EditShards(id, layout) => write!(
+                f,
+                "EditShards({id}, {:?})",
+                layout.as_ref().map(|layout| &layout.holders)
+            ),
         }
     }
 }
 
+impl MessageContent {
+    /// Whether the message changes the filesystem, and so is kept in the journal of the pod sending it
+    pub fn is_mutation(&self) -> bool {
+        matches!(
+            self,
+            MessageContent::Remove(_)
+                | MessageContent::Inode(_)
+                | MessageContent::Rename(_, _, _, _, _)
+                | MessageContent::EditHosts(_, _)
+                | MessageContent::RevokeFile(_, _, _, _)
+                | MessageContent::AddHosts(_, _)
+                | MessageContent::RemoveHosts(_, _)
+                | MessageContent::EditMetadata(_, _)
+                | MessageContent::SetXAttr(_, _, _)
+                | MessageContent::RemoveXAttr(_, _)
+                | MessageContent::EditShards(_, _)
+        )
+    }
+}
+
+/// Part of a file sent through the network
+/// Files are streamed as a sequence of chunks, see [crate::pods::network::transfer]
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FileChunk {
+    pub ino: InodeId,
+    /// Position of `data` in the file
+    pub offset: u64,
+    /// Size of the complete file
+    pub total_size: u64,
+    pub data: Vec<u8>,
+}
+
+impl FileChunk {
+    /// Offset right after this chunk
+    pub fn end(&self) -> u64 {
+        self.offset + self.data.len() as u64
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.end() >= self.total_size
+    }
+}
+
+impl fmt::Debug for FileChunk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}, {}..{}/{}, <bin>",
+            self.ino,
+            self.offset,
+            self.end(),
+            self.total_size
+        )
+    }
+}
+
 pub type MessageAndStatus = (MessageContent, Option<UnboundedSender<WhResult<()>>>);
 
 pub type Address = String;
@@ -160,5 +321,9 @@ impl fmt::Display for ToNetworkMessage {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct FileSystemSerialized {
     pub fs_index: ArboIndex,
+    /// fs_index only holds the subtrees that changed, see [crate::pods::arbo::Arbo::apply_diff]
+    pub incremental: bool,
     pub next_inode: InodeId,
+    /// Journal entries already applied to this arbo
+    pub journal_frontier: JournalFrontier,
 }
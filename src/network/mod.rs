@@ -1,7 +1,9 @@
 pub mod forward;
+pub mod invite;
 pub mod ip;
 pub mod message;
 pub mod peer_ipc;
+pub mod secure;
 // pub mod peers_operations;
 pub mod server;
 // pub mod watchdogs;
@@ -1,7 +1,16 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
 use futures_util::{
     stream::{SplitSink, SplitStream},
     StreamExt,
 };
+use serde::{Deserialize, Serialize};
 use tokio::{
     net::TcpStream,
     sync::mpsc::{self, UnboundedSender},
@@ -12,27 +21,142 @@ use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 use crate::network::forward::{forward_read_to_sender, forward_receiver_to_write};
 
 use super::message::{Address, FromNetworkMessage, MessageAndStatus};
+use super::secure::{handshake_initiator, Decryptor, Encryptor, LinkSecurity};
+
+/// Interval between two pings on an idle link
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// Silence after which a peer is suspected gone
+pub const SUSPECT_AFTER: Duration = Duration::from_secs(15);
+/// Silence after which a peer is considered gone and its link dropped
+pub const DOWN_AFTER: Duration = Duration::from_secs(30);
+/// First delay before dialing a lost peer again, doubled on each failure
+pub const RECONNECT_MIN_DELAY: Duration = Duration::from_secs(1);
+pub const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PeerState {
+    /// Being dialed again after going down
+    Connecting,
+    Up,
+    /// Silent for more than [SUSPECT_AFTER], still used
+    Suspect,
+    /// Link closed or silent for more than [DOWN_AFTER], nothing is sent to it
+    Down,
+}
+
+/// Shared between a link and its supervisor, to tell how long the peer has been silent
+#[derive(Debug)]
+pub struct Liveness {
+    since: Instant,
+    /// Milliseconds between `since` and the last frame received
+    last_seen: AtomicU64,
+    closed: AtomicBool,
+}
+
+impl Liveness {
+    fn new() -> Self {
+        Self {
+            since: Instant::now(),
+            last_seen: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn seen(&self) {
+        self.last_seen
+            .store(self.since.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+
+    /// State of the link according to the last frame received
+    pub fn state(&self) -> PeerState {
+        let silence = self.since.elapsed().saturating_sub(Duration::from_millis(
+            self.last_seen.load(Ordering::Relaxed),
+        ));
+
+        if self.closed.load(Ordering::Relaxed) || silence > DOWN_AFTER {
+            PeerState::Down
+        } else if silence > SUSPECT_AFTER {
+            PeerState::Suspect
+        } else {
+            PeerState::Up
+        }
+    }
+}
+
+/// Exponential backoff between the attempts to dial a lost peer
+#[derive(Debug, Clone)]
+pub struct Reconnection {
+    pub attempts: u32,
+    pub next_try: Instant,
+}
+
+impl Default for Reconnection {
+    fn default() -> Self {
+        Self {
+            attempts: 0,
+            next_try: Instant::now(),
+        }
+    }
+}
+
+impl Reconnection {
+    pub fn failed(&mut self) {
+        let delay = RECONNECT_MIN_DELAY
+            .saturating_mul(1 << self.attempts.min(16))
+            .min(RECONNECT_MAX_DELAY);
+        self.attempts += 1;
+        self.next_try = Instant::now() + delay;
+    }
+}
 
 #[derive(Debug)]
 pub struct PeerIPC {
     pub address: Address,
     pub thread: tokio::task::JoinHandle<()>,
     pub sender: mpsc::UnboundedSender<MessageAndStatus>, // send a message to the peer
-                                                         // pub receiver: mpsc::Receiver<NetworkMessage>, // receive a message from the peer
+    // pub receiver: mpsc::Receiver<NetworkMessage>, // receive a message from the peer
+    pub liveness: Arc<Liveness>,
+    /// Last state seen by the supervisor, see [crate::pods::network::supervisor]
+    pub state: PeerState,
+    pub reconnection: Reconnection,
 }
 
 impl PeerIPC {
+    fn new(
+        address: Address,
+        thread: tokio::task::JoinHandle<()>,
+        sender: mpsc::UnboundedSender<MessageAndStatus>,
+        liveness: Arc<Liveness>,
+    ) -> Self {
+        Self {
+            address,
+            thread,
+            sender,
+            liveness,
+            state: PeerState::Up,
+            reconnection: Reconnection::default(),
+        }
+    }
+
     async fn work(
         stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
         sender: mpsc::UnboundedSender<FromNetworkMessage>,
         mut receiver: mpsc::UnboundedReceiver<MessageAndStatus>,
         address: Address,
+        (encryptor, decryptor): (Encryptor, Decryptor),
+        liveness: Arc<Liveness>,
     ) {
         let (write, read) = stream.split();
-        tokio::join!(
-            forward_read_to_sender(read, sender, address),
-            forward_receiver_to_write(write, &mut receiver)
-        );
+        // the link is over as soon as one way breaks
+        tokio::select! {
+            _ = forward_read_to_sender(read, sender, address, decryptor, &liveness) => (),
+            _ = forward_receiver_to_write(write, &mut receiver, encryptor) => (),
+        }
+        liveness.close();
     }
 
     async fn work_from_incomming(
@@ -41,63 +165,82 @@ impl PeerIPC {
         sender: mpsc::UnboundedSender<FromNetworkMessage>,
         mut receiver: mpsc::UnboundedReceiver<MessageAndStatus>,
         address: Address,
+        (encryptor, decryptor): (Encryptor, Decryptor),
+        liveness: Arc<Liveness>,
     ) {
-        tokio::join!(
-            forward_read_to_sender(read, sender, address),
-            forward_receiver_to_write(write, &mut receiver)
-        );
+        tokio::select! {
+            _ = forward_read_to_sender(read, sender, address, decryptor, &liveness) => (),
+            _ = forward_receiver_to_write(write, &mut receiver, encryptor) => (),
+        }
+        liveness.close();
     }
 
+    /// The link must already be secured, see [super::secure::handshake_responder]
     pub fn connect_from_incomming(
         address: Address,
         on_recept: UnboundedSender<FromNetworkMessage>,
         write: SplitSink<WebSocketStream<TcpStream>, Message>,
         read: SplitStream<WebSocketStream<TcpStream>>,
+        keys: (Encryptor, Decryptor),
     ) -> Self {
         let (peer_send, peer_recv) = mpsc::unbounded_channel();
-        Self {
-            thread: tokio::spawn(Self::work_from_incomming(
-                write,
-                read,
-                on_recept,
-                peer_recv,
-                address.clone(),
-            )),
-            address,
-            sender: peer_send,
-        }
+        let liveness = Arc::new(Liveness::new());
+        let thread = tokio::spawn(Self::work_from_incomming(
+            write,
+            read,
+            on_recept,
+            peer_recv,
+            address.clone(),
+            keys,
+            liveness.clone(),
+        ));
+        Self::new(address, thread, peer_send, liveness)
     }
 
     pub async fn connect(
         address: Address,
         nfa_tx: UnboundedSender<FromNetworkMessage>,
+        security: &LinkSecurity,
     ) -> Option<Self> {
         let (peer_send, peer_recv) = mpsc::unbounded_channel();
 
-        let thread = match tokio_tungstenite::connect_async("ws://".to_string() + &address).await {
-            Ok((stream, _)) => tokio::spawn(Self::work(stream, nfa_tx, peer_recv, address.clone())),
+        let mut stream =
+            match tokio_tungstenite::connect_async("ws://".to_string() + &address).await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    log::warn!("failed to connect to {}. Error: {}", address, e);
+                    return None;
+                }
+            };
+        let keys = match handshake_initiator(&mut stream, security).await {
+            Ok(keys) => keys,
             Err(e) => {
-                log::warn!("failed to connect to {}. Error: {}", address, e);
+                log::warn!("failed to secure the link to {}. Error: {}", address, e);
                 return None;
             }
         };
-        Some(Self {
-            thread,
-            address,
-            sender: peer_send,
-            // receiver: inbound_recv,
-        })
+        let liveness = Arc::new(Liveness::new());
+        let thread = tokio::spawn(Self::work(
+            stream,
+            nfa_tx,
+            peer_recv,
+            address.clone(),
+            keys,
+            liveness.clone(),
+        ));
+        Some(Self::new(address, thread, peer_send, liveness))
     }
 
     // start connexions to peers
     pub async fn peer_startup(
         peers_ip_list: Vec<Address>,
         from_network_message_tx: UnboundedSender<FromNetworkMessage>,
+        security: &LinkSecurity,
     ) -> Vec<PeerIPC> {
         futures_util::future::join_all(
             peers_ip_list
                 .into_iter()
-                .map(|ip| PeerIPC::connect(ip, from_network_message_tx.clone())), // .filter(|peer| !peer.thread.is_finished())
+                .map(|ip| PeerIPC::connect(ip, from_network_message_tx.clone(), security)), // .filter(|peer| !peer.thread.is_finished())
         )
         .await
         .into_iter()
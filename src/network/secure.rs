@@ -0,0 +1,371 @@
+use std::{fmt, fs, io, path::Path, sync::Arc, time::Duration};
+
+use custom_error::custom_error;
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
+use snow::{HandshakeState, StatelessTransportState};
+use tokio_tungstenite::tungstenite::{self, Message};
+
+use super::invite::{serve_join, Invites};
+
+/// Secret shared by every pod of a network, see [crate::config::types::SecurityConfig]
+pub type NetworkKey = [u8; 32];
+/// Public part of the [StaticKey] of a pod
+pub type PublicKey = [u8; 32];
+
+/// Used when the network has a key: both sides prove they know it
+const NOISE_PARAMS_PSK: &str = "Noise_XXpsk3_25519_ChaChaPoly_BLAKE2s";
+/// Used when the network has no key: the peers are trusted from their static key
+const NOISE_PARAMS: &str = "Noise_XX_25519_ChaChaPoly_BLAKE2s";
+
+/// Maximum size of a Noise message
+const NOISE_MAX_LEN: usize = 65535;
+const NOISE_TAG_LEN: usize = 16;
+/// Maximum size of the plaintext encrypted in one Noise message
+const NOISE_MAX_PLAINTEXT: usize = NOISE_MAX_LEN - NOISE_TAG_LEN;
+
+pub(super) const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// First message of a link opened between two pods of the network
+const LINK_HELLO: &str = "link";
+/// First message of a link opened by a pod joining with an invitation, see [super::invite]
+pub(super) const JOIN_HELLO: &str = "join";
+
+custom_error! {pub SecureError
+    Noise{source: snow::Error} = "Noise error: {source}",
+    WebSocket{source: Box<tungstenite::Error>} = "WebSocket error: {source}",
+    Closed = "Connection closed during the handshake",
+    UnexpectedMessage = "Unexpected message during the handshake",
+    Timeout = "Handshake timed out",
+    InvalidKey = "The network key must be 64 hexadecimal characters",
+    InvalidPublicKey = "A trusted peer must be given by its public key, 64 hexadecimal characters",
+    KeyFile{io: io::Error} = "Can't load the static key of the pod: {io}",
+    NoTrust = "Neither a network key nor trusted peers are configured, links are refused",
+    UntrustedPeer{key: String} = "The peer's static key {key} isn't trusted",
+    InviteRefused = "The invitation is unknown, already used or revoked",
+}
+
+pub fn parse_network_key(key: &str) -> Result<NetworkKey, SecureError> {
+    hex::decode(key)
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .ok_or(SecureError::InvalidKey)
+}
+
+/// 32 random bytes
+pub(super) fn random_key() -> Result<[u8; 32], SecureError> {
+    // a x25519 private key is 32 random bytes
+    let keypair = snow::Builder::new(NOISE_PARAMS.parse()?).generate_keypair()?;
+    keypair
+        .private
+        .try_into()
+        .map_err(|_| SecureError::InvalidKey)
+}
+
+/// Generates a new random network key, encoded as written in the configuration
+pub fn generate_network_key() -> Result<String, SecureError> {
+    Ok(hex::encode(random_key()?))
+}
+
+pub fn parse_public_key(key: &str) -> Result<PublicKey, SecureError> {
+    hex::decode(key)
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .ok_or(SecureError::InvalidPublicKey)
+}
+
+/// x25519 key pair identifying a pod on its links, kept across restarts
+#[derive(Clone)]
+pub struct StaticKey {
+    private: Vec<u8>,
+    pub public: PublicKey,
+}
+
+impl fmt::Debug for StaticKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "StaticKey({})", hex::encode(self.public))
+    }
+}
+
+impl StaticKey {
+    pub fn generate() -> Result<Self, SecureError> {
+        let keypair = snow::Builder::new(NOISE_PARAMS.parse()?).generate_keypair()?;
+        Ok(Self {
+            public: keypair
+                .public
+                .try_into()
+                .map_err(|_| SecureError::InvalidPublicKey)?,
+            private: keypair.private,
+        })
+    }
+
+    /// Reads the key saved at `path`, generating and saving one on first use
+    ///
+    /// The file holds the private then the public key, in hexadecimal, one per line.
+    pub fn load_or_create(path: &Path) -> Result<Self, SecureError> {
+        match fs::read_to_string(path) {
+            Ok(saved) => {
+                let mut lines = saved.lines();
+                let private = lines.next().and_then(|key| parse_network_key(key).ok());
+                let public = lines.next().and_then(|key| parse_public_key(key).ok());
+                match (private, public) {
+                    (Some(private), Some(public)) => Ok(Self {
+                        private: private.to_vec(),
+                        public,
+                    }),
+                    _ => Err(SecureError::KeyFile {
+                        io: io::Error::new(io::ErrorKind::InvalidData, "malformed key file"),
+                    }),
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let key = Self::generate()?;
+                key.save(path).map_err(|io| SecureError::KeyFile { io })?;
+                Ok(key)
+            }
+            Err(io) => Err(SecureError::KeyFile { io }),
+        }
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        // only the pod may read its private key
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        io::Write::write_all(
+            &mut options.open(path)?,
+            format!(
+                "{}\n{}\n",
+                hex::encode(&self.private),
+                hex::encode(self.public)
+            )
+            .as_bytes(),
+        )
+    }
+}
+
+/// What a pod checks to accept a link
+#[derive(Debug, Clone)]
+pub struct LinkSecurity {
+    pub network_key: Option<NetworkKey>,
+    pub static_key: StaticKey,
+    /// Static keys of the pods allowed to link, any pod knowing the network key if empty
+    pub trusted_peers: Vec<PublicKey>,
+}
+
+impl LinkSecurity {
+    /// Fails if no link could be trusted
+    pub fn check(&self) -> Result<(), SecureError> {
+        if self.network_key.is_none() && self.trusted_peers.is_empty() {
+            Err(SecureError::NoTrust)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn trusts(&self, remote: Option<&[u8]>) -> Result<(), SecureError> {
+        match remote {
+            _ if self.trusted_peers.is_empty() => Ok(()),
+            Some(remote) if self.trusted_peers.iter().any(|key| key == remote) => Ok(()),
+            remote => Err(SecureError::UntrustedPeer {
+                key: remote.map(hex::encode).unwrap_or_default(),
+            }),
+        }
+    }
+}
+
+/// Encrypts the messages sent to a peer
+#[derive(Debug)]
+pub struct Encryptor {
+    transport: Arc<StatelessTransportState>,
+    nonce: u64,
+}
+
+/// Decrypts the messages received from a peer
+#[derive(Debug)]
+pub struct Decryptor {
+    transport: Arc<StatelessTransportState>,
+    nonce: u64,
+}
+
+impl Encryptor {
+    /// Plaintexts bigger than a Noise message are split,
+    /// every piece but the last one giving a full size Noise message
+    pub fn seal(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, SecureError> {
+        let mut sealed = Vec::with_capacity(
+            plaintext.len() + (plaintext.len() / NOISE_MAX_PLAINTEXT + 1) * NOISE_TAG_LEN,
+        );
+        let mut buf = vec![0; NOISE_MAX_LEN];
+
+        // an empty plaintext still gives a (tag only) message
+        let pieces: Vec<&[u8]> = if plaintext.is_empty() {
+            vec![plaintext]
+        } else {
+            plaintext.chunks(NOISE_MAX_PLAINTEXT).collect()
+        };
+        for piece in pieces {
+            let len = self.transport.write_message(self.nonce, piece, &mut buf)?;
+            self.nonce += 1;
+            sealed.extend_from_slice(&buf[..len]);
+        }
+        Ok(sealed)
+    }
+}
+
+impl Decryptor {
+    pub fn open(&mut self, sealed: &[u8]) -> Result<Vec<u8>, SecureError> {
+        let mut plaintext = Vec::with_capacity(sealed.len());
+        let mut buf = vec![0; NOISE_MAX_LEN];
+
+        for piece in sealed.chunks(NOISE_MAX_LEN) {
+            let len = self.transport.read_message(self.nonce, piece, &mut buf)?;
+            self.nonce += 1;
+            plaintext.extend_from_slice(&buf[..len]);
+        }
+        Ok(plaintext)
+    }
+}
+
+fn new_handshake(security: &LinkSecurity, initiator: bool) -> Result<HandshakeState, SecureError> {
+    let params = match security.network_key {
+        Some(_) => NOISE_PARAMS_PSK,
+        None => NOISE_PARAMS,
+    }
+    .parse()?;
+    let mut builder = snow::Builder::new(params).local_private_key(&security.static_key.private);
+    if let Some(key) = &security.network_key {
+        builder = builder.psk(3, key);
+    }
+
+    Ok(if initiator {
+        builder.build_initiator()?
+    } else {
+        builder.build_responder()?
+    })
+}
+
+pub(super) async fn send<S>(stream: &mut S, payload: &[u8]) -> Result<(), SecureError>
+where
+    S: Sink<Message, Error = tungstenite::Error> + Unpin,
+{
+    Ok(stream
+        .send(Message::binary(payload.to_vec()))
+        .await
+        .map_err(Box::new)?)
+}
+
+pub(super) async fn receive<S>(stream: &mut S) -> Result<Vec<u8>, SecureError>
+where
+    S: Stream<Item = Result<Message, tungstenite::Error>> + Unpin,
+{
+    match stream.next().await {
+        Some(Ok(Message::Binary(payload))) => Ok(payload),
+        Some(Ok(_)) => Err(SecureError::UnexpectedMessage),
+        Some(Err(e)) => Err(Box::new(e).into()),
+        None => Err(SecureError::Closed),
+    }
+}
+
+pub(super) async fn handshake<S>(
+    stream: &mut S,
+    security: &LinkSecurity,
+    initiator: bool,
+) -> Result<(Encryptor, Decryptor), SecureError>
+where
+    S: Stream<Item = Result<Message, tungstenite::Error>>
+        + Sink<Message, Error = tungstenite::Error>
+        + Unpin,
+{
+    let mut noise = new_handshake(security, initiator)?;
+    let mut buf = vec![0; NOISE_MAX_LEN];
+
+    // XX pattern: -> e / <- e, ee, s, es / -> s, se (, psk)
+    while !noise.is_handshake_finished() {
+        if noise.is_my_turn() {
+            let len = noise.write_message(&[], &mut buf)?;
+            send(stream, &buf[..len]).await?;
+        } else {
+            let message = receive(stream).await?;
+            noise.read_message(&message, &mut buf)?;
+            // an untrusted peer is dropped before the handshake ends on its side
+            if noise.get_remote_static().is_some() {
+                security.trusts(noise.get_remote_static())?;
+            }
+        }
+    }
+    security.trusts(noise.get_remote_static())?;
+
+    let transport = Arc::new(noise.into_stateless_transport_mode()?);
+    let mut encryptor = Encryptor {
+        transport: transport.clone(),
+        nonce: 0,
+    };
+    let mut decryptor = Decryptor {
+        transport,
+        nonce: 0,
+    };
+
+    // the responder is the last to check the key, it confirms the link to the initiator
+    if initiator {
+        decryptor.open(&receive(stream).await?)?;
+    } else {
+        send(stream, &encryptor.seal(&[])?).await?;
+    }
+    Ok((encryptor, decryptor))
+}
+
+/// Secures a link this node opened.
+/// Fails if the peer doesn't know the network key or isn't trusted.
+pub async fn handshake_initiator<S>(
+    stream: &mut S,
+    security: &LinkSecurity,
+) -> Result<(Encryptor, Decryptor), SecureError>
+where
+    S: Stream<Item = Result<Message, tungstenite::Error>>
+        + Sink<Message, Error = tungstenite::Error>
+        + Unpin,
+{
+    security.check()?;
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, async {
+        stream
+            .send(Message::text(LINK_HELLO))
+            .await
+            .map_err(Box::new)?;
+        handshake(stream, security, true).await
+    })
+    .await
+    .map_err(|_| SecureError::Timeout)?
+}
+
+/// Secures a link opened by another node.
+/// Fails if the peer doesn't know the network key or isn't trusted.
+///
+/// A pod joining with an invitation is given the network key if `invites` holds its invitation,
+/// the link is then over and `None` is returned.
+pub async fn handshake_responder<S>(
+    stream: &mut S,
+    security: &LinkSecurity,
+    invites: &Invites,
+) -> Result<Option<(Encryptor, Decryptor)>, SecureError>
+where
+    S: Stream<Item = Result<Message, tungstenite::Error>>
+        + Sink<Message, Error = tungstenite::Error>
+        + Unpin,
+{
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, async {
+        match stream.next().await {
+            Some(Ok(Message::Text(hello))) if hello == LINK_HELLO => {
+                security.check()?;
+                handshake(stream, security, false).await.map(Some)
+            }
+            Some(Ok(Message::Text(hello))) if hello == JOIN_HELLO => {
+                serve_join(stream, security, invites).await.map(|_| None)
+            }
+            Some(Ok(_)) => Err(SecureError::UnexpectedMessage),
+            Some(Err(e)) => Err(Box::new(e).into()),
+            None => Err(SecureError::Closed),
+        }
+    })
+    .await
+    .map_err(|_| SecureError::Timeout)?
+}
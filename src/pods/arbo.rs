@@ -1,8 +1,9 @@
 use crate::{error::WhResult, network::message::Address};
 use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs, io,
     sync::Arc,
     time::{Duration, SystemTime},
@@ -11,11 +12,19 @@ use std::{
 #[cfg(target_os = "linux")]
 use std::os::unix::fs::{MetadataExt, PermissionsExt};
 
+use crate::config::types::IgnoreRules;
+use crate::data::metrics::METRICS;
 use crate::error::WhError;
 use crate::pods::filesystem::fs_interface::SimpleFileType;
 use crate::pods::whpath::WhPath;
 
-use super::filesystem::{make_inode::MakeInodeError, remove_inode::RemoveInodeError};
+use super::{
+    filesystem::{
+        conflict::VersionVector, integrity::ContentHash, make_inode::MakeInodeError,
+        remove_inode::RemoveInodeError,
+    },
+    network::erasure::ShardLayout,
+};
 
 // SECTION consts
 
@@ -33,6 +42,30 @@ pub const LOCAL_CONFIG_INO: u64 = 3;
 pub const LOCAL_CONFIG_FNAME: &str = ".local_config.toml";
 pub const ARBO_FILE_INO: u64 = 4;
 pub const ARBO_FILE_FNAME: &str = ".arbo";
+/// Start of the [ARBO_FILE_FNAME] files, followed by their format version
+const ARBO_FILE_MAGIC: [u8; 8] = *b"wharbo\0\0";
+/// Version of the [ARBO_FILE_FNAME] files written by this build, to bump when [Inode] changes.
+/// Format 0 is the one written before the files had a version, see [format_v0].
+pub const ARBO_FILE_FORMAT: u32 = 1;
+/// Journal of the pod, kept next to the files but not part of the arbo
+pub const JOURNAL_FNAME: &str = ".journal";
+/// Static key of the pod (see [crate::network::secure::StaticKey]), kept next to the files but not part of the arbo
+pub const STATIC_KEY_FNAME: &str = ".static_key";
+/// Invitations issued by the pod (see [crate::network::invite::Invites]), kept next to the files but not part of the arbo
+pub const INVITES_FNAME: &str = ".invites";
+/// Shards held by the pod, kept next to the files but not part of the arbo
+pub const SHARDS_DIR: &str = ".shards";
+/// Names the pod keeps for itself at the root of the mount point, never part of the arbo
+const RESERVED_FNAMES: [&str; 5] = [
+    ARBO_FILE_FNAME,
+    STATIC_KEY_FNAME,
+    INVITES_FNAME,
+    JOURNAL_FNAME,
+    SHARDS_DIR,
+];
+/// Inodes of the ignored paths (see [crate::config::types::GeneralGlobalConfig::ignore_paths])
+/// start here, so they never collide with the ones of the network
+pub const FIRST_LOCAL_INO: InodeId = 1 << 62;
 
 // SECTION types
 
@@ -45,6 +78,12 @@ pub type InodeId = u64;
 pub enum FsEntry {
     File(Hosts),
     Directory(Vec<InodeId>),
+    /// Target of the link, replicated as is
+    Symlink(String),
+    /// Other name of a file, which holds the data and the metadata
+    HardLink(InodeId),
+    /// Fifo, socket or device, only its metadata is shared
+    Special(SimpleFileType),
 }
 
 pub type XAttrs = HashMap<String, Vec<u8>>;
@@ -57,13 +96,22 @@ pub struct Inode {
     pub entry: FsEntry,
     pub meta: Metadata,
     pub xattrs: XAttrs,
+    pub version: VersionVector,
+    /// Where the shards of an erasure coded file are, see [crate::pods::network::erasure]
+    pub shards: Option<ShardLayout>,
 }
 
 pub type ArboIndex = HashMap<InodeId, Inode>;
 
+/// Digest of each directory's subtree, see [Arbo::directory_digests]
+pub type ArboDigests = HashMap<InodeId, ContentHash>;
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Arbo {
     entries: ArboIndex,
+    /// Hard links pointing to each file, rebuilt from the entries when they are replaced
+    #[serde(skip)]
+    links: HashMap<InodeId, Vec<InodeId>>,
 }
 
 pub const BLOCK_SIZE: u64 = 512;
@@ -91,16 +139,16 @@ impl FsEntry {
         match self {
             FsEntry::File(_) => SimpleFileType::File,
             FsEntry::Directory(_) => SimpleFileType::Directory,
+            FsEntry::Symlink(_) => SimpleFileType::Symlink,
+            FsEntry::HardLink(_) => SimpleFileType::File,
+            FsEntry::Special(kind) => kind.clone(),
         }
     }
 
     pub fn get_children(&self) -> io::Result<&Vec<InodeId>> {
         match self {
-            FsEntry::File(_) => Err(io::Error::new(
-                io::ErrorKind::Other,
-                "entry is not a directory",
-            )),
             FsEntry::Directory(children) => Ok(children),
+            _ => Err(io::Error::other("entry is not a directory")),
         }
     }
 }
@@ -109,23 +157,24 @@ impl Inode {
     pub fn new(name: String, parent_ino: InodeId, id: InodeId, entry: FsEntry, perm: u16) -> Self {
         let meta = Metadata {
             ino: id,
-            size: 0,
+            size: match &entry {
+                FsEntry::Symlink(target) => target.len() as u64,
+                _ => 0,
+            },
             blocks: 0,
             atime: SystemTime::now(),
             mtime: SystemTime::now(),
             ctime: SystemTime::now(),
             crtime: SystemTime::now(),
-            kind: match entry {
-                FsEntry::Directory(_) => SimpleFileType::Directory,
-                FsEntry::File(_) => SimpleFileType::File,
-            },
+            kind: entry.get_filetype(),
             perm,
-            nlink: 0,
+            nlink: 1,
             uid: 0,
             gid: 0,
             rdev: 0,
             blksize: BLOCK_SIZE as u32,
             flags: 0,
+            hash: None,
         };
 
         let xattrs = HashMap::new();
@@ -137,6 +186,8 @@ impl Inode {
             entry: entry,
             meta,
             xattrs,
+            version: VersionVector::new(),
+            shards: None,
         }
     }
 }
@@ -149,6 +200,7 @@ impl Arbo {
     pub fn new() -> Self {
         let mut arbo: Self = Self {
             entries: HashMap::new(),
+            links: HashMap::new(),
         };
 
         arbo.entries.insert(
@@ -174,8 +226,11 @@ impl Arbo {
                     rdev: 0,
                     blksize: 1,
                     flags: 0,
+                    hash: None,
                 },
                 xattrs: HashMap::new(),
+                version: VersionVector::new(),
+                shards: None,
             },
         );
         arbo
@@ -183,6 +238,17 @@ impl Arbo {
 
     pub fn overwrite_self(&mut self, entries: ArboIndex) {
         self.entries = entries;
+        self.index_links();
+    }
+
+    /// Rebuilds the index of [Arbo::links_to] from the entries
+    fn index_links(&mut self) {
+        self.links.clear();
+        for inode in self.entries.values() {
+            if let FsEntry::HardLink(target) = inode.entry {
+                self.links.entry(target).or_default().push(inode.id);
+            }
+        }
     }
 
     pub fn get_raw_entries(&self) -> ArboIndex {
@@ -206,12 +272,27 @@ impl Arbo {
         }
     }
 
+    /// Whether `name` is kept by the pod for itself, so it can't be created, moved or received
+    pub fn is_reserved(name: &str, parent_ino: InodeId) -> bool {
+        parent_ino == ROOT && RESERVED_FNAMES.contains(&name)
+    }
+
     pub fn is_special(ino: u64) -> bool {
         ino <= 10u64
     }
 
     pub fn is_local_only(ino: u64) -> bool {
         ino == LOCAL_CONFIG_INO // ".local_config.toml"
+            || ino >= FIRST_LOCAL_INO
+    }
+
+    /// First unused inode among the local only ones, see [FIRST_LOCAL_INO]
+    pub fn next_local_ino(&self) -> InodeId {
+        self.entries
+            .keys()
+            .filter(|ino| **ino >= FIRST_LOCAL_INO)
+            .max()
+            .map_or(FIRST_LOCAL_INO, |ino| ino + 1)
     }
 
     #[must_use]
@@ -222,6 +303,7 @@ impl Arbo {
         if let Some(arbo) = arbo.try_read_for(LOCK_TIMEOUT) {
             Ok(arbo)
         } else {
+            METRICS.lock_timed_out();
             Err(io::Error::new(
                 io::ErrorKind::WouldBlock,
                 format!("{}: unable to read_lock arbo", called_from),
@@ -234,9 +316,8 @@ impl Arbo {
         arbo: &'a Arc<RwLock<Arbo>>,
         called_from: &'a str,
     ) -> WhResult<RwLockReadGuard<'a, Arbo>> {
-        arbo.try_read_for(LOCK_TIMEOUT).ok_or(WhError::WouldBlock {
-            called_from: called_from.to_owned(),
-        })
+        arbo.try_read_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block(called_from))
     }
 
     #[must_use]
@@ -247,6 +328,7 @@ impl Arbo {
         if let Some(arbo) = arbo.try_write_for(LOCK_TIMEOUT) {
             Ok(arbo)
         } else {
+            METRICS.lock_timed_out();
             Err(io::Error::new(
                 io::ErrorKind::WouldBlock,
                 format!("{}: unable to write_lock arbo", called_from),
@@ -259,9 +341,8 @@ impl Arbo {
         arbo: &'a Arc<RwLock<Arbo>>,
         called_from: &'a str,
     ) -> WhResult<RwLockWriteGuard<'a, Arbo>> {
-        arbo.try_write_for(LOCK_TIMEOUT).ok_or(WhError::WouldBlock {
-            called_from: called_from.to_owned(),
-        })
+        arbo.try_write_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block(called_from))
     }
 
     pub fn files_hosted_only_by<'a>(
@@ -270,7 +351,6 @@ impl Arbo {
     ) -> impl Iterator<Item = &'a Inode> + use<'a> {
         self.iter()
             .filter_map(move |(_, inode)| match &inode.entry {
-                FsEntry::Directory(_) => None,
                 FsEntry::File(hosts) => {
                     if hosts.len() == 1 && hosts.contains(&host) {
                         Some(inode)
@@ -278,9 +358,44 @@ impl Arbo {
                         None
                     }
                 }
+                _ => None,
             })
     }
 
+    /// Number of files with fewer hosts than the redundancy target,
+    /// or with lost shards if they are erasure coded
+    ///
+    /// Ignored files aren't replicated anymore, they are never counted.
+    pub fn under_replicated(&self, ignored: &IgnoreRules, target: u64) -> usize {
+        self.under_replicated_by(ignored, |_| target)
+    }
+
+    /// Tells if a file has fewer hosts than `target`, or lost shards if it is erasure coded
+    pub fn lacks_hosts(inode: &Inode, target: u64) -> bool {
+        match (&inode.entry, &inode.shards) {
+            (FsEntry::File(_), Some(layout)) => layout.missing() > 0,
+            (FsEntry::File(hosts), None) => (hosts.len() as u64) < target,
+            _ => false,
+        }
+    }
+
+    /// Same as [Arbo::under_replicated], with a target given for each file
+    pub fn under_replicated_by(
+        &self,
+        ignored: &IgnoreRules,
+        target: impl Fn(InodeId) -> u64,
+    ) -> usize {
+        self.iter()
+            .filter(|(ino, inode)| {
+                !Arbo::is_local_only(**ino) && Arbo::lacks_hosts(inode, target(**ino))
+            })
+            .filter(|(ino, _)| match self.n_get_path_from_inode_id(**ino) {
+                Ok(path) => !ignored.is_ignored(&path.inner, false),
+                Err(_) => false,
+            })
+            .count()
+    }
+
     #[must_use]
     /// Insert a given [Inode] inside the local arbo
     pub fn add_inode(&mut self, inode: Inode) -> Result<(), MakeInodeError> {
@@ -297,8 +412,16 @@ impl Arbo {
                 entry: FsEntry::Directory(parent_children),
                 meta: _,
                 xattrs: _,
+                version: _,
+                shards: _,
             }) => {
                 parent_children.push(inode.id);
+                if let FsEntry::HardLink(target) = inode.entry {
+                    if let Some(target) = self.entries.get_mut(&target) {
+                        target.meta.nlink += 1;
+                    }
+                    self.links.entry(target).or_default().push(inode.id);
+                }
                 self.entries.insert(inode.id, inode);
                 Ok(())
             }
@@ -306,6 +429,48 @@ impl Arbo {
         }
     }
 
+    /// Drops one link of the file a removed [FsEntry::HardLink] pointed to
+    fn unlink_target(&mut self, removed: &Inode) {
+        if let FsEntry::HardLink(target) = removed.entry {
+            if let Some(target) = self.entries.get_mut(&target) {
+                target.meta.nlink = target.meta.nlink.saturating_sub(1);
+            }
+            if let Some(links) = self.links.get_mut(&target) {
+                links.retain(|link| *link != removed.id);
+                if links.is_empty() {
+                    self.links.remove(&target);
+                }
+            }
+        }
+    }
+
+    /// Follows a [FsEntry::HardLink] to the file holding its data,
+    /// any other inode is returned as is
+    pub fn resolve<'a>(&'a self, inode: &'a Inode) -> io::Result<&'a Inode> {
+        match inode.entry {
+            FsEntry::HardLink(target) => self.get_inode(target),
+            _ => Ok(inode),
+        }
+    }
+
+    /// See [Arbo::resolve]
+    pub fn n_resolve<'a>(&'a self, inode: &'a Inode) -> WhResult<&'a Inode> {
+        match inode.entry {
+            FsEntry::HardLink(target) => self.n_get_inode(target),
+            _ => Ok(inode),
+        }
+    }
+
+    /// Hard links pointing to the given file
+    pub fn links_to(&self, ino: InodeId) -> Vec<&Inode> {
+        self.links
+            .get(&ino)
+            .into_iter()
+            .flatten()
+            .filter_map(|link| self.entries.get(link))
+            .collect()
+    }
+
     #[must_use]
     /// Create a new [Inode] from the given parameters and insert it inside the local arbo
     pub fn add_inode_from_parameters(
@@ -326,11 +491,11 @@ impl Arbo {
         let parent = self.get_inode_mut(parent)?;
 
         let children = match &mut parent.entry {
-            FsEntry::File(_) => Err(io::Error::new(
+            FsEntry::Directory(children) => Ok(children),
+            _ => Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 "remove_children: specified parent is not a folder",
             )),
-            FsEntry::Directory(children) => Ok(children),
         }?;
 
         children.retain(|v| *v != child);
@@ -342,9 +507,9 @@ impl Arbo {
         let parent = self.n_get_inode_mut(parent)?;
 
         let children = match &mut parent.entry {
-            // REVIEW: Can we expect parent to always be a file to not flood wherror with errors that will never happen
-            FsEntry::File(_) => panic!("Parent is a file"),
             FsEntry::Directory(children) => Ok(children),
+            // REVIEW: Can we expect parent to always be a file to not flood wherror with errors that will never happen
+            _ => panic!("Parent is a file"),
         }?;
 
         children.retain(|parent_child| *parent_child != child);
@@ -356,11 +521,11 @@ impl Arbo {
         let parent = self.get_inode_mut(parent)?;
 
         let children = match &mut parent.entry {
-            FsEntry::File(_) => Err(io::Error::new(
+            FsEntry::Directory(children) => Ok(children),
+            _ => Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 "add_children: specified parent is not a folder",
             )),
-            FsEntry::Directory(children) => Ok(children),
         }?;
 
         children.push(child);
@@ -371,8 +536,8 @@ impl Arbo {
         let parent = self.n_get_inode_mut(parent)?;
 
         let children = match &mut parent.entry {
-            FsEntry::File(_) => Err(WhError::InodeIsNotADirectory),
             FsEntry::Directory(children) => Ok(children),
+            _ => Err(WhError::InodeIsNotADirectory),
         }?;
 
         children.push(child);
@@ -390,6 +555,7 @@ impl Arbo {
         }?;
 
         self.remove_children(removed.parent, id)?;
+        self.unlink_target(&removed);
 
         Ok(removed)
     }
@@ -399,16 +565,18 @@ impl Arbo {
     pub fn n_remove_inode(&mut self, id: InodeId) -> Result<Inode, RemoveInodeError> {
         let inode = self.n_get_inode(id)?;
         match &inode.entry {
-            FsEntry::File(_) => {}
             FsEntry::Directory(children) if children.len() == 0 => {}
             FsEntry::Directory(_) => return Err(RemoveInodeError::NonEmpty),
+            _ => {}
         }
 
         self.n_remove_child(inode.parent, inode.id)?;
 
-        self.entries.remove(&id).ok_or(RemoveInodeError::WhError {
+        let removed = self.entries.remove(&id).ok_or(RemoveInodeError::WhError {
             source: WhError::InodeNotFound,
-        })
+        })?;
+        self.unlink_target(&removed);
+        Ok(removed)
     }
 
     #[must_use]
@@ -652,6 +820,15 @@ impl Arbo {
         Ok(())
     }
 
+    /// See [Arbo::remove_inode_hosts]
+    pub fn n_remove_inode_hosts(&mut self, ino: InodeId, removed: &[Address]) -> WhResult<()> {
+        match &mut self.n_get_inode_mut(ino)?.entry {
+            FsEntry::File(hosts) => hosts.retain(|host| !removed.contains(host)),
+            _ => return Err(WhError::InodeIsADirectory),
+        };
+        Ok(())
+    }
+
     pub fn set_inode_meta(&mut self, ino: InodeId, meta: Metadata) -> io::Result<()> {
         let inode = self.get_inode_mut(ino)?;
 
@@ -666,6 +843,20 @@ impl Arbo {
         Ok(())
     }
 
+    pub fn n_set_inode_shards(
+        &mut self,
+        ino: InodeId,
+        layout: Option<ShardLayout>,
+    ) -> WhResult<()> {
+        let inode = self.n_get_inode_mut(ino)?;
+
+        match inode.entry {
+            FsEntry::File(_) => inode.shards = layout,
+            _ => return Err(WhError::InodeIsADirectory),
+        };
+        Ok(())
+    }
+
     pub fn set_inode_size(&mut self, ino: InodeId, size: u64) -> WhResult<()> {
         self.n_get_inode_mut(ino)?.meta.size = size;
         Ok(())
@@ -688,27 +879,314 @@ impl Arbo {
 
 // !SECTION
 
-/// If arbo can be read and deserialized from parent_folder/[ARBO_FILE_NAME] returns Some(Arbo)
-fn recover_serialized_arbo(parent_folder: &WhPath) -> Option<Arbo> {
-    // error handling is silent on purpose as it will be recoded with the new error system
-    // If an error happens, will just proceed like the arbo was not on disk
-    // In the future, we should maybe warn and keep a copy, avoiding the user from losing data
-    bincode::deserialize(&fs::read(parent_folder.join(ARBO_FILE_FNAME).to_string()).ok()?).ok()
+// SECTION incremental synchronization
+
+/// Digest of an inode alone, the same on every pod knowing the same version of it
+///
+/// Children of directories are left to [Arbo::subtree_digest], and the access time
+/// is ignored as reading a file doesn't change it for the network.
+fn inode_digest(inode: &Inode) -> ContentHash {
+    let entry = match &inode.entry {
+        FsEntry::File(hosts) => {
+            let mut hosts = hosts.clone();
+            hosts.sort();
+            FsEntry::File(hosts)
+        }
+        FsEntry::Directory(_) => FsEntry::Directory(Vec::new()),
+        other => other.clone(),
+    };
+    let mut meta = inode.meta.clone();
+    meta.atime = SystemTime::UNIX_EPOCH;
+    let mut xattrs: Vec<_> = inode.xattrs.iter().collect();
+    xattrs.sort();
+
+    let bytes = bincode::serialize(&(
+        inode.id,
+        inode.parent,
+        &inode.name,
+        entry,
+        meta,
+        xattrs,
+        &inode.version,
+        &inode.shards,
+    ))
+    .expect("inodes are always serializable");
+    Sha256::digest(bytes).into()
+}
+
+impl Arbo {
+    /// Merkle-style digest of an inode and everything under it
+    fn subtree_digest(&self, inode: &Inode, digests: &mut ArboDigests) -> ContentHash {
+        let own = inode_digest(inode);
+        let FsEntry::Directory(children) = &inode.entry else {
+            return own;
+        };
+        let mut children: Vec<&Inode> = children
+            .iter()
+            .filter(|child| !Arbo::is_local_only(**child))
+            .filter_map(|child| self.entries.get(child))
+            .collect();
+        children.sort_by_key(|child| child.id);
+
+        let mut hasher = Sha256::new();
+        hasher.update(own);
+        for child in children {
+            hasher.update(self.subtree_digest(child, digests));
+        }
+        let digest: ContentHash = hasher.finalize().into();
+        digests.insert(inode.id, digest);
+        digest
+    }
+
+    /// Digests of every directory, sent by a returning pod so it only gets the subtrees that changed
+    pub fn directory_digests(&self) -> ArboDigests {
+        let mut digests = ArboDigests::new();
+        if let Some(root) = self.entries.get(&ROOT) {
+            self.subtree_digest(root, &mut digests);
+        }
+        digests
+    }
+
+    /// Inodes of the subtrees whose digest isn't in `known`
+    ///
+    /// Every directory listed has all of its direct children listed too,
+    /// as the receiving pod can't tell which files changed in it.
+    pub fn diff_from(&self, known: &ArboDigests) -> ArboIndex {
+        let digests = self.directory_digests();
+        let mut diff = ArboIndex::new();
+        let mut to_visit = vec![ROOT];
+
+        while let Some(id) = to_visit.pop() {
+            if known
+                .get(&id)
+                .is_some_and(|digest| digests.get(&id) == Some(digest))
+            {
+                continue;
+            }
+            let Some(dir) = self.entries.get(&id) else {
+                continue;
+            };
+            diff.insert(id, dir.clone());
+            let FsEntry::Directory(children) = &dir.entry else {
+                continue;
+            };
+            for child in children
+                .iter()
+                .filter(|child| !Arbo::is_local_only(**child))
+            {
+                match self.entries.get(child) {
+                    Some(inode) if matches!(inode.entry, FsEntry::Directory(_)) => {
+                        to_visit.push(*child)
+                    }
+                    Some(inode) => {
+                        diff.insert(*child, inode.clone());
+                    }
+                    None => (),
+                }
+            }
+        }
+        diff
+    }
+
+    /// Applies the answer to [Arbo::directory_digests]
+    ///
+    /// Inodes no longer reachable from the root were removed or moved away while this pod was gone.
+    /// Local only inodes are kept in their parent, as long as it is still there.
+    pub fn apply_diff(&mut self, diff: ArboIndex) {
+        self.entries.extend(diff);
+
+        let local_only: Vec<(InodeId, InodeId)> = self
+            .entries
+            .values()
+            .filter(|inode| Arbo::is_local_only(inode.id))
+            .map(|inode| (inode.id, inode.parent))
+            .collect();
+        for (id, parent) in local_only {
+            if let Some(FsEntry::Directory(children)) = self
+                .entries
+                .get_mut(&parent)
+                .map(|parent| &mut parent.entry)
+            {
+                if !children.contains(&id) {
+                    children.push(id);
+                }
+            }
+        }
+
+        let mut reachable = HashSet::from([ROOT]);
+        let mut to_visit = vec![ROOT];
+        while let Some(id) = to_visit.pop() {
+            if let Some(FsEntry::Directory(children)) = self.entries.get(&id).map(|i| &i.entry) {
+                for child in children {
+                    if reachable.insert(*child) {
+                        to_visit.push(*child);
+                    }
+                }
+            }
+        }
+        self.entries.retain(|id, _| reachable.contains(id));
+        self.index_links();
+    }
+}
+
+// !SECTION
+
+custom_error::custom_error! {pub ArboFileError
+    Unreadable{source: bincode::Error} = "the file is corrupted: {source}",
+    UnknownFormat{version: u32} = "the file has the format {version}, written by a newer version of wormhole",
+}
+
+/// Layout of the [ARBO_FILE_FNAME] files written before they had a version,
+/// before the content hashes, the versions and the shards of the inodes
+mod format_v0 {
+    use std::{collections::HashMap, time::SystemTime};
+
+    use serde::Deserialize;
+
+    use super::{FsEntry, InodeId, SimpleFileType, XAttrs};
+
+    #[derive(Deserialize)]
+    pub struct Metadata {
+        pub ino: u64,
+        pub size: u64,
+        pub blocks: u64,
+        pub atime: SystemTime,
+        pub mtime: SystemTime,
+        pub ctime: SystemTime,
+        pub crtime: SystemTime,
+        pub kind: SimpleFileType,
+        pub perm: u16,
+        pub nlink: u32,
+        pub uid: u32,
+        pub gid: u32,
+        pub rdev: u32,
+        pub blksize: u32,
+        pub flags: u32,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Inode {
+        pub parent: InodeId,
+        pub id: InodeId,
+        pub name: String,
+        pub entry: FsEntry,
+        pub meta: Metadata,
+        pub xattrs: XAttrs,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Arbo {
+        pub entries: HashMap<InodeId, Inode>,
+    }
+}
+
+impl From<format_v0::Inode> for Inode {
+    fn from(inode: format_v0::Inode) -> Self {
+        let meta = inode.meta;
+        Inode {
+            parent: inode.parent,
+            id: inode.id,
+            name: inode.name,
+            entry: inode.entry,
+            meta: Metadata {
+                ino: meta.ino,
+                size: meta.size,
+                blocks: meta.blocks,
+                atime: meta.atime,
+                mtime: meta.mtime,
+                ctime: meta.ctime,
+                crtime: meta.crtime,
+                kind: meta.kind,
+                perm: meta.perm,
+                nlink: meta.nlink,
+                uid: meta.uid,
+                gid: meta.gid,
+                rdev: meta.rdev,
+                blksize: meta.blksize,
+                flags: meta.flags,
+                hash: None,
+            },
+            xattrs: inode.xattrs,
+            version: VersionVector::default(),
+            shards: None,
+        }
+    }
+}
+
+/// Content of the [ARBO_FILE_FNAME] file saving `arbo`
+pub fn encode_arbo_file(arbo: &Arbo) -> Vec<u8> {
+    bincode::serialize(&(ARBO_FILE_MAGIC, ARBO_FILE_FORMAT, arbo))
+        .expect("can't serialize arbo to bincode")
+}
+
+/// Reads an [ARBO_FILE_FNAME] file, migrating the ones of an older format
+pub fn decode_arbo_file(bytes: &[u8]) -> Result<Arbo, ArboFileError> {
+    if !bytes.starts_with(&ARBO_FILE_MAGIC) {
+        let arbo: format_v0::Arbo = bincode::deserialize(bytes)?;
+        let mut arbo = Arbo {
+            entries: arbo
+                .entries
+                .into_iter()
+                .map(|(ino, inode)| (ino, inode.into()))
+                .collect(),
+            links: HashMap::new(),
+        };
+        arbo.index_links();
+        return Ok(arbo);
+    }
+    let mut arbo = match bincode::deserialize::<([u8; 8], u32)>(bytes)? {
+        (_, ARBO_FILE_FORMAT) => bincode::deserialize::<([u8; 8], u32, Arbo)>(bytes)?.2,
+        (_, version) => return Err(ArboFileError::UnknownFormat { version }),
+    };
+    arbo.index_links();
+    Ok(arbo)
+}
+
+/// If arbo can be read and deserialized from parent_folder/[ARBO_FILE_FNAME] returns Some(Arbo)
+///
+/// An unreadable file is reported and the pod proceeds like it was not on disk,
+/// it is overwritten when the pod stops.
+pub fn recover_serialized_arbo(parent_folder: &WhPath) -> Option<Arbo> {
+    let path = parent_folder.join(ARBO_FILE_FNAME);
+    let bytes = fs::read(&path.inner).ok()?;
+    if !bytes.starts_with(&ARBO_FILE_MAGIC) {
+        log::warn!("{path} has the format 0, migrating it to the format {ARBO_FILE_FORMAT}");
+    }
+    decode_arbo_file(&bytes)
+        .inspect_err(|e| {
+            log::error!(
+                "Can't read {path}, {e}. The saved state of the pod is ignored \
+                and will be overwritten when it stops"
+            )
+        })
+        .ok()
+}
+
+/// State of [index_folder_recursive] kept through the whole indexing
+struct Indexing<'a> {
+    host: &'a String,
+    ignored: &'a IgnoreRules,
+    next_ino: InodeId,
+    next_local_ino: InodeId,
+    /// Disk inode (device and number) of the first indexed name of a file with hard links
+    links: HashMap<(u64, u64), InodeId>,
 }
 
 #[cfg(target_os = "linux")]
 fn index_folder_recursive(
     arbo: &mut Arbo,
     parent: InodeId,
-    ino: &mut InodeId,
     path: &WhPath,
-    host: &String,
+    indexing: &mut Indexing,
 ) -> io::Result<()> {
     let str_path = path.to_string();
     for entry in fs::read_dir(str_path)? {
         let entry = entry.expect("error in filesystem indexion (1)");
         let ftype = entry.file_type().expect("error in filesystem indexion (2)");
         let fname = entry.file_name().to_string_lossy().to_string();
+        if Arbo::is_reserved(&fname, parent) {
+            continue;
+        }
         let meta = entry.metadata()?;
 
         let special_ino = Arbo::get_special(&fname, parent);
@@ -721,53 +1199,95 @@ fn index_folder_recursive(
                 ))
             }
             Some(ino) => ino,
+            None if Arbo::is_local_only(parent)
+                || indexing.ignored.is_ignored(
+                    &arbo.get_path_from_inode_id(parent)?.join(&fname).inner,
+                    ftype.is_dir(),
+                ) =>
+            {
+                indexing.next_local_ino += 1;
+                indexing.next_local_ino - 1
+            }
             None => {
-                let used = *ino;
-                *ino += 1;
-                used
+                indexing.next_ino += 1;
+                indexing.next_ino - 1
             }
         };
 
+        let linked = if ftype.is_file() && meta.nlink() > 1 {
+            indexing.links.get(&(meta.dev(), meta.ino())).copied()
+        } else {
+            None
+        };
+        let fs_entry = if let Some(target) = linked {
+            FsEntry::HardLink(target)
+        } else if ftype.is_file() {
+            FsEntry::File(vec![indexing.host.clone()])
+        } else if ftype.is_dir() {
+            FsEntry::Directory(Vec::new())
+        } else if ftype.is_symlink() {
+            FsEntry::Symlink(fs::read_link(entry.path())?.to_string_lossy().to_string())
+        } else {
+            FsEntry::Special(Metadata::kind_of(&meta))
+        };
         arbo.add_inode(Inode::new(
             fname.clone(),
             parent,
             used_ino,
-            if ftype.is_file() {
-                FsEntry::File(vec![host.clone()])
-            } else {
-                FsEntry::Directory(Vec::new())
-            },
+            fs_entry,
             meta.permissions().mode() as u16,
         ))
         .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err.to_string()))?;
+        if linked.is_some() {
+            continue;
+        }
+        if ftype.is_file() && meta.nlink() > 1 {
+            indexing.links.insert((meta.dev(), meta.ino()), used_ino);
+        }
         let mut meta: Metadata = meta.try_into()?;
         meta.ino = used_ino;
+        // the arbo counts its own links, the ones outside of the pod don't matter
+        meta.nlink = 1;
         arbo.set_inode_meta(used_ino, meta)?;
 
         if ftype.is_dir() {
-            index_folder_recursive(arbo, *ino - 1, ino, &path.join(&fname), host)
+            index_folder_recursive(arbo, used_ino, &path.join(&fname), indexing)
                 .expect("error in filesystem indexion (3)");
         };
     }
     Ok(())
 }
 
-pub fn generate_arbo(path: &WhPath, host: &String) -> io::Result<(Arbo, InodeId)> {
+/// Recovers the arbo saved in the pod, or indexes its folder
+///
+/// Returns the arbo and the first inode free for the network
+pub fn generate_arbo(
+    path: &WhPath,
+    host: &String,
+    ignored: &IgnoreRules,
+) -> io::Result<(Arbo, InodeId)> {
     if let Some(arbo) = recover_serialized_arbo(path) {
         let next_ino: u64 = *arbo
             .entries
             .keys()
+            .filter(|ino| **ino < FIRST_LOCAL_INO)
             .reduce(|acc, i| std::cmp::max(acc, i))
             .unwrap_or(&11)
             + 1;
         Ok((arbo, next_ino))
     } else {
         let mut arbo = Arbo::new();
-        let mut next_ino = Arbo::first_ino(); // NOTE - will be the first registered inode after root
+        let mut indexing = Indexing {
+            host,
+            ignored,
+            next_ino: Arbo::first_ino(), // NOTE - will be the first registered inode after root
+            next_local_ino: FIRST_LOCAL_INO,
+            links: HashMap::new(),
+        };
 
         #[cfg(target_os = "linux")]
-        index_folder_recursive(&mut arbo, ROOT, &mut next_ino, path, host)?;
-        Ok((arbo, next_ino))
+        index_folder_recursive(&mut arbo, ROOT, path, &mut indexing)?;
+        Ok((arbo, indexing.next_ino))
     }
 }
 
@@ -806,6 +1326,33 @@ pub struct Metadata {
     pub blksize: u32,
     /// Flags (macOS only, see chflags(2))
     pub flags: u32,
+    /// Hash of the content, None while unknown (folders, files being written)
+    pub hash: Option<ContentHash>,
+}
+
+#[cfg(target_os = "linux")]
+impl Metadata {
+    /// Kind of a file as seen on disk, without following symlinks
+    pub fn kind_of(meta: &fs::Metadata) -> SimpleFileType {
+        use std::os::unix::fs::FileTypeExt;
+
+        let ftype = meta.file_type();
+        if ftype.is_dir() {
+            SimpleFileType::Directory
+        } else if ftype.is_symlink() {
+            SimpleFileType::Symlink
+        } else if ftype.is_fifo() {
+            SimpleFileType::NamedPipe
+        } else if ftype.is_char_device() {
+            SimpleFileType::CharDevice
+        } else if ftype.is_block_device() {
+            SimpleFileType::BlockDevice
+        } else if ftype.is_socket() {
+            SimpleFileType::Socket
+        } else {
+            SimpleFileType::File
+        }
+    }
 }
 
 #[cfg(target_os = "linux")]
@@ -820,11 +1367,7 @@ impl TryInto<Metadata> for fs::Metadata {
             mtime: self.modified()?,
             ctime: self.modified()?,
             crtime: self.created()?,
-            kind: if self.is_file() {
-                SimpleFileType::File
-            } else {
-                SimpleFileType::Directory
-            },
+            kind: Metadata::kind_of(&self),
             perm: self.permissions().mode() as u16,
             nlink: self.nlink() as u32,
             uid: self.uid(),
@@ -832,6 +1375,7 @@ impl TryInto<Metadata> for fs::Metadata {
             rdev: self.rdev() as u32,
             blksize: self.blksize() as u32,
             flags: 0,
+            hash: None,
         })
     }
 }
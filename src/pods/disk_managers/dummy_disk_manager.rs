@@ -221,7 +221,7 @@ impl DiskManager for DummyDiskManager {
             .expect("VirtDisk::read_file rwLock")
             .get(&path.clone().set_relative())
         {
-            let len = std::cmp::min(buf.len(), file.len() - offset);
+            let len = std::cmp::min(buf.len(), file.len().saturating_sub(offset));
             buf[0..len].copy_from_slice(&file[(offset)..(offset + len)]);
             Ok(len)
         } else {
@@ -246,6 +246,12 @@ impl DiskManager for DummyDiskManager {
         Ok(())
     }
 
+    /// The virtual disk has no links, the target is kept as the content of a file
+    fn new_symlink(&self, path: &WhPath, target: &str) -> io::Result<()> {
+        self.new_file(path, 0o777)?;
+        self.write_file(path, target.as_bytes(), 0).map(|_| ())
+    }
+
     fn size_info(&self) -> io::Result<DiskSizeInfo> {
         let s = sysinfo::System::new_all();
         Ok(DiskSizeInfo {
@@ -32,5 +32,7 @@ pub trait DiskManager: Send + Sync + Debug {
 
     fn new_dir(&self, path: &WhPath, permissions: u16) -> io::Result<()>;
 
+    fn new_symlink(&self, path: &WhPath, target: &str) -> io::Result<()>;
+
     fn size_info(&self) -> io::Result<DiskSizeInfo>;
 }
@@ -1,6 +1,5 @@
 use std::{
     ffi::CString,
-    io::Read,
     os::{
         fd::{AsRawFd, RawFd},
         unix::fs::FileExt,
@@ -105,14 +104,14 @@ impl DiskManager for UnixDiskManager {
 
     fn read_file(&self, path: &WhPath, offset: usize, buf: &mut [u8]) -> io::Result<usize> {
         let file = self.handle.open_file(path.clone().set_relative())?;
-        let mut read = file
-            .bytes()
-            .skip(offset)
-            .take(buf.len())
-            .map_while(|b| b.ok())
-            .collect::<Vec<u8>>();
-        let read_len = read.len();
-        buf[0..read_len].swap_with_slice(&mut read);
+        let mut read_len = 0;
+        // read_at can return less than asked before the end of the file
+        while read_len < buf.len() {
+            match file.read_at(&mut buf[read_len..], (offset + read_len) as u64)? {
+                0 => break,
+                read => read_len += read,
+            }
+        }
         Ok(read_len)
     }
 
@@ -121,6 +120,10 @@ impl DiskManager for UnixDiskManager {
             .create_dir(path.clone().set_relative(), permissions.into()) // TODO look more in c mode_t value
     }
 
+    fn new_symlink(&self, path: &WhPath, target: &str) -> io::Result<()> {
+        self.handle.symlink(path.clone().set_relative(), target)
+    }
+
     fn set_permisions(&self, path: &WhPath, permissions: u16) -> std::io::Result<()> {
         let raw_fd: RawFd = self.handle.as_raw_fd();
         let c_string_path = CString::new(path.clone().set_relative().as_str())
@@ -137,6 +140,13 @@ impl DiskManager for UnixDiskManager {
     }
 
     fn size_info(&self) -> std::io::Result<super::DiskSizeInfo> {
-        todo!()
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::fstatvfs(self.handle.as_raw_fd(), &mut stat) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(super::DiskSizeInfo {
+            free_size: (stat.f_bavail as u64 * stat.f_frsize as u64) as usize,
+            total_size: (stat.f_blocks as u64 * stat.f_frsize as u64) as usize,
+        })
     }
 }
@@ -126,6 +126,10 @@ impl DiskManager for WindowsDiskManager {
         std::fs::create_dir(&self.mount_point.join(path).inner)
     }
 
+    fn new_symlink(&self, path: &WhPath, target: &str) -> io::Result<()> {
+        std::os::windows::fs::symlink_file(target, &self.mount_point.join(path).inner)
+    }
+
     fn size_info(&self) -> std::io::Result<super::DiskSizeInfo> {
         self.get_volume_info_inner()
     }
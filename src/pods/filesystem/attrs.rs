@@ -75,6 +75,8 @@ impl FsInterface {
                         }
                     }
                 }
+                // only the arbo knows their metadata
+                FsEntry::Symlink(_) | FsEntry::HardLink(_) | FsEntry::Special(_) => {}
             }
         }
 
@@ -134,6 +136,7 @@ impl FsInterface {
                         .map_err(|io| SetAttrError::SetFileSizeIoError { io })?;
                     meta.size = size;
                     meta.blocks = (size + BLOCK_SIZE - 1) / BLOCK_SIZE;
+                    meta.hash = None;
                 }
             };
         }
@@ -176,6 +179,12 @@ impl FsInterface {
             meta.flags = flags;
         }
         self.network_interface.update_metadata(ino, meta.clone())?;
+        if size.is_some() {
+            match self.update_hash(ino) {
+                Ok(hash) => meta.hash = Some(hash),
+                Err(e) => log::warn!("setattr: can't update the hash of {ino}: {e}"),
+            }
+        }
         return Ok(meta);
     }
 }
@@ -0,0 +1,82 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use parking_lot::{Mutex, MutexGuard};
+
+use crate::{
+    error::{WhError, WhResult},
+    pods::arbo::{InodeId, LOCK_TIMEOUT},
+};
+
+/// Size of the blocks kept by the [BlockCache].
+/// Remote reads are aligned on it so neighbouring reads hit the cache.
+pub const CACHE_BLOCK_SIZE: u64 = 64 * 1024;
+
+/// In-memory cache of blocks read from files not hosted by this node
+///
+/// The least recently used blocks are evicted first.
+#[derive(Debug, Default)]
+pub struct BlockCache {
+    /// (file, block index) -> (data, last use)
+    blocks: HashMap<(InodeId, u64), (Vec<u8>, u64)>,
+    /// last use -> (file, block index), oldest first
+    uses: BTreeMap<u64, (InodeId, u64)>,
+    clock: u64,
+}
+
+impl BlockCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&mut self, ino: InodeId, block: u64) -> Option<Vec<u8>> {
+        self.clock += 1;
+        let clock = self.clock;
+
+        let (data, last_use) = self.blocks.get_mut(&(ino, block))?;
+        self.uses.remove(last_use);
+        self.uses.insert(clock, (ino, block));
+        *last_use = clock;
+        Some(data.clone())
+    }
+
+    /// Adds a block, evicting others to stay under `capacity` bytes
+    pub fn insert(&mut self, ino: InodeId, block: u64, data: Vec<u8>, capacity: u64) {
+        let max_blocks = (capacity / CACHE_BLOCK_SIZE) as usize;
+        if max_blocks == 0 {
+            self.blocks.clear();
+            self.uses.clear();
+            return;
+        }
+
+        if let Some((_, last_use)) = self.blocks.remove(&(ino, block)) {
+            self.uses.remove(&last_use);
+        }
+        while self.blocks.len() >= max_blocks {
+            match self.uses.pop_first() {
+                Some((_, key)) => self.blocks.remove(&key),
+                None => break,
+            };
+        }
+        self.clock += 1;
+        self.uses.insert(self.clock, (ino, block));
+        self.blocks.insert((ino, block), (data, self.clock));
+    }
+
+    /// Drops every block of a file, to call when its content changes
+    pub fn invalidate(&mut self, ino: InodeId) {
+        self.blocks.retain(|(file, _), _| *file != ino);
+        self.uses.retain(|_, (file, _)| *file != ino);
+    }
+
+    pub fn lock<'a>(
+        block_cache: &'a Arc<Mutex<BlockCache>>,
+        called_from: &'a str,
+    ) -> WhResult<MutexGuard<'a, BlockCache>> {
+        block_cache
+            .try_lock_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block(called_from))
+    }
+}
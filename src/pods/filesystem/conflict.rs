@@ -0,0 +1,198 @@
+use std::{cmp::Ordering, collections::BTreeMap};
+
+use custom_error::custom_error;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{
+        types::{Config, ConflictPolicy},
+        GlobalConfig, LocalConfig,
+    },
+    error::WhError,
+    network::message::Address,
+    pods::{
+        arbo::{Arbo, FsEntry, InodeId, Metadata},
+        network::transfer::CHUNK_SIZE,
+    },
+};
+
+use super::{
+    attrs::AcknoledgeSetAttrError,
+    fs_interface::{FsInterface, SimpleFileType},
+    make_inode::MakeInodeError,
+};
+
+custom_error! {pub ConflictError
+    WhError{source: WhError} = "{source}",
+    MakeInode{source: MakeInodeError} = "{source}",
+    LocalCopyFailed{io: std::io::Error} = "Local copy failed: {io}",
+}
+
+/// Number of writes made by each pod on a file
+///
+/// Two versions where neither contains the other were written concurrently
+/// (e.g. on both sides of a network partition) and are in conflict.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct VersionVector(BTreeMap<Address, u64>);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VersionOrdering {
+    Equal,
+    /// Every write of this version is known by the other one
+    Older,
+    /// This version knows every write of the other one
+    Newer,
+    Concurrent,
+}
+
+impl VersionVector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a write made by `host`
+    pub fn increment(&mut self, host: &Address) {
+        *self.0.entry(host.clone()).or_insert(0) += 1;
+    }
+
+    /// Version knowing the writes of both
+    pub fn merge(&mut self, other: &Self) {
+        for (host, writes) in &other.0 {
+            let known = self.0.entry(host.clone()).or_insert(0);
+            *known = (*known).max(*writes);
+        }
+    }
+
+    pub fn compare(&self, other: &Self) -> VersionOrdering {
+        let mut older = false;
+        let mut newer = false;
+
+        for host in self.0.keys().chain(other.0.keys()) {
+            let mine = self.0.get(host).unwrap_or(&0);
+            let theirs = other.0.get(host).unwrap_or(&0);
+            match mine.cmp(theirs) {
+                Ordering::Less => older = true,
+                Ordering::Greater => newer = true,
+                Ordering::Equal => (),
+            }
+        }
+        match (older, newer) {
+            (false, false) => VersionOrdering::Equal,
+            (true, false) => VersionOrdering::Older,
+            (false, true) => VersionOrdering::Newer,
+            (true, true) => VersionOrdering::Concurrent,
+        }
+    }
+}
+
+/// Decides which of two concurrent versions is kept under the file's name
+///
+/// Only depends on the versions themselves so every pod picks the same one,
+/// whatever order they received them in.
+pub fn remote_version_wins(
+    local: (&Metadata, &VersionVector),
+    remote: (&Metadata, &VersionVector),
+) -> bool {
+    (remote.0.mtime, remote.1) > (local.0.mtime, local.1)
+}
+
+/// Name given to the losing version of a conflict when both are kept
+pub fn conflict_name(name: &str, host: &Address) -> String {
+    format!("{name}.conflict-{}", host.replace([':', '/'], "_"))
+}
+
+impl FsInterface {
+    /// Settles a file written concurrently here (or on a pod this one knows the version of)
+    /// and on `host`, by the [ConflictPolicy] of the network
+    ///
+    /// With [ConflictPolicy::KeepBoth], the pod holding the losing version
+    /// shares it as a new file before dropping it.
+    pub fn resolve_conflict(
+        &self,
+        id: InodeId,
+        host: Address,
+        meta: Metadata,
+        version: VersionVector,
+    ) -> Result<(), AcknoledgeSetAttrError> {
+        let self_addr = LocalConfig::read_lock(&self.network_interface.local_config, "conflict")?
+            .general
+            .address
+            .clone();
+        let policy = GlobalConfig::read_lock(&self.network_interface.global_config, "conflict")?
+            .conflict
+            .policy;
+        let (local_meta, local_version, hosted_here) = {
+            let arbo = Arbo::n_read_lock(&self.arbo, "resolve_conflict")?;
+            let inode = arbo.n_get_inode(id)?;
+            let hosted_here = match &inode.entry {
+                FsEntry::File(hosts) => hosts.contains(&self_addr),
+                FsEntry::Directory(_) => return Err(WhError::InodeIsADirectory.into()),
+                _ => false,
+            };
+            (inode.meta.clone(), inode.version.clone(), hosted_here)
+        };
+        let mut merged = local_version.clone();
+        merged.merge(&version);
+
+        if !remote_version_wins((&local_meta, &local_version), (&meta, &version)) {
+            log::warn!("Conflict on {id}: keeping the local version over the one of {host}");
+            Arbo::n_write_lock(&self.arbo, "resolve_conflict")?
+                .n_get_inode_mut(id)?
+                .version = merged;
+            return Ok(());
+        }
+
+        log::warn!("Conflict on {id}: the version of {host} replaces the local one");
+        if policy == ConflictPolicy::KeepBoth && hosted_here {
+            if let Err(e) = self.keep_conflicting_copy(id, &self_addr) {
+                log::error!("Conflict on {id}: can't keep the local version: {e}");
+            }
+        }
+        self.revoke_local_copy(id, host, meta, merged)
+    }
+
+    /// Shares the local content of `id` as a sibling file named after this pod
+    fn keep_conflicting_copy(&self, id: InodeId, self_addr: &Address) -> Result<(), ConflictError> {
+        let (parent, name, path, size, perm) = {
+            let arbo = Arbo::n_read_lock(&self.arbo, "keep_conflicting_copy")?;
+            let inode = arbo.n_get_inode(id)?;
+            (
+                inode.parent,
+                inode.name.clone(),
+                arbo.n_get_path_from_inode_id(id)?,
+                inode.meta.size,
+                inode.meta.perm,
+            )
+        };
+        let copy = self.make_inode(
+            parent,
+            conflict_name(&name, self_addr),
+            perm,
+            SimpleFileType::File,
+        )?;
+        let copy_path = Arbo::n_read_lock(&self.arbo, "keep_conflicting_copy")?
+            .n_get_path_from_inode_id(copy.id)?;
+
+        let mut buf = vec![0; CHUNK_SIZE as usize];
+        let mut offset = 0;
+        while offset < size {
+            let len = (size - offset).min(CHUNK_SIZE) as usize;
+            let read = self
+                .disk
+                .read_file(&path, offset as usize, &mut buf[..len])
+                .and_then(|read| {
+                    self.disk
+                        .write_file(&copy_path, &buf[..read], offset as usize)
+                })
+                .map_err(|io| ConflictError::LocalCopyFailed { io })?;
+            if read == 0 {
+                break;
+            }
+            offset += read as u64;
+        }
+        self.network_interface
+            .write_file(copy.id, offset as usize)?;
+        let _ = self.update_hash(copy.id);
+        Ok(())
+    }
+}
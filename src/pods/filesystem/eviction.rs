@@ -0,0 +1,314 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use custom_error::custom_error;
+use parking_lot::Mutex;
+
+use crate::{
+    config::{
+        types::{Config, RedundancyTargets},
+        GlobalConfig, LocalConfig,
+    },
+    error::{WhError, WhResult},
+    network::message::Address,
+    pods::{
+        arbo::{Arbo, FsEntry, InodeId},
+        network::redundancy::target_redundancy,
+        whpath::WhPath,
+    },
+};
+
+use super::fs_interface::FsInterface;
+
+/// Interval between two checks of the pinned files and of the free space
+pub const EVICTION_INTERVAL: Duration = Duration::from_secs(30);
+
+const MO: u64 = 1024 * 1024;
+
+/// Under a quota, the space kept free is at most this share of it
+const QUOTA_FREE_SHARE: u64 = 10;
+
+custom_error! {pub EvictionError
+    WhError{source: WhError} = "{source}",
+    SpaceUnknown = "Can't tell the space left on this pod",
+}
+
+/// Last time each file was read or written through this pod, since it started
+#[derive(Debug, Default)]
+pub struct AccessLog(Mutex<HashMap<InodeId, SystemTime>>);
+
+impl AccessLog {
+    pub fn touch(&self, ino: InodeId) {
+        self.0.lock().insert(ino, SystemTime::now());
+    }
+
+    pub fn last(&self, ino: InodeId) -> Option<SystemTime> {
+        self.0.lock().get(&ino).copied()
+    }
+
+    pub fn forget(&self, ino: InodeId) {
+        self.0.lock().remove(&ino);
+    }
+}
+
+/// Copy of a file this pod may drop
+#[derive(Debug, Clone)]
+pub struct Evictable {
+    pub ino: InodeId,
+    pub size: u64,
+    pub last_access: SystemTime,
+}
+
+/// Picks the least recently used copies until `to_free` bytes are freed
+pub fn pick_evictions(mut copies: Vec<Evictable>, to_free: u64) -> Vec<InodeId> {
+    copies.sort_by_key(|copy| copy.last_access);
+    let mut freed = 0;
+    copies
+        .into_iter()
+        .take_while(|copy| {
+            let needed = freed < to_free;
+            freed += copy.size;
+            needed
+        })
+        .map(|copy| copy.ino)
+        .collect()
+}
+
+/// Space kept free under a `quota`: `min_free`, at most a [QUOTA_FREE_SHARE] of the quota.
+/// A quota smaller than `min_free` would else have the copies dropped all the time.
+pub fn quota_min_free(min_free: u64, quota: u64) -> u64 {
+    min_free.min(quota / QUOTA_FREE_SHARE)
+}
+
+/// Tells if this pod may drop its copy of a file:
+/// - the file keeps at least `target` other hosts, and at least one
+/// - as many of them are `alive`, so the last reachable copy is never dropped
+/// - this pod isn't one of the `target` hosts picked to keep the file,
+///   so pods short of space at the same time don't drop every copy
+pub fn may_drop(
+    ino: InodeId,
+    hosts: &[Address],
+    self_addr: &Address,
+    target: u64,
+    alive: &[Address],
+) -> bool {
+    let keep = target.max(1) as usize;
+    if hosts.len() <= keep || !hosts.contains(self_addr) {
+        return false;
+    }
+    let reachable = hosts
+        .iter()
+        .filter(|host| *host != self_addr && alive.contains(host))
+        .count();
+    if reachable < keep {
+        return false;
+    }
+    let mut hosts = hosts.to_vec();
+    hosts.sort();
+    let first = (ino % hosts.len() as u64) as usize;
+    !(0..keep).any(|i| hosts[(first + i) % hosts.len()] == *self_addr)
+}
+
+impl FsInterface {
+    /// Keeps a copy of the pinned files and drops cold copies every [EVICTION_INTERVAL]
+    pub async fn eviction_worker(fs_interface: Arc<FsInterface>) {
+        let mut ticker = tokio::time::interval(EVICTION_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let fs_interface = fs_interface.clone();
+            // pulls block until the file is received
+            match tokio::task::spawn_blocking(move || {
+                fs_interface.fetch_pinned_copies()?;
+                fs_interface.evict_cold_copies()
+            })
+            .await
+            {
+                Ok(Ok(())) => (),
+                Ok(Err(e)) => log::warn!("Eviction: {e}"),
+                Err(e) => log::error!("eviction_worker: error in thread pool: {e}"),
+            }
+        }
+    }
+
+    /// Pulls the pinned files this pod has no copy of, returns the ones that couldn't be pulled
+    pub fn fetch_pinned_copies(&self) -> WhResult<Vec<WhPath>> {
+        let (self_addr, storage) = {
+            let local_config =
+                LocalConfig::read_lock(&self.network_interface.local_config, "pinned_copies")?;
+            (
+                local_config.general.address.clone(),
+                local_config.storage.clone(),
+            )
+        };
+        if storage.pinned.is_empty() {
+            return Ok(Vec::new());
+        }
+        let missing: Vec<(InodeId, WhPath)> = {
+            let arbo = Arbo::n_read_lock(&self.arbo, "pinned_copies")?;
+            arbo.iter()
+                .filter(|(ino, _)| !Arbo::is_special(**ino) && !Arbo::is_local_only(**ino))
+                .filter(|(_, inode)| match &inode.entry {
+                    FsEntry::File(hosts) => {
+                        !hosts.contains(&self_addr) && (!hosts.is_empty() || inode.shards.is_some())
+                    }
+                    _ => false,
+                })
+                .filter_map(|(ino, _)| Some((*ino, arbo.n_get_path_from_inode_id(*ino).ok()?)))
+                .filter(|(_, path)| storage.is_pinned(&path.inner))
+                .collect()
+        };
+
+        let mut not_pulled = Vec::new();
+        for (ino, path) in missing {
+            if let Err(e) = self.fetch_local_copy(ino) {
+                log::warn!("Eviction: can't pull pinned {path}: {e}");
+                not_pulled.push(path);
+            }
+        }
+        Ok(not_pulled)
+    }
+
+    /// Drops the least recently used copies while this pod has less than
+    /// [crate::config::types::StorageLocalConfig::min_free_space] left on its disk,
+    /// or less than [quota_min_free] under its quota
+    pub fn evict_cold_copies(&self) -> Result<(), EvictionError> {
+        let (self_addr, storage) = {
+            let local_config =
+                LocalConfig::read_lock(&self.network_interface.local_config, "evict_cold_copies")?;
+            (
+                local_config.general.address.clone(),
+                local_config.storage.clone(),
+            )
+        };
+        if storage.min_free_space == 0 {
+            return Ok(());
+        }
+        let min_free = storage.min_free_space * MO;
+        let disk_free = self
+            .disk
+            .size_info()
+            .inspect_err(|e| log::warn!("Can't get the disk size: {e}"))
+            .ok()
+            .map(|size| size.free_size as u64);
+        let quota_left = match self.quota()? {
+            Some(quota) => Some((
+                quota.saturating_sub(self.used_space()?),
+                quota_min_free(min_free, quota),
+            )),
+            None => None,
+        };
+        let free = match (disk_free, quota_left) {
+            (Some(disk), Some((quota, _))) => disk.min(quota),
+            (Some(disk), None) => disk,
+            (None, Some((quota, _))) => quota,
+            (None, None) => return Err(EvictionError::SpaceUnknown),
+        };
+        let to_free = disk_free
+            .map_or(0, |free| min_free.saturating_sub(free))
+            .max(quota_left.map_or(0, |(left, wanted)| wanted.saturating_sub(left)));
+        if to_free == 0 {
+            return Ok(());
+        }
+
+        let targets =
+            GlobalConfig::read_lock(&self.network_interface.global_config, "evict_cold_copies")?
+                .redundancy
+                .targets();
+        let alive = self.alive_pods()?;
+        let copies = {
+            let arbo = Arbo::n_read_lock(&self.arbo, "evict_cold_copies")?;
+            let mut copies = Vec::new();
+            for (ino, inode) in arbo.iter() {
+                if Arbo::is_special(*ino) || Arbo::is_local_only(*ino) || inode.shards.is_some() {
+                    continue;
+                }
+                let FsEntry::File(hosts) = &inode.entry else {
+                    continue;
+                };
+                if !may_drop(
+                    *ino,
+                    hosts,
+                    &self_addr,
+                    target_redundancy(&arbo, &targets, *ino)?,
+                    &alive,
+                ) || storage.is_pinned(&arbo.n_get_path_from_inode_id(*ino)?.inner)
+                {
+                    continue;
+                }
+                copies.push(Evictable {
+                    ino: *ino,
+                    size: inode.meta.size,
+                    last_access: self
+                        .accesses
+                        .last(*ino)
+                        .unwrap_or(inode.meta.atime.max(inode.meta.mtime)),
+                });
+            }
+            copies
+        };
+
+        let evicted = pick_evictions(copies, to_free);
+        log::info!(
+            "Eviction: {} Mo free, dropping {} copies",
+            free / MO,
+            evicted.len()
+        );
+        for ino in evicted {
+            if let Err(e) = self.drop_copy(ino, &self_addr, &targets, &alive) {
+                log::warn!("Eviction: can't drop the copy of {ino}: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Pods heard from through the gossip lately, see [crate::pods::network::gossip::GOSSIP_EXPIRY]
+    fn alive_pods(&self) -> WhResult<Vec<Address>> {
+        Ok(self
+            .network_interface
+            .gossip
+            .statuses()?
+            .into_keys()
+            .collect())
+    }
+
+    /// Removes this pod from the hosts of a file, then deletes its copy
+    ///
+    /// The hosts are checked again, they may have changed since the copy was picked.
+    fn drop_copy(
+        &self,
+        ino: InodeId,
+        self_addr: &Address,
+        targets: &RedundancyTargets,
+        alive: &[Address],
+    ) -> WhResult<()> {
+        let path = {
+            let arbo = Arbo::n_read_lock(&self.arbo, "drop_copy")?;
+            match &arbo.n_get_inode(ino)?.entry {
+                FsEntry::File(hosts) => {
+                    if !may_drop(
+                        ino,
+                        hosts,
+                        self_addr,
+                        target_redundancy(&arbo, targets, ino)?,
+                        alive,
+                    ) {
+                        return Ok(());
+                    }
+                    arbo.n_get_path_from_inode_id(ino)?
+                }
+                _ => return Err(WhError::InodeIsADirectory),
+            }
+        };
+        self.network_interface
+            .remove_hosts(ino, vec![self_addr.clone()])?;
+        self.network_interface.transfers.forget_received(ino)?;
+        self.accesses.forget(ino);
+        if let Err(e) = self.disk.remove_file(&path) {
+            log::debug!("drop_copy: can't delete file. {e}");
+        }
+        Ok(())
+    }
+}
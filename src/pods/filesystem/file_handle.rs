@@ -83,9 +83,7 @@ impl FileHandleManager {
     ) -> WhResult<RwLockReadGuard<'a, FileHandleManager>> {
         file_handle_manager
             .try_read_for(LOCK_TIMEOUT)
-            .ok_or(WhError::WouldBlock {
-                called_from: called_from.to_owned(),
-            })
+            .ok_or_else(|| WhError::would_block(called_from))
     }
 
     pub fn write_lock<'a>(
@@ -94,8 +92,6 @@ impl FileHandleManager {
     ) -> WhResult<RwLockWriteGuard<'a, FileHandleManager>> {
         file_handle_manager
             .try_write_for(LOCK_TIMEOUT)
-            .ok_or(WhError::WouldBlock {
-                called_from: called_from.to_owned(),
-            })
+            .ok_or_else(|| WhError::would_block(called_from))
     }
 }
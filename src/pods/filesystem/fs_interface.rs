@@ -1,25 +1,35 @@
 use crate::config::{types::Config, LocalConfig};
 use crate::error::WhResult;
 use crate::network::message::Address;
-use crate::pods::arbo::{Arbo, FsEntry, Inode, InodeId, Metadata, GLOBAL_CONFIG_INO};
+use crate::pods::arbo::{Arbo, ArboDigests, FsEntry, Inode, InodeId, Metadata, GLOBAL_CONFIG_INO};
 use crate::pods::disk_managers::DiskManager;
 use crate::pods::filesystem::attrs::AcknoledgeSetAttrError;
 use crate::pods::network::callbacks::Callback;
 use crate::pods::network::network_interface::NetworkInterface;
+use crate::pods::network::transfer::{TransferKind, MAX_RANGE_SIZE};
 
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
 use std::io::{self, ErrorKind};
 use std::sync::Arc;
 
+use super::block_cache::BlockCache;
+use super::conflict::{VersionOrdering, VersionVector};
+use super::eviction::AccessLog;
 use super::file_handle::FileHandleManager;
+use super::integrity::ScrubLog;
 use super::make_inode::MakeInodeError;
+use super::quota::UsageCache;
 
 #[derive(Debug)]
 pub struct FsInterface {
     pub network_interface: Arc<NetworkInterface>,
     pub disk: Box<dyn DiskManager>,
     pub file_handles: Arc<RwLock<FileHandleManager>>,
+    pub block_cache: Arc<Mutex<BlockCache>>,
+    pub accesses: AccessLog,
+    pub usage: UsageCache,
+    pub scrubbed: ScrubLog,
     pub arbo: Arc<RwLock<Arbo>>, // here only to read, as most write are made by network_interface
                                  // REVIEW - check self.arbo usage to be only reading
 }
@@ -28,14 +38,16 @@ pub struct FsInterface {
 pub enum SimpleFileType {
     File,
     Directory,
+    Symlink,
+    NamedPipe,
+    CharDevice,
+    BlockDevice,
+    Socket,
 }
 
 impl Into<SimpleFileType> for &FsEntry {
     fn into(self) -> SimpleFileType {
-        match self {
-            FsEntry::File(_) => SimpleFileType::File,
-            FsEntry::Directory(_) => SimpleFileType::Directory,
-        }
+        self.get_filetype()
     }
 }
 
@@ -51,6 +63,10 @@ impl FsInterface {
             network_interface,
             disk: disk_manager,
             file_handles: Arc::new(RwLock::new(FileHandleManager::new())),
+            block_cache: Arc::new(Mutex::new(BlockCache::new())),
+            accesses: AccessLog::default(),
+            usage: UsageCache::default(),
+            scrubbed: ScrubLog::default(),
             arbo,
         }
     }
@@ -71,10 +87,11 @@ impl FsInterface {
 
     // SECTION - local -> read
 
+    /// Hard links are followed to the file they point to
     pub fn get_entry_from_name(&self, parent: InodeId, name: String) -> io::Result<Inode> {
         let arbo = Arbo::read_lock(&self.arbo, "fs_interface.get_entry_from_name")?;
-        arbo.get_inode_child_by_name(arbo.get_inode(parent)?, &name)
-            .cloned()
+        let entry = arbo.get_inode_child_by_name(arbo.get_inode(parent)?, &name)?;
+        Ok(arbo.resolve(entry)?.clone())
     }
 
     pub fn get_inode_attributes(&self, ino: InodeId) -> io::Result<Metadata> {
@@ -97,7 +114,11 @@ impl FsInterface {
 
         if let FsEntry::Directory(children) = &dir.entry {
             for entry in children {
-                entries.push(arbo.get_inode(*entry)?.clone());
+                // hard links are listed under their name with the data of their file
+                let link = arbo.get_inode(*entry)?;
+                let mut entry = arbo.resolve(link)?.clone();
+                entry.name = link.name.clone();
+                entries.push(entry);
             }
             Ok(entries)
         } else {
@@ -111,6 +132,13 @@ impl FsInterface {
 
     // SECTION - remote -> write
     pub fn recept_inode(&self, inode: Inode) -> Result<(), MakeInodeError> {
+        // a peer can't take the place of the files the pod keeps for itself
+        if Arbo::is_reserved(&inode.name, inode.parent)
+            || Arbo::get_special(&inode.name, inode.parent).is_some_and(|ino| ino != inode.id)
+            || Arbo::is_local_only(inode.id)
+        {
+            return Err(MakeInodeError::AlreadyExist);
+        }
         self.network_interface
             .acknowledge_new_file(inode.clone(), inode.id)?;
         self.network_interface.promote_next_inode(inode.id + 1)?;
@@ -139,75 +167,42 @@ impl FsInterface {
                 .disk
                 .new_dir(&new_path, inode.meta.perm)
                 .map_err(|io| MakeInodeError::LocalCreationFailed { io }),
+            FsEntry::Symlink(target) => self
+                .disk
+                .new_symlink(&new_path, &target)
+                .map_err(|io| MakeInodeError::LocalCreationFailed { io }),
+            FsEntry::HardLink(_) | FsEntry::Special(_) => Ok(()),
             // TODO - remove when merge is handled because new file should create folder
             // FsEntry::Directory(_) => {}
         }
     }
 
-    pub fn recept_redundancy(&self, id: InodeId, binary: Arc<Vec<u8>>) -> WhResult<()> {
-        let arbo = Arbo::write_lock(&self.arbo, "recept_binary")
-            .expect("recept_binary: can't read lock arbo");
-        let (path, perms) = arbo
-            .n_get_path_from_inode_id(id)
-            .and_then(|path| arbo.n_get_inode(id).map(|inode| (path, inode.meta.perm)))?;
-        drop(arbo);
-
-        let _created = self.disk.new_file(&path, perms);
-        self.disk
-            .write_file(&path, &binary, 0)
-            .inspect_err(|e| log::error!("{e}"))
-            .expect("disk error");
-        // TODO -> in case of failure, other hosts still think this one is valid. Should send error report to the redundancy manager
-
+    /// Marks this node as host of a file once fully received
+    pub fn complete_reception(&self, id: InodeId, kind: TransferKind) -> WhResult<()> {
         let address =
-            LocalConfig::read_lock(&self.network_interface.local_config, "recept_redundancy")?
+            LocalConfig::read_lock(&self.network_interface.local_config, "complete_reception")?
                 .general
                 .address
                 .clone();
-        Arbo::n_write_lock(&self.arbo, "recept_redundancy")?
-            .n_add_inode_hosts(id, vec![address])
-            .inspect_err(|e| {
-                log::error!("Can't update (local) hosts for redundancy pulled file ({id}): {e}")
-            })
-    }
 
-    pub fn recept_binary(&self, id: InodeId, binary: Vec<u8>) -> io::Result<()> {
-        let address =
-            LocalConfig::read_lock(&self.network_interface.local_config, "revoke_remote_hosts")
-                .expect("can't read local_config")
-                .general
-                .address
-                .clone();
-        let arbo = Arbo::read_lock(&self.arbo, "recept_binary")
-            .expect("recept_binary: can't read lock arbo");
-        let (path, perms) = match arbo
-            .n_get_path_from_inode_id(id)
-            .and_then(|path| arbo.n_get_inode(id).map(|inode| (path, inode.meta.perm)))
-        {
-            Ok(value) => value,
-            Err(_) => {
-                return self
+        match kind {
+            TransferKind::Pull => {
+                let _ = self.network_interface.transfers.pulled(id);
+                let _ = self
                     .network_interface
                     .callbacks
-                    .resolve(Callback::Pull(id), false)
+                    .resolve(Callback::Pull(id), true);
+                self.network_interface.add_inode_hosts(id, vec![address])
             }
-        };
-        drop(arbo);
-
-        let _created = self.disk.new_file(&path, perms);
-        let status = self
-            .disk
-            .write_file(&path, &binary, 0)
-            .inspect_err(|e| log::error!("writing pulled file: {e}"));
-        let _ = self
-            .network_interface
-            .callbacks
-            .resolve(Callback::Pull(id), status.is_ok());
-        status?;
-        self.network_interface
-            .add_inode_hosts(id, vec![address])
-            .expect("can't update inode hosts");
-        Ok(())
+            // the sender updates the hosts for everyone once the redundancy is applied
+            TransferKind::Redundancy => Arbo::n_write_lock(&self.arbo, "complete_reception")?
+                .n_add_inode_hosts(id, vec![address])
+                .inspect_err(|e| {
+                    log::error!("Can't update (local) hosts for redundancy pulled file ({id}): {e}")
+                }),
+            // holding a shard doesn't make this node a host, see [FsInterface::recept_chunk]
+            TransferKind::Shard(_) | TransferKind::ShardAnswer(_) => Ok(()),
+        }
     }
 
     pub fn recept_edit_hosts(&self, id: InodeId, hosts: Vec<Address>) -> WhResult<()> {
@@ -230,12 +225,53 @@ impl FsInterface {
         id: InodeId,
         host: Address,
         meta: Metadata,
+        version: VersionVector,
+    ) -> Result<(), AcknoledgeSetAttrError> {
+        let local_version = Arbo::n_read_lock(&self.arbo, "recept_revoke_hosts")?
+            .n_get_inode(id)?
+            .version
+            .clone();
+
+        match local_version.compare(&version) {
+            VersionOrdering::Older => self.revoke_local_copy(id, host, meta, version),
+            VersionOrdering::Concurrent => self.resolve_conflict(id, host, meta, version),
+            VersionOrdering::Equal | VersionOrdering::Newer => {
+                log::debug!("recept_revoke_hosts: outdated write of {id} by {host} ignored");
+                Ok(())
+            }
+        }
+    }
+
+    /// Replaces the local version of a file by the one written on `host`
+    pub fn revoke_local_copy(
+        &self,
+        id: InodeId,
+        host: Address,
+        meta: Metadata,
+        version: VersionVector,
     ) -> Result<(), AcknoledgeSetAttrError> {
         let needs_delete = host
             != LocalConfig::read_lock(&self.network_interface.local_config, "recept_binary")?
                 .general
                 .address;
         self.acknowledge_metadata(id, meta)?;
+        let shards = {
+            let mut arbo = Arbo::n_write_lock(&self.arbo, "revoke_local_copy")?;
+            let inode = arbo.n_get_inode_mut(id)?;
+            inode.version = version;
+            inode.shards.take()
+        };
+        if let Some(layout) = shards {
+            self.drop_local_shards(id, &layout)
+                .map_err(|source| AcknoledgeSetAttrError::WhError { source })?;
+        }
+        self.network_interface
+            .transfers
+            .forget_received(id)
+            .map_err(|source| AcknoledgeSetAttrError::WhError { source })?;
+        BlockCache::lock(&self.block_cache, "recept_revoke_hosts")
+            .map_err(|source| AcknoledgeSetAttrError::WhError { source })?
+            .invalidate(id);
         self.network_interface
             .acknowledge_hosts_edition(id, vec![host])
             .map_err(|source| AcknoledgeSetAttrError::WhError { source })?;
@@ -275,7 +311,7 @@ impl FsInterface {
     // !SECTION
 
     // SECTION remote -> read
-    pub fn send_filesystem(&self, to: Address) -> io::Result<()> {
+    pub fn send_filesystem(&self, to: Address, known: Option<ArboDigests>) -> io::Result<()> {
         let arbo = Arbo::read_lock(&self.arbo, "fs_interface::send_filesystem")?;
         let global_config_file_size = arbo
             .get_inode(GLOBAL_CONFIG_INO)
@@ -302,22 +338,36 @@ impl FsInterface {
                     .expect("disk can't read file (global condfig)");
             }
         }
-        self.network_interface.send_arbo(to, global_config_bytes)
+        self.network_interface
+            .send_arbo(to, known, global_config_bytes)
+    }
+
+    pub fn register_new_node(&self, socket: Address, addr: Address) -> WhResult<()> {
+        self.network_interface.register_new_node(socket, addr)
     }
 
-    pub fn register_new_node(&self, socket: Address, addr: Address) {
-        self.network_interface.register_new_node(socket, addr);
+    /// Answers a [crate::network::message::MessageContent::RequestRange] with the asked bytes of a local file
+    pub fn send_range(&self, inode: InodeId, to: Address, offset: u64, len: u64) -> WhResult<()> {
+        let data = self.read_local_range(inode, offset, len).ok();
+        if data.is_none() {
+            log::warn!("send_range: can't read {inode} locally");
+        }
+        self.network_interface.send_range(inode, to, offset, data)
     }
 
-    pub fn send_file(&self, inode: InodeId, to: Address) -> io::Result<()> {
-        let arbo = Arbo::read_lock(&self.arbo, "send_arbo")?;
-        let path = arbo.get_path_from_inode_id(inode)?;
-        let mut size = arbo.get_inode(inode)?.meta.size as usize;
-        let mut data = Vec::new();
-        data.resize(size, 0);
-        size = self.disk.read_file(&path, 0, &mut data)?;
-        data.resize(size, 0);
-        self.network_interface.send_file(inode, data, to)
+    fn read_local_range(&self, inode: InodeId, offset: u64, len: u64) -> WhResult<Vec<u8>> {
+        let arbo = Arbo::n_read_lock(&self.arbo, "read_local_range")?;
+        let path = arbo.n_get_path_from_inode_id(inode)?;
+        let size = arbo.n_get_inode(inode)?.meta.size;
+        drop(arbo);
+
+        let mut buff = vec![0; size.saturating_sub(offset).min(len).min(MAX_RANGE_SIZE) as usize];
+        let read = self
+            .disk
+            .read_file(&path, offset as usize, &mut buff)
+            .map_err(|_| crate::error::WhError::InodeNotFound)?;
+        buff.truncate(read);
+        Ok(buff)
     }
 
     pub fn read_local_file(&self, inode: InodeId) -> WhResult<Vec<u8>> {
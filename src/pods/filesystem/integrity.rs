@@ -0,0 +1,222 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use custom_error::custom_error;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::{types::Config, LocalConfig},
+    error::{WhError, WhResult},
+    network::message::Address,
+    pods::{
+        arbo::{Arbo, FsEntry, InodeId},
+        network::transfer::CHUNK_SIZE,
+    },
+};
+
+use super::fs_interface::FsInterface;
+
+/// SHA-256 of the content of a file
+pub type ContentHash = [u8; 32];
+
+/// Interval between two scrubbing passes, see [FsInterface::scrub]
+pub const SCRUB_INTERVAL: Duration = Duration::from_secs(60);
+/// Bytes rehashed at most by a scrubbing pass, so a pass stays short
+pub const SCRUB_BUDGET: u64 = 256 * 1024 * 1024;
+/// Age of the last check after which an unchanged copy is checked again, to catch it rotting on disk
+pub const SCRUB_STALE_AFTER: Duration = Duration::from_secs(7 * 24 * 3600);
+
+custom_error! {pub IntegrityError
+    WhError{source: WhError} = "{source}",
+    LocalReadFailed{io: std::io::Error} = "Local read failed: {io}",
+    Mismatch{ino: InodeId} = "The local content of {ino} doesn't match its hash",
+}
+
+pub fn hash_content(data: &[u8]) -> ContentHash {
+    Sha256::digest(data).into()
+}
+
+/// Hash each local copy was last found to match, and when, since the pod started
+#[derive(Debug, Default)]
+pub struct ScrubLog(Mutex<HashMap<InodeId, (ContentHash, SystemTime)>>);
+
+impl ScrubLog {
+    pub fn checked(&self, ino: InodeId, hash: ContentHash) {
+        self.0.lock().insert(ino, (hash, SystemTime::now()));
+    }
+
+    /// Last check of a copy still matching `hash`, none if the file changed since
+    pub fn last(&self, ino: InodeId, hash: &ContentHash) -> Option<SystemTime> {
+        match self.0.lock().get(&ino) {
+            Some((checked, at)) if checked == hash => Some(*at),
+            _ => None,
+        }
+    }
+
+    /// Forgets the copies not hosted anymore
+    fn retain(&self, hosted: impl Fn(InodeId) -> bool) {
+        self.0.lock().retain(|ino, _| hosted(*ino));
+    }
+}
+
+impl FsInterface {
+    /// Hashes the local copy of a file, reading it one [CHUNK_SIZE] at a time
+    pub fn hash_local_file(&self, ino: InodeId) -> Result<ContentHash, IntegrityError> {
+        let (path, size) = {
+            let arbo = Arbo::n_read_lock(&self.arbo, "hash_local_file")?;
+            (
+                arbo.n_get_path_from_inode_id(ino)?,
+                arbo.n_get_inode(ino)?.meta.size,
+            )
+        };
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0; CHUNK_SIZE as usize];
+        let mut offset = 0;
+
+        while offset < size {
+            let len = (size - offset).min(CHUNK_SIZE) as usize;
+            let read = self
+                .disk
+                .read_file(&path, offset as usize, &mut buf[..len])
+                .map_err(|io| IntegrityError::LocalReadFailed { io })?;
+            if read == 0 {
+                return Err(IntegrityError::LocalReadFailed {
+                    io: std::io::ErrorKind::UnexpectedEof.into(),
+                });
+            }
+            hasher.update(&buf[..read]);
+            offset += read as u64;
+        }
+        Ok(hasher.finalize().into())
+    }
+
+    /// Hashes the local copy of a file and shares the hash with the network
+    pub fn update_hash(&self, ino: InodeId) -> Result<ContentHash, IntegrityError> {
+        let hash = self.hash_local_file(ino)?;
+        let mut meta = Arbo::n_read_lock(&self.arbo, "update_hash")?
+            .n_get_inode(ino)?
+            .meta
+            .clone();
+
+        meta.hash = Some(hash);
+        self.network_interface.update_metadata(ino, meta)?;
+        self.scrubbed.checked(ino, hash);
+        Ok(hash)
+    }
+
+    /// Checks the local copy of a file against the hash kept in the arbo
+    ///
+    /// Files whose hash is still unknown are considered valid.
+    pub fn verify_local_file(&self, ino: InodeId) -> Result<(), IntegrityError> {
+        let expected = Arbo::n_read_lock(&self.arbo, "verify_local_file")?
+            .n_get_inode(ino)?
+            .meta
+            .hash;
+
+        match expected {
+            Some(expected) if expected != self.hash_local_file(ino)? => {
+                Err(IntegrityError::Mismatch { ino })
+            }
+            Some(expected) => {
+                self.scrubbed.checked(ino, expected);
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Scrubs the local copies every [SCRUB_INTERVAL]
+    pub async fn scrub_worker(fs_interface: Arc<FsInterface>) {
+        let mut ticker = tokio::time::interval(SCRUB_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let fs_interface = fs_interface.clone();
+            // hashing reads the copies from the disk
+            match tokio::task::spawn_blocking(move || fs_interface.scrub(SCRUB_BUDGET)).await {
+                Ok(Ok(0)) => (),
+                Ok(Ok(corrupted)) => log::warn!("Scrub: {corrupted} corrupted copies found"),
+                Ok(Err(e)) => log::warn!("Scrub: {e}"),
+                Err(e) => log::error!("scrub_worker: error in thread pool: {e}"),
+            }
+        }
+    }
+
+    /// Checks the copies hosted by this pod against their hash, rehashing at most `budget` bytes
+    /// (and at least one file), and returns the number of corrupted copies found:
+    /// - unknown hashes are computed by the first host
+    /// - copies never checked, changed or checked more than [SCRUB_STALE_AFTER] ago
+    ///   are rehashed, the least recently checked first
+    /// - corrupted copies are dropped and pulled again from another host
+    pub fn scrub(&self, budget: u64) -> WhResult<usize> {
+        let self_addr = LocalConfig::read_lock(&self.network_interface.local_config, "scrub")?
+            .general
+            .address
+            .clone();
+        let hosted: Vec<(InodeId, Vec<Address>, Option<ContentHash>, u64)> =
+            Arbo::n_read_lock(&self.arbo, "scrub")?
+                .iter()
+                .filter(|(ino, _)| !Arbo::is_local_only(**ino))
+                .filter_map(|(ino, inode)| match &inode.entry {
+                    FsEntry::File(hosts) if hosts.contains(&self_addr) => {
+                        Some((*ino, hosts.clone(), inode.meta.hash, inode.meta.size))
+                    }
+                    _ => None,
+                })
+                .collect();
+        self.scrubbed
+            .retain(|ino| hosted.iter().any(|(hosted, ..)| *hosted == ino));
+
+        let now = SystemTime::now();
+        let mut due: Vec<_> = hosted
+            .into_iter()
+            .filter_map(|(ino, mut hosts, hash, size)| {
+                let last = hash.and_then(|hash| self.scrubbed.last(ino, &hash));
+                let stale = last.is_none_or(|last| {
+                    now.duration_since(last).unwrap_or_default() >= SCRUB_STALE_AFTER
+                });
+                hosts.sort();
+                // only the first host computes a missing hash
+                let hashing = hash.is_some() || hosts[0] == self_addr;
+                (stale && hashing).then_some((ino, hosts, hash, size, last))
+            })
+            .collect();
+        due.sort_by_key(|(.., last)| *last);
+
+        let mut spent = 0;
+        let mut corrupted = 0;
+        for (ino, mut hosts, hash, size, _) in due {
+            if spent > 0 && spent + size > budget {
+                break;
+            }
+            spent += size;
+            if hash.is_none() {
+                let _ = self
+                    .update_hash(ino)
+                    .inspect_err(|e| log::warn!("Scrub: can't hash {ino}: {e}"));
+                continue;
+            }
+            match self.verify_local_file(ino) {
+                Ok(()) => (),
+                Err(e @ IntegrityError::Mismatch { ino: _ }) => {
+                    corrupted += 1;
+                    hosts.retain(|host| *host != self_addr);
+                    match hosts.first() {
+                        Some(sane_host) => {
+                            log::warn!("Scrub: {e}, pulling it again from {sane_host}");
+                            self.network_interface.update_hosts(ino, hosts.clone())?;
+                            self.network_interface
+                                .request_file(ino, sane_host, &self_addr)?;
+                        }
+                        None => log::error!("Scrub: {e}, and no other host has it"),
+                    }
+                }
+                Err(e) => log::warn!("Scrub: can't check {ino}: {e}"),
+            }
+        }
+        Ok(corrupted)
+    }
+}
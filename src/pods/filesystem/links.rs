@@ -0,0 +1,62 @@
+use custom_error::custom_error;
+
+use crate::{
+    error::WhError,
+    pods::arbo::{Arbo, FsEntry, Inode, InodeId},
+};
+
+use super::{fs_interface::FsInterface, make_inode::MakeInodeError};
+
+custom_error! {pub LinkError
+    WhError{source: WhError} = "{source}",
+    MakeInode{source: MakeInodeError} = "{source}",
+    NotAFile = "Only files can be hard linked",
+    NotASymlink = "Inode isn't a symlink",
+}
+
+impl FsInterface {
+    /// Create a symlink to `target`, which is replicated as is on every pod
+    pub fn make_symlink(
+        &self,
+        parent_ino: InodeId,
+        name: String,
+        target: String,
+    ) -> Result<Inode, MakeInodeError> {
+        self.make_inode_with_entry(parent_ino, name, 0o777, FsEntry::Symlink(target), 0)
+    }
+
+    pub fn read_link(&self, ino: InodeId) -> Result<String, LinkError> {
+        let arbo = Arbo::n_read_lock(&self.arbo, "fs_interface::read_link")?;
+
+        match &arbo.n_get_inode(ino)?.entry {
+            FsEntry::Symlink(target) => Ok(target.clone()),
+            _ => Err(LinkError::NotASymlink),
+        }
+    }
+
+    /// Give another name to a file
+    ///
+    /// Returns the linked file, as the link shares its inode for the kernel
+    pub fn make_hard_link(
+        &self,
+        ino: InodeId,
+        new_parent: InodeId,
+        new_name: String,
+    ) -> Result<Inode, LinkError> {
+        let (target, perm) = {
+            let arbo = Arbo::n_read_lock(&self.arbo, "fs_interface::make_hard_link")?;
+            let inode = arbo.n_resolve(arbo.n_get_inode(ino)?)?;
+            match inode.entry {
+                FsEntry::File(_) => (inode.id, inode.meta.perm),
+                _ => return Err(LinkError::NotAFile),
+            }
+        };
+
+        self.make_inode_with_entry(new_parent, new_name, perm, FsEntry::HardLink(target), 0)?;
+        Ok(
+            Arbo::n_read_lock(&self.arbo, "fs_interface::make_hard_link")?
+                .n_get_inode(target)?
+                .clone(),
+        )
+    }
+}
@@ -1,9 +1,12 @@
 use custom_error::custom_error;
 
 use crate::{
-    config::{types::Config, LocalConfig},
-    error::WhError,
-    pods::arbo::{Arbo, FsEntry, Inode},
+    config::{types::Config, GlobalConfig, LocalConfig},
+    error::{WhError, WhResult},
+    pods::{
+        arbo::{Arbo, FsEntry, Inode, InodeId},
+        whpath::WhPath,
+    },
 };
 
 use super::{
@@ -19,6 +22,7 @@ custom_error! {pub MakeInodeError
     ParentNotFolder = "Parent isn't a folder",
     LocalCreationFailed{io: std::io::Error} = "Local creation failed: {io}",
     ProtectedNameIsFolder = "Protected name can't be used for folders",
+    SymlinkWithoutTarget = "A symlink can't be made without its target",
 }
 
 custom_error! {pub CreateError
@@ -71,31 +75,77 @@ impl FsInterface {
             .address
             .clone()]),
             SimpleFileType::Directory => FsEntry::Directory(Vec::new()),
+            SimpleFileType::Symlink => return Err(MakeInodeError::SymlinkWithoutTarget),
+            special => FsEntry::Special(special),
         };
 
+        self.make_inode_with_entry(parent_ino, name, permissions, new_entry, 0)
+    }
+
+    /// Create a fifo, a socket or a device, only known by the arbo
+    pub fn make_special(
+        &self,
+        parent_ino: u64,
+        name: String,
+        permissions: u16,
+        kind: SimpleFileType,
+        rdev: u32,
+    ) -> Result<Inode, MakeInodeError> {
+        match kind {
+            SimpleFileType::File | SimpleFileType::Directory => {
+                self.make_inode(parent_ino, name, permissions, kind)
+            }
+            SimpleFileType::Symlink => Err(MakeInodeError::SymlinkWithoutTarget),
+            special => self.make_inode_with_entry(
+                parent_ino,
+                name,
+                permissions,
+                FsEntry::Special(special),
+                rdev,
+            ),
+        }
+    }
+
+    /// Whether an entry at `path`, in `parent_ino`, stays on this pod
+    pub(super) fn is_ignored(
+        &self,
+        parent_ino: InodeId,
+        path: &WhPath,
+        is_dir: bool,
+    ) -> WhResult<bool> {
+        // everything under an ignored folder stays on this pod too
+        Ok(Arbo::is_local_only(parent_ino)
+            || GlobalConfig::read_lock(&self.network_interface.global_config, "is_ignored")?
+                .general
+                .ignore_rules()
+                .is_ignored(&path.inner, is_dir))
+    }
+
+    /// Register a new [Inode] holding `new_entry` in the network,
+    /// creating on disk what the entry needs
+    pub(super) fn make_inode_with_entry(
+        &self,
+        parent_ino: u64,
+        name: String,
+        permissions: u16,
+        new_entry: FsEntry,
+        rdev: u32,
+    ) -> Result<Inode, MakeInodeError> {
+        if Arbo::is_reserved(&name, parent_ino) {
+            return Err(MakeInodeError::AlreadyExist);
+        }
         let special_ino = Arbo::get_special(&name, parent_ino);
-        if special_ino.is_some() && kind != SimpleFileType::File {
+        if special_ino.is_some() && !matches!(new_entry, FsEntry::File(_)) {
             return Err(MakeInodeError::ProtectedNameIsFolder);
         }
-        let new_inode_id = special_ino
-            .ok_or(())
-            .or_else(|_| self.network_interface.n_get_next_inode())?;
-
-        let new_inode = Inode::new(
-            name.clone(),
-            parent_ino,
-            new_inode_id,
-            new_entry,
-            permissions,
-        );
 
         let mut new_path;
         {
             let arbo = Arbo::n_read_lock(&self.arbo, "make inode")?;
 
-            let parent = arbo.n_get_inode(new_inode.parent)?;
+            let parent = arbo.n_get_inode(parent_ino)?;
             //check if already exist
-            match arbo.n_get_inode_child_by_name(&parent, &new_inode.name) {
+            match arbo.n_get_inode_child_by_name(parent, &name) {
                 Ok(_) => return Err(MakeInodeError::AlreadyExist),
                 Err(WhError::InodeNotFound) => {}
                 Err(err) => return Err(MakeInodeError::WhError { source: err }),
@@ -104,16 +154,34 @@ impl FsInterface {
             new_path.push(&name);
         }
 
-        match kind {
-            SimpleFileType::File => self
-                .disk
-                .new_file(&new_path, new_inode.meta.perm)
-                .map_err(|io| MakeInodeError::LocalCreationFailed { io }),
-            SimpleFileType::Directory => self
-                .disk
-                .new_dir(&new_path, new_inode.meta.perm)
-                .map_err(|io| MakeInodeError::LocalCreationFailed { io }),
-        }?;
+        let ignored = self.is_ignored(
+            parent_ino,
+            &new_path,
+            matches!(new_entry, FsEntry::Directory(_)),
+        )?;
+        let new_inode_id = match special_ino {
+            Some(ino) => ino,
+            None if ignored => self.network_interface.n_get_next_local_inode()?,
+            None => self.network_interface.n_get_next_inode()?,
+        };
+
+        let mut new_inode = Inode::new(
+            name.clone(),
+            parent_ino,
+            new_inode_id,
+            new_entry,
+            permissions,
+        );
+        new_inode.meta.rdev = rdev;
+
+        match &new_inode.entry {
+            FsEntry::File(_) => self.disk.new_file(&new_path, new_inode.meta.perm),
+            FsEntry::Directory(_) => self.disk.new_dir(&new_path, new_inode.meta.perm),
+            FsEntry::Symlink(target) => self.disk.new_symlink(&new_path, target),
+            // the data is the one of the linked file, specials have none
+            FsEntry::HardLink(_) | FsEntry::Special(_) => Ok(()),
+        }
+        .map_err(|io| MakeInodeError::LocalCreationFailed { io })?;
         self.network_interface
             .register_new_inode(new_inode.clone())?;
         Ok(new_inode)
@@ -1,9 +1,15 @@
 pub mod attrs;
+pub mod block_cache;
+pub mod conflict;
+pub mod eviction;
 pub mod file_handle;
 pub mod fs_interface;
+pub mod integrity;
+pub mod links;
 pub mod make_inode;
 pub mod open;
 pub mod permissions;
+pub mod quota;
 pub mod read;
 pub mod release;
 pub mod remove_inode;
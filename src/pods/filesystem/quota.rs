@@ -0,0 +1,157 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use custom_error::custom_error;
+use parking_lot::Mutex;
+
+use crate::{
+    config::{types::Config, LocalConfig},
+    error::{WhError, WhResult},
+    pods::{
+        arbo::{Arbo, FsEntry, InodeId},
+        network::transfer::TransferPart,
+    },
+};
+
+use super::fs_interface::FsInterface;
+
+/// Time during which the space used by the pod is trusted without counting it again
+pub const USAGE_REFRESH: Duration = Duration::from_secs(1);
+
+const MO: u64 = 1024 * 1024;
+
+custom_error! {pub QuotaError
+    WhError{source: WhError} = "{source}",
+    NoSpace{needed: u64, available: u64} = "Not enough space: {needed} bytes needed, {available} available",
+}
+
+/// Space used by the pod, counted again at most every [USAGE_REFRESH]
+#[derive(Debug, Default)]
+pub struct UsageCache {
+    counted: Mutex<Option<(Instant, u64)>>,
+    /// Bytes reserved by the receptions in progress, the arbo doesn't count them until they complete
+    incoming: Mutex<HashMap<(InodeId, TransferPart), u64>>,
+}
+
+impl UsageCache {
+    fn get(&self, count: impl FnOnce() -> WhResult<u64>) -> WhResult<u64> {
+        let mut usage = self.counted.lock();
+        match *usage {
+            Some((counted, used)) if counted.elapsed() < USAGE_REFRESH => Ok(used),
+            _ => {
+                let used = count()?;
+                *usage = Some((Instant::now(), used));
+                Ok(used)
+            }
+        }
+    }
+
+    /// Accounts for bytes stored since the last count
+    fn add(&self, bytes: u64) {
+        if let Some((_, used)) = self.counted.lock().as_mut() {
+            *used += bytes;
+        }
+    }
+
+    fn incoming(&self) -> u64 {
+        self.incoming.lock().values().sum()
+    }
+}
+
+impl FsInterface {
+    /// Bytes taken by the copies and shards held by this pod, or being received
+    pub fn used_space(&self) -> WhResult<u64> {
+        let counted = self.usage.get(|| {
+            let self_addr =
+                LocalConfig::read_lock(&self.network_interface.local_config, "used_space")?
+                    .general
+                    .address
+                    .clone();
+            Ok(Arbo::n_read_lock(&self.arbo, "used_space")?
+                .iter()
+                .map(|(_, inode)| match (&inode.entry, &inode.shards) {
+                    (FsEntry::File(hosts), _) if hosts.contains(&self_addr) => inode.meta.size,
+                    (FsEntry::File(_), Some(layout)) => {
+                        inode.meta.size.div_ceil(layout.data_shards.max(1) as u64)
+                            * layout.held_by(&self_addr).len() as u64
+                    }
+                    _ => 0,
+                })
+                .sum())
+        })?;
+        Ok(counted + self.usage.incoming())
+    }
+
+    /// Bytes this pod accepts to hold, None without quota
+    pub fn quota(&self) -> WhResult<Option<u64>> {
+        Ok(
+            LocalConfig::read_lock(&self.network_interface.local_config, "quota")?
+                .storage
+                .quota
+                .map(|quota| quota * MO),
+        )
+    }
+
+    /// Bytes this pod can still store: the free space of its disk, limited by its quota.
+    /// None if neither is known.
+    pub fn available_space(&self) -> WhResult<Option<u64>> {
+        let disk = self
+            .disk
+            .size_info()
+            .inspect_err(|e| log::warn!("Can't get the disk size: {e}"))
+            .ok()
+            .map(|size| size.free_size as u64);
+        let quota = match self.quota()? {
+            Some(quota) => Some(quota.saturating_sub(self.used_space()?)),
+            None => None,
+        };
+        Ok(match (disk, quota) {
+            (Some(disk), Some(quota)) => Some(disk.min(quota)),
+            (disk, quota) => disk.or(quota),
+        })
+    }
+
+    /// Reserves `needed` more bytes, refused if the disk or the quota can't take them
+    pub fn reserve_space(&self, needed: u64) -> Result<(), QuotaError> {
+        if needed == 0 {
+            return Ok(());
+        }
+        match self.available_space()? {
+            Some(available) if available < needed => Err(QuotaError::NoSpace { needed, available }),
+            _ => {
+                self.usage.add(needed);
+                Ok(())
+            }
+        }
+    }
+
+    /// Reserves the `needed` bytes of a file or shard received from another pod,
+    /// until [FsInterface::release_reception]
+    pub fn reserve_reception(
+        &self,
+        ino: InodeId,
+        part: TransferPart,
+        needed: u64,
+    ) -> Result<(), QuotaError> {
+        // a reception started again replaces the reservation of the previous attempt
+        self.usage.incoming.lock().remove(&(ino, part));
+        match self.available_space()? {
+            Some(available) if available < needed => Err(QuotaError::NoSpace { needed, available }),
+            _ => {
+                self.usage.incoming.lock().insert((ino, part), needed);
+                Ok(())
+            }
+        }
+    }
+
+    /// Ends the reservation of a reception, its bytes stay used if it was `stored`
+    pub fn release_reception(&self, ino: InodeId, part: TransferPart, stored: bool) {
+        if let Some(bytes) = self.usage.incoming.lock().remove(&(ino, part)) {
+            if stored {
+                self.usage.add(bytes);
+            }
+        }
+    }
+}
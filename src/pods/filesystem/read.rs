@@ -1,4 +1,9 @@
-use crate::pods::arbo::Arbo;
+use std::time::Instant;
+
+use crate::config::{types::Config, LocalConfig};
+use crate::data::metrics::METRICS;
+use crate::pods::arbo::{Arbo, FsEntry};
+use crate::pods::filesystem::block_cache::{BlockCache, CACHE_BLOCK_SIZE};
 use crate::pods::filesystem::file_handle::{AccessMode, FileHandle, FileHandleManager, UUID};
 use crate::pods::network::pull_file::PullError;
 use crate::{error::WhError, pods::arbo::InodeId};
@@ -45,14 +50,7 @@ impl FsInterface {
         offset: usize,
         buf: &mut [u8],
     ) -> Result<usize, ReadError> {
-        let ok = match self.network_interface.pull_file_sync(file)? {
-            None => true,
-            Some(call) => self.network_interface.callbacks.n_wait_for(call)?,
-        };
-
-        if !ok {
-            return Err(ReadError::CantPull);
-        }
+        self.fetch_local_copy(file)?;
 
         self.disk
             .read_file(
@@ -63,6 +61,39 @@ impl FsInterface {
             .map_err(|io| ReadError::LocalReadFailed { io })
     }
 
+    /// Makes sure a complete copy of the file is on this pod, pulling or rebuilding it if needed
+    pub fn fetch_local_copy(&self, file: InodeId) -> Result<(), ReadError> {
+        if self.only_in_shards(file)? {
+            return self.rebuild_file(file).map_err(|e| {
+                log::error!("Can't rebuild {file} from its shards: {e}");
+                ReadError::CantPull
+            });
+        }
+        let started = Instant::now();
+        let ok = match self
+            .network_interface
+            .pull_file_sync(file)
+            .inspect_err(|_| METRICS.pull_failed())?
+        {
+            None => true,
+            Some(call) => {
+                let ok = self
+                    .network_interface
+                    .callbacks
+                    .n_wait_for(call)
+                    .inspect_err(|_| METRICS.pull_failed())?;
+                METRICS.pull_done(started.elapsed(), ok);
+                ok
+            }
+        };
+
+        if ok {
+            Ok(())
+        } else {
+            Err(ReadError::CantPull)
+        }
+    }
+
     pub fn read_file(
         &self,
         file: InodeId,
@@ -74,7 +105,127 @@ impl FsInterface {
             let file_handles = FileHandleManager::read_lock(&self.file_handles, "read")?;
             let _file_handle = check_file_handle(&file_handles, file_handle)?;
         }
+        self.accesses.touch(file);
+
+        if self.is_hosted_locally(file)? || self.only_in_shards(file)? {
+            self.get_file_data(file, offset, buf)
+        } else {
+            self.read_remote_range(file, offset, buf)
+        }
+    }
+
+    /// Tells if an erasure coded file has no complete copy left to read from
+    fn only_in_shards(&self, file: InodeId) -> Result<bool, ReadError> {
+        let arbo = Arbo::n_read_lock(&self.arbo, "read")?;
+        let inode = arbo.n_get_inode(file)?;
+        Ok(inode.shards.is_some()
+            && matches!(&inode.entry, FsEntry::File(hosts) if hosts.is_empty()))
+    }
+
+    fn is_hosted_locally(&self, file: InodeId) -> Result<bool, ReadError> {
+        let address = LocalConfig::read_lock(&self.network_interface.local_config, "read")?
+            .general
+            .address
+            .clone();
+        Ok(
+            match &Arbo::n_read_lock(&self.arbo, "read")?
+                .n_get_inode(file)?
+                .entry
+            {
+                FsEntry::File(hosts) => hosts.contains(&address),
+                _ => return Err(WhError::InodeIsADirectory.into()),
+            },
+        )
+    }
+
+    /// Serves a read of a file hosted elsewhere, asking only the needed blocks to the hosts
+    ///
+    /// Blocks already known are taken from the [BlockCache] and each run
+    /// of missing blocks is fetched with a single range request.
+    fn read_remote_range(
+        &self,
+        file: InodeId,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, ReadError> {
+        let size = Arbo::n_read_lock(&self.arbo, "read_remote_range")?
+            .n_get_inode(file)?
+            .meta
+            .size;
+        let cache_capacity =
+            LocalConfig::read_lock(&self.network_interface.local_config, "read_remote_range")?
+                .cache
+                .size
+                * 1024
+                * 1024;
+        let offset = offset as u64;
+        let end = size.min(offset + buf.len() as u64);
+        if offset >= end {
+            return Ok(0);
+        }
+
+        let first_block = offset / CACHE_BLOCK_SIZE;
+        let last_block = (end - 1) / CACHE_BLOCK_SIZE;
+        let mut blocks: Vec<Option<Vec<u8>>> = {
+            let mut cache = BlockCache::lock(&self.block_cache, "read_remote_range")?;
+            (first_block..=last_block)
+                .map(|block| cache.get(file, block))
+                .collect()
+        };
+
+        let mut i = 0;
+        while i < blocks.len() {
+            if blocks[i].is_some() {
+                i += 1;
+                continue;
+            }
+            let missing = blocks[i..].iter().take_while(|b| b.is_none()).count();
+            let range_start = (first_block + i as u64) * CACHE_BLOCK_SIZE;
+            let range_len = (missing as u64 * CACHE_BLOCK_SIZE).min(size - range_start);
+
+            // hosts answer at most MAX_RANGE_SIZE at once, only an empty answer means the file ended
+            let mut data = Vec::new();
+            while (data.len() as u64) < range_len {
+                let part = self.network_interface.pull_range_sync(
+                    file,
+                    range_start + data.len() as u64,
+                    range_len - data.len() as u64,
+                )?;
+                if part.is_empty() {
+                    break;
+                }
+                data.extend(part);
+            }
+
+            let mut cache = BlockCache::lock(&self.block_cache, "read_remote_range")?;
+            for (j, block) in data.chunks(CACHE_BLOCK_SIZE as usize).enumerate() {
+                cache.insert(
+                    file,
+                    first_block + (i + j) as u64,
+                    block.to_vec(),
+                    cache_capacity,
+                );
+                blocks[i + j] = Some(block.to_vec());
+            }
+            if (data.len() as u64) < range_len {
+                // the file is shorter than expected, nothing to read after
+                break;
+            }
+            i += missing;
+        }
 
-        self.get_file_data(file, offset, buf)
+        let mut read = 0;
+        for (i, block) in blocks.iter().enumerate() {
+            let Some(block) = block else { break };
+            let position = offset + read as u64;
+            let in_block = (position - (first_block + i as u64) * CACHE_BLOCK_SIZE) as usize;
+            if in_block >= block.len() {
+                break;
+            }
+            let len = (block.len() - in_block).min((end - position) as usize);
+            buf[read..read + len].copy_from_slice(&block[in_block..in_block + len]);
+            read += len;
+        }
+        Ok(read)
     }
 }
@@ -1,14 +1,36 @@
-use crate::error::WhResult;
+use crate::{
+    error::WhResult,
+    pods::arbo::{Arbo, InodeId},
+};
 
 use super::{
-    file_handle::{FileHandleManager, UUID},
+    file_handle::{AccessMode, FileHandle, FileHandleManager, UUID},
     fs_interface::FsInterface,
 };
 
 impl FsInterface {
-    pub fn release(&self, file_handle: UUID) -> WhResult<()> {
+    pub fn release(&self, ino: InodeId, file_handle: UUID) -> WhResult<()> {
         let mut file_handles = FileHandleManager::write_lock(&self.file_handles, "release")?;
-        file_handles.handles.remove(&file_handle);
+        let released = file_handles.handles.remove(&file_handle);
+        drop(file_handles);
+
+        // writes reset the hash, it is computed again once the writer is done
+        let written = matches!(
+            released,
+            Some(FileHandle {
+                perm: AccessMode::Write | AccessMode::ReadWrite,
+                ..
+            })
+        ) && Arbo::n_read_lock(&self.arbo, "release")?
+            .n_get_inode(ino)
+            .is_ok_and(|inode| inode.meta.hash.is_none());
+        if written {
+            match self.update_hash(ino) {
+                // erasure coding waits for the file to be written, see [FsInterface::encode_file]
+                Ok(_) => self.network_interface.apply_redundancy(ino),
+                Err(e) => log::warn!("release: can't update the hash of {ino}: {e}"),
+            }
+        }
         return Ok(());
     }
 }
@@ -6,7 +6,7 @@ use crate::{
     pods::arbo::{Arbo, FsEntry, InodeId},
 };
 
-use super::fs_interface::FsInterface;
+use super::{block_cache::BlockCache, fs_interface::FsInterface, rename::RenameError};
 
 custom_error! {
     /// Error describing the removal of a [Inode] from the [Arbo]
@@ -20,7 +20,8 @@ custom_error! {
     pub RemoveFileError
     WhError{source: WhError} = "{source}",
     NonEmpty = "Can't remove non-empty dir",
-    LocalDeletionFailed{io: std::io::Error} = "Local Deletion failed: {io}"
+    LocalDeletionFailed{io: std::io::Error} = "Local Deletion failed: {io}",
+    LinkPromotionFailed{source: RenameError} = "Moving the file in place of one of its links failed: {source}",
 }
 
 impl From<RemoveInodeError> for RemoveFileError {
@@ -52,6 +53,9 @@ impl FsInterface {
     pub fn remove_inode_locally(&self, id: InodeId) -> Result<(), RemoveFileError> {
         let arbo = Arbo::n_read_lock(&self.arbo, "fs_interface::remove_inode")?;
         let to_remove_path = arbo.n_get_path_from_inode_id(id)?;
+        if let Some(layout) = &arbo.n_get_inode(id)?.shards {
+            self.drop_local_shards(id, layout)?;
+        }
 
         match &arbo.n_get_inode(id)?.entry {
             FsEntry::File(hosts)
@@ -69,6 +73,7 @@ impl FsInterface {
                     .map_err(|io| RemoveFileError::LocalDeletionFailed { io })?
             }
             FsEntry::File(_) => {
+                BlockCache::lock(&self.block_cache, "remove_inode_locally")?.invalidate(id);
                 // TODO: Remove when wormhole initialisation is cleaner
                 // try to delete the file even if it's not owned to prevent from conflicts on creation later
                 let _ = self.disk.remove_file(&to_remove_path);
@@ -78,11 +83,41 @@ impl FsInterface {
                 .remove_dir(&to_remove_path)
                 .map_err(|io| RemoveFileError::LocalDeletionFailed { io })?,
             FsEntry::Directory(_) => return Err(RemoveFileError::NonEmpty),
+            FsEntry::Symlink(_) => self
+                .disk
+                .remove_file(&to_remove_path)
+                .map_err(|io| RemoveFileError::LocalDeletionFailed { io })?,
+            // only the indexed ones can be found on disk
+            FsEntry::HardLink(_) | FsEntry::Special(_) => {
+                let _ = self.disk.remove_file(&to_remove_path);
+            }
         };
         Ok(())
     }
 
+    /// Moves a file that still has hard links in place of one of them, so its data
+    /// and inode live on under that name
+    ///
+    /// Returns false when the file has no link left
+    pub fn promote_link(&self, id: InodeId) -> Result<bool, RemoveFileError> {
+        let (file, link) = {
+            let arbo = Arbo::n_read_lock(&self.arbo, "fs_interface::promote_link")?;
+            let link = match arbo.links_to(id).first() {
+                Some(link) => (link.parent, link.name.clone()),
+                None => return Ok(false),
+            };
+            let file = arbo.n_get_inode(id)?;
+            ((file.parent, file.name.clone()), link)
+        };
+
+        self.rename(file.0, link.0, &file.1, &link.1, true)?;
+        Ok(true)
+    }
+
     pub fn remove_inode(&self, id: InodeId) -> Result<(), RemoveFileError> {
+        if self.promote_link(id)? {
+            return Ok(());
+        }
         self.remove_inode_locally(id)?;
         self.network_interface.unregister_inode(id)?;
         Ok(())
@@ -5,7 +5,7 @@ use custom_error::custom_error;
 use crate::{
     error::{WhError, WhResult},
     pods::{
-        arbo::{Arbo, InodeId, Metadata},
+        arbo::{Arbo, FsEntry, InodeId, Metadata},
         whpath::WhPath,
     },
 };
@@ -30,6 +30,8 @@ custom_error! {
     ProtectedNameIsFolder = "Protected name can't be used for folders",
     ReadFailed{source: ReadError} = "Read failed on copy: {source}",
     LocalWriteFailed{io: std::io::Error} = "Write failed on copy: {io}",
+    SymlinkWithoutTarget = "A symlink can't be copied without its target",
+    CrossesIgnoredPaths = "Can't move a file in or out of the paths kept off the network",
 }
 
 impl FsInterface {
@@ -49,10 +51,20 @@ impl FsInterface {
     ) -> Result<(), RenameError> {
         let parent_path = self.construct_file_path(parent, name)?;
         let new_parent_path = self.construct_file_path(new_parent, new_name)?;
+        let on_disk = {
+            let arbo = Arbo::n_read_lock(&self.arbo, "fs_interface::rename_locally")?;
+            let parent = arbo.n_get_inode(parent)?;
+            !matches!(
+                arbo.n_get_inode_child_by_name(parent, name)?.entry,
+                FsEntry::HardLink(_) | FsEntry::Special(_)
+            )
+        };
 
-        self.disk
-            .mv_file(&parent_path, &new_parent_path)
-            .map_err(|io| RenameError::LocalRenamingFailed { io })
+        match self.disk.mv_file(&parent_path, &new_parent_path) {
+            // only the indexed links and special files can be found on disk
+            Err(io) if !on_disk && io.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result.map_err(|io| RenameError::LocalRenamingFailed { io }),
+        }
     }
 
     pub fn set_meta_size(&self, ino: InodeId, meta: Metadata) -> Result<(), RenameError> {
@@ -109,6 +121,7 @@ impl FsInterface {
                         RenameError::LocalRenamingFailed { io }
                     }
                     MakeInodeError::ProtectedNameIsFolder => RenameError::ProtectedNameIsFolder,
+                    MakeInodeError::SymlinkWithoutTarget => RenameError::SymlinkWithoutTarget,
                 })?
                 .id
         };
@@ -130,6 +143,7 @@ impl FsInterface {
             RemoveFileError::WhError { source } => RenameError::WhError { source },
             RemoveFileError::NonEmpty => unreachable!("special files cannot be folders"),
             RemoveFileError::LocalDeletionFailed { io } => RenameError::LocalRenamingFailed { io },
+            RemoveFileError::LinkPromotionFailed { source } => source,
         })?;
 
         Ok(())
@@ -156,9 +170,12 @@ impl FsInterface {
         if parent == new_parent && name == new_name {
             return Ok(());
         }
+        if Arbo::is_reserved(new_name, new_parent) {
+            return Err(RenameError::DestinationExists);
+        }
 
         let arbo = Arbo::n_read_lock(&self.arbo, "fs_interface::remove_inode")?;
-        let src_ino = arbo
+        let src = arbo
             .n_get_inode_child_by_name(
                 arbo.n_get_inode(parent).map_err(|err| match err {
                     WhError::InodeNotFound => RenameError::SourceParentNotFound,
@@ -171,8 +188,8 @@ impl FsInterface {
                 WhError::InodeNotFound => RenameError::SourceParentNotFound,
                 WhError::InodeIsNotADirectory => RenameError::SourceParentNotFolder,
                 source => RenameError::WhError { source },
-            })?
-            .id; // assert source file exists
+            })?; // assert source file exists
+        let (src_ino, src_is_dir) = (src.id, matches!(src.entry, FsEntry::Directory(_)));
         let dest_ino =
             match arbo.n_get_inode_child_by_name(arbo.n_get_inode(new_parent)?, &new_name) {
                 Ok(inode) => Some(inode.id),
@@ -190,10 +207,23 @@ impl FsInterface {
         {
             return self.rename_special(new_parent, new_name, src_ino, dest_ino);
         }
+        // the network can't follow a file moved in or out of the ignored paths,
+        // it is left to a copy as between two file systems
+        let new_path = self.construct_file_path(new_parent, new_name)?;
+        if Arbo::is_local_only(src_ino) != self.is_ignored(new_parent, &new_path, src_is_dir)? {
+            return Err(RenameError::CrossesIgnoredPaths);
+        }
 
         if let Some(dest_ino) = dest_ino {
             log::debug!("overwriting!!");
-            match self.recept_remove_inode(dest_ino) {
+            // an overwritten file keeps living through its hard links
+            let removed = self
+                .promote_link(dest_ino)
+                .and_then(|promoted| match promoted {
+                    true => Ok(()),
+                    false => self.recept_remove_inode(dest_ino),
+                });
+            match removed {
                 Ok(_) => (),
                 Err(RemoveFileError::LocalDeletionFailed { io }) => {
                     return Err(RenameError::LocalOverwriteFailed { io })
@@ -202,6 +232,7 @@ impl FsInterface {
                 Err(RemoveFileError::WhError { source }) => {
                     return Err(RenameError::WhError { source })
                 }
+                Err(RemoveFileError::LinkPromotionFailed { source }) => return Err(source),
             }
         }
 
@@ -219,6 +250,9 @@ impl FsInterface {
         new_name: &String,
         overwrite: bool,
     ) -> Result<(), RenameError> {
+        if Arbo::is_reserved(new_name, new_parent) {
+            return Err(RenameError::DestinationExists);
+        }
         let arbo = Arbo::n_read_lock(&self.arbo, "fs_interface::remove_inode")?;
         let dest_ino =
             match arbo.n_get_inode_child_by_name(arbo.n_get_inode(new_parent)?, &new_name) {
@@ -239,6 +273,7 @@ impl FsInterface {
                     Err(RemoveFileError::WhError { source }) => {
                         return Err(RenameError::WhError { source })
                     }
+                    Err(RemoveFileError::LinkPromotionFailed { source }) => return Err(source),
                 }
             } else {
                 log::debug!("not overwriting!!");
@@ -6,8 +6,10 @@ use custom_error::custom_error;
 use parking_lot::RwLockReadGuard;
 
 use super::{
+    block_cache::BlockCache,
     file_handle::{AccessMode, FileHandle, FileHandleManager, UUID},
     fs_interface::FsInterface,
+    quota::QuotaError,
 };
 
 custom_error! {
@@ -17,6 +19,7 @@ custom_error! {
     LocalWriteFailed{io: std::io::Error} = "Local write failed: {io}",
     NoFileHandle = "The file doesn't have a file handle",
     NoWritePermission = "The permissions doesn't allow to write",
+    Quota{source: QuotaError} = "{source}",
 }
 
 fn check_file_handle<'a>(
@@ -49,18 +52,26 @@ impl FsInterface {
     ) -> Result<usize, WriteError> {
         let file_handles = FileHandleManager::read_lock(&self.file_handles, "write")?;
         let _file_handle = check_file_handle(&file_handles, file_handle)?;
+        self.accesses.touch(id);
 
         let arbo = Arbo::n_read_lock(&self.arbo, "fs_interface.write")?;
         let path = arbo.n_get_path_from_inode_id(id)?;
+        let inode = arbo.n_get_inode(id)?;
+        let size = inode.meta.size;
+        if let Some(layout) = &inode.shards {
+            self.drop_local_shards(id, layout)?;
+        }
         drop(arbo);
 
         let new_size = offset + data.len();
+        self.reserve_space((new_size as u64).saturating_sub(size))?;
         let written = self
             .disk
             .write_file(&path, data, offset)
             .map_err(|io| WriteError::LocalWriteFailed { io })?;
 
         self.network_interface.write_file(id, new_size)?;
+        BlockCache::lock(&self.block_cache, "write")?.invalidate(id);
         Ok(written)
     }
 }
@@ -29,12 +29,25 @@ impl Callbacks {
             };
             Ok(call)
         } else {
-            Err(crate::error::WhError::WouldBlock {
-                called_from: "create callback".to_owned(),
-            })
+            Err(crate::error::WhError::would_block("create callback"))
         }
     }
 
+    /// Creates the callback, or joins it if it already exists.
+    /// Returns whether it was created.
+    pub fn n_create_or_join(&self, call: Callback) -> WhResult<bool> {
+        let mut callbacks = self
+            .callbacks
+            .try_write_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("create or join callback"))?;
+        if callbacks.contains_key(&call) {
+            return Ok(false);
+        }
+        let (tx, _) = broadcast::channel(1);
+        callbacks.insert(call, tx);
+        Ok(true)
+    }
+
     pub fn create(&self, call: Callback) -> io::Result<Callback> {
         if let Some(mut callbacks) = self.callbacks.try_write_for(LOCK_TIMEOUT) {
             if !callbacks.contains_key(&call) {
@@ -81,9 +94,7 @@ impl Callbacks {
                 });
             }
         } else {
-            return Err(WhError::WouldBlock {
-                called_from: "unable to read_lock callbacks".to_string(),
-            });
+            return Err(WhError::would_block("unable to read_lock callbacks"));
         };
 
         match waiter.blocking_recv() {
@@ -0,0 +1,793 @@
+use std::collections::{HashMap, VecDeque};
+
+use custom_error::custom_error;
+use futures_util::future::join_all;
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::{types::Config, LocalConfig},
+    error::{WhError, WhResult},
+    network::message::{Address, MessageContent, ToNetworkMessage},
+    pods::{
+        arbo::{Arbo, FsEntry, InodeId, SHARDS_DIR},
+        filesystem::{fs_interface::FsInterface, integrity::ContentHash},
+        whpath::WhPath,
+    },
+};
+
+use super::{
+    network_interface::NetworkInterface,
+    transfer::{ShardWait, TransferError, TransferKind, CHUNK_SIZE},
+};
+
+/// Permissions of the shard files, only read by the pod
+pub const SHARD_PERMISSIONS: u16 = 0o600;
+
+custom_error! {pub ErasureError
+    WhError{source: WhError} = "{source}",
+    Codec{source: reed_solomon_erasure::Error} = "Erasure coding failed: {source}",
+    LocalReadFailed{io: std::io::Error} = "Local read failed: {io}",
+    LocalWriteFailed{io: std::io::Error} = "Local write failed: {io}",
+    NotEnoughShards{ino: InodeId, found: usize, needed: usize} = "Only {found} shards of {ino} are available, {needed} are needed to rebuild it",
+    NotEnoughPods{ino: InodeId} = "Not enough pods could take a shard of {ino}",
+    NoRuntime = "Shards can only be sent from within the runtime",
+}
+
+/// Where the shards of an erasure coded file are
+///
+/// The file is split in `data_shards` shards, and `parity_shards` more are computed from them.
+/// Any `data_shards` of them are enough to rebuild the file, so that many pods can be lost.
+/// The file itself has no host anymore, pods reading it rebuild a local copy.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ShardLayout {
+    pub data_shards: usize,
+    pub parity_shards: usize,
+    /// Pod holding each shard, None once it is lost
+    pub holders: Vec<Option<Address>>,
+    /// Hash of each shard, a shard not matching it is treated as lost
+    pub hashes: Vec<ContentHash>,
+}
+
+impl ShardLayout {
+    /// Number of shards lost
+    pub fn missing(&self) -> usize {
+        self.holders
+            .iter()
+            .filter(|holder| holder.is_none())
+            .count()
+    }
+
+    /// Indexes of the shards held by `pod`
+    pub fn held_by(&self, pod: &Address) -> Vec<usize> {
+        self.holders
+            .iter()
+            .enumerate()
+            .filter(|(_, holder)| holder.as_ref() == Some(pod))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Marks the shards held by a lost pod as lost, returns how many there were
+    pub fn forget_holder(&mut self, pod: &Address) -> usize {
+        let held = self.held_by(pod);
+        held.iter().for_each(|index| self.holders[*index] = None);
+        held.len()
+    }
+}
+
+/// Size of each shard of a file of `size` bytes
+pub fn shard_size(size: u64, data_shards: usize) -> u64 {
+    size.div_ceil(data_shards.max(1) as u64).max(1)
+}
+
+/// Part of the shards encoded together, so a file is never held whole in memory
+///
+/// Stripe `n` holds the `len` bytes at `offset` = `n` * [CHUNK_SIZE] in every shard.
+/// Its data shards hold the `len` * `data_shards` bytes of the file at `offset` * `data_shards`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stripe {
+    pub offset: u64,
+    pub len: usize,
+}
+
+impl Stripe {
+    /// Position in the file of the data encoded in this stripe
+    pub fn file_offset(&self, data_shards: usize) -> u64 {
+        self.offset * data_shards as u64
+    }
+}
+
+/// Stripes of shards of `shard_size` bytes
+pub fn stripes(shard_size: u64) -> impl Iterator<Item = Stripe> {
+    (0..shard_size.div_ceil(CHUNK_SIZE)).map(move |n| {
+        let offset = n * CHUNK_SIZE;
+        Stripe {
+            offset,
+            len: (shard_size - offset).min(CHUNK_SIZE) as usize,
+        }
+    })
+}
+
+/// Splits the `len` * `data_shards` bytes of a stripe in pieces and computes the parity pieces
+fn encode_stripe(
+    codec: &ReedSolomon,
+    data: &[u8],
+    len: usize,
+) -> Result<Vec<Vec<u8>>, ErasureError> {
+    let mut pieces: Vec<Vec<u8>> = data
+        .chunks(len)
+        .map(<[u8]>::to_vec)
+        .chain((0..codec.parity_shard_count()).map(|_| vec![0; len]))
+        .collect();
+    codec.encode(&mut pieces)?;
+    Ok(pieces)
+}
+
+/// Pieces of a stripe in the shards present
+fn stripe_pieces(shards: &[Option<Vec<u8>>], stripe: Stripe) -> Vec<Option<Vec<u8>>> {
+    let start = stripe.offset as usize;
+    shards
+        .iter()
+        .map(|shard| Some(shard.as_ref()?[start..start + stripe.len].to_vec()))
+        .collect()
+}
+
+/// Size of the shards present, checking there are enough of them
+fn present_size(shards: &[Option<Vec<u8>>], data_shards: usize) -> Result<u64, ErasureError> {
+    if shards.iter().flatten().count() < data_shards {
+        return Err(reed_solomon_erasure::Error::TooFewShardsPresent.into());
+    }
+    Ok(shards.iter().flatten().next().map_or(0, Vec::len) as u64)
+}
+
+/// Splits `data` in `data_shards` shards of the same size and computes `parity_shards` more
+///
+/// The last data shard is padded with zeros, see [decode].
+pub fn encode(
+    data: &[u8],
+    data_shards: usize,
+    parity_shards: usize,
+) -> Result<Vec<Vec<u8>>, ErasureError> {
+    let codec = ReedSolomon::new(data_shards, parity_shards)?;
+    let mut shards = vec![Vec::new(); data_shards + parity_shards];
+    for stripe in stripes(shard_size(data.len() as u64, data_shards)) {
+        let start = (stripe.file_offset(data_shards) as usize).min(data.len());
+        let end = (start + stripe.len * data_shards).min(data.len());
+        let mut part = data[start..end].to_vec();
+        part.resize(stripe.len * data_shards, 0);
+
+        for (shard, piece) in shards
+            .iter_mut()
+            .zip(encode_stripe(&codec, &part, stripe.len)?)
+        {
+            shard.extend(piece);
+        }
+    }
+    Ok(shards)
+}
+
+/// Computes the missing shards (None) from the others
+pub fn reconstruct(
+    shards: &mut [Option<Vec<u8>>],
+    data_shards: usize,
+    parity_shards: usize,
+) -> Result<(), ErasureError> {
+    let codec = ReedSolomon::new(data_shards, parity_shards)?;
+    let mut rebuilt = vec![Vec::new(); shards.len()];
+    for stripe in stripes(present_size(shards, data_shards)?) {
+        let mut pieces = stripe_pieces(shards, stripe);
+        codec.reconstruct(&mut pieces)?;
+        for (shard, piece) in rebuilt.iter_mut().zip(pieces) {
+            shard.extend(piece.unwrap_or_default());
+        }
+    }
+    for (shard, rebuilt) in shards.iter_mut().zip(rebuilt) {
+        shard.get_or_insert(rebuilt);
+    }
+    Ok(())
+}
+
+/// Rebuilds the `size` bytes of a file from at least `data_shards` of its shards
+pub fn decode(
+    shards: Vec<Option<Vec<u8>>>,
+    data_shards: usize,
+    parity_shards: usize,
+    size: u64,
+) -> Result<Vec<u8>, ErasureError> {
+    let codec = ReedSolomon::new(data_shards, parity_shards)?;
+    let mut data = Vec::with_capacity(size as usize);
+    for stripe in stripes(present_size(&shards, data_shards)?) {
+        let mut pieces = stripe_pieces(&shards, stripe);
+        codec.reconstruct_data(&mut pieces)?;
+        pieces
+            .into_iter()
+            .take(data_shards)
+            .flatten()
+            .for_each(|piece| data.extend(piece));
+    }
+    data.truncate(size as usize);
+    Ok(data)
+}
+
+pub(super) fn shard_path(ino: InodeId, index: usize) -> WhPath {
+    WhPath::from(&format!("/{SHARDS_DIR}/{ino}.{index}"))
+}
+
+impl NetworkInterface {
+    /// Asks `from` for a shard it holds, streamed to the local shard file
+    pub fn request_shard(
+        &self,
+        from: &Address,
+        ino: InodeId,
+        index: usize,
+    ) -> WhResult<ShardWait<'_>> {
+        let answer = self.transfers.wait_shard(from, ino, index)?;
+        self.send_to(MessageContent::RequestShard(ino, index), from)?;
+        Ok(answer)
+    }
+
+    pub fn update_shards(&self, ino: InodeId, layout: Option<ShardLayout>) -> WhResult<()> {
+        Arbo::n_write_lock(&self.arbo, "update_shards")?.n_set_inode_shards(ino, layout.clone())?;
+        self.to_network_message_tx
+            .send(ToNetworkMessage::BroadcastMessage(
+                MessageContent::EditShards(ino, layout),
+            ))
+            .map_err(|_| WhError::NetworkDied {
+                called_from: "update_shards".to_owned(),
+            })
+    }
+}
+
+impl FsInterface {
+    fn self_address(&self, called_from: &str) -> WhResult<Address> {
+        Ok(
+            LocalConfig::read_lock(&self.network_interface.local_config, called_from)?
+                .general
+                .address
+                .clone(),
+        )
+    }
+
+    pub(super) fn make_shards_dir(&self) {
+        // the directory is made with the first shard written
+        let _ = self
+            .disk
+            .new_dir(&WhPath::from(&format!("/{SHARDS_DIR}")), 0o700);
+    }
+
+    fn new_local_shard(&self, ino: InodeId, index: usize) -> Result<(), ErasureError> {
+        self.make_shards_dir();
+        self.disk
+            .new_file(&shard_path(ino, index), SHARD_PERMISSIONS)
+            .map_err(|io| ErasureError::LocalWriteFailed { io })
+    }
+
+    /// Reads at `offset` until `buf` is full or the end of the file, returns the bytes read
+    fn read_at(&self, path: &WhPath, offset: u64, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            let read = self
+                .disk
+                .read_file(path, offset as usize + filled, &mut buf[filled..])?;
+            if read == 0 {
+                break;
+            }
+            filled += read;
+        }
+        Ok(filled)
+    }
+
+    /// Reads the pieces of a stripe in the local shards marked available
+    fn read_stripe(
+        &self,
+        ino: InodeId,
+        available: &[bool],
+        stripe: Stripe,
+    ) -> Result<Vec<Option<Vec<u8>>>, ErasureError> {
+        (0..available.len())
+            .map(|index| {
+                if !available[index] {
+                    return Ok(None);
+                }
+                let mut piece = vec![0; stripe.len];
+                let read = self
+                    .read_at(&shard_path(ino, index), stripe.offset, &mut piece)
+                    .map_err(|io| ErasureError::LocalReadFailed { io })?;
+                if read < stripe.len {
+                    return Err(ErasureError::LocalReadFailed {
+                        io: std::io::ErrorKind::UnexpectedEof.into(),
+                    });
+                }
+                Ok(Some(piece))
+            })
+            .collect()
+    }
+
+    fn hash_local_shard(
+        &self,
+        ino: InodeId,
+        index: usize,
+        shard_size: u64,
+    ) -> std::io::Result<ContentHash> {
+        let path = shard_path(ino, index);
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0; CHUNK_SIZE as usize];
+        for stripe in stripes(shard_size) {
+            let read = self.read_at(&path, stripe.offset, &mut buf[..stripe.len])?;
+            if read < stripe.len {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            hasher.update(&buf[..read]);
+        }
+        Ok(hasher.finalize().into())
+    }
+
+    /// Removes the shards of a file held by this pod, once they are not needed anymore
+    pub fn drop_local_shards(&self, ino: InodeId, layout: &ShardLayout) -> WhResult<()> {
+        for index in layout.held_by(&self.self_address("drop_local_shards")?) {
+            if let Err(e) = self.disk.remove_file(&shard_path(ino, index)) {
+                log::debug!("drop_local_shards: can't delete shard {index} of {ino}: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the shards of a file asked to the other pods, once the file is rebuilt
+    fn drop_gathered_shards(&self, ino: InodeId, layout: &ShardLayout, self_addr: &Address) {
+        for (index, holder) in layout.holders.iter().enumerate() {
+            if holder.as_ref().is_some_and(|holder| holder != self_addr) {
+                let _ = self.disk.remove_file(&shard_path(ino, index));
+            }
+        }
+    }
+
+    /// Answers a [MessageContent::DropShards], keeping the shards this pod holds
+    pub fn recept_drop_shards(&self, ino: InodeId, indexes: Vec<usize>) -> WhResult<()> {
+        let self_addr = self.self_address("recept_drop_shards")?;
+        let held = Arbo::n_read_lock(&self.arbo, "recept_drop_shards")?
+            .n_get_inode(ino)?
+            .shards
+            .as_ref()
+            .map(|layout| layout.held_by(&self_addr))
+            .unwrap_or_default();
+        for index in indexes {
+            if !held.contains(&index) {
+                let _ = self.disk.remove_file(&shard_path(ino, index));
+            }
+        }
+        Ok(())
+    }
+
+    /// Size of the shards of a file erasure coded, from its layout
+    fn layout_shard_size(&self, ino: InodeId) -> Result<u64, TransferError> {
+        let arbo = Arbo::n_read_lock(&self.arbo, "layout_shard_size")?;
+        let inode = arbo.n_get_inode(ino)?;
+        match &inode.shards {
+            Some(layout) => Ok(shard_size(inode.meta.size, layout.data_shards)),
+            None => Err(TransferError::LocalReadFailed {
+                io: std::io::ErrorKind::NotFound.into(),
+            }),
+        }
+    }
+
+    /// Answers a [MessageContent::RequestShard], streaming the shard or telling it is missing
+    pub async fn send_shard(&self, to: Address, ino: InodeId, index: usize) -> WhResult<()> {
+        let status = match self.layout_shard_size(ino) {
+            Ok(size) => {
+                self.stream(
+                    ino,
+                    shard_path(ino, index),
+                    size,
+                    to.clone(),
+                    0,
+                    TransferKind::ShardAnswer(index),
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = status {
+            log::warn!("Can't send shard {index} of {ino} to {to}: {e}");
+            self.network_interface
+                .send_to(MessageContent::ShardMissing(ino, index), &to)?;
+        }
+        Ok(())
+    }
+
+    pub fn recept_edit_shards(&self, ino: InodeId, layout: Option<ShardLayout>) -> WhResult<()> {
+        let previous = Arbo::n_read_lock(&self.arbo, "recept_edit_shards")?
+            .n_get_inode(ino)?
+            .shards
+            .clone();
+        if let Some(previous) = previous {
+            let self_addr = self.self_address("recept_edit_shards")?;
+            // only the shards this pod doesn't hold anymore are dropped
+            let kept = layout
+                .as_ref()
+                .map(|layout| layout.held_by(&self_addr))
+                .unwrap_or_default();
+            for index in previous.held_by(&self_addr) {
+                if !kept.contains(&index) {
+                    let _ = self.disk.remove_file(&shard_path(ino, index));
+                }
+            }
+        }
+        Arbo::n_write_lock(&self.arbo, "recept_edit_shards")?.n_set_inode_shards(ino, layout)
+    }
+
+    /// Gets at least `data_shards` valid shards of a file on this pod, asking the others if needed
+    ///
+    /// Returns the shards available locally, see [FsInterface::drop_gathered_shards].
+    fn gather_shards(
+        &self,
+        ino: InodeId,
+        layout: &ShardLayout,
+        shard_size: u64,
+    ) -> Result<Vec<bool>, ErasureError> {
+        let self_addr = self.self_address("gather_shards")?;
+        let mut available = vec![false; layout.holders.len()];
+        let mut found = 0;
+        let mut sources: VecDeque<(usize, &Address)> = layout
+            .holders
+            .iter()
+            .enumerate()
+            .filter_map(|(index, holder)| Some((index, holder.as_ref()?)))
+            .collect();
+        sources
+            .make_contiguous()
+            .sort_by_key(|(_, holder)| **holder != self_addr);
+
+        // only the shards still needed are asked for, more are asked if some don't come
+        while found < layout.data_shards && !sources.is_empty() {
+            let batch: Vec<(usize, &Address)> = sources
+                .drain(..(layout.data_shards - found).min(sources.len()))
+                .collect();
+            let mut waits = Vec::with_capacity(batch.len());
+            for (index, holder) in batch {
+                let wait = if *holder == self_addr {
+                    None
+                } else {
+                    Some(self.network_interface.request_shard(holder, ino, index)?)
+                };
+                waits.push((index, holder, wait));
+            }
+
+            for (index, holder, wait) in waits {
+                let received = match wait {
+                    None => true,
+                    Some(wait) => wait.wait()?,
+                };
+                if !received {
+                    log::warn!("Can't get shard {index} of {ino} from {holder}");
+                    continue;
+                }
+                match self.hash_local_shard(ino, index, shard_size) {
+                    Ok(hash) if hash == layout.hashes[index] => {
+                        available[index] = true;
+                        found += 1;
+                    }
+                    Ok(_) => log::warn!("Shard {index} of {ino} held by {holder} is corrupted"),
+                    Err(e) => log::warn!("Can't read shard {index} of {ino}: {e}"),
+                }
+            }
+        }
+
+        if found < layout.data_shards {
+            self.drop_gathered_shards(ino, layout, &self_addr);
+            return Err(ErasureError::NotEnoughShards {
+                ino,
+                found,
+                needed: layout.data_shards,
+            });
+        }
+        Ok(available)
+    }
+
+    /// Streams the local shards at `indexes` to the candidates, in order, trying the next one on failure
+    ///
+    /// Returns the pod that took each shard. If the candidates run out, the pods that
+    /// took a shard are told to drop it. Must be called from within the multi-threaded runtime,
+    /// a worker calling it is handed over to the blocking pool while the shards are sent.
+    fn store_shards(
+        &self,
+        ino: InodeId,
+        indexes: &[usize],
+        shard_size: u64,
+        candidates: &[Address],
+    ) -> Result<Vec<(usize, Address)>, ErasureError> {
+        let runtime = tokio::runtime::Handle::try_current().map_err(|_| ErasureError::NoRuntime)?;
+        let mut candidates = candidates.iter();
+        let mut placed = Vec::with_capacity(indexes.len());
+        let mut tried: Vec<(usize, &Address)> = Vec::with_capacity(indexes.len());
+        let mut pending = indexes.to_vec();
+
+        while !pending.is_empty() {
+            let batch: Option<Vec<(usize, &Address)>> = pending
+                .drain(..)
+                .map(|index| Some((index, candidates.next()?)))
+                .collect();
+            let Some(batch) = batch else {
+                self.drop_stored_shards(ino, &tried);
+                return Err(ErasureError::NotEnoughPods { ino });
+            };
+            tried.extend(&batch);
+
+            let streams = batch.iter().map(|(index, to)| {
+                self.stream(
+                    ino,
+                    shard_path(ino, *index),
+                    shard_size,
+                    (*to).clone(),
+                    0,
+                    TransferKind::Shard(*index),
+                )
+            });
+            let statuses = tokio::task::block_in_place(|| runtime.block_on(join_all(streams)));
+            for ((index, to), status) in batch.iter().zip(statuses) {
+                match status {
+                    Ok(()) => placed.push((*index, (*to).clone())),
+                    Err(e) => {
+                        log::warn!(
+                            "{to} didn't store shard {index} of {ino}, trying next pod: {e}"
+                        );
+                        pending.push(*index);
+                    }
+                }
+            }
+        }
+        Ok(placed)
+    }
+
+    /// Tells the pods that took shards of a file not encoded in the end to drop them
+    fn drop_stored_shards(&self, ino: InodeId, stored: &[(usize, &Address)]) {
+        let mut by_pod: HashMap<&Address, Vec<usize>> = HashMap::new();
+        for (index, pod) in stored {
+            by_pod.entry(*pod).or_default().push(*index);
+        }
+        for (pod, indexes) in by_pod {
+            if let Err(e) = self
+                .network_interface
+                .send_to(MessageContent::DropShards(ino, indexes), pod)
+            {
+                log::warn!("Can't tell {pod} to drop the shards of {ino}: {e}");
+            }
+        }
+    }
+
+    /// Writes every shard of a local file, one [Stripe] at a time, returns their hashes
+    fn write_shards(
+        &self,
+        codec: &ReedSolomon,
+        ino: InodeId,
+        path: &WhPath,
+        size: u64,
+    ) -> Result<Vec<ContentHash>, ErasureError> {
+        let data_shards = codec.data_shard_count();
+        let mut hashers = vec![Sha256::new(); codec.total_shard_count()];
+        for index in 0..codec.total_shard_count() {
+            self.new_local_shard(ino, index)?;
+        }
+
+        for stripe in stripes(shard_size(size, data_shards)) {
+            // the end of the last stripe is past the end of the file and stays zeroed
+            let mut data = vec![0; stripe.len * data_shards];
+            self.read_at(path, stripe.file_offset(data_shards), &mut data)
+                .map_err(|io| ErasureError::LocalReadFailed { io })?;
+            for (index, piece) in encode_stripe(codec, &data, stripe.len)?.iter().enumerate() {
+                self.disk
+                    .write_file(&shard_path(ino, index), piece, stripe.offset as usize)
+                    .map_err(|io| ErasureError::LocalWriteFailed { io })?;
+                hashers[index].update(piece);
+            }
+        }
+        Ok(hashers
+            .into_iter()
+            .map(|hasher| hasher.finalize().into())
+            .collect())
+    }
+
+    /// Computes the shards at `lost` from the `available` local ones, one [Stripe] at a time
+    fn rebuild_shards(
+        &self,
+        codec: &ReedSolomon,
+        ino: InodeId,
+        available: &[bool],
+        lost: &[usize],
+        shard_size: u64,
+    ) -> Result<(), ErasureError> {
+        for index in lost {
+            self.new_local_shard(ino, *index)?;
+        }
+        for stripe in stripes(shard_size) {
+            let mut pieces = self.read_stripe(ino, available, stripe)?;
+            codec.reconstruct(&mut pieces)?;
+            for index in lost {
+                self.disk
+                    .write_file(
+                        &shard_path(ino, *index),
+                        pieces[*index].as_deref().unwrap_or_default(),
+                        stripe.offset as usize,
+                    )
+                    .map_err(|io| ErasureError::LocalWriteFailed { io })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the `size` bytes of a file decoded from the `available` local shards, one [Stripe] at a time
+    fn decode_shards(
+        &self,
+        codec: &ReedSolomon,
+        ino: InodeId,
+        available: &[bool],
+        path: &WhPath,
+        size: u64,
+    ) -> Result<(), ErasureError> {
+        let data_shards = codec.data_shard_count();
+        for stripe in stripes(shard_size(size, data_shards)) {
+            let offset = stripe.file_offset(data_shards);
+            if offset >= size {
+                break;
+            }
+            let mut pieces = self.read_stripe(ino, available, stripe)?;
+            codec.reconstruct_data(&mut pieces)?;
+            let data: Vec<u8> = pieces
+                .into_iter()
+                .take(data_shards)
+                .flatten()
+                .flatten()
+                .collect();
+            let len = (size - offset).min(data.len() as u64) as usize;
+            self.disk
+                .write_file(path, &data[..len], offset as usize)
+                .map_err(|io| ErasureError::LocalWriteFailed { io })?;
+        }
+        self.disk
+            .set_file_size(path, size as usize)
+            .map_err(|io| ErasureError::LocalWriteFailed { io })
+    }
+
+    /// Replaces the copies of a file held by this pod by shards spread over the candidates
+    ///
+    /// This pod keeps the first shard. The file is left untouched if not enough
+    /// candidates can take a shard, so it can still be replicated.
+    pub fn encode_file(
+        &self,
+        ino: InodeId,
+        data_shards: usize,
+        parity_shards: usize,
+        candidates: &[Address],
+    ) -> Result<(), ErasureError> {
+        // refuses a layout without data or parity shards
+        let codec = ReedSolomon::new(data_shards, parity_shards)?;
+        let count = codec.total_shard_count();
+        if candidates.len() + 1 < count {
+            return Err(ErasureError::NotEnoughPods { ino });
+        }
+        let self_addr = self.self_address("encode_file")?;
+        let (path, size) = {
+            let arbo = Arbo::n_read_lock(&self.arbo, "encode_file")?;
+            (
+                arbo.n_get_path_from_inode_id(ino)?,
+                arbo.n_get_inode(ino)?.meta.size,
+            )
+        };
+
+        let others: Vec<usize> = (1..count).collect();
+        let encoded = self
+            .write_shards(&codec, ino, &path, size)
+            .and_then(|hashes| {
+                let placed =
+                    self.store_shards(ino, &others, shard_size(size, data_shards), candidates)?;
+                Ok((hashes, placed))
+            });
+        // only the first shard is kept here, the others were written to be sent
+        for index in &others {
+            let _ = self.disk.remove_file(&shard_path(ino, *index));
+        }
+        let (hashes, placed) = encoded.inspect_err(|_| {
+            let _ = self.disk.remove_file(&shard_path(ino, 0));
+        })?;
+
+        let mut holders = vec![None; count];
+        holders[0] = Some(self_addr);
+        for (index, pod) in placed {
+            holders[index] = Some(pod);
+        }
+        self.network_interface.update_shards(
+            ino,
+            Some(ShardLayout {
+                data_shards,
+                parity_shards,
+                holders,
+                hashes,
+            }),
+        )?;
+        // the shards replace every copy, the other hosts drop theirs
+        self.network_interface.update_hosts(ino, vec![])?;
+        if let Err(e) = self.disk.remove_file(&path) {
+            log::warn!("encode_file: can't delete the local copy of {ino}: {e}");
+        }
+        Ok(())
+    }
+
+    /// Computes the lost shards of a file from the others and gives them to the candidates
+    ///
+    /// Returns the number of shards still lost.
+    pub fn heal_shards(&self, ino: InodeId, candidates: &[Address]) -> Result<usize, ErasureError> {
+        let (mut layout, size) = {
+            let arbo = Arbo::n_read_lock(&self.arbo, "heal_shards")?;
+            let inode = arbo.n_get_inode(ino)?;
+            let Some(layout) = inode.shards.clone() else {
+                return Ok(0);
+            };
+            (layout, inode.meta.size)
+        };
+        let lost: Vec<usize> = (0..layout.holders.len())
+            .filter(|index| layout.holders[*index].is_none())
+            .collect();
+        let to_place = &lost[..lost.len().min(candidates.len())];
+        if to_place.is_empty() {
+            return Ok(lost.len());
+        }
+
+        let self_addr = self.self_address("heal_shards")?;
+        let codec = ReedSolomon::new(layout.data_shards, layout.parity_shards)?;
+        let shard_size = shard_size(size, layout.data_shards);
+        let available = self.gather_shards(ino, &layout, shard_size)?;
+        let healed = self
+            .rebuild_shards(&codec, ino, &available, to_place, shard_size)
+            .and_then(|()| self.store_shards(ino, to_place, shard_size, candidates));
+        // the shards rebuilt or asked for are only needed to heal
+        for index in to_place {
+            let _ = self.disk.remove_file(&shard_path(ino, *index));
+        }
+        self.drop_gathered_shards(ino, &layout, &self_addr);
+
+        for (index, pod) in healed? {
+            layout.holders[index] = Some(pod);
+        }
+        let missing = layout.missing();
+        self.network_interface.update_shards(ino, Some(layout))?;
+        Ok(missing)
+    }
+
+    /// Rebuilds a local copy of an erasure coded file, making this pod one of its hosts
+    pub fn rebuild_file(&self, ino: InodeId) -> Result<(), ErasureError> {
+        let self_addr = self.self_address("rebuild_file")?;
+        let (layout, path, size, perm) = {
+            let arbo = Arbo::n_read_lock(&self.arbo, "rebuild_file")?;
+            let inode = arbo.n_get_inode(ino)?;
+            let FsEntry::File(_) = inode.entry else {
+                return Err(WhError::InodeIsADirectory.into());
+            };
+            let Some(layout) = inode.shards.clone() else {
+                return Ok(());
+            };
+            (
+                layout,
+                arbo.n_get_path_from_inode_id(ino)?,
+                inode.meta.size,
+                inode.meta.perm,
+            )
+        };
+
+        let codec = ReedSolomon::new(layout.data_shards, layout.parity_shards)?;
+        let available = self.gather_shards(ino, &layout, shard_size(size, layout.data_shards))?;
+        let rebuilt = self
+            .disk
+            .new_file(&path, perm)
+            .map_err(|io| ErasureError::LocalWriteFailed { io })
+            .and_then(|()| self.decode_shards(&codec, ino, &available, &path, size));
+        self.drop_gathered_shards(ino, &layout, &self_addr);
+        if let Err(e) = rebuilt {
+            let _ = self.disk.remove_file(&path);
+            return Err(e);
+        }
+        self.network_interface
+            .add_inode_hosts(ino, vec![self_addr])?;
+        Ok(())
+    }
+}
@@ -0,0 +1,281 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{types::Config, LocalConfig},
+    error::{WhError, WhResult},
+    network::message::{Address, MessageContent, ToNetworkMessage},
+    pods::{
+        arbo::{Arbo, FsEntry, LOCK_TIMEOUT},
+        filesystem::fs_interface::FsInterface,
+    },
+};
+
+use super::network_interface::{get_all_peers_address, NetworkInterface};
+
+/// Interval between two rounds of gossip
+pub const GOSSIP_INTERVAL: Duration = Duration::from_secs(10);
+/// Number of peers each round of gossip is sent to
+pub const GOSSIP_FANOUT: usize = 3;
+/// Time after which the entry of a pod nobody heard of is dropped
+pub const GOSSIP_EXPIRY: Duration = Duration::from_secs(300);
+
+/// What a pod tells the others about itself
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct NodeStatus {
+    /// Bytes available on the disk of the pod, None if the pod can't tell
+    pub free_space: Option<u64>,
+    pub total_space: Option<u64>,
+    /// Number of files being sent or pulled
+    pub load: u64,
+    pub failure_domain: Option<String>,
+    /// Seconds since the pod started
+    pub uptime: u64,
+    /// Version of wormhole run by the pod
+    pub version: String,
+    /// Number of files the pod holds a copy or a shard of
+    pub hosted_files: u64,
+    /// Bytes taken by the copies and shards held by the pod
+    pub used_space: u64,
+    /// Bytes the pod accepts to hold, None without quota
+    pub quota: Option<u64>,
+}
+
+impl NodeStatus {
+    pub fn free_ratio(&self) -> f64 {
+        match (self.free_space, self.total_space) {
+            (Some(free), Some(total)) if total > 0 => free as f64 / total as f64,
+            _ => 0.,
+        }
+    }
+
+    /// Bytes the pod can still take before reaching its quota
+    pub fn quota_left(&self) -> Option<u64> {
+        self.quota
+            .map(|quota| quota.saturating_sub(self.used_space))
+    }
+}
+
+/// Status of a pod as spread through the network
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GossipEntry {
+    pub address: Address,
+    /// Unix time the pod started at, so the entries of a restarted pod replace the old ones
+    pub started: u64,
+    /// Incremented by the pod at each of its rounds
+    pub heartbeat: u64,
+    pub status: NodeStatus,
+}
+
+impl GossipEntry {
+    fn is_newer_than(&self, other: &GossipEntry) -> bool {
+        (self.started, self.heartbeat) > (other.started, other.heartbeat)
+    }
+}
+
+/// View of the network built from the entries gossiped by the pods
+///
+/// Each round, a pod refreshes its own entry and sends every entry it knows
+/// to a few peers, so the statuses spread without anyone talking to everyone.
+#[derive(Debug)]
+pub struct Gossip {
+    started: u64,
+    started_at: Instant,
+    heartbeat: AtomicU64,
+    /// Rotates through the peers so every one of them is gossiped with
+    next_peer: AtomicUsize,
+    /// Latest entry of each pod, with when it was last updated here
+    entries: RwLock<HashMap<Address, (GossipEntry, Instant)>>,
+}
+
+impl Default for Gossip {
+    fn default() -> Self {
+        Self {
+            started: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_secs())
+                .unwrap_or(0),
+            started_at: Instant::now(),
+            heartbeat: AtomicU64::new(0),
+            next_peer: AtomicUsize::new(0),
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+impl Gossip {
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// Entry of this pod for the next round
+    pub fn own_entry(&self, address: Address, status: NodeStatus) -> GossipEntry {
+        GossipEntry {
+            address,
+            started: self.started,
+            heartbeat: self.heartbeat.fetch_add(1, Ordering::Relaxed) + 1,
+            status,
+        }
+    }
+
+    /// Keeps the entries newer than the known ones, returns how many were
+    pub fn merge(&self, entries: Vec<GossipEntry>) -> WhResult<usize> {
+        let mut known = self
+            .entries
+            .try_write_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("gossip merge"))?;
+        let now = Instant::now();
+        let mut updated = 0;
+
+        for entry in entries {
+            if known
+                .get(&entry.address)
+                .is_none_or(|(current, _)| entry.is_newer_than(current))
+            {
+                known.insert(entry.address.clone(), (entry, now));
+                updated += 1;
+            }
+        }
+        Ok(updated)
+    }
+
+    /// Drops the entries not updated for [GOSSIP_EXPIRY]
+    pub fn expire(&self) -> WhResult<()> {
+        self.entries
+            .try_write_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("gossip expire"))?
+            .retain(|_, (_, updated)| updated.elapsed() < GOSSIP_EXPIRY);
+        Ok(())
+    }
+
+    pub fn entries(&self) -> WhResult<Vec<GossipEntry>> {
+        Ok(self
+            .entries
+            .try_read_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("gossip entries"))?
+            .values()
+            .map(|(entry, _)| entry.clone())
+            .collect())
+    }
+
+    /// Latest status known of each pod
+    pub fn statuses(&self) -> WhResult<HashMap<Address, NodeStatus>> {
+        Ok(self
+            .entries
+            .try_read_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("gossip statuses"))?
+            .iter()
+            .map(|(address, (entry, _))| (address.clone(), entry.status.clone()))
+            .collect())
+    }
+
+    /// Peers to send this round to, at most [GOSSIP_FANOUT] of them
+    fn pick_targets(&self, mut peers: Vec<Address>) -> Vec<Address> {
+        if peers.len() <= GOSSIP_FANOUT {
+            return peers;
+        }
+        peers.sort();
+        let start = self.next_peer.fetch_add(GOSSIP_FANOUT, Ordering::Relaxed);
+        (0..GOSSIP_FANOUT)
+            .map(|i| peers[(start + i) % peers.len()].clone())
+            .collect()
+    }
+}
+
+impl NetworkInterface {
+    pub fn recept_gossip(&self, entries: Vec<GossipEntry>) -> WhResult<()> {
+        let self_addr = LocalConfig::read_lock(&self.local_config, "recept_gossip")?
+            .general
+            .address
+            .clone();
+        // this pod is the only one knowing its status for sure
+        self.gossip.merge(
+            entries
+                .into_iter()
+                .filter(|entry| entry.address != self_addr)
+                .collect(),
+        )?;
+        Ok(())
+    }
+}
+
+impl FsInterface {
+    pub fn node_status(&self) -> WhResult<NodeStatus> {
+        let size = self
+            .disk
+            .size_info()
+            .inspect_err(|e| log::warn!("Can't get the disk size: {e}"))
+            .ok();
+        let (used_space, quota) = (self.used_space()?, self.quota()?);
+        let local_config =
+            LocalConfig::read_lock(&self.network_interface.local_config, "node_status")?;
+        let self_addr = &local_config.general.address;
+        let hosted_files = Arbo::n_read_lock(&self.arbo, "node_status")?
+            .iter()
+            .filter(|(ino, _)| !Arbo::is_local_only(**ino))
+            .filter(|(_, inode)| match (&inode.entry, &inode.shards) {
+                (FsEntry::File(hosts), shards) => {
+                    hosts.contains(self_addr)
+                        || shards
+                            .as_ref()
+                            .is_some_and(|layout| !layout.held_by(self_addr).is_empty())
+                }
+                _ => false,
+            })
+            .count() as u64;
+
+        Ok(NodeStatus {
+            free_space: size.as_ref().map(|size| size.free_size as u64),
+            total_space: size.as_ref().map(|size| size.total_size as u64),
+            load: self.network_interface.transfers.load()?,
+            failure_domain: local_config.general.failure_domain.clone(),
+            uptime: self.network_interface.gossip.uptime().as_secs(),
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            hosted_files,
+            used_space,
+            quota,
+        })
+    }
+
+    /// Runs a round of [Gossip] every [GOSSIP_INTERVAL]
+    pub async fn gossip_worker(fs_interface: Arc<FsInterface>) {
+        let mut ticker = tokio::time::interval(GOSSIP_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = fs_interface.gossip_round() {
+                log::warn!("Can't gossip the status of this pod: {e}");
+            }
+        }
+    }
+
+    fn gossip_round(&self) -> WhResult<()> {
+        let gossip = &self.network_interface.gossip;
+        let self_addr = LocalConfig::read_lock(&self.network_interface.local_config, "gossip")?
+            .general
+            .address
+            .clone();
+
+        gossip.merge(vec![gossip.own_entry(self_addr, self.node_status()?)])?;
+        gossip.expire()?;
+
+        let targets = gossip.pick_targets(get_all_peers_address(&self.network_interface.peers)?);
+        if !targets.is_empty() {
+            let _ = self.network_interface.to_network_message_tx.send(
+                ToNetworkMessage::SpecificMessage(
+                    (MessageContent::Gossip(gossip.entries()?), None),
+                    targets,
+                ),
+            );
+        }
+        Ok(())
+    }
+}
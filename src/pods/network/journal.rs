@@ -0,0 +1,370 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use parking_lot::{Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{WhError, WhResult},
+    network::message::{Address, MessageContent},
+    pods::arbo::LOCK_TIMEOUT,
+};
+
+use super::network_interface::{get_all_peers_address, NetworkInterface};
+
+/// Number of entries of its own journal a pod keeps for the late peers.
+/// A peer missing older entries only gets a warning.
+pub const JOURNAL_MAX_ENTRIES: usize = 10_000;
+/// Time after which missed entries are asked for again if the pod didn't answer
+pub const JOURNAL_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Mutation of the filesystem made by a pod, numbered in the order it made them
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JournalEntry {
+    /// Pod that made the mutation
+    pub origin: Address,
+    pub seq: u64,
+    pub content: Box<MessageContent>,
+}
+
+/// Last entry applied from the journal of each pod
+pub type JournalFrontier = HashMap<Address, u64>;
+
+/// What is appended to the journal file
+#[derive(Serialize, Deserialize)]
+enum JournalRecord {
+    Own(JournalEntry),
+    Applied(Address, u64),
+}
+
+/// What to do with an entry broadcasted by another pod
+#[derive(Debug, PartialEq)]
+pub enum EntryStatus {
+    New,
+    /// Already applied
+    Known,
+    /// Entries of its origin were missed, they must be asked for before applying this one
+    Missing {
+        after: u64,
+    },
+    /// Entries of its origin were missed and are already asked for
+    Requested,
+}
+
+/// Entries asked to a pod and not received yet
+#[derive(Debug)]
+struct JournalRequest {
+    sent: Instant,
+    /// Last entry of the pod dropped while waiting for the answer
+    dropped: u64,
+}
+
+#[derive(Debug, Default)]
+struct JournalState {
+    own: VecDeque<JournalEntry>,
+    last_seq: u64,
+    applied: JournalFrontier,
+    requests: HashMap<Address, JournalRequest>,
+    file: Option<File>,
+}
+
+/// Durable log of the mutations made by this pod, and of the ones it applied from others
+///
+/// Mutations are broadcasted as journal entries. When pods reconnect, they ask
+/// each other for the entries they missed, so changes made while offline are not lost.
+#[derive(Debug)]
+pub struct Journal {
+    address: Address,
+    state: Mutex<JournalState>,
+}
+
+impl JournalState {
+    fn append(&mut self, record: &JournalRecord) {
+        let Some(file) = &mut self.file else {
+            return;
+        };
+        let status = bincode::serialize(record)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            .and_then(|bytes| {
+                let mut framed = (bytes.len() as u64).to_le_bytes().to_vec();
+                framed.extend_from_slice(&bytes);
+                file.write_all(&framed)?;
+                // a mutation is only broadcasted once it survives a crash
+                file.sync_data()
+            });
+        if let Err(e) = status {
+            log::error!("Journal: can't save to disk, the change is only kept in memory: {e}");
+        }
+    }
+
+    fn mark_applied(&mut self, origin: &Address, seq: u64) {
+        self.applied.insert(origin.clone(), seq);
+        self.append(&JournalRecord::Applied(origin.clone(), seq));
+    }
+
+    fn load(&mut self, bytes: &[u8]) {
+        let mut bytes = bytes;
+        while bytes.len() >= 8 {
+            let (len, rest) = bytes.split_at(8);
+            let len = u64::from_le_bytes(len.try_into().expect("split at 8")) as usize;
+            if rest.len() < len {
+                break; // record cut by a crash
+            }
+            let (record, rest) = rest.split_at(len);
+            match bincode::deserialize(record) {
+                Ok(JournalRecord::Own(entry)) => {
+                    self.last_seq = entry.seq;
+                    self.own.push_back(entry);
+                }
+                Ok(JournalRecord::Applied(origin, seq)) => {
+                    self.applied.insert(origin, seq);
+                }
+                Err(e) => log::warn!("Journal: skipping an unreadable record: {e}"),
+            }
+            bytes = rest;
+        }
+        while self.own.len() > JOURNAL_MAX_ENTRIES {
+            self.own.pop_front();
+        }
+    }
+}
+
+impl Journal {
+    /// Journal only kept in memory
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            state: Mutex::new(JournalState::default()),
+        }
+    }
+
+    /// Loads the journal saved at `path` and keeps it updated
+    ///
+    /// The file is compacted on load, dropping the entries over [JOURNAL_MAX_ENTRIES].
+    pub fn open(address: Address, path: &Path) -> io::Result<Self> {
+        let mut state = JournalState::default();
+        match fs::read(path) {
+            Ok(bytes) => state.load(&bytes),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e),
+        }
+
+        state.file = Some(File::create(path)?);
+        let records: Vec<JournalRecord> = state
+            .own
+            .iter()
+            .cloned()
+            .map(JournalRecord::Own)
+            .chain(
+                state
+                    .applied
+                    .iter()
+                    .map(|(origin, seq)| JournalRecord::Applied(origin.clone(), *seq)),
+            )
+            .collect();
+        records.iter().for_each(|record| state.append(record));
+        state.file = Some(OpenOptions::new().append(true).open(path)?);
+
+        Ok(Self {
+            address,
+            state: Mutex::new(state),
+        })
+    }
+
+    fn lock(&self, called_from: &str) -> WhResult<MutexGuard<'_, JournalState>> {
+        self.state
+            .try_lock_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block(called_from))
+    }
+
+    /// Adds a mutation made by this pod
+    pub fn record(&self, content: MessageContent) -> WhResult<JournalEntry> {
+        let mut state = self.lock("Journal::record")?;
+        state.last_seq += 1;
+        let entry = JournalEntry {
+            origin: self.address.clone(),
+            seq: state.last_seq,
+            content: Box::new(content),
+        };
+
+        state.append(&JournalRecord::Own(entry.clone()));
+        state.own.push_back(entry.clone());
+        if state.own.len() > JOURNAL_MAX_ENTRIES {
+            state.own.pop_front();
+        }
+        Ok(entry)
+    }
+
+    /// Entries of this pod made after `seq`
+    pub fn entries_after(&self, seq: u64) -> WhResult<Vec<JournalEntry>> {
+        Ok(self
+            .lock("Journal::entries_after")?
+            .own
+            .iter()
+            .filter(|entry| entry.seq > seq)
+            .cloned()
+            .collect())
+    }
+
+    /// Last entry of `origin` applied by this pod
+    pub fn applied(&self, origin: &Address) -> WhResult<u64> {
+        Ok(*self
+            .lock("Journal::applied")?
+            .applied
+            .get(origin)
+            .unwrap_or(&0))
+    }
+
+    /// Tells if a mutation broadcasted by another pod must be applied, marking it as applied if so
+    pub fn accept(&self, entry: &JournalEntry) -> WhResult<EntryStatus> {
+        let mut state = self.lock("Journal::accept")?;
+        let applied = *state.applied.get(&entry.origin).unwrap_or(&0);
+
+        Ok(if entry.origin == self.address || entry.seq <= applied {
+            EntryStatus::Known
+        } else if entry.seq > applied + 1 {
+            match state.requests.get_mut(&entry.origin) {
+                Some(request) if request.sent.elapsed() < JOURNAL_REQUEST_TIMEOUT => {
+                    request.dropped = request.dropped.max(entry.seq);
+                    EntryStatus::Requested
+                }
+                _ => {
+                    state.requests.insert(
+                        entry.origin.clone(),
+                        JournalRequest {
+                            sent: Instant::now(),
+                            dropped: entry.seq,
+                        },
+                    );
+                    EntryStatus::Missing { after: applied }
+                }
+            }
+        } else {
+            state.mark_applied(&entry.origin, entry.seq);
+            EntryStatus::New
+        })
+    }
+
+    /// Notes that the entries of `origin` not applied yet are asked for
+    pub fn requested(&self, origin: &Address) -> WhResult<()> {
+        self.lock("Journal::requested")?.requests.insert(
+            origin.clone(),
+            JournalRequest {
+                sent: Instant::now(),
+                dropped: 0,
+            },
+        );
+        Ok(())
+    }
+
+    /// Notes that `origin` answered a request for its entries
+    ///
+    /// Returns the last entry applied if entries dropped while waiting for the answer are
+    /// still missing, they must be asked for again.
+    pub fn answered(&self, origin: &Address) -> WhResult<Option<u64>> {
+        let mut state = self.lock("Journal::answered")?;
+        let applied = *state.applied.get(origin).unwrap_or(&0);
+        Ok(match state.requests.remove(origin) {
+            Some(request) if request.dropped > applied => {
+                state.requests.insert(
+                    origin.clone(),
+                    JournalRequest {
+                        sent: Instant::now(),
+                        dropped: request.dropped,
+                    },
+                );
+                Some(applied)
+            }
+            _ => None,
+        })
+    }
+
+    /// Keeps the entries not applied yet from a [MessageContent::JournalEntries], in order,
+    /// and marks them as applied
+    pub fn select_new(&self, entries: Vec<JournalEntry>) -> WhResult<Vec<JournalEntry>> {
+        let mut state = self.lock("Journal::select_new")?;
+        let mut new_entries = Vec::new();
+
+        for entry in entries {
+            let applied = *state.applied.get(&entry.origin).unwrap_or(&0);
+            if entry.origin == self.address || entry.seq <= applied {
+                continue;
+            }
+            if entry.seq > applied + 1 {
+                log::warn!(
+                    "Journal: changes {}..{} of {} are lost, they were dropped from its journal",
+                    applied + 1,
+                    entry.seq,
+                    entry.origin
+                );
+            }
+            state.mark_applied(&entry.origin, entry.seq);
+            new_entries.push(entry);
+        }
+        Ok(new_entries)
+    }
+
+    /// Entries known by this pod, for each pod (itself included)
+    pub fn frontier(&self) -> WhResult<JournalFrontier> {
+        let state = self.lock("Journal::frontier")?;
+        let mut frontier = state.applied.clone();
+        frontier.insert(self.address.clone(), state.last_seq);
+        Ok(frontier)
+    }
+
+    /// Takes the frontier of an arbo received from another pod as applied
+    ///
+    /// Returns the entries of this pod the received arbo doesn't know about.
+    pub fn catch_up(&self, frontier: &JournalFrontier) -> WhResult<Vec<JournalEntry>> {
+        let mut state = self.lock("Journal::catch_up")?;
+        for (origin, seq) in frontier {
+            if *origin == self.address {
+                continue;
+            }
+            let applied = *state.applied.get(origin).unwrap_or(&0);
+            state.mark_applied(origin, applied.max(*seq));
+        }
+
+        // the numbering goes on after what the network knows, even if this journal was lost
+        let known = *frontier.get(&self.address).unwrap_or(&0);
+        state.last_seq = state.last_seq.max(known);
+        Ok(state
+            .own
+            .iter()
+            .filter(|entry| entry.seq > known)
+            .cloned()
+            .collect())
+    }
+}
+
+impl NetworkInterface {
+    /// Asks `to` for the entries of its journal this pod didn't apply yet
+    pub fn request_journal(&self, to: &Address) -> WhResult<()> {
+        self.journal.requested(to)?;
+        self.send_request_journal(to, self.journal.applied(to)?)
+    }
+
+    pub fn send_request_journal(&self, to: &Address, after: u64) -> WhResult<()> {
+        self.send_to(MessageContent::RequestJournal(after), to)
+    }
+
+    /// Asks every peer for the entries of its journal this pod didn't apply yet
+    pub fn request_journals(&self) -> WhResult<()> {
+        get_all_peers_address(&self.peers)?
+            .iter()
+            .try_for_each(|peer| self.request_journal(peer))
+    }
+
+    /// Answers a [MessageContent::RequestJournal]
+    pub fn send_journal(&self, to: &Address, after: u64) -> WhResult<()> {
+        self.send_to(
+            MessageContent::JournalEntries(self.journal.entries_after(after)?),
+            to,
+        )
+    }
+}
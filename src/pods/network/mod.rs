@@ -1,5 +1,11 @@
 pub mod callbacks;
+pub mod erasure;
+pub mod gossip;
+pub mod journal;
 pub mod network_interface;
+pub mod placement;
 pub mod pull_file;
 pub mod redundancy;
+pub mod supervisor;
+pub mod transfer;
 pub mod xattrs;
@@ -7,23 +7,26 @@ use std::{
 
 use crate::{
     config::{types::Config, GlobalConfig, LocalConfig},
+    data::metrics::METRICS,
     error::{WhError, WhResult},
     network::{
+        invite::Invites,
         message::{
             Address, FileSystemSerialized, FromNetworkMessage, MessageAndStatus, MessageContent,
             RedundancyMessage, ToNetworkMessage,
         },
         peer_ipc::PeerIPC,
+        secure::{handshake_responder, LinkSecurity},
         server::Server,
     },
     pods::filesystem::make_inode::MakeInodeError,
 };
 use parking_lot::{Mutex, RwLock};
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::mpsc::{error::SendError, UnboundedReceiver, UnboundedSender};
 
 use crate::pods::{
     arbo::BLOCK_SIZE,
-    filesystem::{remove_inode::RemoveInodeError, rename::RenameError},
+    filesystem::{conflict::VersionVector, remove_inode::RemoveInodeError, rename::RenameError},
     network::callbacks::Callback,
 };
 use crate::pods::{
@@ -32,19 +35,21 @@ use crate::pods::{
 };
 
 use crate::pods::{
-    arbo::{Arbo, Inode, InodeId, LOCK_TIMEOUT},
+    arbo::{Arbo, ArboDigests, Inode, InodeId, LOCK_TIMEOUT},
     filesystem::fs_interface::FsInterface,
 };
 
 use crate::pods::network::callbacks::Callbacks;
+use crate::pods::network::gossip::Gossip;
+use crate::pods::network::journal::{EntryStatus, Journal, JournalEntry};
+use crate::pods::network::transfer::{TransferKind, Transfers};
 
 pub fn get_all_peers_address(peers: &Arc<RwLock<Vec<PeerIPC>>>) -> WhResult<Vec<Address>> {
     Ok(peers
         .try_read_for(LOCK_TIMEOUT)
-        .ok_or(WhError::WouldBlock {
-            called_from: "get_all_peers_address: can't lock peers mutex".to_string(),
-        })?
+        .ok_or_else(|| WhError::would_block("get_all_peers_address: can't lock peers mutex"))?
         .iter()
+        .filter(|peer| peer.state.is_reachable())
         .map(|peer| peer.address.clone())
         .collect::<Vec<Address>>())
 }
@@ -55,8 +60,15 @@ pub struct NetworkInterface {
     pub to_network_message_tx: UnboundedSender<ToNetworkMessage>,
     pub to_redundancy_tx: UnboundedSender<RedundancyMessage>,
     pub next_inode: Mutex<InodeId>, // TODO - replace with InodeIndex type
+    /// Next inode for the ignored paths, see [crate::pods::arbo::FIRST_LOCAL_INO]
+    pub next_local_inode: Mutex<InodeId>,
     pub callbacks: Callbacks,
+    pub transfers: Transfers,
+    pub journal: Arc<Journal>,
+    pub gossip: Gossip,
     pub peers: Arc<RwLock<Vec<PeerIPC>>>,
+    /// Listen address of the pods that linked to this one, by the socket address of their link
+    aliases: RwLock<HashMap<Address, Address>>,
     pub local_config: Arc<RwLock<LocalConfig>>,
     pub global_config: Arc<RwLock<GlobalConfig>>,
 }
@@ -68,11 +80,13 @@ impl NetworkInterface {
         to_network_message_tx: UnboundedSender<ToNetworkMessage>,
         to_redundancy_tx: UnboundedSender<RedundancyMessage>,
         next_inode: InodeId,
+        journal: Arc<Journal>,
         peers: Arc<RwLock<Vec<PeerIPC>>>,
         local_config: Arc<RwLock<LocalConfig>>,
         global_config: Arc<RwLock<GlobalConfig>>,
     ) -> Self {
         let next_inode = Mutex::new(next_inode);
+        let next_local_inode = Mutex::new(arbo.read().next_local_ino());
 
         Self {
             arbo,
@@ -80,10 +94,15 @@ impl NetworkInterface {
             to_network_message_tx,
             to_redundancy_tx,
             next_inode,
+            next_local_inode,
             callbacks: Callbacks {
                 callbacks: HashMap::new().into(),
             },
+            transfers: Transfers::default(),
+            journal,
+            gossip: Gossip::default(),
             peers,
+            aliases: RwLock::new(HashMap::new()),
             local_config,
             global_config,
         }
@@ -105,12 +124,10 @@ impl NetworkInterface {
 
     /** TODO: Doc when reviews are finished */
     pub fn n_get_next_inode(&self) -> WhResult<u64> {
-        let mut next_inode =
-            self.next_inode
-                .try_lock_for(LOCK_TIMEOUT)
-                .ok_or(WhError::WouldBlock {
-                    called_from: "get_next_inode".to_string(),
-                })?;
+        let mut next_inode = self
+            .next_inode
+            .try_lock_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("get_next_inode"))?;
 
         let available_inode = *next_inode;
         *next_inode += 1;
@@ -118,14 +135,24 @@ impl NetworkInterface {
         Ok(available_inode)
     }
 
+    /// Next inode for a file of an ignored path, never shared with the network
+    pub fn n_get_next_local_inode(&self) -> WhResult<u64> {
+        let mut next_local_inode = self
+            .next_local_inode
+            .try_lock_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("get_next_local_inode"))?;
+        let available_inode = *next_local_inode;
+        *next_local_inode += 1;
+
+        Ok(available_inode)
+    }
+
     #[must_use]
     pub fn promote_next_inode(&self, new: u64) -> WhResult<()> {
-        let mut next_inode =
-            self.next_inode
-                .try_lock_for(LOCK_TIMEOUT)
-                .ok_or(WhError::WouldBlock {
-                    called_from: "promote_next_inode".to_string(),
-                })?;
+        let mut next_inode = self
+            .next_inode
+            .try_lock_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("promote_next_inode"))?;
 
         // REVIEW: next_inode being behind a mutex is weird and
         // the function not taking a mutable ref feels weird, is next_inode behind a mutex just to allow a simple &self?
@@ -163,16 +190,23 @@ impl NetworkInterface {
         let mut arbo = Arbo::n_write_lock(&self.arbo, "arbo_rename_file")?;
 
         arbo.n_mv_inode(parent, new_parent, name, new_name)?;
+        let moved = arbo
+            .n_get_inode_child_by_name(arbo.n_get_inode(new_parent)?, new_name)?
+            .id;
 
-        self.to_network_message_tx
-            .send(ToNetworkMessage::BroadcastMessage(MessageContent::Rename(
-                parent,
-                new_parent,
-                name.clone(),
-                new_name.clone(),
-                overwrite,
-            )))
-            .expect("broadcast_rename_file: unable to update modification on the network thread");
+        if !Arbo::is_local_only(moved) {
+            self.to_network_message_tx
+                .send(ToNetworkMessage::BroadcastMessage(MessageContent::Rename(
+                    parent,
+                    new_parent,
+                    name.clone(),
+                    new_name.clone(),
+                    overwrite,
+                )))
+                .expect(
+                    "broadcast_rename_file: unable to update modification on the network thread",
+                );
+        }
         Ok(())
     }
 
@@ -226,17 +260,11 @@ impl NetworkInterface {
         arbo.n_set_inode_hosts(id, hosts) // TODO - if unable to update for some reason, should be passed to the background worker
     }
 
-    pub fn send_file(&self, inode: InodeId, data: Vec<u8>, to: Address) -> io::Result<()> {
-        self.to_network_message_tx
-            .send(ToNetworkMessage::SpecificMessage(
-                (MessageContent::PullAnswer(inode, data), None),
-                vec![to],
-            ))
-            .expect("send_file: unable to update modification on the network thread");
-        Ok(())
-    }
-
-    fn affect_write_locally(&self, id: InodeId, new_size: usize) -> WhResult<Metadata> {
+    fn affect_write_locally(
+        &self,
+        id: InodeId,
+        new_size: usize,
+    ) -> WhResult<(Metadata, VersionVector)> {
         let mut arbo = Arbo::n_write_lock(&self.arbo, "network_interface.affect_write_locally")?;
         let inode = arbo.n_get_inode_mut(id)?;
         let address = LocalConfig::read_lock(&self.local_config, "affect_write_locally")?
@@ -249,16 +277,21 @@ impl NetworkInterface {
         inode.meta.blocks = ((new_size + BLOCK_SIZE - 1) / BLOCK_SIZE) as u64;
 
         inode.meta.mtime = SystemTime::now();
+        // the content changed, the hash is computed again on release
+        inode.meta.hash = None;
+        inode.version.increment(&address);
+        // the shards hold the previous content, the file is encoded again once written
+        inode.shards = None;
 
         inode.entry = match &inode.entry {
             FsEntry::File(_) => FsEntry::File(vec![address]),
             _ => panic!("Can't edit hosts on folder"),
         };
-        Ok(inode.meta.clone())
+        Ok((inode.meta.clone(), inode.version.clone()))
     }
 
     pub fn write_file(&self, id: InodeId, new_size: usize) -> WhResult<()> {
-        let meta = self.affect_write_locally(id, new_size)?;
+        let (meta, version) = self.affect_write_locally(id, new_size)?;
         let address = LocalConfig::read_lock(&self.local_config, "affect_write_locally")?
             .general
             .address
@@ -267,7 +300,7 @@ impl NetworkInterface {
         if !Arbo::is_local_only(id) {
             self.to_network_message_tx
                 .send(ToNetworkMessage::BroadcastMessage(
-                    MessageContent::RevokeFile(id, address, meta),
+                    MessageContent::RevokeFile(id, address, meta, version),
                 ))
                 .expect("revoke_remote_hosts: unable to update modification on the network thread");
             self.apply_redundancy(id);
@@ -297,6 +330,23 @@ impl NetworkInterface {
         self.update_remote_hosts(ino)
     }
 
+    /// Removes hosts from a file, only telling the network which ones left
+    /// so the hosts added meanwhile by other pods are kept
+    pub fn remove_hosts(&self, ino: InodeId, hosts: Vec<Address>) -> WhResult<()> {
+        Arbo::n_write_lock(&self.arbo, "network_interface::remove_hosts")?
+            .n_remove_inode_hosts(ino, &hosts)?;
+        if !Arbo::is_local_only(ino) {
+            self.to_network_message_tx
+                .send(ToNetworkMessage::BroadcastMessage(
+                    MessageContent::RemoveHosts(ino, hosts),
+                ))
+                .or(Err(WhError::NetworkDied {
+                    called_from: "remove_hosts".to_string(),
+                }))?;
+        }
+        Ok(())
+    }
+
     fn update_remote_hosts(&self, ino: InodeId) -> WhResult<()> {
         let inode = Arbo::n_read_lock(&self.arbo, "update_remote_hosts")?
             .n_get_inode(ino)?
@@ -372,11 +422,23 @@ impl NetworkInterface {
     // SECTION Redundancy related
 
     pub fn apply_redundancy(&self, file_id: InodeId) {
-        self.to_redundancy_tx
-            .send(RedundancyMessage::ApplyTo(file_id))
+        self.queue_redundancy(RedundancyMessage::ApplyTo(file_id))
             .expect("network_interface::apply_redundancy: tx error");
     }
 
+    /// Sends a message to the [super::redundancy::redundancy_worker], counting it in the queue depth
+    pub(super) fn queue_redundancy(
+        &self,
+        message: RedundancyMessage,
+    ) -> Result<(), SendError<RedundancyMessage>> {
+        self.to_redundancy_tx.send(message).inspect(|_| {
+            match LocalConfig::read_lock(&self.local_config, "queue_redundancy") {
+                Ok(config) => METRICS.redundancy_queued(&config.general.address),
+                Err(e) => log::warn!("queue_redundancy: not counted in the queue depth: {e}"),
+            }
+        })
+    }
+
     // !SECTION ^ Redundancy related
 
     // SECTION Node related
@@ -413,7 +475,21 @@ impl NetworkInterface {
 
     pub fn edit_peer_ip(&self, actual: Address, new: Address) {
         log::info!("changing host {} to {}", actual, new);
+        // the link keeps tagging its messages with the socket address, see [Self::registered_address]
+        if actual != new {
+            match self.aliases.try_write_for(LOCK_TIMEOUT) {
+                Some(mut aliases) => {
+                    aliases.retain(|_, address| *address != new);
+                    aliases.insert(actual.clone(), new.clone());
+                }
+                None => log::error!("edit_peer_ip: can't lock the aliases, {actual} stays unknown"),
+            }
+        }
         if let Some(mut peers) = self.peers.try_write_for(LOCK_TIMEOUT) {
+            // a peer coming back replaces its lost link
+            if actual != new {
+                peers.retain(|peer| peer.address != new);
+            }
             for peer in peers.iter_mut() {
                 if peer.address == actual {
                     log::info!("done once");
@@ -423,17 +499,41 @@ impl NetworkInterface {
         }
     }
 
-    pub fn send_arbo(&self, to: Address, global_config_bytes: Vec<u8>) -> io::Result<()> {
+    /// Address under which the pod that sent a message is known
+    ///
+    /// Messages coming through a link opened by another pod carry the socket address of that link,
+    /// they are routed back to the listen address it registered with.
+    pub fn registered_address(&self, origin: &Address) -> WhResult<Address> {
+        Ok(self
+            .aliases
+            .try_read_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("registered_address: can't lock the aliases"))?
+            .get(origin)
+            .unwrap_or(origin)
+            .clone())
+    }
+
+    /// Sends the arbo, or only what changed since the `known` digests of a returning pod
+    pub fn send_arbo(
+        &self,
+        to: Address,
+        known: Option<ArboDigests>,
+        global_config_bytes: Vec<u8>,
+    ) -> io::Result<()> {
         let arbo = Arbo::read_lock(&self.arbo, "send_arbo")?;
-        let mut entries = arbo.get_raw_entries();
+        let mut entries = match &known {
+            Some(known) => arbo.diff_from(known),
+            None => arbo.get_raw_entries(),
+        };
+        drop(arbo);
 
         //Remove ignored entries
         entries.retain(|ino, _| !Arbo::is_local_only(*ino));
-        entries.entry(1u64).and_modify(|inode| {
+        for inode in entries.values_mut() {
             if let FsEntry::Directory(childrens) = &mut inode.entry {
                 childrens.retain(|x| !Arbo::is_local_only(*x));
             }
-        });
+        }
 
         if let Some(peers) = self.peers.try_read_for(LOCK_TIMEOUT) {
             let peers_address_list = peers
@@ -453,7 +553,11 @@ impl NetworkInterface {
                         MessageContent::FsAnswer(
                             FileSystemSerialized {
                                 fs_index: entries,
+                                incremental: known.is_some(),
                                 next_inode: self.get_next_inode()?,
+                                journal_frontier: self.journal.frontier().map_err(|e| {
+                                    io::Error::new(io::ErrorKind::WouldBlock, e.to_string())
+                                })?,
                             },
                             peers_address_list,
                             global_config_bytes,
@@ -472,11 +576,12 @@ impl NetworkInterface {
         }
     }
 
-    pub fn register_new_node(&self, socket: Address, addr: Address) {
-        self.edit_peer_ip(socket, addr);
-        self.to_redundancy_tx
-            .send(RedundancyMessage::CheckIntegrity)
+    pub fn register_new_node(&self, socket: Address, addr: Address) -> WhResult<()> {
+        self.edit_peer_ip(socket, addr.clone());
+        self.queue_redundancy(RedundancyMessage::CheckIntegrity)
             .unwrap();
+        // the pod may come back with changes made while offline
+        self.request_journal(&addr)
     }
 
     pub fn disconnect_peer(&self, addr: Address) -> io::Result<()> {
@@ -487,16 +592,35 @@ impl NetworkInterface {
                 format!("disconnect_peer: can't write lock peers"),
             ))?
             .retain(|p| p.address != addr);
+        if let Some(mut aliases) = self.aliases.try_write_for(LOCK_TIMEOUT) {
+            aliases.retain(|_, address| *address != addr);
+        }
+
+        log::debug!("Disconnecting {addr}");
+        self.forget_host(&addr)
+            .map_err(|e| io::Error::other(format!("WhError: {e}")))
+    }
 
-        log::debug!("Disconnecting {addr}. Removing from inodes hosts");
-        for inode in Arbo::write_lock(&self.arbo, "disconnect_peer")?.inodes_mut() {
+    /// Strips a host that left or died from the files, and schedules their re-replication
+    ///
+    /// Every pod strips it on its own as they all notice the host leaving.
+    pub fn forget_host(&self, addr: &Address) -> WhResult<()> {
+        let mut lost_copies = 0;
+        let mut lost_shards = 0;
+        for inode in Arbo::n_write_lock(&self.arbo, "forget_host")?.inodes_mut() {
             if let FsEntry::File(hosts) = &mut inode.entry {
-                hosts.retain(|h| *h != addr);
+                let count = hosts.len();
+                hosts.retain(|h| h != addr);
+                lost_copies += count - hosts.len();
+            }
+            if let Some(layout) = &mut inode.shards {
+                lost_shards += layout.forget_holder(addr);
             }
         }
-        self.to_redundancy_tx
-            .send(RedundancyMessage::CheckIntegrity)
-            .unwrap();
+        log::info!(
+            "{addr} is gone, {lost_copies} files lost a copy and {lost_shards} shards were lost"
+        );
+        let _ = self.queue_redundancy(RedundancyMessage::CheckIntegrity);
         Ok(())
     }
 
@@ -509,6 +633,13 @@ impl NetworkInterface {
                 Some(message) => message,
                 None => continue,
             };
+            let origin = fs_interface
+                .network_interface
+                .registered_address(&origin)
+                .unwrap_or_else(|e| {
+                    log::warn!("Network airport: {e}");
+                    origin
+                });
             if log::log_enabled!(log::Level::Debug) {
                 log::debug!("From {}: {:?}", origin, content);
             } else {
@@ -516,122 +647,331 @@ impl NetworkInterface {
             }
             let content_debug = format!("{content:?}");
 
-            let action_result = match content {
-                MessageContent::PullAnswer(id, binary) => fs_interface.recept_binary(id, binary),
-                MessageContent::RedundancyFile(id, binary) => fs_interface.recept_redundancy(id, binary)
-                    .map_err(|e| std::io::Error::new(
+            if let Err(error) = Self::handle_content(&fs_interface, origin, content) {
+                log::error!(
+                    "Network airport couldn't operate operation {content_debug}, error found: {error}"
+                );
+            }
+        }
+    }
+
+    fn handle_content(
+        fs_interface: &Arc<FsInterface>,
+        origin: Address,
+        content: MessageContent,
+    ) -> io::Result<()> {
+        match content {
+            MessageContent::PullAnswer(chunk) => fs_interface.recept_chunk(chunk, origin, TransferKind::Pull)
+                .map_err(|e| std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("WhError: {e}"),
+            )),
+            MessageContent::RedundancyFile(chunk) => fs_interface.recept_chunk(chunk, origin, TransferKind::Redundancy)
+                .map_err(|e| std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("WhError: {e}"),
+            )),
+            MessageContent::ChunkAck(id, offset, accepted) => fs_interface
+                .network_interface
+                .transfers
+                .acknowledge(&origin, id, offset, accepted)
+                .map_err(|e| std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("WhError: {e}"),
+            )),
+            MessageContent::Inode(inode) => fs_interface.recept_inode(inode).or_else(|err| {
+                    Err(std::io::Error::new(
                         std::io::ErrorKind::Other,
-                        format!("WhError: {e}"),
-                )),
-                MessageContent::Inode(inode) => fs_interface.recept_inode(inode).or_else(|err| {
-                        Err(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            format!("WhError: {err}"),
-                        ))
-                    }),
-                MessageContent::EditHosts(id, hosts) => fs_interface.recept_edit_hosts(id, hosts).or_else(|err| {
-                        Err(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            format!("WhError: {err}"),
-                        ))
-                    }),
-                MessageContent::RevokeFile(id, host, meta) => fs_interface.recept_revoke_hosts(id, host, meta).or_else(|err| {
-                        Err(std::io::Error::new(
+                        format!("WhError: {err}"),
+                    ))
+                }),
+            MessageContent::EditHosts(id, hosts) => fs_interface.recept_edit_hosts(id, hosts).or_else(|err| {
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!("WhError: {err}"),
+                    ))
+                }),
+            MessageContent::RevokeFile(id, host, meta, version) => fs_interface.recept_revoke_hosts(id, host, meta, version).or_else(|err| {
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!("WhError: {err}"),
+                    ))
+                }),
+            MessageContent::AddHosts(id, hosts) => fs_interface.recept_add_hosts(id, hosts),
+            MessageContent::RemoveHosts(id, hosts) => {
+                fs_interface.recept_remove_hosts(id, hosts)
+            }
+            MessageContent::EditMetadata(id, meta) =>
+                fs_interface.acknowledge_metadata(id, meta).or_else(|err| {
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!("WhError: {err}"),
+                    ))
+                }),
+            MessageContent::Remove(id) => fs_interface.recept_remove_inode(id).or_else(|err| {
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!("WhError: {err}"),
+                    ))
+                }),
+            MessageContent::RequestFile(inode, peer, offset) => {
+                // streamed aside so the airport keeps receiving the acknowledgments
+                let fs_interface = fs_interface.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = fs_interface
+                        .stream_file(inode, peer, offset, TransferKind::Pull)
+                        .await
+                    {
+                        log::error!("Sending file {inode} failed: {e}");
+                    }
+                });
+                Ok(())
+            }
+            MessageContent::RequestRange(inode, offset, len) => fs_interface
+                .send_range(inode, origin, offset, len)
+                .map_err(|e| std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("WhError: {e}"),
+            )),
+            MessageContent::RangeAnswer(inode, offset, data) => fs_interface
+                .network_interface
+                .transfers
+                .resolve_range(inode, offset, data)
+                .map_err(|e| std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("WhError: {e}"),
+            )),
+            MessageContent::RequestFs => fs_interface.send_filesystem(origin, None),
+            MessageContent::RequestFsDiff(known) => {
+                fs_interface.send_filesystem(origin, Some(known))
+            }
+            MessageContent::Register(addr) => fs_interface.register_new_node(origin, addr)
+                .map_err(|e| std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("WhError: {e}"),
+            )),
+            MessageContent::Rename(parent, new_parent, name, new_name, overwrite) =>
+                fs_interface
+                .recept_rename(parent, new_parent, &name, &new_name, overwrite)
+                .map_err(|err| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!("WhError: {err}"),
+                    )
+                }),
+            MessageContent::SetXAttr(ino, key, data) => fs_interface
+                .network_interface
+                .recept_inode_xattr(ino, key, data)
+                .or_else(|err| {
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!("WhError: {err}"),
+                    ))
+                }),
+            MessageContent::RemoveXAttr(ino, key) => fs_interface
+                .network_interface
+                .recept_remove_inode_xattr(ino, key)
+                .or_else(|err| {
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!("WhError: {err}"),
+                    ))
+                }),
+            MessageContent::FsAnswer(_, _, _) => {
+                Err(io::Error::new(ErrorKind::InvalidInput,
+                    "Late answer from first connection, loaded network interface shouldn't recieve FsAnswer"))
+            },
+            MessageContent::Disconnect(addr) => fs_interface.network_interface.disconnect_peer(addr),
+            // only mutations are journaled, anything else would be replayed on every catch up
+            MessageContent::Journal(entry) if !entry.content.is_mutation() => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "{} journaled a {}, which isn't a mutation",
+                    entry.origin,
+                    entry.content.kind()
+                ),
+            )),
+            MessageContent::Journal(entry) => {
+                let status = fs_interface.network_interface.journal.accept(&entry);
+                match status {
+                    Ok(EntryStatus::New) => Self::handle_content(fs_interface, origin, *entry.content),
+                    Ok(EntryStatus::Known | EntryStatus::Requested) => Ok(()),
+                    // the missed entries are asked for, this one comes back with them
+                    Ok(EntryStatus::Missing { after }) => fs_interface
+                        .network_interface
+                        .send_request_journal(&entry.origin, after)
+                        .map_err(|e| std::io::Error::new(
                             std::io::ErrorKind::Other,
-                            format!("WhError: {err}"),
-                        ))
-                    }),
-                MessageContent::AddHosts(id, hosts) => fs_interface.recept_add_hosts(id, hosts),
-                MessageContent::RemoveHosts(id, hosts) => {
-                    fs_interface.recept_remove_hosts(id, hosts)
+                            format!("WhError: {e}"),
+                        )),
+                    Err(e) => Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!("WhError: {e}"),
+                    )),
                 }
-                MessageContent::EditMetadata(id, meta) =>
-                    fs_interface.acknowledge_metadata(id, meta).or_else(|err| {
-                        Err(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            format!("WhError: {err}"),
-                        ))
-                    }),
-                MessageContent::Remove(id) => fs_interface.recept_remove_inode(id).or_else(|err| {
-                        Err(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            format!("WhError: {err}"),
-                        ))
-                    }),
-                MessageContent::RequestFile(inode, peer) => fs_interface.send_file(inode, peer),
-                MessageContent::RequestFs => fs_interface.send_filesystem(origin),
-                MessageContent::Register(addr) => Ok(fs_interface.register_new_node(origin, addr)),
-                MessageContent::Rename(parent, new_parent, name, new_name, overwrite) =>
-                    fs_interface
-                    .recept_rename(parent, new_parent, &name, &new_name, overwrite)
-                    .map_err(|err| {
-                        std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            format!("WhError: {err}"),
-                        )
-                    }),
-                MessageContent::SetXAttr(ino, key, data) => fs_interface
-                    .network_interface
-                    .recept_inode_xattr(ino, key, data)
-                    .or_else(|err| {
-                        Err(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            format!("WhError: {err}"),
-                        ))
-                    }),
-                MessageContent::RemoveXAttr(ino, key) => fs_interface
-                    .network_interface
-                    .recept_remove_inode_xattr(ino, key)
-                    .or_else(|err| {
-                        Err(std::io::Error::new(
-                            std::io::ErrorKind::Other,
-                            format!("WhError: {err}"),
-                        ))
-                    }),
-                MessageContent::FsAnswer(_, _, _) => {
-                    Err(io::Error::new(ErrorKind::InvalidInput,
-                        "Late answer from first connection, loaded network interface shouldn't recieve FsAnswer"))
-                },
-                MessageContent::Disconnect(addr) => fs_interface.network_interface.disconnect_peer(addr)
-            };
-            if let Err(error) = action_result {
+            }
+            MessageContent::Gossip(entries) => fs_interface
+                .network_interface
+                .recept_gossip(entries)
+                .map_err(|e| std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("WhError: {e}"),
+            )),
+            MessageContent::StoreShard(index, chunk) => fs_interface
+                .recept_chunk(chunk, origin, TransferKind::Shard(index))
+                .map_err(|e| std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("WhError: {e}"),
+            )),
+            MessageContent::RequestShard(inode, index) => {
+                // streamed aside so the airport keeps receiving the acknowledgments
+                let fs_interface = fs_interface.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = fs_interface.send_shard(origin, inode, index).await {
+                        log::error!("Sending shard {index} of {inode} failed: {e}");
+                    }
+                });
+                Ok(())
+            }
+            MessageContent::ShardAnswer(index, chunk) => fs_interface
+                .recept_chunk(chunk, origin, TransferKind::ShardAnswer(index))
+                .map_err(|e| std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("WhError: {e}"),
+            )),
+            MessageContent::ShardMissing(inode, index) => fs_interface
+                .network_interface
+                .transfers
+                .resolve_shard(&origin, inode, index, false)
+                .map_err(|e| std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("WhError: {e}"),
+            )),
+            MessageContent::DropShards(inode, indexes) => fs_interface
+                .recept_drop_shards(inode, indexes)
+                .map_err(|e| std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("WhError: {e}"),
+            )),
+            MessageContent::EditShards(inode, layout) => fs_interface
+                .recept_edit_shards(inode, layout)
+                .map_err(|e| std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("WhError: {e}"),
+            )),
+            MessageContent::RequestJournal(after) => fs_interface
+                .network_interface
+                .send_journal(&origin, after)
+                .map_err(|e| std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("WhError: {e}"),
+            )),
+            MessageContent::JournalEntries(entries) => fs_interface
+                .network_interface
+                .journal
+                .select_new(entries)
+                .map(|entries| Self::replay_journal(fs_interface, entries))
+                .and_then(|_| fs_interface.network_interface.journal.answered(&origin))
+                .and_then(|missing| match missing {
+                    // entries broadcasted while the answer was on its way
+                    Some(after) => fs_interface
+                        .network_interface
+                        .send_request_journal(&origin, after),
+                    None => Ok(()),
+                })
+                .map_err(|e| std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("WhError: {e}"),
+            )),
+        }
+    }
+
+    /// Applies mutations made by other pods (or by this one, on an arbo that missed them)
+    pub fn replay_journal(fs_interface: &Arc<FsInterface>, entries: Vec<JournalEntry>) {
+        for JournalEntry {
+            origin,
+            seq,
+            content,
+        } in entries
+        {
+            if !content.is_mutation() {
                 log::error!(
-                    "Network airport couldn't operate operation {content_debug}, error found: {error}"
+                    "Journal: {origin} journaled a {}, which isn't a mutation",
+                    content.kind()
                 );
+                continue;
+            }
+            let content_debug = format!("{content:?}");
+            if let Err(error) = Self::handle_content(fs_interface, origin.clone(), *content) {
+                log::error!("Journal: can't replay {content_debug} ({origin}, {seq}): {error}");
             }
         }
     }
 
     pub async fn contact_peers(
         peers_list: Arc<RwLock<Vec<PeerIPC>>>,
+        journal: Arc<Journal>,
         mut rx: UnboundedReceiver<ToNetworkMessage>,
     ) {
         log::info!("contact peers");
         while let Some(message) = rx.recv().await {
             // geeting all peers network senders
+            // lost peers catch up from the journal once back
             let peers_tx: Vec<(UnboundedSender<MessageAndStatus>, String)> = peers_list
                 .try_read_for(LOCK_TIMEOUT)
                 .expect("mutext error on contact_peers") // TODO - handle timeout
                 .iter()
+                .filter(|peer| peer.state.is_reachable())
                 .map(|peer| (peer.sender.clone(), peer.address.clone()))
                 .collect();
 
             match message {
                 ToNetworkMessage::BroadcastMessage(message_content) => {
+                    // recorded even without peers, they will ask for it when coming back
+                    let message_content = if message_content.is_mutation() {
+                        match journal.record(message_content.clone()) {
+                            Ok(entry) => MessageContent::Journal(entry),
+                            Err(e) => {
+                                log::error!("contact_peers: can't journal {message_content}: {e}");
+                                message_content
+                            }
+                        }
+                    } else {
+                        message_content
+                    };
                     peers_tx.iter().for_each(|(channel, address)| {
-                        channel
-                            .send((message_content.clone(), None))
-                            .expect(&format!("failed to send message to peer {}", address))
+                        if channel.send((message_content.clone(), None)).is_err() {
+                            log::warn!("contact_peers: link to {address} is closed");
+                        }
                     });
                 }
                 ToNetworkMessage::SpecificMessage((message_content, status_tx), origins) => {
+                    if !peers_tx
+                        .iter()
+                        .any(|(_, address)| origins.contains(address))
+                    {
+                        log::warn!("contact_peers: no link to {origins:?}");
+                        if let Some(status_tx) = &status_tx {
+                            let _ = status_tx.send(Err(WhError::NetworkDied {
+                                called_from: "contact_peers".to_owned(),
+                            }));
+                        }
+                    }
                     peers_tx
                         .iter()
                         .filter(|&(_, address)| origins.contains(address))
                         .for_each(|(channel, address)| {
-                            channel
+                            if channel
                                 .send((message_content.clone(), status_tx.clone()))
-                                .expect(&format!("failed to send message to peer {}", address))
+                                .is_err()
+                            {
+                                log::warn!("contact_peers: link to {address} is closed");
+                                if let Some(status_tx) = &status_tx {
+                                    let _ = status_tx.send(Err(WhError::NetworkDied {
+                                        called_from: "contact_peers".to_owned(),
+                                    }));
+                                }
+                            }
                         });
                 }
             };
@@ -642,22 +982,46 @@ impl NetworkInterface {
         server: Arc<Server>,
         nfa_tx: UnboundedSender<FromNetworkMessage>,
         existing_peers: Arc<RwLock<Vec<PeerIPC>>>,
+        security: LinkSecurity,
+        invites: Arc<Invites>,
     ) {
         while let Ok((stream, addr)) = server.listener.accept().await {
             log::debug!("GOT ADDRESS {addr}");
-            let ws_stream = tokio_tungstenite::accept_async(stream)
-                .await
-                .expect("Error during the websocket handshake occurred");
-
-            let (write, read) = futures_util::StreamExt::split(ws_stream);
-            let new_peer =
-                PeerIPC::connect_from_incomming(addr.to_string(), nfa_tx.clone(), write, read);
-            {
+            let nfa_tx = nfa_tx.clone();
+            let existing_peers = existing_peers.clone();
+            let security = security.clone();
+            let invites = invites.clone();
+
+            // handshakes are made aside so a slow peer can't hold the other connections
+            tokio::spawn(async move {
+                let mut ws_stream = match tokio_tungstenite::accept_async(stream).await {
+                    Ok(ws_stream) => ws_stream,
+                    Err(e) => {
+                        log::warn!("Error during the websocket handshake with {addr}: {e}");
+                        return;
+                    }
+                };
+                // untrusted peers are refused before any message is read
+                let keys = match handshake_responder(&mut ws_stream, &security, &invites).await {
+                    Ok(Some(keys)) => keys,
+                    Ok(None) => {
+                        log::info!("{addr} joined the network with an invitation");
+                        return;
+                    }
+                    Err(e) => {
+                        log::warn!("Refusing connection from {addr}: {e}");
+                        return;
+                    }
+                };
+
+                let (write, read) = futures_util::StreamExt::split(ws_stream);
+                let new_peer =
+                    PeerIPC::connect_from_incomming(addr.to_string(), nfa_tx, write, read, keys);
                 existing_peers
                     .try_write_for(LOCK_TIMEOUT)
                     .expect("incoming_connections_watchdog: can't lock existing peers")
                     .push(new_peer);
-            }
+            });
         }
     }
 
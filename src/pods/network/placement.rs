@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use crate::{config::types::PlacementConfig, network::message::Address};
+
+use super::gossip::NodeStatus;
+
+const MO: u64 = 1024 * 1024;
+
+/// Orders the peers that may receive a copy of a file, best first
+///
+/// Peers that would have less than [PlacementConfig::min_free_space] left, or go over their quota, are dropped.
+/// The others are ranked by failure domains not hosting the file yet, then load, then free space.
+/// Peers that didn't send their status yet come last.
+pub fn rank_candidates(
+    candidates: &[Address],
+    statuses: &HashMap<Address, NodeStatus>,
+    hosts_domains: &[Option<String>],
+    file_size: u64,
+    config: &PlacementConfig,
+) -> Vec<Address> {
+    let mut remaining: Vec<(&Address, Option<&NodeStatus>)> = candidates
+        .iter()
+        .map(|peer| (peer, statuses.get(peer)))
+        .filter(|(_, status)| {
+            status
+                .and_then(|status| status.free_space)
+                .is_none_or(|free| free >= file_size.saturating_add(config.min_free_space * MO))
+                && status
+                    .and_then(NodeStatus::quota_left)
+                    .is_none_or(|left| left >= file_size)
+        })
+        .collect();
+    let mut used_domains: Vec<&String> = hosts_domains.iter().flatten().collect();
+    let mut ranked = Vec::with_capacity(remaining.len());
+
+    // the domains used change with each pick, so the best one is looked for again every time
+    while !remaining.is_empty() {
+        let best = remaining
+            .iter()
+            .enumerate()
+            .min_by(|(_, (a_addr, a)), (_, (b_addr, b))| {
+                let key = |status: &Option<&NodeStatus>| {
+                    let shared_domain = config.spread_failure_domains
+                        && status
+                            .and_then(|status| status.failure_domain.as_ref())
+                            .is_some_and(|domain| used_domains.contains(&domain));
+                    (
+                        shared_domain,
+                        status.is_none(),
+                        status.map_or(0, |status| status.load),
+                    )
+                };
+                key(a)
+                    .cmp(&key(b))
+                    .then_with(|| {
+                        let ratio = |status: &Option<&NodeStatus>| {
+                            status.map_or(0., |status| status.free_ratio())
+                        };
+                        ratio(b).total_cmp(&ratio(a))
+                    })
+                    .then_with(|| a_addr.cmp(b_addr))
+            })
+            .map(|(index, _)| index)
+            .expect("remaining is not empty");
+
+        let (peer, status) = remaining.remove(best);
+        if let Some(domain) = status.and_then(|status| status.failure_domain.as_ref()) {
+            used_domains.push(domain);
+        }
+        ranked.push(peer.clone());
+    }
+    ranked
+}
@@ -5,6 +5,7 @@ use crate::network::message::{MessageContent, ToNetworkMessage};
 use crate::pods::arbo::{Arbo, FsEntry};
 use crate::pods::network::callbacks::Callback;
 use crate::pods::network::network_interface::NetworkInterface;
+use crate::pods::network::transfer::TRANSFER_TIMEOUT;
 use crate::{error::WhError, pods::arbo::InodeId};
 use custom_error::custom_error;
 
@@ -42,7 +43,13 @@ impl NetworkInterface {
             // if the asked file is already on disk
             Ok(None)
         } else {
-            let callback = self.callbacks.n_create(Callback::Pull(file))?;
+            let callback = Callback::Pull(file);
+            // a second pull of the file would write over the chunks of the running one
+            if !self.callbacks.n_create_or_join(callback)? {
+                return Ok(Some(callback));
+            }
+            // resumes an interrupted pull of this file, if any
+            let offset = self.transfers.received(file)?;
             let (status_tx, mut status_rx) = tokio::sync::mpsc::unbounded_channel::<WhResult<()>>();
 
             // will try to pull on all redundancies until success
@@ -51,7 +58,7 @@ impl NetworkInterface {
                 self.to_network_message_tx
                     .send(ToNetworkMessage::SpecificMessage(
                         (
-                            MessageContent::RequestFile(file, self_addr.clone()),
+                            MessageContent::RequestFile(file, self_addr.clone(), offset),
                             Some(status_tx.clone()),
                         ),
                         vec![host.clone()], // NOTE - naive choice for now
@@ -63,13 +70,63 @@ impl NetworkInterface {
                     .blocking_recv()
                     .expect("pull_file: unable to get status from the network thread")
                 {
-                    Ok(()) => return Ok(Some(callback)),
+                    Ok(()) => {
+                        self.transfers.pulling_from(file, host)?;
+                        return Ok(Some(callback));
+                    }
                     Err(_) => continue,
                 }
             }
-            let _ = self.callbacks.resolve(callback, true);
+            let _ = self.callbacks.resolve(callback, false);
             log::error!("No host is currently able to send the file.\nFile: {file}");
             return Err(PullError::NoHostAvailable);
         }
     }
+
+    /// Reads `len` bytes at `offset` of a file hosted elsewhere, without pulling the whole file
+    ///
+    /// The returned data is shorter than `len` if the file ends before,
+    /// or if `len` is over [crate::pods::network::transfer::MAX_RANGE_SIZE].
+    pub fn pull_range_sync(
+        &self,
+        file: InodeId,
+        offset: u64,
+        len: u64,
+    ) -> Result<Vec<u8>, PullError> {
+        let hosts = {
+            let arbo = Arbo::n_read_lock(&self.arbo, "pull_range_sync")?;
+            if let FsEntry::File(hosts) = &arbo.n_get_inode(file)?.entry {
+                hosts.clone()
+            } else {
+                return Err(WhError::InodeIsADirectory.into());
+            }
+        };
+
+        for host in hosts {
+            // unregistered on every way out of this iteration
+            let answer = self.transfers.wait_range(file, offset)?;
+            let (status_tx, mut status_rx) = tokio::sync::mpsc::unbounded_channel::<WhResult<()>>();
+
+            self.to_network_message_tx
+                .send(ToNetworkMessage::SpecificMessage(
+                    (
+                        MessageContent::RequestRange(file, offset, len),
+                        Some(status_tx),
+                    ),
+                    vec![host.clone()],
+                ))
+                .expect("pull_range: unable to request on the network thread");
+
+            if !matches!(status_rx.blocking_recv(), Some(Ok(()))) {
+                continue;
+            }
+            match answer.recv_timeout(TRANSFER_TIMEOUT) {
+                Ok(Some(data)) => return Ok(data),
+                Ok(None) => log::warn!("{host} can't read range of {file}, trying next host"),
+                Err(_) => log::warn!("{host} didn't answer range of {file}, trying next host"),
+            }
+        }
+        log::error!("No host is currently able to send the range.\nFile: {file}");
+        Err(PullError::NoHostAvailable)
+    }
 }
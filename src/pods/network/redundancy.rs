@@ -1,40 +1,112 @@
-use super::network_interface::{get_all_peers_address, NetworkInterface};
+use super::{
+    erasure::{shard_size, ErasureError, ShardLayout},
+    network_interface::{get_all_peers_address, NetworkInterface},
+    placement::rank_candidates,
+    transfer::{TransferError, TransferKind},
+};
 use crate::{
+    config::{
+        types::{Config, ErasureConfig, IgnoreRules, RedundancyTargets},
+        GlobalConfig, LocalConfig,
+    },
+    data::metrics::METRICS,
     error::{WhError, WhResult},
-    network::message::{Address, MessageContent, RedundancyMessage, ToNetworkMessage},
+    network::message::{Address, RedundancyMessage},
     pods::{
-        arbo::{Arbo, FsEntry, InodeId},
+        arbo::{Arbo, FsEntry, Inode, InodeId},
         filesystem::fs_interface::FsInterface,
     },
 };
 use futures_util::future::join_all;
-use std::{error, future::Future, sync::Arc};
-use tokio::{
-    sync::{
-        futures,
-        mpsc::{unbounded_channel, UnboundedReceiver},
-    },
-    task::JoinSet,
-};
+use std::{sync::Arc, time::Duration};
+use tokio::{sync::mpsc::UnboundedReceiver, task::JoinSet};
 
 custom_error::custom_error! {pub RedundancyError
     WhError{source: WhError} = "{source}",
     InsufficientHosts = "Redundancy: Not enough nodes to satisfies the target redundancies number.", // warning only
 }
 
+/// Interval between two attempts to heal the files lacking hosts
+pub const HEALING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Extended attribute setting the copies wanted of a file, or of the files under a directory.
+/// Its value is the number in text (e.g. `setfattr -n user.wormhole.redundancy -v 3 <path>`),
+/// it overrides [crate::config::types::RedundancyConfig::policies].
+pub const REDUNDANCY_XATTR: &str = "user.wormhole.redundancy";
+
 /// Redundancy Worker
 /// Worker that applies the redundancy to files
 pub async fn redundancy_worker(
     mut reception: UnboundedReceiver<RedundancyMessage>,
     nw_interface: Arc<NetworkInterface>,
     fs_interface: Arc<FsInterface>,
-    redundancy: u64, // TODO - when updated in conf, send a message to this worker for update
-    self_addr: Address, // TODO - Same
+    self_addr: Address, // TODO - when updated in conf, send a message to this worker for update
 ) {
+    let mut healing = tokio::time::interval(HEALING_INTERVAL);
+    // peers and count of files lacking hosts when healing was last tried
+    let mut last_healing: Option<(Vec<Address>, usize)> = None;
+
     loop {
-        let message = match reception.recv().await {
-            Some(message) => message,
-            None => continue,
+        let message = tokio::select! {
+            message = reception.recv() => match message {
+                Some(message) => {
+                    METRICS.redundancy_dequeued(&self_addr);
+                    message
+                }
+                None => return,
+            },
+            // files still lacking hosts (e.g. not enough peers last time) are tried again
+            _ = healing.tick() => {
+                let (targets, ignored) = match GlobalConfig::read_lock(&nw_interface.global_config, "redundancy_worker") {
+                    Ok(config) => (config.redundancy.targets(), config.general.ignore_rules()),
+                    Err(e) => {
+                        log::warn!("Redundancy: {e}");
+                        continue;
+                    }
+                };
+                let mut peers = match get_all_peers_address(&nw_interface.peers) {
+                    Ok(peers) => peers,
+                    Err(e) => {
+                        log::warn!("Redundancy: {e}");
+                        continue;
+                    }
+                };
+                peers.sort();
+                match Arbo::n_read_lock(&nw_interface.arbo, "redundancy_worker") {
+                    Ok(arbo) => {
+                        let lacking = under_replicated(&arbo, &targets, &ignored);
+                        METRICS.set_under_replicated(&self_addr, lacking);
+                        // e.g. a lone pod can't heal anything, and healing isn't tried again
+                        // before the peers or the files lacking hosts change
+                        let state = (peers, lacking);
+                        if healable(&arbo, &targets, &ignored, state.0.len() + 1) == 0
+                            || last_healing.as_ref() == Some(&state)
+                        {
+                            continue;
+                        }
+                        last_healing = Some(state);
+                        RedundancyMessage::CheckIntegrity
+                    }
+                    Err(e) => {
+                        log::warn!("Redundancy: {e}");
+                        continue;
+                    }
+                }
+            }
+        };
+        let (targets, erasure, ignored) = match GlobalConfig::read_lock(
+            &nw_interface.global_config,
+            "redundancy_worker",
+        ) {
+            Ok(config) => (
+                config.redundancy.targets(),
+                config.redundancy.erasure.clone(),
+                config.general.ignore_rules(),
+            ),
+            Err(e) => {
+                log::error!("Redundancy: can't read the configuration (ignoring request \"{message:?}\"): {e}");
+                continue;
+            }
         };
         let peers = match get_all_peers_address(&nw_interface.peers) {
             Ok(peers) => peers,
@@ -49,10 +121,21 @@ pub async fn redundancy_worker(
 
         let _ = match message {
             RedundancyMessage::ApplyTo(ino) => {
+                match Arbo::n_read_lock(&nw_interface.arbo, "redundancy_worker")
+                    .and_then(|arbo| is_ignored(&arbo, &ignored, ino))
+                {
+                    Ok(false) => (),
+                    Ok(true) => continue,
+                    Err(e) => {
+                        log::error!("Redundancy error: {e}");
+                        continue;
+                    }
+                }
                 let _ = apply_to(
                     &nw_interface,
                     &fs_interface,
-                    redundancy,
+                    &targets,
+                    erasure.as_ref(),
                     &peers,
                     &self_addr,
                     ino,
@@ -61,10 +144,17 @@ pub async fn redundancy_worker(
                 .inspect_err(|e| log::error!("Redundancy error: {e}"));
             }
             RedundancyMessage::CheckIntegrity => {
-                let _ =
-                    check_integrity(&nw_interface, &fs_interface, redundancy, &peers, &self_addr)
-                        .await
-                        .inspect_err(|e| log::error!("Redundancy error: {e}"));
+                let _ = check_integrity(
+                    &nw_interface,
+                    &fs_interface,
+                    &targets,
+                    erasure.as_ref(),
+                    &ignored,
+                    &peers,
+                    &self_addr,
+                )
+                .await
+                .inspect_err(|e| log::error!("Redundancy error: {e}"));
             }
         };
     }
@@ -74,7 +164,8 @@ pub async fn redundancy_worker(
 /// - needs more hosts
 /// - the network contains more hosts
 /// - this node possesses the file
-/// - this node is first on the sorted hosts list (naive approach to avoid many hosts applying the same file)
+/// - this node is the host picked for this inode in the sorted hosts list,
+///   so a single host applies each file and the work is spread among them
 ///
 /// Intended for use in the check_intergrity function
 fn eligible_to_apply(
@@ -87,16 +178,45 @@ fn eligible_to_apply(
     if Arbo::is_local_only(ino) {
         return None;
     }
-    let hosts = if let FsEntry::File(hosts) = entry {
-        let mut hosts = hosts.clone();
-        hosts.sort();
-        hosts
-    } else {
-        return None;
+    let hosts = match entry {
+        FsEntry::File(hosts) => hosts,
+        _ => return None,
     };
     if hosts.len() < target_redundancy as usize
         && available_peers > hosts.len()
-        && hosts[0] == *self_addr
+        && is_elected(ino, hosts, self_addr)
+    {
+        Some(ino)
+    } else {
+        None
+    }
+}
+
+/// Tells if this node is the one picked among `pods` to work on a file,
+/// so a single one does it and the work is spread among them
+///
+/// Always false without pods, e.g. when every copy of the file is lost.
+fn is_elected(ino: InodeId, pods: &[Address], self_addr: &Address) -> bool {
+    let mut pods = pods.to_vec();
+    pods.sort();
+    !pods.is_empty() && pods[(ino % pods.len() as u64) as usize] == *self_addr
+}
+
+/// Checks if the lost shards of an erasure coded file can be rebuilt by this node:
+/// - enough shards are left to rebuild them
+/// - the network contains pods not holding a shard yet
+/// - this node is the holder picked for this inode
+fn eligible_to_heal(
+    ino: InodeId,
+    layout: &ShardLayout,
+    available_peers: usize,
+    self_addr: &Address,
+) -> Option<InodeId> {
+    let holders: Vec<Address> = layout.holders.iter().flatten().cloned().collect();
+    if layout.missing() > 0
+        && holders.len() >= layout.data_shards
+        && available_peers > holders.len()
+        && is_elected(ino, &holders, self_addr)
     {
         Some(ino)
     } else {
@@ -104,30 +224,133 @@ fn eligible_to_apply(
     }
 }
 
+/// Checks if a file must be erasure coded by this node:
+/// - it isn't yet, and is not being written (its hash is known)
+/// - this node is the host picked for this inode
+fn eligible_to_encode(ino: InodeId, inode: &Inode, self_addr: &Address) -> Option<InodeId> {
+    match &inode.entry {
+        FsEntry::File(hosts)
+            if inode.meta.size > 0
+                && inode.meta.hash.is_some()
+                && is_elected(ino, hosts, self_addr) =>
+        {
+            Some(ino)
+        }
+        _ => None,
+    }
+}
+
+/// Tells if a file was shared before its path was ignored, it isn't replicated anymore
+fn is_ignored(arbo: &Arbo, ignored: &IgnoreRules, ino: InodeId) -> WhResult<bool> {
+    Ok(ignored.is_ignored(&arbo.n_get_path_from_inode_id(ino)?.inner, false))
+}
+
+/// Copies wanted of a file: the [REDUNDANCY_XATTR] of the file or of its closest parent having one,
+/// else the last policy matching its path, else the default number
+pub fn target_redundancy(arbo: &Arbo, targets: &RedundancyTargets, ino: InodeId) -> WhResult<u64> {
+    let mut current = arbo.n_get_inode(ino)?;
+    loop {
+        let number = current
+            .xattrs
+            .get(REDUNDANCY_XATTR)
+            .and_then(|value| std::str::from_utf8(value).ok())
+            .and_then(|value| value.trim().parse::<u64>().ok());
+        if let Some(number) = number {
+            return Ok(number);
+        }
+        if current.id == current.parent {
+            break;
+        }
+        current = arbo.n_get_inode(current.parent)?;
+    }
+    Ok(targets.target(&arbo.n_get_path_from_inode_id(ino)?.inner))
+}
+
+/// Counts the files lacking copies according to their own target, see [Arbo::under_replicated_by]
+pub fn under_replicated(arbo: &Arbo, targets: &RedundancyTargets, ignored: &IgnoreRules) -> usize {
+    arbo.under_replicated_by(ignored, |ino| {
+        target_redundancy(arbo, targets, ino).unwrap_or(0)
+    })
+}
+
+/// Counts the files lacking hosts that can be healed: a copy (or enough shards) is left
+/// to rebuild them from, and the network has pods not hosting them yet
+pub fn healable(
+    arbo: &Arbo,
+    targets: &RedundancyTargets,
+    ignored: &IgnoreRules,
+    available_peers: usize,
+) -> usize {
+    arbo.iter()
+        .filter(|(ino, _)| !Arbo::is_local_only(**ino))
+        .filter(|(ino, inode)| match (&inode.entry, &inode.shards) {
+            (FsEntry::File(_), Some(layout)) => {
+                let holders = layout.holders.iter().flatten().count();
+                layout.missing() > 0 && holders >= layout.data_shards && available_peers > holders
+            }
+            (FsEntry::File(hosts), None) => {
+                !hosts.is_empty()
+                    && available_peers > hosts.len()
+                    && (hosts.len() as u64) < target_redundancy(arbo, targets, **ino).unwrap_or(0)
+            }
+            _ => false,
+        })
+        .filter(|(ino, _)| !is_ignored(arbo, ignored, **ino).unwrap_or(true))
+        .count()
+}
+
+/// Tells if a file is in a directory where files are erasure coded
+fn erasure_coded(arbo: &Arbo, erasure: Option<&ErasureConfig>, ino: InodeId) -> WhResult<bool> {
+    Ok(match erasure {
+        Some(config) => config.applies_to(&arbo.n_get_path_from_inode_id(ino)?.inner),
+        None => false,
+    })
+}
+
 async fn check_integrity(
     nw_interface: &Arc<NetworkInterface>,
     fs_interface: &Arc<FsInterface>,
-    redundancy: u64,
+    targets: &RedundancyTargets,
+    erasure: Option<&ErasureConfig>,
+    ignored: &IgnoreRules,
     peers: &Vec<Address>,
     self_addr: &Address,
 ) -> WhResult<()> {
     let available_peers = peers.len() + 1;
 
     // Applies redundancy to needed files
-    let selected_files: Vec<InodeId> =
-        Arbo::n_read_lock(&nw_interface.arbo, "redundancy: check_integrity")?
-            .iter()
-            .filter_map(|(ino, inode)| {
-                eligible_to_apply(*ino, &inode.entry, redundancy, available_peers, self_addr)
-            })
-            .collect();
+    let selected_files: Vec<InodeId> = {
+        let arbo = Arbo::n_read_lock(&nw_interface.arbo, "redundancy: check_integrity")?;
+        let mut selected = Vec::new();
+        for (ino, inode) in arbo.iter().filter(|(ino, _)| !Arbo::is_local_only(**ino)) {
+            if is_ignored(&arbo, ignored, *ino)? {
+                continue;
+            }
+            let eligible = match &inode.shards {
+                Some(layout) => eligible_to_heal(*ino, layout, available_peers, self_addr),
+                None if erasure_coded(&arbo, erasure, *ino)? => {
+                    eligible_to_encode(*ino, inode, self_addr)
+                }
+                None => eligible_to_apply(
+                    *ino,
+                    &inode.entry,
+                    target_redundancy(&arbo, targets, *ino)?,
+                    available_peers,
+                    self_addr,
+                ),
+            };
+            selected.extend(eligible);
+        }
+        selected
+    };
     let futures = selected_files
         .iter()
         .map(|ino| {
             apply_to(
                 nw_interface,
                 fs_interface,
-                redundancy,
+                targets,
+                erasure,
                 peers,
                 self_addr,
                 ino.clone(),
@@ -151,13 +374,24 @@ async fn check_integrity(
         );
         errors.iter().for_each(|e| log::error!("{e}"));
     }
+
+    let lacking = under_replicated(
+        &*Arbo::n_read_lock(&nw_interface.arbo, "redundancy: check_integrity")?,
+        targets,
+        ignored,
+    );
+    METRICS.set_under_replicated(self_addr, lacking);
+    if lacking > 0 {
+        log::warn!("Redundancy: {lacking} files have fewer hosts than wanted or lost shards");
+    }
     Ok(())
 }
 
 async fn apply_to(
     nw_interface: &Arc<NetworkInterface>,
     fs_interface: &Arc<FsInterface>,
-    redundancy: u64,
+    targets: &RedundancyTargets,
+    erasure: Option<&ErasureConfig>,
     peers: &Vec<Address>,
     self_addr: &Address,
     ino: u64,
@@ -165,108 +399,242 @@ async fn apply_to(
     if Arbo::is_local_only(ino) {
         return Ok(0);
     }
-    let file_binary = Arc::new(fs_interface.read_local_file(ino)?);
-
-    let missing_hosts_count: usize;
-    let available_hosts = peers.len() + 1; // + myself
-    let target_redundancy = if redundancy as usize > available_hosts {
-        missing_hosts_count = redundancy as usize - peers.len();
-        peers.len()
-    } else {
-        missing_hosts_count = 0;
-        (redundancy - 1) as usize
+    let (redundancy, hosts, size, written, shards, coded) = {
+        let arbo = Arbo::n_read_lock(&nw_interface.arbo, "redundancy: apply_to")?;
+        let inode = arbo.n_get_inode(ino)?;
+        match &inode.entry {
+            FsEntry::File(hosts) => (
+                target_redundancy(&arbo, targets, ino)?,
+                hosts.clone(),
+                inode.meta.size,
+                inode.meta.hash.is_some(),
+                inode.shards.clone(),
+                erasure.filter(|_| erasure_coded(&arbo, erasure, ino).unwrap_or(false)),
+            ),
+            _ => return Err(WhError::InodeIsADirectory),
+        }
     };
+    if let Some(layout) = shards {
+        return heal(
+            nw_interface,
+            fs_interface,
+            layout,
+            peers,
+            self_addr,
+            ino,
+            size,
+        )
+        .await;
+    }
+    if !hosts.contains(self_addr) {
+        return Ok(0); // only a host can send the file
+    }
+    if let Some(config) = coded.filter(|_| size > 0) {
+        if !written {
+            return Ok(0); // encoded once written, see [FsInterface::release]
+        }
+        match encode(
+            nw_interface,
+            fs_interface,
+            config,
+            peers,
+            self_addr,
+            ino,
+            size,
+        )
+        .await
+        {
+            Ok(()) => return Ok(0),
+            Err(e @ ErasureError::NotEnoughPods { ino: _ }) => {
+                log::warn!("Redundancy: {e}, replicating it instead")
+            }
+            Err(e) => {
+                log::error!("Redundancy: can't erasure code {ino}: {e}");
+                return Ok(0);
+            }
+        }
+    }
+    // the other hosts are kept, only the missing copies are sent
+    let candidates: Vec<Address> = peers
+        .iter()
+        .filter(|peer| !hosts.contains(peer))
+        .cloned()
+        .collect();
+    let candidates = place_copies(nw_interface, &candidates, &hosts, self_addr, size)?;
+    let needed = (redundancy as usize).saturating_sub(hosts.len());
 
-    let new_hosts = push_redundancy(
+    let new_hosts =
+        push_redundancy(fs_interface, &candidates, ino, needed.min(candidates.len())).await;
+
+    let missing_hosts_count = needed - new_hosts.len();
+    if !new_hosts.is_empty() {
+        nw_interface.add_inode_hosts(ino, new_hosts)?;
+    }
+    Ok(missing_hosts_count)
+}
+
+/// Replaces the copies of a file by shards spread over the peers, see [FsInterface::encode_file]
+async fn encode(
+    nw_interface: &Arc<NetworkInterface>,
+    fs_interface: &Arc<FsInterface>,
+    config: &ErasureConfig,
+    peers: &[Address],
+    self_addr: &Address,
+    ino: InodeId,
+    size: u64,
+) -> Result<(), ErasureError> {
+    let (data_shards, parity_shards) = (config.data_shards, config.parity_shards);
+    let shard_size = shard_size(size, data_shards);
+    let candidates = place_copies(
         nw_interface,
         peers,
-        ino,
-        file_binary,
-        target_redundancy,
-        self_addr.clone(),
-    )
-    .await;
-
-    nw_interface.update_hosts(ino, new_hosts)?;
-    Ok(missing_hosts_count)
+        std::slice::from_ref(self_addr),
+        self_addr,
+        shard_size,
+    )?;
+
+    // the shards are encoded on a blocking thread, which waits for them to be streamed
+    let fs_interface = Arc::clone(fs_interface);
+    tokio::task::spawn_blocking(move || {
+        fs_interface.encode_file(ino, data_shards, parity_shards, &candidates)
+    })
+    .await
+    .unwrap_or_else(|e| {
+        log::error!("redundancy_worker: error in thread pool: {e}");
+        Ok(())
+    })
 }
 
-/// start download to others concurrently
-async fn push_redundancy(
+/// Rebuilds the lost shards of a file on pods not holding one yet, returns the shards still lost
+async fn heal(
+    nw_interface: &Arc<NetworkInterface>,
+    fs_interface: &Arc<FsInterface>,
+    layout: ShardLayout,
+    peers: &[Address],
+    self_addr: &Address,
+    ino: InodeId,
+    size: u64,
+) -> WhResult<usize> {
+    let holders: Vec<Address> = layout.holders.iter().flatten().cloned().collect();
+    if layout.missing() == 0 || !is_elected(ino, &holders, self_addr) {
+        return Ok(layout.missing());
+    }
+    let candidates: Vec<Address> = peers
+        .iter()
+        .filter(|peer| !holders.contains(peer))
+        .cloned()
+        .collect();
+    let shard_size = shard_size(size, layout.data_shards);
+    let candidates = place_copies(nw_interface, &candidates, &holders, self_addr, shard_size)?;
+
+    let fs_interface = Arc::clone(fs_interface);
+    let healing = tokio::task::spawn_blocking(move || fs_interface.heal_shards(ino, &candidates));
+    match healing.await {
+        Ok(Ok(missing)) => Ok(missing),
+        Ok(Err(e)) => {
+            log::error!("Redundancy: can't rebuild the lost shards of {ino}: {e}");
+            Ok(layout.missing())
+        }
+        Err(e) => {
+            log::error!("redundancy_worker: error in thread pool: {e}");
+            Ok(layout.missing())
+        }
+    }
+}
+
+/// Orders the candidates by the placement rules of the network, see [rank_candidates]
+fn place_copies(
     nw_interface: &Arc<NetworkInterface>,
+    candidates: &[Address],
+    hosts: &[Address],
+    self_addr: &Address,
+    size: u64,
+) -> WhResult<Vec<Address>> {
+    let statuses = nw_interface.gossip.statuses()?;
+    let self_domain = LocalConfig::read_lock(&nw_interface.local_config, "place_copies")?
+        .general
+        .failure_domain
+        .clone();
+    let hosts_domains: Vec<Option<String>> = hosts
+        .iter()
+        .map(|host| {
+            if host == self_addr {
+                self_domain.clone()
+            } else {
+                statuses
+                    .get(host)
+                    .and_then(|status| status.failure_domain.clone())
+            }
+        })
+        .collect();
+    let placement = GlobalConfig::read_lock(&nw_interface.global_config, "place_copies")?
+        .redundancy
+        .placement
+        .clone();
+
+    let ranked = rank_candidates(candidates, &statuses, &hosts_domains, size, &placement);
+    if ranked.len() < candidates.len() {
+        log::warn!(
+            "Redundancy: {} peers don't have enough free space for a copy of {size} bytes",
+            candidates.len() - ranked.len()
+        );
+    }
+    Ok(ranked)
+}
+
+/// start download to others concurrently, returns the peers that received the file
+async fn push_redundancy(
+    fs_interface: &Arc<FsInterface>,
     all_peers: &Vec<String>,
     ino: InodeId,
-    file_binary: Arc<Vec<u8>>,
     target_redundancy: usize,
-    self_addr: Address,
 ) -> Vec<Address> {
-    let mut success_hosts: Vec<Address> = vec![self_addr];
-    let mut set: JoinSet<WhResult<Address>> = JoinSet::new();
+    let mut success_hosts: Vec<Address> = Vec::new();
+    let mut set: JoinSet<Result<Address, TransferError>> = JoinSet::new();
 
     for i in 0..target_redundancy {
-        let nwi_clone = Arc::clone(nw_interface);
-        let bin_clone = file_binary.clone();
+        let fsi_clone = Arc::clone(fs_interface);
         let addr = all_peers[i].clone();
 
-        set.spawn(async move { nwi_clone.send_file_redundancy(ino, bin_clone, addr).await });
+        set.spawn(async move { fsi_clone.send_file_redundancy(ino, addr).await });
     }
 
-    // check for success and try next hosts if failure
+    // check for success and try next hosts if failure,
+    // every transfer started is waited for so the hosts that got the file are all counted
     let mut current_try = target_redundancy;
-    loop {
-        match set.join_next().await {
-            None => break,
-            Some(Err(e)) => {
-                log::error!("redundancy_worker: error in thread pool: {e}");
-                break;
+    let mut retry = true;
+    while let Some(joined) = set.join_next().await {
+        match joined {
+            Ok(Ok(host)) => success_hosts.push(host),
+            Ok(Err(e @ TransferError::LocalReadFailed { io: _ })) => {
+                log::error!("Redundancy: can't read the file to send: {e}");
+                retry = false;
             }
-            Some(Ok(Ok(host))) => success_hosts.push(host),
-            Some(Ok(Err(crate::error::WhError::NetworkDied { called_from: _ }))) => {
-                log::warn!("Redundancy: NetworkDied on some host. Trying next...");
-                if current_try >= all_peers.len() {
-                    log::error!("Redundancy: Not enough answering hosts to apply redundancy.");
-                    break;
-                }
-                let nwi_clone = Arc::clone(nw_interface);
-                let bin_clone = file_binary.clone();
+            Ok(Err(e)) if retry && current_try < all_peers.len() => {
+                log::warn!("Redundancy: {e} on some host. Trying next...");
+                let fsi_clone = Arc::clone(fs_interface);
                 let addr = all_peers[current_try].clone();
 
-                set.spawn(
-                    async move { nwi_clone.send_file_redundancy(ino, bin_clone, addr).await },
-                );
+                set.spawn(async move { fsi_clone.send_file_redundancy(ino, addr).await });
                 current_try += 1;
             }
-            Some(Ok(Err(e))) => {
-                log::error!("Redundancy: unknown error when applying redundancy: {e}");
-                break;
-            }
+            Ok(Err(e)) => log::error!(
+                "Redundancy: {e} on some host, not enough answering hosts to apply redundancy."
+            ),
+            Err(e) => log::error!("redundancy_worker: error in thread pool: {e}"),
         }
     }
     success_hosts
 }
 
-impl NetworkInterface {
+impl FsInterface {
     pub async fn send_file_redundancy(
         &self,
         inode: InodeId,
-        data: Arc<Vec<u8>>,
         to: Address,
-    ) -> WhResult<Address> {
-        let (status_tx, mut status_rx) = unbounded_channel();
-
-        self.to_network_message_tx
-            .send(ToNetworkMessage::SpecificMessage(
-                (MessageContent::RedundancyFile(inode, data), Some(status_tx)),
-                vec![to.clone()],
-            ))
-            .expect("send_file: unable to update modification on the network thread");
-
-        status_rx
-            .recv()
+    ) -> Result<Address, TransferError> {
+        self.stream_file(inode, to.clone(), 0, TransferKind::Redundancy)
             .await
-            .unwrap_or(Err(WhError::NetworkDied {
-                called_from: "network_interface::send_file_redundancy".to_owned(),
-            }))
             .map(|()| to)
     }
 }
@@ -0,0 +1,182 @@
+use std::{sync::Arc, time::Instant};
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    config::{types::Config, LocalConfig},
+    error::{WhError, WhResult},
+    network::{
+        message::{Address, FromNetworkMessage, MessageContent, RedundancyMessage},
+        peer_ipc::{PeerIPC, PeerState, HEARTBEAT_INTERVAL},
+        secure::LinkSecurity,
+    },
+    pods::arbo::LOCK_TIMEOUT,
+};
+
+use super::{callbacks::Callback, network_interface::NetworkInterface};
+
+/// Change of state of a peer: address, previous state, new state
+type PeerStateChange = (Address, PeerState, PeerState);
+
+impl PeerState {
+    /// Tells if messages can still be sent to a peer in this state
+    pub fn is_reachable(self) -> bool {
+        matches!(self, PeerState::Up | PeerState::Suspect)
+    }
+}
+
+impl NetworkInterface {
+    /// Watches the links to the peers from their heartbeats, and dials the lost ones again
+    ///
+    /// Only the pod with the lowest address dials, so two pods don't open two links to each other.
+    /// The other one waits for the incoming connection.
+    pub async fn peers_supervisor(
+        network_interface: Arc<NetworkInterface>,
+        nfa_tx: UnboundedSender<FromNetworkMessage>,
+        security: LinkSecurity,
+    ) {
+        let mut ticker = tokio::time::interval(HEARTBEAT_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let self_addr =
+                match LocalConfig::read_lock(&network_interface.local_config, "peers_supervisor") {
+                    Ok(config) => config.general.address.clone(),
+                    Err(e) => {
+                        log::warn!("peers_supervisor: {e}");
+                        continue;
+                    }
+                };
+
+            let (changes, to_dial) = match network_interface.update_peer_states(&self_addr) {
+                Ok(updates) => updates,
+                Err(e) => {
+                    log::warn!("peers_supervisor: {e}");
+                    continue;
+                }
+            };
+            for (address, from, to) in changes {
+                network_interface.peer_state_changed(&address, from, to);
+            }
+            for address in to_dial {
+                tokio::spawn(Self::reconnect(
+                    network_interface.clone(),
+                    address,
+                    self_addr.clone(),
+                    nfa_tx.clone(),
+                    security.clone(),
+                ));
+            }
+        }
+    }
+
+    /// Moves every peer to the state of its link
+    ///
+    /// Returns the changes, and the lost peers to dial again now.
+    fn update_peer_states(
+        &self,
+        self_addr: &Address,
+    ) -> WhResult<(Vec<PeerStateChange>, Vec<Address>)> {
+        let mut peers = self
+            .peers
+            .try_write_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("update_peer_states"))?;
+        let now = Instant::now();
+        let mut changes = Vec::new();
+        let mut to_dial = Vec::new();
+
+        for peer in peers.iter_mut() {
+            // settled by the task dialing it
+            if peer.state == PeerState::Connecting {
+                continue;
+            }
+            let state = peer.liveness.state();
+            if state != peer.state {
+                if state == PeerState::Down {
+                    peer.thread.abort(); // a silent link may never close by itself
+                }
+                changes.push((peer.address.clone(), peer.state, state));
+                peer.state = state;
+            }
+            if peer.state == PeerState::Down
+                && *self_addr < peer.address
+                && peer.reconnection.next_try <= now
+            {
+                peer.state = PeerState::Connecting;
+                to_dial.push(peer.address.clone());
+            }
+        }
+        Ok((changes, to_dial))
+    }
+
+    /// Dials a lost peer, replacing its link on success
+    async fn reconnect(
+        network_interface: Arc<NetworkInterface>,
+        address: Address,
+        self_addr: Address,
+        nfa_tx: UnboundedSender<FromNetworkMessage>,
+        security: LinkSecurity,
+    ) {
+        let new_peer = PeerIPC::connect(address.clone(), nfa_tx, &security)
+            .await
+            // the peer replaces the link it had with this one on register
+            .filter(|peer| {
+                peer.sender
+                    .send((MessageContent::Register(self_addr), None))
+                    .is_ok()
+            });
+
+        let Some(mut peers) = network_interface.peers.try_write_for(LOCK_TIMEOUT) else {
+            log::warn!("reconnect: can't lock the peers, {address} will be dialed again");
+            return;
+        };
+        // the peer may have left for good in the meantime
+        let Some(peer) = peers.iter_mut().find(|peer| peer.address == address) else {
+            return;
+        };
+        match new_peer {
+            Some(new_peer) => {
+                *peer = new_peer;
+                drop(peers);
+                network_interface.peer_state_changed(
+                    &address,
+                    PeerState::Connecting,
+                    PeerState::Up,
+                );
+            }
+            None => {
+                peer.state = PeerState::Down;
+                peer.reconnection.failed();
+                log::debug!(
+                    "Can't reach {address} yet ({} attempts)",
+                    peer.reconnection.attempts
+                );
+            }
+        }
+    }
+
+    /// Tells the redundancy worker and the pull path about a peer coming and going
+    pub fn peer_state_changed(&self, address: &Address, from: PeerState, to: PeerState) {
+        log::info!("Peer {address}: {from:?} -> {to:?}");
+        match (from, to) {
+            (_, PeerState::Down) => {
+                // readers waiting for a file from this peer would wait forever
+                match self.transfers.take_pulls_from(address) {
+                    Ok(pulls) => pulls.into_iter().for_each(|ino| {
+                        let _ = self.callbacks.resolve(Callback::Pull(ino), false);
+                    }),
+                    Err(e) => log::warn!("Can't fail the pulls from {address}: {e}"),
+                }
+                if let Err(e) = self.forget_host(address) {
+                    log::error!("Can't re-replicate the files of {address}: {e}");
+                }
+            }
+            (PeerState::Down | PeerState::Connecting, PeerState::Up) => {
+                let _ = self.queue_redundancy(RedundancyMessage::CheckIntegrity);
+                if let Err(e) = self.request_journal(address) {
+                    log::warn!("Can't ask {address} for the changes missed: {e}");
+                }
+            }
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,657 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::RecvTimeoutError,
+    },
+    time::Duration,
+};
+
+use custom_error::custom_error;
+use parking_lot::RwLock;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+use crate::{
+    error::{WhError, WhResult},
+    network::message::{Address, FileChunk, MessageContent, ToNetworkMessage},
+    pods::{
+        arbo::{Arbo, InodeId, LOCK_TIMEOUT},
+        filesystem::{fs_interface::FsInterface, integrity::IntegrityError, quota::QuotaError},
+        whpath::WhPath,
+    },
+};
+
+use super::{
+    erasure::{shard_path, SHARD_PERMISSIONS},
+    network_interface::NetworkInterface,
+};
+
+/// Maximum size of the data carried by one [FileChunk]
+pub const CHUNK_SIZE: u64 = 256 * 1024;
+/// Maximum number of chunks sent but not yet acknowledged by the receiver
+pub const TRANSFER_WINDOW: u64 = 8;
+/// Time after which a receiver that stopped acknowledging is considered gone
+pub const TRANSFER_TIMEOUT: Duration = Duration::from_secs(30);
+/// Maximum size of the data answered to a [MessageContent::RequestRange]
+pub const MAX_RANGE_SIZE: u64 = 4 * 1024 * 1024;
+
+custom_error! {pub TransferError
+    WhError{source: WhError} = "{source}",
+    LocalReadFailed{io: std::io::Error} = "Local read failed: {io}",
+    LocalWriteFailed{io: std::io::Error} = "Local write failed: {io}",
+    UnexpectedChunk{offset: u64, expected: u64} = "Received a chunk starting at {offset} while expecting {expected}",
+    Integrity{source: IntegrityError} = "{source}",
+    Quota{source: QuotaError} = "{source}",
+    Refused = "The receiver refused the transfer",
+    Timeout = "The receiver stopped acknowledging the transfer",
+    AlreadyRunning = "The file is already being sent to the receiver",
+}
+
+/// Acknowledged offset and acceptance, as sent in a [MessageContent::ChunkAck]
+type ChunkAck = (u64, bool);
+/// Data of a [MessageContent::RangeAnswer], None if the host couldn't read it
+pub type RangeData = Option<Vec<u8>>;
+/// Readers waiting for the same range, by id
+type RangeWaiters = Vec<(u64, std::sync::mpsc::Sender<RangeData>)>;
+/// Reader waiting for a shard, by id, told whether it was received
+type ShardWaiter = (u64, std::sync::mpsc::Sender<bool>);
+/// Part of the file a transfer is for: the file itself (None) or one of its shards
+pub type TransferPart = Option<usize>;
+
+/// Why a file is streamed, decides how the receiver handles it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransferKind {
+    /// Answer to a [MessageContent::RequestFile]
+    Pull,
+    /// Copy sent to satisfy the redundancy
+    Redundancy,
+    /// Shard given to the receiver to hold, by index, see [super::erasure]
+    Shard(usize),
+    /// Answer to a [MessageContent::RequestShard], by index
+    ShardAnswer(usize),
+}
+
+impl TransferKind {
+    fn message(self, chunk: FileChunk) -> MessageContent {
+        match self {
+            TransferKind::Pull => MessageContent::PullAnswer(chunk),
+            TransferKind::Redundancy => MessageContent::RedundancyFile(chunk),
+            TransferKind::Shard(index) => MessageContent::StoreShard(index, chunk),
+            TransferKind::ShardAnswer(index) => MessageContent::ShardAnswer(index, chunk),
+        }
+    }
+
+    /// Index of the shard transferred, None for a whole file
+    pub fn shard(self) -> TransferPart {
+        match self {
+            TransferKind::Pull | TransferKind::Redundancy => None,
+            TransferKind::Shard(index) | TransferKind::ShardAnswer(index) => Some(index),
+        }
+    }
+}
+
+/// Book-keeping of the running file transfers
+#[derive(Debug, Default)]
+pub struct Transfers {
+    /// Channels forwarding the acknowledgments to the streams sending files, by (receiver, inode)
+    outgoing: RwLock<HashMap<(Address, InodeId), UnboundedSender<ChunkAck>>>,
+    /// Bytes already written for the files (or shards) being received.
+    /// Kept when a transfer is interrupted so the next one can resume from there.
+    incoming: RwLock<HashMap<(InodeId, TransferPart), u64>>,
+    /// Readers waiting for a [MessageContent::RangeAnswer], by (inode, offset)
+    ranges: RwLock<HashMap<(InodeId, u64), RangeWaiters>>,
+    /// Id of the next reader waiting in `ranges` or `shards`
+    next_reader: AtomicU64,
+    /// Host each file being pulled comes from
+    pulls: RwLock<HashMap<InodeId, Address>>,
+    /// Waiting for the end of a [MessageContent::ShardAnswer] transfer, by (pod, inode, shard index)
+    shards: RwLock<HashMap<(Address, InodeId, usize), ShardWaiter>>,
+}
+
+impl Transfers {
+    /// Fails if the file is already being sent to `to`,
+    /// as the chunks of both streams would be written over each other
+    fn register_outgoing(
+        &self,
+        to: &Address,
+        ino: InodeId,
+        ack_tx: UnboundedSender<ChunkAck>,
+    ) -> Result<(), TransferError> {
+        match self
+            .outgoing
+            .try_write_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Transfers::register_outgoing"))?
+            .entry((to.clone(), ino))
+        {
+            Entry::Occupied(_) => Err(TransferError::AlreadyRunning),
+            Entry::Vacant(entry) => {
+                entry.insert(ack_tx);
+                Ok(())
+            }
+        }
+    }
+
+    fn unregister_outgoing(&self, to: &Address, ino: InodeId) -> WhResult<()> {
+        self.outgoing
+            .try_write_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Transfers::unregister_outgoing"))?
+            .remove(&(to.clone(), ino));
+        Ok(())
+    }
+
+    /// Forwards a [MessageContent::ChunkAck] to the stream sending this file
+    pub fn acknowledge(
+        &self,
+        from: &Address,
+        ino: InodeId,
+        offset: u64,
+        accepted: bool,
+    ) -> WhResult<()> {
+        let outgoing = self
+            .outgoing
+            .try_read_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Transfers::acknowledge"))?;
+
+        match outgoing.get(&(from.clone(), ino)) {
+            Some(ack_tx) => {
+                // the stream may have given up in the meantime
+                let _ = ack_tx.send((offset, accepted));
+            }
+            None => log::debug!("Late acknowledgment from {from} for {ino}"),
+        }
+        Ok(())
+    }
+
+    /// Offset from which the reception of the file should (re)start
+    pub fn received(&self, ino: InodeId) -> WhResult<u64> {
+        self.received_part(ino, None)
+    }
+
+    fn received_part(&self, ino: InodeId, part: TransferPart) -> WhResult<u64> {
+        Ok(*self
+            .incoming
+            .try_read_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Transfers::received"))?
+            .get(&(ino, part))
+            .unwrap_or(&0))
+    }
+
+    fn set_received(&self, ino: InodeId, part: TransferPart, offset: u64) -> WhResult<()> {
+        self.incoming
+            .try_write_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Transfers::set_received"))?
+            .insert((ino, part), offset);
+        Ok(())
+    }
+
+    /// Drops the partial reception of a file, the next transfer will start from scratch
+    pub fn forget_received(&self, ino: InodeId) -> WhResult<()> {
+        self.forget_received_part(ino, None)
+    }
+
+    fn forget_received_part(&self, ino: InodeId, part: TransferPart) -> WhResult<()> {
+        self.incoming
+            .try_write_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Transfers::forget_received"))?
+            .remove(&(ino, part));
+        Ok(())
+    }
+}
+
+impl Transfers {
+    pub fn pulling_from(&self, ino: InodeId, host: &Address) -> WhResult<()> {
+        self.pulls
+            .try_write_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Transfers::pulling_from"))?
+            .insert(ino, host.clone());
+        Ok(())
+    }
+
+    pub fn pulled(&self, ino: InodeId) -> WhResult<()> {
+        self.pulls
+            .try_write_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Transfers::pulled"))?
+            .remove(&ino);
+        Ok(())
+    }
+
+    /// Number of files being sent or pulled
+    pub fn load(&self) -> WhResult<u64> {
+        let outgoing = self
+            .outgoing
+            .try_read_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Transfers::load"))?
+            .len();
+        let pulls = self
+            .pulls
+            .try_read_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Transfers::load"))?
+            .len();
+        Ok((outgoing + pulls) as u64)
+    }
+
+    /// Forgets the pulls from a lost host, returning the files they were for
+    pub fn take_pulls_from(&self, host: &Address) -> WhResult<Vec<InodeId>> {
+        let mut pulls = self
+            .pulls
+            .try_write_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Transfers::take_pulls_from"))?;
+        let lost: Vec<InodeId> = pulls
+            .iter()
+            .filter(|(_, from)| *from == host)
+            .map(|(ino, _)| *ino)
+            .collect();
+        lost.iter().for_each(|ino| {
+            pulls.remove(ino);
+        });
+        Ok(lost)
+    }
+}
+
+/// Reader waiting for a [MessageContent::RangeAnswer], unregistered once dropped
+pub struct RangeWait<'a> {
+    transfers: &'a Transfers,
+    key: (InodeId, u64),
+    id: u64,
+    answer: std::sync::mpsc::Receiver<RangeData>,
+}
+
+impl RangeWait<'_> {
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<RangeData, RecvTimeoutError> {
+        self.answer.recv_timeout(timeout)
+    }
+}
+
+impl Drop for RangeWait<'_> {
+    fn drop(&mut self) {
+        let Some(mut ranges) = self.transfers.ranges.try_write_for(LOCK_TIMEOUT) else {
+            log::error!("RangeWait: can't unregister the reader of {:?}", self.key);
+            return;
+        };
+        if let Some(waiting) = ranges.get_mut(&self.key) {
+            waiting.retain(|(id, _)| *id != self.id);
+            if waiting.is_empty() {
+                ranges.remove(&self.key);
+            }
+        }
+    }
+}
+
+impl Transfers {
+    /// Registers a reader for the answer of a range request, until the returned [RangeWait] is dropped
+    pub fn wait_range(&self, ino: InodeId, offset: u64) -> WhResult<RangeWait<'_>> {
+        let (tx, answer) = std::sync::mpsc::channel();
+        let id = self.next_reader.fetch_add(1, Ordering::Relaxed);
+        self.ranges
+            .try_write_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Transfers::wait_range"))?
+            .entry((ino, offset))
+            .or_default()
+            .push((id, tx));
+        Ok(RangeWait {
+            transfers: self,
+            key: (ino, offset),
+            id,
+            answer,
+        })
+    }
+
+    /// Gives a [MessageContent::RangeAnswer] to every reader waiting for it
+    pub fn resolve_range(&self, ino: InodeId, offset: u64, data: RangeData) -> WhResult<()> {
+        let waiting = self
+            .ranges
+            .try_write_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Transfers::resolve_range"))?
+            .remove(&(ino, offset));
+
+        match waiting {
+            // readers may have given up in the meantime
+            Some(waiting) => waiting.iter().for_each(|(_, tx)| {
+                let _ = tx.send(data.clone());
+            }),
+            None => log::debug!("Late range answer for {ino} at {offset}"),
+        }
+        Ok(())
+    }
+}
+
+/// Wait for a shard asked to a pod, unregistered once dropped
+pub struct ShardWait<'a> {
+    transfers: &'a Transfers,
+    key: (Address, InodeId, usize),
+    id: u64,
+    answer: std::sync::mpsc::Receiver<bool>,
+}
+
+impl ShardWait<'_> {
+    /// Waits until the shard is fully received, false if the pod couldn't send it
+    ///
+    /// The wait goes on as long as chunks keep coming, so large shards aren't cut short.
+    pub fn wait(&self) -> WhResult<bool> {
+        let (_, ino, index) = self.key;
+        let mut progress = 0;
+        loop {
+            match self.answer.recv_timeout(TRANSFER_TIMEOUT) {
+                Ok(received) => return Ok(received),
+                Err(RecvTimeoutError::Disconnected) => return Ok(false),
+                Err(RecvTimeoutError::Timeout) => {
+                    let now = self.transfers.received_part(ino, Some(index))?;
+                    if now <= progress {
+                        return Ok(false);
+                    }
+                    progress = now;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ShardWait<'_> {
+    fn drop(&mut self) {
+        let Some(mut shards) = self.transfers.shards.try_write_for(LOCK_TIMEOUT) else {
+            log::error!("ShardWait: can't unregister the wait for {:?}", self.key);
+            return;
+        };
+        if shards.get(&self.key).is_some_and(|(id, _)| *id == self.id) {
+            shards.remove(&self.key);
+        }
+    }
+}
+
+impl Transfers {
+    /// Registers a wait for a shard asked to `from`, until the returned [ShardWait] is dropped
+    pub fn wait_shard(
+        &self,
+        from: &Address,
+        ino: InodeId,
+        index: usize,
+    ) -> WhResult<ShardWait<'_>> {
+        let (tx, answer) = std::sync::mpsc::channel();
+        let id = self.next_reader.fetch_add(1, Ordering::Relaxed);
+        let key = (from.clone(), ino, index);
+        self.forget_received_part(ino, Some(index))?;
+        self.shards
+            .try_write_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Transfers::wait_shard"))?
+            .insert(key.clone(), (id, tx));
+        Ok(ShardWait {
+            transfers: self,
+            key,
+            id,
+            answer,
+        })
+    }
+
+    /// Tells the one waiting for a shard from `from` whether it was received
+    pub fn resolve_shard(
+        &self,
+        from: &Address,
+        ino: InodeId,
+        index: usize,
+        received: bool,
+    ) -> WhResult<()> {
+        let waiting = self
+            .shards
+            .try_write_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Transfers::resolve_shard"))?
+            .remove(&(from.clone(), ino, index));
+
+        match waiting {
+            Some((_, tx)) => {
+                let _ = tx.send(received);
+            }
+            None => log::debug!("Late answer from {from} for shard {index} of {ino}"),
+        }
+        Ok(())
+    }
+}
+
+impl NetworkInterface {
+    pub(super) fn send_to(&self, content: MessageContent, to: &Address) -> WhResult<()> {
+        self.to_network_message_tx
+            .send(ToNetworkMessage::SpecificMessage(
+                (content, None),
+                vec![to.clone()],
+            ))
+            .map_err(|_| WhError::NetworkDied {
+                called_from: "network_interface::send_to".to_owned(),
+            })
+    }
+
+    pub fn send_chunk_ack(
+        &self,
+        to: &Address,
+        ino: InodeId,
+        offset: u64,
+        accepted: bool,
+    ) -> WhResult<()> {
+        self.send_to(MessageContent::ChunkAck(ino, offset, accepted), to)
+    }
+
+    /// Asks `from` to stream the whole file to this node, without waiting for it
+    pub fn request_file(&self, ino: InodeId, from: &Address, self_addr: &Address) -> WhResult<()> {
+        self.transfers.forget_received(ino)?;
+        self.send_to(MessageContent::RequestFile(ino, self_addr.clone(), 0), from)
+    }
+
+    pub fn send_range(
+        &self,
+        ino: InodeId,
+        to: Address,
+        offset: u64,
+        data: RangeData,
+    ) -> WhResult<()> {
+        self.send_to(MessageContent::RangeAnswer(ino, offset, data), &to)
+    }
+}
+
+impl FsInterface {
+    /// Streams the local file `ino` to `to`, starting at `offset`
+    ///
+    /// The file is read from the disk one [CHUNK_SIZE] at a time and
+    /// at most [TRANSFER_WINDOW] chunks are waiting for an acknowledgment,
+    /// so neither side has to hold the whole file in memory.
+    /// Returns once the receiver acknowledged the last chunk.
+    pub async fn stream_file(
+        &self,
+        ino: InodeId,
+        to: Address,
+        offset: u64,
+        kind: TransferKind,
+    ) -> Result<(), TransferError> {
+        let (path, total_size) = {
+            let arbo = Arbo::n_read_lock(&self.arbo, "stream_file")?;
+            (
+                arbo.n_get_path_from_inode_id(ino)?,
+                arbo.n_get_inode(ino)?.meta.size,
+            )
+        };
+        self.stream(ino, path, total_size, to, offset, kind).await
+    }
+
+    /// Streams the `total_size` bytes of the local file at `path`, see [FsInterface::stream_file]
+    pub(super) async fn stream(
+        &self,
+        ino: InodeId,
+        path: WhPath,
+        total_size: u64,
+        to: Address,
+        offset: u64,
+        kind: TransferKind,
+    ) -> Result<(), TransferError> {
+        let (ack_tx, mut ack_rx) = unbounded_channel();
+        self.network_interface
+            .transfers
+            .register_outgoing(&to, ino, ack_tx)?;
+
+        let status = async {
+            let offset = offset.min(total_size);
+            let mut next = offset;
+            let mut acknowledged: Option<u64> = None;
+
+            loop {
+                // an empty chunk is still sent for empty files so the receiver creates it
+                while (next < total_size || next == offset && acknowledged.is_none())
+                    && next - acknowledged.unwrap_or(offset) < CHUNK_SIZE * TRANSFER_WINDOW
+                {
+                    let mut data = vec![0; (total_size - next).min(CHUNK_SIZE) as usize];
+                    let read = self
+                        .disk
+                        .read_file(&path, next as usize, &mut data)
+                        .map_err(|io| TransferError::LocalReadFailed { io })?;
+                    if read == 0 && !data.is_empty() {
+                        return Err(TransferError::LocalReadFailed {
+                            io: std::io::ErrorKind::UnexpectedEof.into(),
+                        });
+                    }
+                    data.truncate(read);
+
+                    let chunk = FileChunk {
+                        ino,
+                        offset: next,
+                        total_size,
+                        data,
+                    };
+                    next = chunk.end();
+                    self.network_interface.send_to(kind.message(chunk), &to)?;
+                    if next == offset {
+                        break;
+                    }
+                }
+
+                if acknowledged.is_some_and(|ack| ack >= total_size) {
+                    return Ok(());
+                }
+                match tokio::time::timeout(TRANSFER_TIMEOUT, ack_rx.recv()).await {
+                    Ok(Some((end, true))) => {
+                        acknowledged = Some(acknowledged.map_or(end, |ack| ack.max(end)))
+                    }
+                    Ok(Some((_, false))) => return Err(TransferError::Refused),
+                    Ok(None) | Err(_) => return Err(TransferError::Timeout),
+                }
+            }
+        }
+        .await;
+
+        self.network_interface
+            .transfers
+            .unregister_outgoing(&to, ino)?;
+        status
+    }
+
+    /// Writes a received [FileChunk] to the disk and acknowledges it to the sender
+    pub fn recept_chunk(
+        &self,
+        chunk: FileChunk,
+        from: Address,
+        kind: TransferKind,
+    ) -> Result<(), TransferError> {
+        let ino = chunk.ino;
+        let end = chunk.end();
+        let status = self.write_chunk(&chunk, kind);
+
+        self.network_interface
+            .send_chunk_ack(&from, ino, end, status.is_ok())?;
+        if let Err(e) = status {
+            self.release_reception(ino, kind.shard(), false);
+            let transfers = &self.network_interface.transfers;
+            transfers.forget_received_part(ino, kind.shard())?;
+            match kind {
+                TransferKind::Pull => {
+                    let _ = self
+                        .network_interface
+                        .callbacks
+                        .resolve(super::callbacks::Callback::Pull(ino), false);
+                }
+                TransferKind::Redundancy => (),
+                TransferKind::Shard(index) => {
+                    let _ = self.disk.remove_file(&shard_path(ino, index));
+                }
+                TransferKind::ShardAnswer(index) => {
+                    let _ = self.disk.remove_file(&shard_path(ino, index));
+                    transfers.resolve_shard(&from, ino, index, false)?;
+                }
+            }
+            return Err(e);
+        }
+        if chunk.is_last() {
+            self.release_reception(ino, kind.shard(), true);
+            match kind {
+                // the sender shares where the shards are once they are all stored
+                TransferKind::Shard(_) => (),
+                TransferKind::ShardAnswer(index) => self
+                    .network_interface
+                    .transfers
+                    .resolve_shard(&from, ino, index, true)?,
+                TransferKind::Pull | TransferKind::Redundancy => {
+                    self.complete_reception(ino, kind)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_chunk(&self, chunk: &FileChunk, kind: TransferKind) -> Result<(), TransferError> {
+        let part = kind.shard();
+        let (path, perms) = match part {
+            Some(index) => (shard_path(chunk.ino, index), SHARD_PERMISSIONS),
+            None => {
+                let arbo = Arbo::n_read_lock(&self.arbo, "write_chunk")?;
+                (
+                    arbo.n_get_path_from_inode_id(chunk.ino)?,
+                    arbo.n_get_inode(chunk.ino)?.meta.perm,
+                )
+            }
+        };
+
+        if chunk.offset == 0 {
+            // a full pod refuses the file, the sender tries another one.
+            // Shards answered are only kept while the file is rebuilt.
+            if !matches!(kind, TransferKind::ShardAnswer(_)) {
+                self.reserve_reception(chunk.ino, part, chunk.total_size)?;
+            }
+            if part.is_some() {
+                self.make_shards_dir();
+            }
+            self.disk
+                .new_file(&path, perms)
+                .map_err(|io| TransferError::LocalWriteFailed { io })?;
+        } else {
+            let expected = self
+                .network_interface
+                .transfers
+                .received_part(chunk.ino, part)?;
+            if chunk.offset != expected {
+                return Err(TransferError::UnexpectedChunk {
+                    offset: chunk.offset,
+                    expected,
+                });
+            }
+        }
+        self.disk
+            .write_file(&path, &chunk.data, chunk.offset as usize)
+            .map_err(|io| TransferError::LocalWriteFailed { io })?;
+
+        if chunk.is_last() {
+            self.disk
+                .set_file_size(&path, chunk.total_size as usize)
+                .map_err(|io| TransferError::LocalWriteFailed { io })?;
+            self.network_interface
+                .transfers
+                .forget_received_part(chunk.ino, part)?;
+            // a corrupted copy is dropped, the sender can retry or try another host.
+            // Shards are checked against the layout by the pods gathering them.
+            if part.is_none() {
+                if let Err(e) = self.verify_local_file(chunk.ino) {
+                    let _ = self.disk.remove_file(&path);
+                    return Err(e.into());
+                }
+            }
+        } else {
+            self.network_interface
+                .transfers
+                .set_received(chunk.ino, part, chunk.end())?;
+        }
+        Ok(())
+    }
+}
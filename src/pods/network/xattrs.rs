@@ -15,6 +15,9 @@ impl NetworkInterface {
             data.clone(),
         )?;
 
+        if Arbo::is_local_only(ino) {
+            return Ok(());
+        }
         self.to_network_message_tx
             .send(ToNetworkMessage::BroadcastMessage(
                 MessageContent::SetXAttr(ino, key, data),
@@ -36,6 +39,9 @@ impl NetworkInterface {
         Arbo::n_write_lock(&self.arbo, "network_interface::get_inode_xattr")?
             .remove_inode_xattr(ino, key.clone())?;
 
+        if Arbo::is_local_only(ino) {
+            return Ok(());
+        }
         self.to_network_message_tx
             .send(ToNetworkMessage::BroadcastMessage(
                 MessageContent::RemoveXAttr(ino, key),
@@ -1,8 +1,11 @@
 use std::fs;
-use std::{io, sync::Arc};
+use std::path::Path;
+use std::{io, sync::Arc, time::Duration};
 
-use crate::config::types::Config;
+use crate::config::types::{Config, IgnoreRules, RedundancyTargets};
 use crate::config::{GlobalConfig, LocalConfig};
+use crate::data::inspect::{PeerInspect, PodInspect};
+use crate::data::metrics::METRICS;
 use crate::data::tree_hosts::{CliHostTree, TreeLine};
 use crate::error::{WhError, WhResult};
 #[cfg(target_os = "linux")]
@@ -10,27 +13,41 @@ use crate::fuse::fuse_impl::mount_fuse;
 use crate::network::message::{
     FileSystemSerialized, FromNetworkMessage, MessageContent, ToNetworkMessage,
 };
-use crate::pods::arbo::{FsEntry, GLOBAL_CONFIG_FNAME, LOCAL_CONFIG_FNAME, LOCAL_CONFIG_INO, ROOT};
+use crate::pods::arbo::{
+    FsEntry, GLOBAL_CONFIG_FNAME, INVITES_FNAME, JOURNAL_FNAME, LOCAL_CONFIG_FNAME,
+    LOCAL_CONFIG_INO, LOCK_TIMEOUT, STATIC_KEY_FNAME,
+};
 #[cfg(target_os = "windows")]
 use crate::pods::disk_managers::dummy_disk_manager::DummyDiskManager;
 #[cfg(target_os = "linux")]
 use crate::pods::disk_managers::unix_disk_manager::UnixDiskManager;
 use crate::pods::disk_managers::DiskManager;
-use crate::pods::network::redundancy::redundancy_worker;
+use crate::pods::network::journal::{Journal, JournalFrontier};
+use crate::pods::network::redundancy::{redundancy_worker, target_redundancy, under_replicated};
+use crate::pods::network::transfer::{TransferError, TransferKind};
 #[cfg(target_os = "windows")]
 use crate::winfsp::winfsp_impl::{mount_fsp, WinfspHost};
 use custom_error::custom_error;
 #[cfg(target_os = "linux")]
 use fuser;
 use log::info;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use tokio::task::JoinHandle;
 
-use crate::network::{message::Address, peer_ipc::PeerIPC, server::Server};
+use crate::network::{
+    invite::{InviteError, InviteToken, Invites},
+    message::Address,
+    peer_ipc::PeerIPC,
+    secure::{LinkSecurity, PublicKey, SecureError, StaticKey},
+    server::Server,
+};
 
 use crate::pods::{
-    arbo::{generate_arbo, Arbo},
+    arbo::{
+        encode_arbo_file, generate_arbo, recover_serialized_arbo, Arbo, ArboDigests,
+        FIRST_LOCAL_INO,
+    },
     filesystem::fs_interface::FsInterface,
     network::network_interface::NetworkInterface,
     whpath::WhPath,
@@ -38,6 +55,9 @@ use crate::pods::{
 
 use super::arbo::{InodeId, ARBO_FILE_FNAME, ARBO_FILE_INO, GLOBAL_CONFIG_INO};
 
+/// Time given to the other pods to take the files only this one hosts when it stops
+pub const STOP_HANDOFF_TIMEOUT: Duration = Duration::from_secs(60);
+
 #[allow(dead_code)]
 #[derive(Debug)]
 pub struct Pod {
@@ -46,6 +66,9 @@ pub struct Pod {
     fs_interface: Arc<FsInterface>,
     mount_point: WhPath,
     peers: Arc<RwLock<Vec<PeerIPC>>>,
+    /// Identifies this pod on its links, see [crate::config::types::SecurityConfig::trusted_peers]
+    public_key: PublicKey,
+    invites: Arc<Invites>,
     #[cfg(target_os = "linux")]
     fuse_handle: fuser::BackgroundSession,
     #[cfg(target_os = "windows")]
@@ -53,15 +76,27 @@ pub struct Pod {
     network_airport_handle: JoinHandle<()>,
     peer_broadcast_handle: JoinHandle<()>,
     new_peer_handle: JoinHandle<()>,
+    peers_supervisor_handle: JoinHandle<()>,
+    gossip_handle: JoinHandle<()>,
+    eviction_handle: JoinHandle<()>,
+    scrub_handle: JoinHandle<()>,
     redundancy_worker_handle: JoinHandle<()>,
     pub global_config: Arc<RwLock<GlobalConfig>>,
     pub local_config: Arc<RwLock<LocalConfig>>,
 }
 
+/// What [Pod::recurse_tree] needs to tell which files lack hosts
+struct TreeContext<'a> {
+    arbo: &'a Arbo,
+    targets: &'a RedundancyTargets,
+    ignored: &'a IgnoreRules,
+}
+
 custom_error! {pub PodInfoError
     WhError{source: WhError} = "{source}",
     WrongFileType{detail: String} = "PodInfoError: wrong file type: {detail}",
     FileNotFound = "PodInfoError: file not found",
+    NotPinned = "PodInfoError: path isn't pinned",
 }
 
 pub async fn initiate_connection(
@@ -69,13 +104,19 @@ pub async fn initiate_connection(
     server_address: Address,
     tx: &UnboundedSender<FromNetworkMessage>,
     rx: &mut UnboundedReceiver<FromNetworkMessage>,
+    security: &LinkSecurity,
+    known: Option<ArboDigests>,
 ) -> Option<(FileSystemSerialized, Vec<Address>, PeerIPC, Vec<u8>)> {
     if peers_addrs.len() >= 1 {
         for first_contact in peers_addrs {
-            let first_ipc = PeerIPC::connect(first_contact.to_owned(), tx.clone()).await;
+            let first_ipc = PeerIPC::connect(first_contact.to_owned(), tx.clone(), security).await;
 
             if let Some(ipc) = first_ipc {
-                if let Err(err) = ipc.sender.send((MessageContent::RequestFs, None)) {
+                let request = match &known {
+                    Some(known) => MessageContent::RequestFsDiff(known.clone()),
+                    None => MessageContent::RequestFs,
+                };
+                if let Err(err) = ipc.sender.send((request, None)) {
                     info!(
                         "Connection with {first_contact} failed: {err}.\n
                         Trying with next know address"
@@ -129,6 +170,7 @@ fn register_to_others(peers: &Vec<PeerIPC>, self_address: &Address) -> std::io::
 custom_error! {pub PodStopError
     WhError{source: WhError} = "{source}",
     ArboSavingFailed{source: io::Error} = "PodStopError: could not write arbo to disk: {source}",
+    ConfigSavingFailed{reason: String} = "PodStopError: could not write the local configuration to disk: {reason}",
     PodNotRunning = "No pod with this name was found running.",
     FileNotReadable{file: InodeId, reason: String} = "PodStopError: could not read file from disk: ({file}) {reason}",
     FileNotSent{file: InodeId} = "PodStopError: no pod was able to receive this file before stopping: ({file})"
@@ -151,39 +193,87 @@ impl Pod {
         let (to_redundancy_tx, to_redundancy_rx) = mpsc::unbounded_channel();
 
         global_config.general.peers.retain(|x| *x != server_address);
-
+        let invalid = |e: SecureError| io::Error::new(io::ErrorKind::InvalidInput, e.to_string());
+        let security = LinkSecurity {
+            network_key: global_config.security.network_key().map_err(invalid)?,
+            static_key: StaticKey::load_or_create(Path::new(
+                &mount_point.join(STATIC_KEY_FNAME).inner,
+            ))
+            .map_err(invalid)?,
+            trusted_peers: global_config.security.trusted_peers().map_err(invalid)?,
+        };
+        log::info!(
+            "Public key of this pod: {}",
+            hex::encode(security.static_key.public)
+        );
+        if let Err(e) = security.check() {
+            log::warn!("{e}");
+        }
+        let public_key = security.static_key.public;
+        let invites = Arc::new(Invites::open(Path::new(
+            &mount_point.join(INVITES_FNAME).inner,
+        ))?);
+
+        let journal = Arc::new(Journal::open(
+            server_address.clone(),
+            Path::new(&mount_point.join(JOURNAL_FNAME).inner),
+        )?);
         let mut peers = vec![];
+        // a returning pod only asks for what changed since it left
+        let saved_arbo = recover_serialized_arbo(&mount_point);
 
-        let (arbo, next_inode, global_config_bytes) =
+        let (arbo, next_inode, global_config_bytes, frontier) =
             if let Some((fs_serialized, peers_addrs, ipc, global_config_bytes)) =
                 initiate_connection(
                     global_config.general.peers.clone(),
                     server_address.clone(),
                     &from_network_message_tx,
                     &mut from_network_message_rx,
+                    &security,
+                    saved_arbo.as_ref().map(Arbo::directory_digests),
                 )
                 .await
             {
                 // TODO use global_config ?
 
-                peers = PeerIPC::peer_startup(peers_addrs, from_network_message_tx.clone()).await;
+                peers =
+                    PeerIPC::peer_startup(peers_addrs, from_network_message_tx.clone(), &security)
+                        .await;
                 peers.push(ipc);
                 register_to_others(&peers, &server_address)?;
 
-                let mut arbo = Arbo::new();
-                arbo.overwrite_self(fs_serialized.fs_index);
+                let arbo = match saved_arbo {
+                    Some(mut arbo) if fs_serialized.incremental => {
+                        arbo.apply_diff(fs_serialized.fs_index);
+                        arbo
+                    }
+                    _ => {
+                        let mut arbo = Arbo::new();
+                        arbo.overwrite_self(fs_serialized.fs_index);
+                        arbo
+                    }
+                };
                 let next_inode = arbo
                     .iter()
+                    .filter(|(ino, _)| **ino < FIRST_LOCAL_INO)
                     .fold(Arbo::first_ino(), |acc, (ino, _)| u64::max(acc, *ino))
                     + 1;
-                (arbo, next_inode, Some(global_config_bytes))
+                (
+                    arbo,
+                    next_inode,
+                    Some(global_config_bytes),
+                    fs_serialized.journal_frontier,
+                )
             } else {
-                let (arbo, next_inode) =
-                    generate_arbo(&mount_point, &server_address).expect("unable to index folder");
-                (arbo, next_inode, None)
+                let (arbo, next_inode) = generate_arbo(
+                    &mount_point,
+                    &server_address,
+                    &global_config.general.ignore_rules(),
+                )
+                .expect("unable to index folder");
+                (arbo, next_inode, None, JournalFrontier::new())
             };
 
-        let redundancy_target = global_config.redundancy.number;
         let arbo: Arc<RwLock<Arbo>> = Arc::new(RwLock::new(arbo));
         let local = Arc::new(RwLock::new(local_config));
         let global = Arc::new(RwLock::new(global_config));
@@ -194,6 +284,7 @@ impl Pod {
             to_network_message_tx.clone(),
             to_redundancy_tx.clone(),
             next_inode,
+            journal.clone(),
             Arc::new(RwLock::new(peers)),
             local.clone(),
             global.clone(),
@@ -219,6 +310,12 @@ impl Pod {
             arbo.clone(),
         ));
 
+        // changes made here while the network was away
+        let missed = journal
+            .catch_up(&frontier)
+            .map_err(|e| io::Error::other(format!("WhError: {e}")))?;
+        NetworkInterface::replay_journal(&fs_interface, missed);
+
         // Start ability to recieve messages
         let network_airport_handle = tokio::spawn(NetworkInterface::network_airport(
             from_network_message_rx,
@@ -228,22 +325,37 @@ impl Pod {
         // Start ability to send messages
         let peer_broadcast_handle = tokio::spawn(NetworkInterface::contact_peers(
             network_interface.peers.clone(),
+            journal,
             to_network_message_rx,
         ));
+        if let Err(e) = network_interface.request_journals() {
+            log::warn!("Can't ask the peers for the changes missed: {e}");
+        }
 
         let new_peer_handle = tokio::spawn(NetworkInterface::incoming_connections_watchdog(
             server,
             from_network_message_tx.clone(),
             network_interface.peers.clone(),
+            security.clone(),
+            invites.clone(),
         ));
 
+        let peers_supervisor_handle = tokio::spawn(NetworkInterface::peers_supervisor(
+            network_interface.clone(),
+            from_network_message_tx.clone(),
+            security,
+        ));
+
+        let gossip_handle = tokio::spawn(FsInterface::gossip_worker(fs_interface.clone()));
+        let eviction_handle = tokio::spawn(FsInterface::eviction_worker(fs_interface.clone()));
+        let scrub_handle = tokio::spawn(FsInterface::scrub_worker(fs_interface.clone()));
+
         let peers = network_interface.peers.clone();
 
         let redundancy_worker_handle = tokio::spawn(redundancy_worker(
             to_redundancy_rx,
             network_interface.clone(),
             fs_interface.clone(),
-            redundancy_target,
             server_address,
         ));
 
@@ -253,6 +365,8 @@ impl Pod {
             fs_interface: fs_interface.clone(),
             mount_point: mount_point.clone(),
             peers,
+            public_key,
+            invites,
             #[cfg(target_os = "linux")]
             fuse_handle: mount_fuse(&mount_point, fs_interface.clone())?,
             #[cfg(target_os = "windows")]
@@ -260,6 +374,10 @@ impl Pod {
             network_airport_handle,
             peer_broadcast_handle,
             new_peer_handle,
+            peers_supervisor_handle,
+            gossip_handle,
+            eviction_handle,
+            scrub_handle,
             local_config: local.clone(),
             global_config: global.clone(),
             redundancy_worker_handle,
@@ -280,6 +398,9 @@ impl Pod {
             FsEntry::Directory(_) => Err(PodInfoError::WrongFileType {
                 detail: "Asked path is a directory (directories have no hosts)".to_owned(),
             }),
+            _ => Err(PodInfoError::WrongFileType {
+                detail: "Asked path isn't a regular file (only files have hosts)".to_owned(),
+            }),
         }
     }
 
@@ -290,27 +411,100 @@ impl Pod {
             .map_err(|_| PodInfoError::FileNotFound)?
             .id;
 
+        let global_config = GlobalConfig::read_lock(&self.global_config, "Pod::get_info")?;
+        let tree = TreeContext {
+            arbo: &arbo,
+            targets: &global_config.redundancy.targets(),
+            ignored: &global_config.general.ignore_rules(),
+        };
         Ok(CliHostTree {
-            lines: Self::recurse_tree(&*arbo, *ino, 0),
+            lines: Self::recurse_tree(&tree, *ino, 0),
+            redundancy: global_config.redundancy.number,
+        })
+    }
+
+    pub fn inspect(&self) -> Result<PodInspect, PodInfoError> {
+        let global_config = GlobalConfig::read_lock(&self.global_config, "Pod::inspect")?.clone();
+        let local_config = LocalConfig::read_lock(&self.local_config, "Pod::inspect")?.clone();
+        let redundancy = global_config.redundancy.number;
+        let (inodes, under_replicated) = {
+            let arbo = Arbo::n_read_lock(&self.network_interface.arbo, "Pod::inspect")?;
+            (
+                arbo.iter().count(),
+                under_replicated(
+                    &arbo,
+                    &global_config.redundancy.targets(),
+                    &global_config.general.ignore_rules(),
+                ),
+            )
+        };
+        let mut statuses = self.network_interface.gossip.statuses()?;
+        let peers = self
+            .peers
+            .try_read_for(LOCK_TIMEOUT)
+            .ok_or_else(|| WhError::would_block("Pod::inspect"))?
+            .iter()
+            .map(|peer| PeerInspect {
+                address: peer.address.clone(),
+                state: peer.state,
+                status: statuses.remove(&peer.address),
+            })
+            .collect();
+
+        Ok(PodInspect {
+            name: self.name.clone(),
+            mount_point: self.mount_point.to_string(),
+            address: local_config.general.address.clone(),
+            public_key: hex::encode(self.public_key),
+            peers,
+            status: self.fs_interface.node_status()?,
+            inodes,
+            redundancy,
+            under_replicated,
+            global_config,
+            local_config,
         })
     }
 
     /// given ino is not checked -> must exist in arbo
-    fn recurse_tree(arbo: &Arbo, ino: InodeId, indentation: u8) -> Vec<TreeLine> {
-        let entry = &arbo
-            .n_get_inode(ino)
-            .expect("recurse_tree: ino not found")
-            .entry;
+    fn recurse_tree(tree: &TreeContext, ino: InodeId, indentation: u8) -> Vec<TreeLine> {
+        let arbo = tree.arbo;
+        let inode = arbo.n_get_inode(ino).expect("recurse_tree: ino not found");
         let path = arbo
             .n_get_path_from_inode_id(ino)
-            .expect("recurse_tree: unable to get path");
-        match entry {
-            FsEntry::File(hosts) => vec![(indentation, ino, path, hosts.clone())],
+            .expect("recurse_tree: unable to get path")
+            .to_string();
+        let target = target_redundancy(arbo, tree.targets, ino).unwrap_or(0);
+        let lacking = !Arbo::is_local_only(ino)
+            && Arbo::lacks_hosts(inode, target)
+            && !tree.ignored.is_ignored(&path, false);
+        let line = |hosts: Vec<Address>| TreeLine {
+            indentation,
+            ino,
+            path: path.clone(),
+            hosts,
+            target,
+            lacking,
+        };
+        match &inode.entry {
+            // erasure coded files are listed with the pods holding their shards
+            FsEntry::File(hosts) if hosts.is_empty() && inode.shards.is_some() => {
+                let mut holders: Vec<Address> = inode
+                    .shards
+                    .iter()
+                    .flat_map(|layout| layout.holders.iter().flatten().cloned())
+                    .collect();
+                holders.sort();
+                holders.dedup();
+                vec![line(holders)]
+            }
+            FsEntry::File(hosts) => vec![line(hosts.clone())],
             FsEntry::Directory(children) => children
                 .iter()
-                .map(|c| Pod::recurse_tree(arbo, *c, indentation + 1))
-                .flatten()
+                .flat_map(|c| Pod::recurse_tree(tree, *c, indentation + 1))
                 .collect::<Vec<TreeLine>>(),
+            // links and special files have no data to host
+            _ => vec![],
         }
     }
 
@@ -322,57 +516,44 @@ impl Pod {
         possible_hosts: &Vec<Address>,
         ino: InodeId,
     ) -> Result<(), PodStopError> {
-        let file_content =
-            self.fs_interface
-                .read_local_file(ino)
-                .map_err(|e| PodStopError::FileNotReadable {
-                    file: ino,
-                    reason: e.to_string(),
-                })?;
-        let file_content = Arc::new(file_content);
-
         for host in possible_hosts {
-            let (status_tx, mut status_rx) = tokio::sync::mpsc::unbounded_channel::<WhResult<()>>();
-
-            self.network_interface
-                .to_network_message_tx
-                .send(ToNetworkMessage::SpecificMessage(
-                    (
-                        // NOTE - file_content clone is not efficient, but no way to avoid it for now
-                        MessageContent::RedundancyFile(ino, file_content.clone()),
-                        Some(status_tx.clone()),
-                    ),
-                    vec![host.clone()],
-                ))
-                .expect("to_network_message_tx closed.");
-
-            if let Some(Ok(())) = status_rx.recv().await {
-                self.network_interface
-                    .to_network_message_tx
-                    .send(ToNetworkMessage::BroadcastMessage(
-                        MessageContent::EditHosts(ino, vec![host.clone()]),
-                    ))
-                    .expect("to_network_message_tx closed.");
-                return Ok(());
+            match self
+                .fs_interface
+                .stream_file(ino, host.clone(), 0, TransferKind::Redundancy)
+                .await
+            {
+                Ok(()) => {
+                    self.network_interface
+                        .to_network_message_tx
+                        .send(ToNetworkMessage::BroadcastMessage(
+                            MessageContent::EditHosts(ino, vec![host.clone()]),
+                        ))
+                        .expect("to_network_message_tx closed.");
+                    return Ok(());
+                }
+                Err(TransferError::LocalReadFailed { io }) => {
+                    return Err(PodStopError::FileNotReadable {
+                        file: ino,
+                        reason: io.to_string(),
+                    })
+                }
+                Err(e) => log::warn!("Can't send file {ino} to {host}: {e}"),
             }
         }
         Err(PodStopError::FileNotSent { file: ino })
     }
 
-    /// Gets every file hosted by this pod only and sends them to other pods
-    async fn send_files_when_stopping(&self, arbo: &Arbo, peers: Vec<Address>) {
-        let address = if let Ok(local_conf_lock) = LocalConfig::read_lock(
-            &self.network_interface.local_config,
-            "send_files_when_stopping",
-        ) {
-            local_conf_lock.general.address.clone()
-        } else {
-            log::error!("send_files_when_stopping: can't lock local conf to get local address. No files sent.");
-            return;
-        };
-
-        futures_util::future::join_all(
-            arbo.files_hosted_only_by(&address)
+    /// Files hosted by this pod only, lost for the network if it leaves without sending them
+    ///
+    /// Only the ids are kept, as the transfers need to read the arbo.
+    fn files_hosted_only_here(&self) -> WhResult<Vec<InodeId>> {
+        let address = LocalConfig::read_lock(&self.local_config, "files_hosted_only_here")?
+            .general
+            .address
+            .clone();
+        Ok(
+            Arbo::n_read_lock(&self.network_interface.arbo, "files_hosted_only_here")?
+                .files_hosted_only_by(&address)
                 .filter_map(|inode| {
                     if inode.id == GLOBAL_CONFIG_INO
                         || inode.id == LOCAL_CONFIG_INO
@@ -383,26 +564,139 @@ impl Pod {
                         Some(inode.id)
                     }
                 })
-                .map(|id| self.send_file_to_possible_hosts(&peers, id)),
+                .collect(),
         )
-        .await
-        .iter()
-        .for_each(|e| {
-            if let Err(e) = e {
-                log::warn!("{e:?}")
+    }
+
+    /// Sends the files to other pods, returns the ones no pod took in time
+    async fn send_files_when_stopping(
+        &self,
+        files: Vec<InodeId>,
+        peers: Vec<Address>,
+    ) -> Vec<InodeId> {
+        let sent = Mutex::new(Vec::new());
+        let send_all = futures_util::future::join_all(files.iter().map(|id| async {
+            match self.send_file_to_possible_hosts(&peers, *id).await {
+                Ok(()) => sent.lock().push(*id),
+                Err(e) => log::warn!("{e:?}"),
+            }
+        }));
+
+        // the arbo is saved even if the other pods are too slow to take the files
+        if tokio::time::timeout(STOP_HANDOFF_TIMEOUT, send_all)
+            .await
+            .is_err()
+        {
+            log::warn!(
+                "Pod {}: files not handed off after {}s, stopping anyway",
+                self.name,
+                STOP_HANDOFF_TIMEOUT.as_secs()
+            );
+        }
+        let sent = sent.into_inner();
+        files.into_iter().filter(|id| !sent.contains(id)).collect()
+    }
+
+    /// Gets a local copy of every file of the network, returns the ones that couldn't be pulled
+    pub async fn pull_every_file(&self) -> Result<Vec<WhPath>, PodInfoError> {
+        let files: Vec<(InodeId, WhPath)> = {
+            let arbo = Arbo::n_read_lock(&self.network_interface.arbo, "pull_every_file")?;
+            arbo.iter()
+                .filter(|(ino, inode)| {
+                    !Arbo::is_special(**ino) && matches!(inode.entry, FsEntry::File(_))
+                })
+                .filter_map(|(ino, _)| Some((*ino, arbo.n_get_path_from_inode_id(*ino).ok()?)))
+                .collect()
+        };
+
+        let mut not_pulled = Vec::new();
+        for (ino, path) in files {
+            let fs_interface = self.fs_interface.clone();
+            // pulls block until the file is received
+            match tokio::task::spawn_blocking(move || fs_interface.fetch_local_copy(ino)).await {
+                Ok(Ok(())) => (),
+                Ok(Err(e)) => {
+                    log::warn!("Can't pull {path}: {e}");
+                    not_pulled.push(path);
+                }
+                Err(e) => {
+                    log::error!("Can't pull {path}: {e}");
+                    not_pulled.push(path);
+                }
             }
-        });
+        }
+        Ok(not_pulled)
+    }
+
+    /// Keeps a copy of `path` (a file or every file under a directory) on this pod,
+    /// returns the files that couldn't be pulled yet, they are tried again later
+    pub async fn pin(&self, path: WhPath) -> Result<Vec<WhPath>, PodInfoError> {
+        Arbo::n_read_lock(&self.network_interface.arbo, "Pod::pin")?
+            .get_inode_from_path(&path)
+            .map_err(|_| PodInfoError::FileNotFound)?;
+        LocalConfig::write_lock(&self.local_config, "Pod::pin")?
+            .storage
+            .pin(&path.inner);
+
+        let fs_interface = self.fs_interface.clone();
+        // pulls block until the file is received
+        match tokio::task::spawn_blocking(move || fs_interface.fetch_pinned_copies()).await {
+            Ok(not_pulled) => Ok(not_pulled?),
+            Err(e) => {
+                log::error!("Can't pull the pinned files: {e}");
+                Ok(vec![path])
+            }
+        }
+    }
+
+    /// Lets the copies under `path` be dropped again when the disk is short of space
+    pub fn unpin(&self, path: WhPath) -> Result<(), PodInfoError> {
+        if LocalConfig::write_lock(&self.local_config, "Pod::unpin")?
+            .storage
+            .unpin(&path.inner)
+        {
+            Ok(())
+        } else {
+            Err(PodInfoError::NotPinned)
+        }
+    }
+
+    /// Issues a single-use invitation to this network, see [crate::network::invite]
+    pub fn invite(&self) -> Result<InviteToken, InviteError> {
+        if GlobalConfig::read_lock(&self.global_config, "Pod::invite")?
+            .security
+            .network_key
+            .is_none()
+        {
+            return Err(InviteError::NoNetworkKey);
+        }
+        self.invites.issue(self.public_key)
+    }
+
+    /// Ids of the invitations not used yet
+    pub fn pending_invites(&self) -> Result<Vec<String>, InviteError> {
+        self.invites.list()
+    }
+
+    pub fn revoke_invite(&self, id: &str) -> Result<(), InviteError> {
+        self.invites.revoke(id)
     }
 
     pub async fn stop(self) -> Result<(), PodStopError> {
+        self.leave(true).await.map(|_| ())
+    }
+
+    /// Stops the pod, sending the files only it hosts to the others if `hand_off`
+    ///
+    /// Otherwise these files are removed from the network, instead of being left
+    /// there without any host. Returns the files that no other pod hosts.
+    pub async fn leave(self, hand_off: bool) -> Result<Vec<WhPath>, PodStopError> {
         // TODO
         // in actual state, all operations (request from network other than just pulling the asked files)
         // made after calling this function but before dropping the pod are undefined behavior.
 
         // drop(self.fuse_handle); // FIXME - do something like block the filesystem
 
-        let arbo = Arbo::n_read_lock(&self.network_interface.arbo, "Pod::Pod::stop(1)")?;
-
         let peers: Vec<Address> = self
             .peers
             .read()
@@ -410,19 +704,38 @@ impl Pod {
             .map(|peer| peer.address.clone())
             .collect();
 
-        self.send_files_when_stopping(&arbo, peers).await;
-        let arbo_bin = bincode::serialize(&*arbo).expect("can't serialize arbo to bincode");
+        let mut alone = self.files_hosted_only_here()?;
+        if hand_off {
+            alone = self.send_files_when_stopping(alone, peers).await;
+        }
+
+        let alone_paths: Vec<WhPath> = {
+            let arbo = Arbo::n_read_lock(&self.network_interface.arbo, "Pod::Pod::stop(1)")?;
+            alone
+                .iter()
+                .filter_map(|ino| arbo.n_get_path_from_inode_id(*ino).ok())
+                .collect()
+        };
+        if !hand_off {
+            for ino in alone {
+                if let Err(e) = self.network_interface.unregister_inode(ino) {
+                    log::warn!("Pod::leave: can't remove {ino} from the network: {e}");
+                }
+            }
+        }
+        let arbo = Arbo::n_read_lock(&self.network_interface.arbo, "Pod::Pod::stop(2)")?;
+        let arbo_bin = encode_arbo_file(&arbo);
         drop(arbo);
 
+        let address = LocalConfig::read_lock(&self.local_config, "pod::stop")?
+            .general
+            .address
+            .clone();
+        METRICS.forget_pod(&address);
         self.network_interface
             .to_network_message_tx
             .send(ToNetworkMessage::BroadcastMessage(
-                MessageContent::Disconnect(
-                    LocalConfig::read_lock(&self.local_config, "pod::stop")?
-                        .general
-                        .address
-                        .clone(),
-                ),
+                MessageContent::Disconnect(address),
             ))
             .expect("to_network_message_tx closed.");
 
@@ -432,6 +745,8 @@ impl Pod {
             fs_interface: _,
             mount_point,
             peers,
+            public_key: _,
+            invites: _,
             #[cfg(target_os = "linux")]
             fuse_handle,
             #[cfg(target_os = "windows")]
@@ -439,9 +754,13 @@ impl Pod {
             network_airport_handle,
             peer_broadcast_handle,
             new_peer_handle,
+            peers_supervisor_handle,
+            gossip_handle,
+            eviction_handle,
+            scrub_handle,
             redundancy_worker_handle: _,
             global_config: _,
-            local_config: _,
+            local_config,
         } = self;
 
         #[cfg(target_os = "linux")]
@@ -451,12 +770,22 @@ impl Pod {
 
         fs::write(&mount_point.join(&ARBO_FILE_FNAME).inner, arbo_bin)
             .map_err(|io| PodStopError::ArboSavingFailed { source: io })?;
+        // read back by `start` to bring the pod back as it was
+        LocalConfig::read_lock(&local_config, "Pod::stop")?
+            .write(mount_point.join(LOCAL_CONFIG_FNAME).inner)
+            .map_err(|e| PodStopError::ConfigSavingFailed {
+                reason: e.to_string(),
+            })?;
 
         *peers.write() = Vec::new(); // dropping PeerIPCs
         network_airport_handle.abort();
         new_peer_handle.abort();
+        peers_supervisor_handle.abort();
+        gossip_handle.abort();
+        eviction_handle.abort();
+        scrub_handle.abort();
         peer_broadcast_handle.abort();
-        Ok(())
+        Ok(alone_paths)
     }
 
     pub fn get_name(&self) -> &str {
@@ -8,6 +8,7 @@ use crate::{
             fs_interface::SimpleFileType,
             make_inode::{CreateError, MakeInodeError},
             open::OpenError,
+            quota::QuotaError,
             read::ReadError,
             rename::RenameError,
             write::WriteError,
@@ -20,11 +21,11 @@ use nt_time::FileTime;
 use windows::Win32::{
     Foundation::{
         GENERIC_EXECUTE, GENERIC_READ, GENERIC_WRITE, NTSTATUS, STATUS_ACCESS_DENIED,
-        STATUS_DATA_ERROR, STATUS_DIRECTORY_NOT_EMPTY, STATUS_FILE_IS_A_DIRECTORY,
-        STATUS_INVALID_HANDLE, STATUS_INVALID_PARAMETER, STATUS_NETWORK_UNREACHABLE,
-        STATUS_NOT_A_DIRECTORY, STATUS_OBJECT_NAME_EXISTS, STATUS_OBJECT_NAME_INVALID,
-        STATUS_OBJECT_NAME_NOT_FOUND, STATUS_OBJECT_PATH_NOT_FOUND, STATUS_PENDING,
-        STATUS_POSSIBLE_DEADLOCK,
+        STATUS_DATA_ERROR, STATUS_DIRECTORY_NOT_EMPTY, STATUS_DISK_FULL,
+        STATUS_FILE_IS_A_DIRECTORY, STATUS_INVALID_HANDLE, STATUS_INVALID_PARAMETER,
+        STATUS_NETWORK_UNREACHABLE, STATUS_NOT_A_DIRECTORY, STATUS_NOT_SAME_DEVICE,
+        STATUS_OBJECT_NAME_EXISTS, STATUS_OBJECT_NAME_INVALID, STATUS_OBJECT_NAME_NOT_FOUND,
+        STATUS_OBJECT_PATH_NOT_FOUND, STATUS_PENDING, STATUS_POSSIBLE_DEADLOCK,
     },
     Storage::FileSystem::{FILE_ATTRIBUTE_ARCHIVE, FILE_ATTRIBUTE_DIRECTORY, SYNCHRONIZE},
 };
@@ -58,6 +59,8 @@ impl From<&Metadata> for FileInfo {
         let attributes = match value.kind {
             SimpleFileType::File => FILE_ATTRIBUTE_ARCHIVE,
             SimpleFileType::Directory => FILE_ATTRIBUTE_DIRECTORY,
+            // links and special files are shown as the regular files they stand for
+            _ => FILE_ATTRIBUTE_ARCHIVE,
         };
         let now = FileTime::now();
         FileInfo {
@@ -98,6 +101,7 @@ impl From<MakeInodeError> for FspError {
             MakeInodeError::ParentNotFound => STATUS_OBJECT_NAME_NOT_FOUND.into(),
             MakeInodeError::WhError { source } => source.into(),
             MakeInodeError::ProtectedNameIsFolder => STATUS_NOT_A_DIRECTORY.into(),
+            MakeInodeError::SymlinkWithoutTarget => STATUS_INVALID_PARAMETER.into(),
         }
     }
 }
@@ -131,6 +135,12 @@ impl From<WriteError> for FspError {
             WriteError::LocalWriteFailed { io } => io.into(),
             WriteError::NoFileHandle => STATUS_INVALID_HANDLE.into(),
             WriteError::NoWritePermission => STATUS_ACCESS_DENIED.into(),
+            WriteError::Quota {
+                source: QuotaError::WhError { source },
+            } => source.into(),
+            WriteError::Quota {
+                source: QuotaError::NoSpace { .. },
+            } => STATUS_DISK_FULL.into(),
         }
     }
 }
@@ -150,6 +160,8 @@ impl From<RenameError> for FspError {
             RenameError::ProtectedNameIsFolder => STATUS_FILE_IS_A_DIRECTORY.into(),
             RenameError::ReadFailed { source } => source.into(),
             RenameError::LocalWriteFailed { io } => io.into(),
+            RenameError::SymlinkWithoutTarget => STATUS_INVALID_PARAMETER.into(),
+            RenameError::CrossesIgnoredPaths => STATUS_NOT_SAME_DEVICE.into(),
         }
     }
 }
@@ -6,7 +6,18 @@ use std::{
 use assert_fs::TempDir;
 use std::process::Stdio;
 use tokio::process::Command;
-use wormhole::network::ip::IpP;
+use wormhole::{
+    config::{
+        types::{Config, RedundancyPolicy},
+        GlobalConfig,
+    },
+    error::CliResponse,
+    network::ip::IpP,
+    pods::arbo::GLOBAL_CONFIG_FNAME,
+};
+
+/// Network key shared by the pods of the tests
+const NETWORK_KEY: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
 
 pub struct Service {
     pub instance: tokio::process::Child,
@@ -15,16 +26,21 @@ pub struct Service {
     stdin: UnixStream,
     pub ip: IpP,
     pub pods: Vec<(String, IpP, TempDir)>, // (network_name, ip, dir)
+    /// Registry of the service, kept apart from the one of the user
+    config_dir: TempDir,
 }
 
 pub struct EnvironnementManager {
     pub services: Vec<Service>,
+    /// Given to the pods created from now on
+    pub redundancy_policies: Vec<RedundancyPolicy>,
 }
 
 impl EnvironnementManager {
     pub fn new() -> Self {
         return EnvironnementManager {
             services: Vec::new(),
+            redundancy_policies: Vec::new(),
         };
     }
 
@@ -52,6 +68,40 @@ impl EnvironnementManager {
                 },
             );
 
+        let config_dir = assert_fs::TempDir::new()?;
+        let (instance, stdin) = Self::spawn_service(&ip, config_dir.path(), pipe_output)?;
+
+        self.services.push(Service {
+            instance,
+            stdin,
+            ip: ip,
+            pods: Vec::new(),
+            config_dir,
+        });
+
+        Ok(())
+    }
+
+    /// Starts a service again once it stopped, at the same address and with the same registry
+    pub async fn restart_service(
+        &mut self,
+        index: usize,
+        pipe_output: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let service = &mut self.services[index];
+        service.instance.wait().await?;
+        let (instance, stdin) =
+            Self::spawn_service(&service.ip, service.config_dir.path(), pipe_output)?;
+        service.instance = instance;
+        service.stdin = stdin;
+        Ok(())
+    }
+
+    fn spawn_service(
+        ip: &IpP,
+        config_dir: &Path,
+        pipe_output: bool,
+    ) -> Result<(tokio::process::Child, UnixStream), Box<dyn std::error::Error>> {
         let mut command = Command::new("cargo");
         command.kill_on_drop(true);
 
@@ -67,19 +117,44 @@ impl EnvironnementManager {
                 "wormholed".to_string(),
                 ip.to_string(),
             ])
+            .env("WORMHOLE_CONFIG_DIR", config_dir)
             .stdout(Self::generate_pipe(pipe_output))
             .stderr(Self::generate_pipe(pipe_output))
             .stdin(stdio)
             .spawn()?;
+        Ok((instance, write))
+    }
 
-        self.services.push(Service {
-            instance,
-            stdin: write,
-            ip: ip,
-            pods: Vec::new(),
-        });
+    /// Runs the cli against a service and reads its answer
+    pub fn cli_command(
+        &self,
+        service: usize,
+        args: &[&str],
+        pipe_output: bool,
+    ) -> Result<CliResponse, Box<dyn std::error::Error>> {
+        self.cli_command_in(service, Path::new("."), args, pipe_output)
+    }
 
-        Ok(())
+    /// Same as [Self::cli_command] with the cli run from `dir`, to give it relative paths
+    pub fn cli_command_in(
+        &self,
+        service: usize,
+        dir: &Path,
+        args: &[&str],
+        pipe_output: bool,
+    ) -> Result<CliResponse, Box<dyn std::error::Error>> {
+        let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        let output = std::process::Command::new("cargo")
+            .arg("run")
+            .arg("--manifest-path")
+            .arg(manifest)
+            .args(["--bin", "wormhole", &self.services[service].ip.to_string()])
+            .args(args)
+            .args(["--format", "json"])
+            .current_dir(dir)
+            .stderr(Self::generate_pipe(pipe_output))
+            .output()?;
+        Ok(serde_json::from_slice(&output.stdout)?)
     }
 
     /// Cli commands to create a pod
@@ -89,6 +164,7 @@ impl EnvironnementManager {
         dir_path: &Path,
         ip: &IpP,
         connect_to: Option<&IpP>,
+        redundancy_policies: &[RedundancyPolicy],
         pipe_output: bool,
     ) -> Result<std::process::ExitStatus, Box<dyn std::error::Error>> {
         let mut command = std::process::Command::new("cargo");
@@ -106,6 +182,12 @@ impl EnvironnementManager {
             .stderr(Self::generate_pipe(pipe_output))
             .spawn()?
             .wait()?;
+        // links are refused without a network key, the pods of the tests all have the same one
+        let global_path = dir_path.join(GLOBAL_CONFIG_FNAME);
+        let mut global_config = GlobalConfig::read(&global_path)?;
+        global_config.security.network_key = Some(NETWORK_KEY.to_owned());
+        global_config.redundancy.policies = redundancy_policies.to_vec();
+        global_config.write(&global_path)?;
 
         let mut command = std::process::Command::new("cargo");
         log::info!("Cli new pod command.");
@@ -177,6 +259,7 @@ impl EnvironnementManager {
                         temp_dir.path(),
                         &pod_ip,
                         conn_to.as_ref(),
+                        &self.redundancy_policies,
                         pipe_output,
                     );
 
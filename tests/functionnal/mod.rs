@@ -1,4 +1,8 @@
 pub mod environnement_manager;
+pub mod test_inspect;
+pub mod test_interrupt;
+pub mod test_remove;
+pub mod test_start_stop;
 pub mod test_sync;
 pub mod test_transfer;
 
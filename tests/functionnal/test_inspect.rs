@@ -0,0 +1,81 @@
+use crate::functionnal::append_to_path;
+
+use super::environnement_manager;
+
+pub use environnement_manager::EnvironnementManager;
+use serial_test::serial;
+use wormhole::{
+    data::inspect::PodInspect,
+    error::{CliData, CliResponse},
+    network::peer_ipc::PeerState,
+};
+
+fn report(answer: CliResponse) -> Box<PodInspect> {
+    match answer {
+        CliResponse::Success {
+            data: Some(CliData::Inspect(report)),
+            ..
+        } => report,
+        answer => panic!("Not a pod report: {answer:?}"),
+    }
+}
+
+#[serial]
+#[tokio::test]
+async fn inspect_reports_peers_configs_and_replication() {
+    println!("====== STARTING INSPECT ========");
+    let mut env = EnvironnementManager::new();
+    env.add_service(false).unwrap();
+    env.add_service(false).unwrap();
+    std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+    env.create_network("default".to_owned(), false)
+        .await
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+
+    let dir = env.services[0].pods[0].2.path().to_owned();
+    std::fs::write(append_to_path(&dir, "/foo.txt"), "Hello world!").unwrap();
+    std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+
+    let by_name = report(
+        env.cli_command(0, &["inspect", "-n", "default"], false)
+            .unwrap(),
+    );
+    assert_eq!(by_name.name, "default");
+    assert_eq!(by_name.mount_point, dir.to_string_lossy());
+    assert_eq!(by_name.address, env.services[0].pods[0].1.to_string());
+    assert_eq!(by_name.local_config.general.address, by_name.address);
+    assert_eq!(by_name.local_config.general.name, "default");
+    assert_eq!(by_name.global_config.redundancy.number, by_name.redundancy);
+    assert_eq!(by_name.redundancy, 2);
+
+    let peer = env.services[1].pods[0].1.to_string();
+    assert_eq!(by_name.peers.len(), 1, "{:?}", by_name.peers);
+    assert_eq!(by_name.peers[0].address, peer);
+    assert_eq!(by_name.peers[0].state, PeerState::Up);
+
+    // the root and foo.txt, copied to the other pod
+    assert!(by_name.inodes >= 2, "{} inodes", by_name.inodes);
+    assert!(by_name.status.hosted_files >= 1);
+    assert_eq!(by_name.under_replicated, 0);
+
+    // the cli resolves a relative path before the service looks the pod up
+    let parent = dir.parent().unwrap();
+    let folder = dir.file_name().unwrap().to_string_lossy().to_string();
+    let by_path = report(
+        env.cli_command_in(0, parent, &["inspect", "-C", &folder], false)
+            .unwrap(),
+    );
+    assert_eq!(by_path.name, "default");
+    assert_eq!(by_path.mount_point, by_name.mount_point);
+    let from_inside = report(env.cli_command_in(0, &dir, &["inspect"], false).unwrap());
+    assert_eq!(from_inside.name, "default");
+
+    match env
+        .cli_command_in(0, parent, &["inspect", "-C", "not_a_pod"], false)
+        .unwrap()
+    {
+        CliResponse::Error { code, .. } => assert_eq!(code, "pod_not_found"),
+        answer => panic!("Report of a folder that isn't a pod: {answer:?}"),
+    }
+}
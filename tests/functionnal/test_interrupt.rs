@@ -0,0 +1,49 @@
+use crate::functionnal::append_to_path;
+
+use super::environnement_manager;
+
+pub use environnement_manager::EnvironnementManager;
+use serial_test::serial;
+use wormhole::{
+    error::{CliData, CliResponse},
+    pods::arbo::ARBO_FILE_FNAME,
+};
+
+#[serial]
+#[tokio::test]
+async fn interrupt_saves_the_pods() {
+    println!("====== STARTING INTERRUPT ========");
+    let mut env = EnvironnementManager::new();
+    env.add_service(false).unwrap();
+    std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+    env.create_network("default".to_owned(), false)
+        .await
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+
+    let dir = env.services[0].pods[0].2.path().to_owned();
+    std::fs::write(append_to_path(&dir, "/foo.txt"), "Hello world!").unwrap();
+    std::thread::sleep(std::time::Duration::from_secs_f32(1.0));
+
+    let answer = env.cli_command(0, &["interrupt"], false).unwrap();
+    assert!(matches!(answer, CliResponse::Success { .. }), "{answer:?}");
+    env.restart_service(0, false).await.unwrap();
+    // the pod was unmounted, what it saved is in the folder itself
+    assert!(dir.join(ARBO_FILE_FNAME).exists(), "Arbo wasn't saved");
+    std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+
+    // the restarted service brings the pod back from its folder
+    match env
+        .cli_command(0, &["inspect", "-n", "default"], false)
+        .unwrap()
+    {
+        CliResponse::Success {
+            data: Some(CliData::Inspect(report)),
+            ..
+        } => assert!(report.inodes >= 2, "{} inodes", report.inodes),
+        answer => panic!("Pod wasn't started again: {answer:?}"),
+    }
+    let content = std::fs::read_to_string(append_to_path(&dir, "/foo.txt"))
+        .expect("File lost by the restart");
+    assert_eq!(content, "Hello world!", "File content is incorrect");
+}
@@ -0,0 +1,100 @@
+use crate::functionnal::append_to_path;
+
+use super::environnement_manager;
+
+pub use environnement_manager::EnvironnementManager;
+use serial_test::serial;
+use wormhole::{
+    config::types::RedundancyPolicy,
+    error::CliResponse,
+    pods::arbo::{
+        ARBO_FILE_FNAME, GLOBAL_CONFIG_FNAME, JOURNAL_FNAME, LOCAL_CONFIG_FNAME, STATIC_KEY_FNAME,
+    },
+};
+
+#[serial]
+#[tokio::test]
+async fn remove_clone_leaves_a_plain_folder() {
+    println!("====== STARTING REMOVE CLONE ========");
+    let mut env = EnvironnementManager::new();
+    env.add_service(false).unwrap();
+    env.add_service(false).unwrap();
+    std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+    env.create_network("default".to_owned(), false)
+        .await
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+
+    let file_path = append_to_path(&env.services[0].pods[0].2.path().to_owned(), "/foo.txt");
+    std::fs::write(&file_path, "Hello world!").unwrap();
+    std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+
+    let dir = env.services[1].pods[0].2.path().to_owned();
+    let answer = env
+        .cli_command(
+            1,
+            &["remove", "-C", &dir.to_string_lossy(), "--mode", "clone"],
+            false,
+        )
+        .unwrap();
+    assert!(matches!(answer, CliResponse::Success { .. }), "{answer:?}");
+
+    let content =
+        std::fs::read_to_string(append_to_path(&dir, "/foo.txt")).expect("File wasn't cloned");
+    assert_eq!(content, "Hello world!", "File content is incorrect");
+    for file in [
+        ARBO_FILE_FNAME,
+        JOURNAL_FNAME,
+        STATIC_KEY_FNAME,
+        LOCAL_CONFIG_FNAME,
+        GLOBAL_CONFIG_FNAME,
+    ] {
+        assert!(!dir.join(file).exists(), "{file} left in the folder");
+    }
+}
+
+#[serial]
+#[tokio::test]
+async fn remove_take_removes_its_files_from_the_network() {
+    println!("====== STARTING REMOVE TAKE ========");
+    let mut env = EnvironnementManager::new();
+    // the files under solo/ are never copied, only their creator hosts them
+    env.redundancy_policies = vec![RedundancyPolicy {
+        path: "solo/**".to_owned(),
+        number: 1,
+    }];
+    env.add_service(false).unwrap();
+    env.add_service(false).unwrap();
+    std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+    env.create_network("default".to_owned(), false)
+        .await
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+
+    let dir = env.services[0].pods[0].2.path().to_owned();
+    std::fs::create_dir(append_to_path(&dir, "/solo")).unwrap();
+    std::fs::write(append_to_path(&dir, "/solo/foo.txt"), "Hello world!").unwrap();
+    std::fs::write(append_to_path(&dir, "/bar.txt"), "Goodbye world!").unwrap();
+    std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+
+    let other = env.services[1].pods[0].2.path().to_owned();
+    assert!(append_to_path(&other, "/solo/foo.txt").exists());
+
+    let answer = env
+        .cli_command(
+            0,
+            &["remove", "-C", &dir.to_string_lossy(), "--mode", "take"],
+            false,
+        )
+        .unwrap();
+    assert!(matches!(answer, CliResponse::Success { .. }), "{answer:?}");
+    std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+
+    assert!(
+        !append_to_path(&other, "/solo/foo.txt").exists(),
+        "File only the removed pod hosted is still in the network"
+    );
+    let content = std::fs::read_to_string(append_to_path(&other, "/bar.txt"))
+        .expect("Copied file was removed from the network");
+    assert_eq!(content, "Goodbye world!", "File content is incorrect");
+}
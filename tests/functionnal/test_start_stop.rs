@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use crate::functionnal::append_to_path;
+
+use super::environnement_manager;
+
+pub use environnement_manager::EnvironnementManager;
+use serial_test::serial;
+use wormhole::{
+    error::{CliData, CliResponse},
+    pods::arbo::GLOBAL_CONFIG_FNAME,
+};
+
+fn assert_running(env: &EnvironnementManager, dir: &Path) {
+    match env
+        .cli_command(0, &["inspect", "-n", "default"], false)
+        .unwrap()
+    {
+        CliResponse::Success {
+            data: Some(CliData::Inspect(report)),
+            ..
+        } => assert!(report.inodes >= 2, "{} inodes", report.inodes),
+        answer => panic!("Pod isn't running: {answer:?}"),
+    }
+    let content = std::fs::read_to_string(append_to_path(&dir.to_owned(), "/foo.txt"))
+        .expect("File lost by the restart");
+    assert_eq!(content, "Hello world!", "File content is incorrect");
+}
+
+fn assert_error(answer: CliResponse, expected: &str) {
+    match answer {
+        CliResponse::Error { code, .. } => assert_eq!(code, expected),
+        answer => panic!("Expected a {expected} error: {answer:?}"),
+    }
+}
+
+#[serial]
+#[tokio::test]
+async fn stop_and_start_a_pod() {
+    println!("====== STARTING STOP AND START ========");
+    let mut env = EnvironnementManager::new();
+    env.add_service(false).unwrap();
+    std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+    env.create_network("default".to_owned(), false)
+        .await
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+
+    let dir = env.services[0].pods[0].2.path().to_owned();
+    let path = dir.to_string_lossy().to_string();
+    std::fs::write(append_to_path(&dir, "/foo.txt"), "Hello world!").unwrap();
+    std::thread::sleep(std::time::Duration::from_secs_f32(1.0));
+
+    for (stop, start) in [
+        (["stop", "-C", &path], ["start", "-C", &path]),
+        (["stop", "-n", "default"], ["start", "-n", "default"]),
+    ] {
+        let answer = env.cli_command(0, &stop, false).unwrap();
+        assert!(matches!(answer, CliResponse::Success { .. }), "{answer:?}");
+        assert_error(
+            env.cli_command(0, &["inspect", "-n", "default"], false)
+                .unwrap(),
+            "pod_not_found",
+        );
+
+        // brought back from the configuration and the arbo saved in its folder
+        match env.cli_command(0, &start, false).unwrap() {
+            CliResponse::Success {
+                data: Some(CliData::Pod(name)),
+                ..
+            } => assert_eq!(name, "default"),
+            answer => panic!("Pod wasn't started: {answer:?}"),
+        }
+        std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+        assert_running(&env, &dir);
+    }
+
+    // a running pod isn't started twice
+    assert!(matches!(
+        env.cli_command(0, &["start", "-C", &path], false).unwrap(),
+        CliResponse::Error { .. }
+    ));
+    assert_running(&env, &dir);
+}
+
+#[serial]
+#[tokio::test]
+async fn start_refuses_an_unreadable_global_config() {
+    println!("====== STARTING START WITH A BROKEN CONFIG ========");
+    let mut env = EnvironnementManager::new();
+    env.add_service(false).unwrap();
+    std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+    env.create_network("default".to_owned(), false)
+        .await
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_secs_f32(2.0));
+
+    let dir = env.services[0].pods[0].2.path().to_owned();
+    let path = dir.to_string_lossy().to_string();
+    let answer = env.cli_command(0, &["stop", "-C", &path], false).unwrap();
+    assert!(matches!(answer, CliResponse::Success { .. }), "{answer:?}");
+
+    std::fs::write(dir.join(GLOBAL_CONFIG_FNAME), "[redundancy\nnumber = ").unwrap();
+    assert_error(
+        env.cli_command(0, &["start", "-C", &path], false).unwrap(),
+        "invalid_config",
+    );
+    assert_error(
+        env.cli_command(0, &["inspect", "-n", "default"], false)
+            .unwrap(),
+        "pod_not_found",
+    );
+}
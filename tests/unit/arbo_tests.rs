@@ -3,9 +3,13 @@ use std::{
     collections::HashMap,
     time::{Duration, SystemTime},
 };
+use wormhole::config::types::IgnoreRules;
 use wormhole::pods::{
-    arbo::{Arbo, FsEntry, Inode, Metadata, BLOCK_SIZE, ROOT},
-    filesystem::fs_interface::SimpleFileType,
+    arbo::{
+        decode_arbo_file, encode_arbo_file, Arbo, ArboFileError, FsEntry, Inode, Metadata,
+        BLOCK_SIZE, ROOT,
+    },
+    filesystem::{conflict::VersionVector, fs_interface::SimpleFileType},
 };
 
 fn arbo_values(inode: &Inode, expected_result: Inode) {
@@ -81,14 +85,17 @@ fn test_inserting_and_retreiving_files() {
             crtime: SystemTime::now(),
             kind: SimpleFileType::File,
             perm: 0o777,
-            nlink: 0,
+            nlink: 1,
             uid: 0,
             gid: 0,
             rdev: 0,
             blksize: BLOCK_SIZE as u32,
             flags: 0,
+            hash: None,
         },
         xattrs: HashMap::new(),
+        version: VersionVector::new(),
+        shards: None,
     };
 
     let result_two = Inode {
@@ -106,15 +113,194 @@ fn test_inserting_and_retreiving_files() {
             crtime: SystemTime::now(),
             kind: SimpleFileType::File,
             perm: 0o777,
-            nlink: 0,
+            nlink: 1,
             uid: 0,
             gid: 0,
             rdev: 0,
             blksize: BLOCK_SIZE as u32,
             flags: 0,
+            hash: None,
         },
         xattrs: HashMap::new(),
+        version: VersionVector::new(),
+        shards: None,
     };
     arbo_values(&arbo.get_inode(10).unwrap(), result_one);
     arbo_values(&arbo.get_inode(11).unwrap(), result_two);
 }
+
+#[test]
+fn test_incremental_sync() {
+    let mut network = Arbo::new();
+    for (name, id, parent, entry) in [
+        ("a", 20, ROOT, FsEntry::Directory(Vec::new())),
+        ("b", 21, ROOT, FsEntry::Directory(Vec::new())),
+        ("c", 22, ROOT, FsEntry::Directory(Vec::new())),
+        ("f1", 30, 20, FsEntry::File(Vec::new())),
+        ("f2", 31, 21, FsEntry::File(Vec::new())),
+        ("f4", 33, 22, FsEntry::File(Vec::new())),
+    ] {
+        network
+            .add_inode_from_parameters(name.to_owned(), id, parent, entry, 0o644)
+            .unwrap();
+    }
+    let mut saved = network.clone();
+
+    // changes made while the saved pod was gone
+    network
+        .add_inode_from_parameters("f3".to_owned(), 32, 20, FsEntry::File(Vec::new()), 0o644)
+        .unwrap();
+    network.remove_inode(31).unwrap();
+
+    let diff = network.diff_from(&saved.directory_digests());
+    assert!(diff.contains_key(&32));
+    assert!(diff.contains_key(&21));
+    assert!(!diff.contains_key(&22), "unchanged subtrees are not sent");
+    assert!(!diff.contains_key(&33), "unchanged subtrees are not sent");
+
+    saved.apply_diff(diff);
+    assert!(saved.get_inode(32).is_ok());
+    assert!(saved.get_inode(31).is_err());
+    assert!(saved.get_inode(33).is_ok());
+    assert_eq!(saved.directory_digests(), network.directory_digests());
+    assert!(network.diff_from(&saved.directory_digests()).is_empty());
+}
+
+#[test]
+fn test_under_replicated() {
+    let mut arbo = Arbo::new();
+    let host = |n: u8| format!("10.0.0.{n}:8080");
+    arbo.add_inode_from_parameters(
+        "both".to_owned(),
+        20,
+        ROOT,
+        FsEntry::File(vec![host(1), host(2)]),
+        0o644,
+    )
+    .unwrap();
+    arbo.add_inode_from_parameters(
+        "single".to_owned(),
+        21,
+        ROOT,
+        FsEntry::File(vec![host(1)]),
+        0o644,
+    )
+    .unwrap();
+    arbo.add_inode_from_parameters(
+        "dir".to_owned(),
+        22,
+        ROOT,
+        FsEntry::Directory(vec![]),
+        0o755,
+    )
+    .unwrap();
+
+    let none = IgnoreRules::new(&[]);
+    assert_eq!(arbo.under_replicated(&none, 1), 0);
+    assert_eq!(arbo.under_replicated(&none, 2), 1);
+    assert_eq!(arbo.under_replicated(&none, 3), 2);
+    // ignored files aren't replicated anymore
+    let ignored = IgnoreRules::new(&["single".to_owned()]);
+    assert_eq!(arbo.under_replicated(&ignored, 3), 1);
+}
+
+#[test]
+fn test_hard_links() {
+    let mut arbo = Arbo::new();
+    arbo.add_inode_from_parameters("file".to_owned(), 20, ROOT, FsEntry::File(vec![]), 0o644)
+        .unwrap();
+    arbo.add_inode_from_parameters("link".to_owned(), 21, ROOT, FsEntry::HardLink(20), 0o644)
+        .unwrap();
+    arbo.add_inode_from_parameters(
+        "symlink".to_owned(),
+        22,
+        ROOT,
+        FsEntry::Symlink("file".to_owned()),
+        0o777,
+    )
+    .unwrap();
+
+    assert_eq!(arbo.n_get_inode(20).unwrap().meta.nlink, 2);
+    let link = arbo.n_get_inode(21).unwrap();
+    assert_eq!(arbo.n_resolve(link).unwrap().id, 20);
+    let symlink = arbo.n_get_inode(22).unwrap();
+    assert_eq!(symlink.meta.kind, SimpleFileType::Symlink);
+    assert_eq!(symlink.meta.size, 4);
+    assert_eq!(arbo.n_resolve(symlink).unwrap().id, 22);
+    // the links aren't saved, they are found again when the arbo is read
+    let decoded = decode_arbo_file(&encode_arbo_file(&arbo)).unwrap();
+    assert_eq!(decoded.links_to(20).len(), 1);
+    assert_eq!(decoded.links_to(20)[0].id, 21);
+
+    arbo.n_remove_inode(21).unwrap();
+    assert_eq!(arbo.n_get_inode(20).unwrap().meta.nlink, 1);
+    assert!(arbo.links_to(20).is_empty());
+}
+
+#[test]
+fn test_arbo_file_format() {
+    let mut arbo = Arbo::new();
+    let mut inode = Inode::new(
+        "file".to_owned(),
+        ROOT,
+        11,
+        FsEntry::File(vec!["10.0.0.1:8080".to_owned()]),
+        0o644,
+    );
+    inode.meta.hash = Some([1; 32]);
+    arbo.add_inode(inode).unwrap();
+
+    let decoded = decode_arbo_file(&encode_arbo_file(&arbo)).unwrap();
+    assert_eq!(
+        decoded.n_get_inode(11).unwrap(),
+        arbo.n_get_inode(11).unwrap()
+    );
+
+    let mut newer = encode_arbo_file(&arbo);
+    newer[8] += 1;
+    assert!(matches!(
+        decode_arbo_file(&newer),
+        Err(ArboFileError::UnknownFormat { .. })
+    ));
+    assert!(decode_arbo_file(b"garbage").is_err());
+}
+
+#[test]
+fn test_arbo_file_format_0() {
+    // (parent, id, name, entry, meta without hash, xattrs), as saved before the format had a version
+    let now = SystemTime::now();
+    let meta = (
+        11u64,
+        3u64,
+        1u64,
+        now,
+        now,
+        now,
+        now,
+        SimpleFileType::File,
+        0o644u16,
+        1u32,
+        0u32,
+        0u32,
+        0u32,
+        BLOCK_SIZE as u32,
+        0u32,
+    );
+    let inode = (
+        ROOT,
+        11u64,
+        "file".to_owned(),
+        FsEntry::File(vec!["10.0.0.1:8080".to_owned()]),
+        meta,
+        HashMap::<String, Vec<u8>>::new(),
+    );
+    let saved = bincode::serialize(&HashMap::from([(11u64, inode)])).unwrap();
+
+    let arbo = decode_arbo_file(&saved).unwrap();
+    let inode = arbo.n_get_inode(11).unwrap();
+    assert_eq!(inode.name, "file");
+    assert_eq!(inode.meta.size, 3);
+    assert_eq!(inode.meta.hash, None);
+    assert_eq!(inode.version, VersionVector::default());
+    assert!(inode.shards.is_none());
+}
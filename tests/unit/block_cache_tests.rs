@@ -0,0 +1,56 @@
+extern crate wormhole;
+use crate::wormhole::pods::filesystem::block_cache::{BlockCache, CACHE_BLOCK_SIZE};
+
+#[test]
+fn test_block_cache_evicts_least_recently_used() {
+    let mut cache = BlockCache::new();
+    let capacity = 2 * CACHE_BLOCK_SIZE;
+
+    cache.insert(11, 0, vec![0], capacity);
+    cache.insert(11, 1, vec![1], capacity);
+    assert_eq!(cache.get(11, 0), Some(vec![0]));
+
+    cache.insert(12, 0, vec![2], capacity);
+    assert_eq!(cache.get(11, 1), None);
+    assert_eq!(cache.get(11, 0), Some(vec![0]));
+    assert_eq!(cache.get(12, 0), Some(vec![2]));
+}
+
+#[test]
+fn test_block_cache_invalidate() {
+    let mut cache = BlockCache::new();
+    let capacity = 4 * CACHE_BLOCK_SIZE;
+
+    cache.insert(11, 0, vec![0], capacity);
+    cache.insert(11, 1, vec![1], capacity);
+    cache.insert(12, 0, vec![2], capacity);
+    cache.invalidate(11);
+
+    assert_eq!(cache.get(11, 0), None);
+    assert_eq!(cache.get(11, 1), None);
+    assert_eq!(cache.get(12, 0), Some(vec![2]));
+}
+
+#[test]
+fn test_block_cache_disabled() {
+    let mut cache = BlockCache::new();
+
+    cache.insert(11, 0, vec![0], 0);
+    assert_eq!(cache.get(11, 0), None);
+}
+
+#[test]
+fn test_block_cache_reinsert() {
+    let mut cache = BlockCache::new();
+    let capacity = 2 * CACHE_BLOCK_SIZE;
+
+    cache.insert(11, 0, vec![0], capacity);
+    cache.insert(11, 1, vec![1], capacity);
+    // replacing a block makes it the most recent one, without taking a second place
+    cache.insert(11, 0, vec![3], capacity);
+    cache.insert(12, 0, vec![2], capacity);
+
+    assert_eq!(cache.get(11, 1), None);
+    assert_eq!(cache.get(11, 0), Some(vec![3]));
+    assert_eq!(cache.get(12, 0), Some(vec![2]));
+}
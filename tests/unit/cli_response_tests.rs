@@ -0,0 +1,66 @@
+extern crate wormhole;
+use crate::wormhole::error::{CliData, CliError, CliResponse, CliSuccess};
+
+#[test]
+fn test_cli_response_json() {
+    let success = CliResponse::from(Ok(CliSuccess::WithData {
+        message: "Hosts:".to_owned(),
+        data: CliData::Hosts(vec!["10.0.0.1:8080".to_owned()]),
+    }));
+    assert_eq!(
+        serde_json::to_value(&success).unwrap(),
+        serde_json::json!({
+            "status": "success",
+            "message": "Hosts:",
+            "data": { "hosts": ["10.0.0.1:8080"] },
+        })
+    );
+
+    let error = CliResponse::from(Err(CliError::PodNotFound));
+    let json = serde_json::to_string(&error).unwrap();
+    match serde_json::from_str(&json).unwrap() {
+        CliResponse::Error { code, message } => {
+            assert_eq!(code, "pod_not_found");
+            assert_eq!(message, CliError::PodNotFound.to_string());
+        }
+        CliResponse::Success { .. } => panic!("error read back as a success"),
+    }
+}
+
+#[test]
+fn test_cli_side_error_keeps_its_code() {
+    // errors of the CLI itself are printed like the answers of the service
+    match CliResponse::from(CliError::FileConfigName {
+        name: "x.toml".to_owned(),
+    }) {
+        CliResponse::Error { code, message } => {
+            assert_eq!(code, "invalid_config_name");
+            assert!(message.contains("x.toml"));
+        }
+        CliResponse::Success { .. } => panic!("error turned into a success"),
+    }
+}
+
+#[test]
+fn test_cli_response_warnings() {
+    let success = CliResponse::from(Ok(CliSuccess::WithWarnings {
+        message: "Pod removed".to_owned(),
+        warnings: vec!["No other pod took /a.txt".to_owned()],
+    }));
+    assert_eq!(
+        serde_json::to_value(&success).unwrap(),
+        serde_json::json!({
+            "status": "success",
+            "message": "Pod removed",
+            "data": null,
+            "warnings": ["No other pod took /a.txt"],
+        })
+    );
+    assert_eq!(success.to_string(), "Pod removed\nNo other pod took /a.txt");
+
+    // answers without warnings read the same as before
+    match serde_json::from_str(r#"{"status":"success","message":"ok","data":null}"#).unwrap() {
+        CliResponse::Success { warnings, .. } => assert!(warnings.is_empty()),
+        CliResponse::Error { .. } => panic!("success read back as an error"),
+    }
+}
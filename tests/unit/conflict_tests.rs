@@ -0,0 +1,36 @@
+extern crate wormhole;
+use crate::wormhole::pods::filesystem::conflict::{conflict_name, VersionOrdering, VersionVector};
+
+#[test]
+fn test_version_vector_ordering() {
+    let a = "10.0.0.1:8080".to_owned();
+    let b = "10.0.0.2:8080".to_owned();
+    let mut base = VersionVector::new();
+    base.increment(&a);
+
+    let mut written_on_a = base.clone();
+    written_on_a.increment(&a);
+    let mut written_on_b = base.clone();
+    written_on_b.increment(&b);
+
+    assert_eq!(base.compare(&base.clone()), VersionOrdering::Equal);
+    assert_eq!(base.compare(&written_on_a), VersionOrdering::Older);
+    assert_eq!(written_on_a.compare(&base), VersionOrdering::Newer);
+    assert_eq!(
+        written_on_a.compare(&written_on_b),
+        VersionOrdering::Concurrent
+    );
+
+    let mut merged = written_on_a.clone();
+    merged.merge(&written_on_b);
+    assert_eq!(written_on_a.compare(&merged), VersionOrdering::Older);
+    assert_eq!(written_on_b.compare(&merged), VersionOrdering::Older);
+}
+
+#[test]
+fn test_conflict_name() {
+    assert_eq!(
+        conflict_name("notes.txt", &"10.0.0.1:8080".to_owned()),
+        "notes.txt.conflict-10.0.0.1_8080"
+    );
+}
@@ -0,0 +1,156 @@
+extern crate wormhole;
+
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use tokio::sync::mpsc::unbounded_channel;
+
+use crate::wormhole::{
+    config::{types::ErasureConfig, GlobalConfig, LocalConfig},
+    pods::{
+        arbo::{Arbo, FsEntry, Inode, InodeId, ROOT},
+        disk_managers::{dummy_disk_manager::DummyDiskManager, DiskManager},
+        filesystem::fs_interface::FsInterface,
+        network::{
+            erasure::{decode, encode, reconstruct, shard_size, stripes, ErasureError, Stripe},
+            journal::Journal,
+            network_interface::NetworkInterface,
+            transfer::CHUNK_SIZE,
+        },
+        whpath::WhPath,
+    },
+};
+
+#[test]
+fn test_erasure_rebuild_from_any_shards() {
+    let data: Vec<u8> = (0..1000u32).map(|i| (i * 7 % 251) as u8).collect();
+    let shards = encode(&data, 4, 2).unwrap();
+    assert_eq!(shards.len(), 6);
+
+    // any 4 of the 6 shards are enough
+    for lost in [(0, 1), (2, 5), (4, 5)] {
+        let mut partial: Vec<Option<Vec<u8>>> = shards.iter().cloned().map(Some).collect();
+        partial[lost.0] = None;
+        partial[lost.1] = None;
+        assert_eq!(
+            decode(partial.clone(), 4, 2, data.len() as u64).unwrap(),
+            data
+        );
+
+        reconstruct(&mut partial, 4, 2).unwrap();
+        assert_eq!(
+            partial,
+            shards.iter().cloned().map(Some).collect::<Vec<_>>()
+        );
+    }
+
+    let mut too_few: Vec<Option<Vec<u8>>> = shards.into_iter().map(Some).collect();
+    too_few[0] = None;
+    too_few[1] = None;
+    too_few[2] = None;
+    assert!(decode(too_few, 4, 2, data.len() as u64).is_err());
+}
+
+#[test]
+fn test_erasure_stripes() {
+    // shards larger than a chunk are encoded a stripe at a time
+    let size = CHUNK_SIZE * 5 + 3;
+    let data: Vec<u8> = (0..size).map(|i| (i % 253) as u8).collect();
+    assert_eq!(shard_size(size, 2), CHUNK_SIZE * 5 / 2 + 2);
+    assert_eq!(
+        stripes(shard_size(size, 2)).collect::<Vec<_>>(),
+        vec![
+            Stripe {
+                offset: 0,
+                len: CHUNK_SIZE as usize
+            },
+            Stripe {
+                offset: CHUNK_SIZE,
+                len: CHUNK_SIZE as usize
+            },
+            Stripe {
+                offset: CHUNK_SIZE * 2,
+                len: (CHUNK_SIZE / 2 + 2) as usize
+            },
+        ]
+    );
+
+    let shards = encode(&data, 2, 1).unwrap();
+    assert!(shards
+        .iter()
+        .all(|shard| shard.len() as u64 == shard_size(size, 2)));
+    let mut partial: Vec<Option<Vec<u8>>> = shards.iter().cloned().map(Some).collect();
+    partial[0] = None;
+    assert_eq!(decode(partial.clone(), 2, 1, size).unwrap(), data);
+    reconstruct(&mut partial, 2, 1).unwrap();
+    assert_eq!(partial[0].as_ref(), Some(&shards[0]));
+
+    // a layout needs data and parity shards
+    assert!(encode(&data, 0, 0).is_err());
+}
+
+#[test]
+fn test_erasure_config_paths() {
+    let mut config = ErasureConfig {
+        data_shards: 2,
+        parity_shards: 1,
+        paths: vec![],
+    };
+    assert!(config.applies_to("/any/file"));
+
+    config.paths = vec!["archives/".to_owned(), "/media/photos".to_owned()];
+    assert!(config.applies_to("/archives/2024.tar"));
+    assert!(config.applies_to("/media/photos/a/b.jpg"));
+    assert!(!config.applies_to("/media/photos-old/b.jpg"));
+    assert!(!config.applies_to("/src/main.rs"));
+}
+
+const SELF: &str = "10.0.0.1:8080";
+const OTHER: &str = "10.0.0.2:8080";
+const FILE: InodeId = 11;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_encode_file_from_a_worker() {
+    let data = vec![42; 100];
+    let mut arbo = Arbo::new();
+    let mut inode = Inode::new(
+        "file.bin".to_owned(),
+        ROOT,
+        FILE,
+        FsEntry::File(vec![SELF.to_owned()]),
+        0o644,
+    );
+    inode.meta.size = data.len() as u64;
+    arbo.add_inode(inode).unwrap();
+    let path = WhPath::from("/file.bin");
+    let disk = DummyDiskManager::new(&WhPath::from("/tmp/wormhole")).unwrap();
+    disk.new_file(&path, 0o644).unwrap();
+    disk.write_file(&path, &data, 0).unwrap();
+
+    let mut local_config = LocalConfig::default();
+    local_config.general.address = SELF.to_owned();
+    // without a network, the shard sent to the only candidate fails right away
+    let (network_tx, _) = unbounded_channel();
+    let (redundancy_tx, _redundancy) = unbounded_channel();
+    let arbo = Arc::new(RwLock::new(arbo));
+    let network_interface = NetworkInterface::new(
+        arbo.clone(),
+        WhPath::from("/tmp/wormhole"),
+        network_tx,
+        redundancy_tx,
+        Arbo::first_ino() + 1,
+        Arc::new(Journal::new(SELF.to_owned())),
+        Arc::new(RwLock::new(Vec::new())),
+        Arc::new(RwLock::new(local_config)),
+        Arc::new(RwLock::new(GlobalConfig::default())),
+    );
+    let fs = FsInterface::new(Arc::new(network_interface), Box::new(disk), arbo);
+
+    assert!(matches!(
+        fs.encode_file(FILE, 1, 1, &[OTHER.to_owned()]),
+        Err(ErasureError::NotEnoughPods { ino: FILE })
+    ));
+    let mut kept = vec![0; data.len()];
+    assert_eq!(fs.disk.read_file(&path, 0, &mut kept).unwrap(), data.len());
+    assert_eq!(kept, data);
+}
@@ -0,0 +1,79 @@
+extern crate wormhole;
+
+use std::time::{Duration, SystemTime};
+
+use crate::wormhole::{
+    config::types::StorageLocalConfig,
+    pods::filesystem::eviction::{may_drop, pick_evictions, quota_min_free, Evictable},
+};
+
+#[test]
+fn test_pick_evictions() {
+    let now = SystemTime::now();
+    let copy = |ino, size, age| Evictable {
+        ino,
+        size,
+        last_access: now - Duration::from_secs(age),
+    };
+    let copies = vec![
+        copy(2, 10, 5),
+        copy(3, 10, 50),
+        copy(4, 10, 500),
+        copy(5, 10, 0),
+    ];
+
+    assert_eq!(pick_evictions(copies.clone(), 0), Vec::<u64>::new());
+    assert_eq!(pick_evictions(copies.clone(), 15), vec![4, 3]);
+    assert_eq!(pick_evictions(copies, 100), vec![4, 3, 2, 5]);
+}
+
+#[test]
+fn test_may_drop() {
+    let hosts: Vec<String> = vec!["a:1".into(), "b:1".into(), "c:1".into()];
+
+    // exactly one of the three hosts keeps the file when one copy is wanted
+    let droppers = |ino, target| {
+        hosts
+            .iter()
+            .filter(|host| may_drop(ino, &hosts, host, target, &hosts))
+            .count()
+    };
+    assert_eq!(droppers(7, 0), 2);
+    assert_eq!(droppers(7, 1), 2);
+    assert_eq!(droppers(7, 2), 1);
+    assert_eq!(droppers(7, 3), 0);
+    assert!(!may_drop(7, &hosts, &"d:1".to_owned(), 1, &hosts));
+    assert!(!may_drop(7, &hosts[..1], &hosts[0], 0, &hosts));
+    // the other hosts aren't heard from, this copy may be the last one reachable
+    assert_eq!(
+        hosts
+            .iter()
+            .filter(|host| may_drop(7, &hosts, host, 1, &[]))
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn test_quota_min_free() {
+    assert_eq!(quota_min_free(1024, 100_000), 1024);
+    // a quota smaller than the space wanted free keeps a tenth of it free
+    assert_eq!(quota_min_free(1024, 500), 50);
+    assert_eq!(quota_min_free(0, 500), 0);
+}
+
+#[test]
+fn test_pinned_paths() {
+    let mut storage = StorageLocalConfig::default();
+    assert!(storage.pin("./contracts/"));
+    assert!(!storage.pin("/contracts"));
+    assert!(storage.pin("notes.md"));
+
+    assert!(storage.is_pinned("/contracts/2024/lease.pdf"));
+    assert!(storage.is_pinned("./notes.md"));
+    assert!(!storage.is_pinned("/contracts-old/lease.pdf"));
+
+    assert!(storage.unpin("/contracts"));
+    assert!(!storage.unpin("/contracts"));
+    assert!(!storage.is_pinned("/contracts/2024/lease.pdf"));
+}
@@ -0,0 +1,40 @@
+extern crate wormhole;
+
+use crate::wormhole::pods::network::gossip::{Gossip, GossipEntry, NodeStatus};
+
+fn entry(address: &str, started: u64, heartbeat: u64, load: u64) -> GossipEntry {
+    GossipEntry {
+        address: address.to_owned(),
+        started,
+        heartbeat,
+        status: NodeStatus {
+            load,
+            ..Default::default()
+        },
+    }
+}
+
+#[test]
+fn test_gossip_keeps_newest_entries() {
+    let gossip = Gossip::default();
+
+    assert_eq!(
+        gossip
+            .merge(vec![entry("a", 100, 5, 1), entry("b", 100, 2, 1)])
+            .unwrap(),
+        2
+    );
+    // older heartbeat of "a" is ignored, newer one of "b" is kept
+    assert_eq!(
+        gossip
+            .merge(vec![entry("a", 100, 4, 2), entry("b", 100, 3, 2)])
+            .unwrap(),
+        1
+    );
+    // "a" restarted, its heartbeats count from zero again
+    assert_eq!(gossip.merge(vec![entry("a", 200, 1, 3)]).unwrap(), 1);
+
+    let statuses = gossip.statuses().unwrap();
+    assert_eq!(statuses["a"].load, 3);
+    assert_eq!(statuses["b"].load, 2);
+}
@@ -0,0 +1,160 @@
+extern crate wormhole;
+
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+use crate::wormhole::{
+    config::{types::IgnoreRules, GlobalConfig, LocalConfig},
+    network::message::{MessageContent, RedundancyMessage, ToNetworkMessage},
+    pods::{
+        arbo::{Arbo, FsEntry, Inode, InodeId, FIRST_LOCAL_INO, ROOT},
+        disk_managers::{dummy_disk_manager::DummyDiskManager, DiskManager},
+        filesystem::{fs_interface::FsInterface, rename::RenameError},
+        network::{journal::Journal, network_interface::NetworkInterface},
+        whpath::WhPath,
+    },
+};
+
+#[test]
+fn test_ignore_paths() {
+    let rules = IgnoreRules::new(&[
+        "target/".to_owned(),
+        "node_modules".to_owned(),
+        "*.log".to_owned(),
+        "!keep.log".to_owned(),
+        "/build".to_owned(),
+    ]);
+
+    assert!(rules.is_ignored("/target", true));
+    assert!(rules.is_ignored("/target/debug/wormhole", false));
+    assert!(rules.is_ignored("/crate/target/release", true));
+    assert!(!rules.is_ignored("/target", false));
+    assert!(rules.is_ignored("/web/node_modules/left-pad/index.js", false));
+    assert!(rules.is_ignored("/logs/service.log", false));
+    assert!(!rules.is_ignored("/logs/keep.log", false));
+    assert!(rules.is_ignored("/build/out.o", false));
+    assert!(!rules.is_ignored("/src/build/mod.rs", false));
+    assert!(!rules.is_ignored("/", true));
+
+    assert!(Arbo::is_local_only(FIRST_LOCAL_INO));
+    assert!(!Arbo::is_local_only(FIRST_LOCAL_INO - 1));
+    assert_eq!(Arbo::new().next_local_ino(), FIRST_LOCAL_INO);
+}
+
+const SELF: &str = "10.0.0.1:8080";
+const FILE: InodeId = 11;
+const IGNORED: InodeId = FIRST_LOCAL_INO;
+const LOCAL_FILE: InodeId = FIRST_LOCAL_INO + 1;
+
+struct TestPod {
+    fs: FsInterface,
+    outbox: UnboundedReceiver<ToNetworkMessage>,
+    _redundancy: UnboundedReceiver<RedundancyMessage>,
+}
+
+/// Pod with a shared `/file.bin` and an ignored `/ignored/local.txt`
+fn pod() -> TestPod {
+    let mut arbo = Arbo::new();
+    let disk = DummyDiskManager::new(&WhPath::from("/tmp/wormhole")).unwrap();
+    for (name, parent, ino, entry) in [
+        ("file.bin", ROOT, FILE, FsEntry::File(vec![SELF.to_owned()])),
+        ("ignored", ROOT, IGNORED, FsEntry::Directory(vec![])),
+        (
+            "local.txt",
+            IGNORED,
+            LOCAL_FILE,
+            FsEntry::File(vec![SELF.to_owned()]),
+        ),
+    ] {
+        let is_dir = matches!(entry, FsEntry::Directory(_));
+        arbo.add_inode(Inode::new(name.to_owned(), parent, ino, entry, 0o644))
+            .unwrap();
+        let path = arbo.n_get_path_from_inode_id(ino).unwrap();
+        match is_dir {
+            true => disk.new_dir(&path, 0o755).unwrap(),
+            false => disk.new_file(&path, 0o644).unwrap(),
+        }
+    }
+
+    let mut local_config = LocalConfig::default();
+    local_config.general.address = SELF.to_owned();
+    let mut global_config = GlobalConfig::default();
+    global_config.general.ignore_paths = vec!["/ignored".to_owned(), "*.log".to_owned()];
+    let (network_tx, outbox) = unbounded_channel();
+    let (redundancy_tx, redundancy) = unbounded_channel();
+    let arbo = Arc::new(RwLock::new(arbo));
+    let network_interface = NetworkInterface::new(
+        arbo.clone(),
+        WhPath::from("/tmp/wormhole"),
+        network_tx,
+        redundancy_tx,
+        Arbo::first_ino() + 1,
+        Arc::new(Journal::new(SELF.to_owned())),
+        Arc::new(RwLock::new(Vec::new())),
+        Arc::new(RwLock::new(local_config)),
+        Arc::new(RwLock::new(global_config)),
+    );
+    TestPod {
+        fs: FsInterface::new(Arc::new(network_interface), Box::new(disk), arbo),
+        outbox,
+        _redundancy: redundancy,
+    }
+}
+
+#[test]
+fn test_rename_into_ignored_paths() {
+    let mut pod = pod();
+    let name = "file.bin".to_owned();
+
+    assert!(matches!(
+        pod.fs.rename(ROOT, IGNORED, &name, &name, false),
+        Err(RenameError::CrossesIgnoredPaths)
+    ));
+    // an ignored name is as local as an ignored folder
+    assert!(matches!(
+        pod.fs
+            .rename(ROOT, ROOT, &name, &"file.log".to_owned(), false),
+        Err(RenameError::CrossesIgnoredPaths)
+    ));
+    assert_eq!(pod.fs.arbo.read().n_get_inode(FILE).unwrap().parent, ROOT);
+    assert!(pod.outbox.try_recv().is_err());
+
+    // moves between shared paths still reach the network
+    pod.fs
+        .rename(ROOT, ROOT, &name, &"moved.bin".to_owned(), false)
+        .unwrap();
+    assert!(matches!(
+        pod.outbox.try_recv(),
+        Ok(ToNetworkMessage::BroadcastMessage(MessageContent::Rename(
+            ..
+        )))
+    ));
+}
+
+#[test]
+fn test_rename_out_of_ignored_paths() {
+    let mut pod = pod();
+    let name = "local.txt".to_owned();
+
+    assert!(matches!(
+        pod.fs.rename(IGNORED, ROOT, &name, &name, false),
+        Err(RenameError::CrossesIgnoredPaths)
+    ));
+    assert_eq!(
+        pod.fs.arbo.read().n_get_inode(LOCAL_FILE).unwrap().parent,
+        IGNORED
+    );
+    assert!(pod.outbox.try_recv().is_err());
+
+    // moves between ignored paths stay on this pod
+    pod.fs
+        .rename(IGNORED, ROOT, &name, &"local.log".to_owned(), false)
+        .unwrap();
+    assert_eq!(
+        pod.fs.arbo.read().n_get_inode(LOCAL_FILE).unwrap().parent,
+        ROOT
+    );
+    assert!(pod.outbox.try_recv().is_err());
+}
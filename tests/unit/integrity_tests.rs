@@ -0,0 +1,113 @@
+extern crate wormhole;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+use crate::wormhole::{
+    config::{GlobalConfig, LocalConfig},
+    network::message::{MessageContent, ToNetworkMessage},
+    pods::{
+        arbo::{Arbo, FsEntry, Inode, InodeId, ROOT},
+        disk_managers::{dummy_disk_manager::DummyDiskManager, DiskManager},
+        filesystem::{fs_interface::FsInterface, integrity::hash_content},
+        network::{journal::Journal, network_interface::NetworkInterface},
+        whpath::WhPath,
+    },
+};
+
+#[test]
+fn test_hash_content() {
+    assert_eq!(
+        hex::encode(hash_content(b"abc")),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_ne!(hash_content(b"abc"), hash_content(b"abd"));
+}
+
+const SELF: &str = "10.0.0.1:8080";
+const OTHER: &str = "10.0.0.2:8080";
+const FILE: InodeId = 11;
+
+/// Pod hosting [FILE] along with [OTHER], with `content` on its disk while the hash of `original` is shared
+fn pod(original: &[u8], content: &[u8]) -> (FsInterface, UnboundedReceiver<ToNetworkMessage>) {
+    let mut arbo = Arbo::new();
+    let mut inode = Inode::new(
+        "file.bin".to_owned(),
+        ROOT,
+        FILE,
+        FsEntry::File(vec![SELF.to_owned(), OTHER.to_owned()]),
+        0o644,
+    );
+    inode.meta.size = content.len() as u64;
+    inode.meta.hash = Some(hash_content(original));
+    arbo.add_inode(inode).unwrap();
+    let path = arbo.n_get_path_from_inode_id(FILE).unwrap();
+
+    let disk = DummyDiskManager::new(&WhPath::from("/tmp/wormhole")).unwrap();
+    disk.new_file(&path, 0o644).unwrap();
+    disk.write_file(&path, content, 0).unwrap();
+
+    let mut local_config = LocalConfig::default();
+    local_config.general.address = SELF.to_owned();
+    let (network_tx, outbox) = unbounded_channel();
+    let (redundancy_tx, _) = unbounded_channel();
+    let arbo = Arc::new(RwLock::new(arbo));
+    let network_interface = NetworkInterface::new(
+        arbo.clone(),
+        WhPath::from("/tmp/wormhole"),
+        network_tx,
+        redundancy_tx,
+        Arbo::first_ino() + 1,
+        Arc::new(Journal::new(SELF.to_owned())),
+        Arc::new(RwLock::new(Vec::new())),
+        Arc::new(RwLock::new(local_config)),
+        Arc::new(RwLock::new(GlobalConfig::default())),
+    );
+    (
+        FsInterface::new(Arc::new(network_interface), Box::new(disk), arbo),
+        outbox,
+    )
+}
+
+fn hosts(fs: &FsInterface) -> Vec<String> {
+    match &fs.arbo.read().n_get_inode(FILE).unwrap().entry {
+        FsEntry::File(hosts) => hosts.clone(),
+        _ => panic!("not a file"),
+    }
+}
+
+#[test]
+fn test_scrub_keeps_sane_copy() {
+    let (fs, mut outbox) = pod(b"sane content", b"sane content");
+
+    assert_eq!(fs.scrub(u64::MAX).unwrap(), 0);
+    assert_eq!(hosts(&fs), vec![SELF.to_owned(), OTHER.to_owned()]);
+    assert!(outbox.try_recv().is_err());
+    assert!(fs
+        .scrubbed
+        .last(FILE, &hash_content(b"sane content"))
+        .is_some());
+}
+
+#[test]
+fn test_scrub_drops_corrupted_copy() {
+    let (fs, mut outbox) = pod(b"sane content", b"sane c0ntent");
+
+    assert_eq!(fs.scrub(u64::MAX).unwrap(), 1);
+    assert_eq!(hosts(&fs), vec![OTHER.to_owned()]);
+    // the copy is pulled again from the other host
+    let mut pulled = false;
+    while let Ok(message) = outbox.try_recv() {
+        if let ToNetworkMessage::SpecificMessage(
+            (MessageContent::RequestFile(FILE, to, _), _),
+            from,
+        ) = message
+        {
+            assert_eq!(to, SELF);
+            assert_eq!(from, vec![OTHER.to_owned()]);
+            pulled = true;
+        }
+    }
+    assert!(pulled);
+}
@@ -0,0 +1,84 @@
+extern crate wormhole;
+use crate::wormhole::{
+    network::message::MessageContent,
+    pods::network::journal::{EntryStatus, Journal, JournalEntry},
+};
+
+fn entry(origin: &str, seq: u64) -> JournalEntry {
+    JournalEntry {
+        origin: origin.to_owned(),
+        seq,
+        content: Box::new(MessageContent::Remove(42)),
+    }
+}
+
+#[test]
+fn test_journal_accept_in_order() {
+    let journal = Journal::new("10.0.0.1:8080".to_owned());
+    let peer = "10.0.0.2:8080";
+
+    assert_eq!(journal.accept(&entry(peer, 1)).unwrap(), EntryStatus::New);
+    assert_eq!(journal.accept(&entry(peer, 1)).unwrap(), EntryStatus::Known);
+    assert_eq!(
+        journal.accept(&entry(peer, 4)).unwrap(),
+        EntryStatus::Missing { after: 1 }
+    );
+
+    let replayed = journal
+        .select_new(vec![entry(peer, 1), entry(peer, 2), entry(peer, 3)])
+        .unwrap();
+    assert_eq!(replayed.iter().map(|e| e.seq).collect::<Vec<_>>(), [2, 3]);
+    assert_eq!(journal.applied(&peer.to_owned()).unwrap(), 3);
+}
+
+#[test]
+fn test_journal_requests_each_gap_once() {
+    let journal = Journal::new("10.0.0.1:8080".to_owned());
+    let peer = "10.0.0.2:8080";
+
+    assert_eq!(
+        journal.accept(&entry(peer, 3)).unwrap(),
+        EntryStatus::Missing { after: 0 }
+    );
+    // the entries are already asked for
+    assert_eq!(
+        journal.accept(&entry(peer, 4)).unwrap(),
+        EntryStatus::Requested
+    );
+
+    // the answer was made before entry 4
+    journal
+        .select_new(vec![entry(peer, 1), entry(peer, 2), entry(peer, 3)])
+        .unwrap();
+    assert_eq!(journal.answered(&peer.to_owned()).unwrap(), Some(3));
+    journal.select_new(vec![entry(peer, 4)]).unwrap();
+    assert_eq!(journal.answered(&peer.to_owned()).unwrap(), None);
+    assert_eq!(journal.accept(&entry(peer, 5)).unwrap(), EntryStatus::New);
+}
+
+#[test]
+fn test_journal_persistence() {
+    let dir = assert_fs::TempDir::new().expect("can't create temp dir");
+    let path = dir.path().join(".journal");
+    let address = "10.0.0.1:8080".to_owned();
+    let peer = "10.0.0.2:8080";
+
+    {
+        let journal = Journal::open(address.clone(), &path).unwrap();
+        journal.record(MessageContent::Remove(42)).unwrap();
+        journal.record(MessageContent::Remove(43)).unwrap();
+        journal.accept(&entry(peer, 1)).unwrap();
+    }
+
+    let journal = Journal::open(address.clone(), &path).unwrap();
+    assert_eq!(journal.entries_after(1).unwrap().len(), 1);
+    assert_eq!(journal.applied(&peer.to_owned()).unwrap(), 1);
+
+    // the network only knows the first change made here
+    let missed = journal
+        .catch_up(&[(address.clone(), 1), (peer.to_owned(), 5)].into())
+        .unwrap();
+    assert_eq!(missed.iter().map(|e| e.seq).collect::<Vec<_>>(), [2]);
+    assert_eq!(journal.applied(&peer.to_owned()).unwrap(), 5);
+    assert_eq!(journal.record(MessageContent::Remove(44)).unwrap().seq, 3);
+}
@@ -0,0 +1,37 @@
+extern crate wormhole;
+use std::time::Duration;
+
+use crate::wormhole::data::metrics::Metrics;
+
+#[test]
+fn test_metrics_render() {
+    let metrics = Metrics::default();
+    metrics.message_sent("Inode", 100);
+    metrics.message_sent("Inode", 50);
+    metrics.message_received("Gossip", 30);
+    metrics.pull_done(Duration::from_millis(3), true);
+    metrics.pull_done(Duration::from_secs(2), false);
+    metrics.redundancy_queued(&"10.0.0.1:8080".to_owned());
+    metrics.set_under_replicated(&"10.0.0.1:8080".to_owned(), 4);
+
+    let rendered = metrics.render();
+    for line in [
+        "wormhole_messages_sent_total{kind=\"Inode\"} 2",
+        "wormhole_messages_received_total{kind=\"Gossip\"} 1",
+        "wormhole_sent_bytes_total 150",
+        "wormhole_pull_duration_seconds_bucket{le=\"0.005\"} 1",
+        "wormhole_pull_duration_seconds_bucket{le=\"5\"} 2",
+        "wormhole_pull_duration_seconds_count 2",
+        "wormhole_pull_failures_total 1",
+        "wormhole_redundancy_queue_depth{pod=\"10.0.0.1:8080\"} 1",
+        "wormhole_under_replicated_files{pod=\"10.0.0.1:8080\"} 4",
+    ] {
+        assert!(
+            rendered.lines().any(|rendered| rendered == line),
+            "missing \"{line}\" in:\n{rendered}"
+        );
+    }
+
+    metrics.forget_pod(&"10.0.0.1:8080".to_owned());
+    assert!(!metrics.render().contains("10.0.0.1:8080"));
+}
@@ -1,2 +1,19 @@
 pub mod arbo_tests;
+pub mod block_cache_tests;
+pub mod cli_response_tests;
+pub mod conflict_tests;
+pub mod erasure_tests;
+pub mod eviction_tests;
+pub mod gossip_tests;
+pub mod ignore_tests;
+pub mod integrity_tests;
+pub mod journal_tests;
+pub mod metrics_tests;
+pub mod peer_ipc_tests;
+pub mod placement_tests;
+pub mod redundancy_policy_tests;
+pub mod registry_tests;
+pub mod reserved_tests;
+pub mod secure_tests;
+pub mod transfer_tests;
 pub mod whpath_test;
@@ -0,0 +1,21 @@
+extern crate wormhole;
+use std::time::Instant;
+
+use crate::wormhole::network::peer_ipc::{Reconnection, RECONNECT_MAX_DELAY, RECONNECT_MIN_DELAY};
+
+#[test]
+fn test_reconnection_backoff() {
+    let mut reconnection = Reconnection::default();
+    assert!(reconnection.next_try <= Instant::now());
+
+    reconnection.failed();
+    let first = reconnection.next_try - Instant::now();
+    assert!(first <= RECONNECT_MIN_DELAY);
+    reconnection.failed();
+    let second = reconnection.next_try - Instant::now();
+    assert!(second > first && second <= RECONNECT_MIN_DELAY * 2);
+
+    (0..20).for_each(|_| reconnection.failed());
+    assert_eq!(reconnection.attempts, 22);
+    assert!(reconnection.next_try - Instant::now() <= RECONNECT_MAX_DELAY);
+}
@@ -0,0 +1,95 @@
+extern crate wormhole;
+use std::collections::HashMap;
+
+use crate::wormhole::{
+    config::{types::PlacementConfig, GlobalConfig},
+    pods::network::{gossip::NodeStatus, placement::rank_candidates},
+};
+
+const MO: u64 = 1024 * 1024;
+
+fn status(free_mo: u64, load: u64, domain: Option<&str>) -> NodeStatus {
+    NodeStatus {
+        free_space: Some(free_mo * MO),
+        total_space: Some(10_000 * MO),
+        load,
+        failure_domain: domain.map(str::to_owned),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_rank_candidates() {
+    let candidates: Vec<String> = ["a", "b", "c", "d", "e"].map(str::to_owned).to_vec();
+    let statuses = HashMap::from([
+        ("a".to_owned(), status(100, 0, Some("rack1"))), // not enough space
+        ("b".to_owned(), status(5000, 0, Some("rack1"))),
+        ("c".to_owned(), status(5000, 3, Some("rack2"))),
+        ("d".to_owned(), status(8000, 3, Some("rack2"))),
+    ]);
+    let config = PlacementConfig::default();
+
+    // rack1 already hosts the file, "e" didn't share its status so its domain may be a new one
+    let ranked = rank_candidates(
+        &candidates,
+        &statuses,
+        &[Some("rack1".to_owned())],
+        MO,
+        &config,
+    );
+    assert_eq!(ranked, vec!["d", "e", "b", "c"]);
+
+    let config = PlacementConfig {
+        spread_failure_domains: false,
+        ..config
+    };
+    let ranked = rank_candidates(
+        &candidates,
+        &statuses,
+        &[Some("rack1".to_owned())],
+        MO,
+        &config,
+    );
+    assert_eq!(ranked, vec!["b", "d", "c", "e"]);
+}
+
+#[test]
+fn test_rank_candidates_quota() {
+    let candidates: Vec<String> = ["a", "b", "c"].map(str::to_owned).to_vec();
+    let with_quota = |used_mo: u64, quota_mo: u64| NodeStatus {
+        used_space: used_mo * MO,
+        quota: Some(quota_mo * MO),
+        ..status(5000, 0, None)
+    };
+    let statuses = HashMap::from([
+        ("a".to_owned(), with_quota(99, 100)), // quota reached
+        ("b".to_owned(), with_quota(10, 100)),
+        ("c".to_owned(), status(5000, 1, None)),
+    ]);
+
+    let ranked = rank_candidates(
+        &candidates,
+        &statuses,
+        &[],
+        2 * MO,
+        &PlacementConfig::default(),
+    );
+    assert_eq!(ranked, vec!["b", "c"]);
+    assert_eq!(statuses["b"].quota_left(), Some(90 * MO));
+    assert_eq!(statuses["c"].quota_left(), None);
+}
+
+#[test]
+fn test_placement_config_defaults() {
+    // written before the placement settings existed
+    let config: GlobalConfig = toml::from_str(
+        "[general]\npeers = []\nignore_paths = []\npods_names = []\n\n[redundancy]\nnumber = 2\n",
+    )
+    .unwrap();
+    assert_eq!(config.redundancy.placement.min_free_space, 512);
+    assert!(config.redundancy.placement.spread_failure_domains);
+
+    let placement: PlacementConfig = toml::from_str("spread_failure_domains = false").unwrap();
+    assert_eq!(placement.min_free_space, 512);
+    assert!(!placement.spread_failure_domains);
+}
@@ -0,0 +1,89 @@
+extern crate wormhole;
+
+use crate::wormhole::{
+    config::types::{IgnoreRules, RedundancyConfig, RedundancyPolicy},
+    pods::{
+        arbo::{Arbo, FsEntry, ROOT},
+        network::redundancy::{healable, target_redundancy, under_replicated, REDUNDANCY_XATTR},
+    },
+};
+
+#[test]
+fn test_redundancy_policies() {
+    let policy = |path: &str, number| RedundancyPolicy {
+        path: path.to_owned(),
+        number,
+    };
+    let targets = RedundancyConfig {
+        number: 2,
+        policies: vec![
+            policy("/src", 3),
+            policy("scratch/", 1),
+            policy("/src/cache", 0),
+        ],
+        ..Default::default()
+    }
+    .targets();
+
+    assert_eq!(targets.target("/README.md"), 2);
+    assert_eq!(targets.target("/src/main.rs"), 3);
+    assert_eq!(targets.target("/src/cache/a.bin"), 0);
+    assert_eq!(targets.target("/data/scratch/tmp"), 1);
+
+    let ignored = IgnoreRules::new(&[]);
+    let mut arbo = Arbo::new();
+    arbo.add_inode_from_parameters(
+        "src".to_owned(),
+        10,
+        ROOT,
+        FsEntry::Directory(vec![]),
+        0o755,
+    )
+    .unwrap();
+    arbo.add_inode_from_parameters("main.rs".to_owned(), 11, 10, FsEntry::File(vec![]), 0o644)
+        .unwrap();
+    arbo.add_inode_from_parameters("notes".to_owned(), 12, ROOT, FsEntry::File(vec![]), 0o644)
+        .unwrap();
+
+    assert_eq!(target_redundancy(&arbo, &targets, 11).unwrap(), 3);
+    assert_eq!(under_replicated(&arbo, &targets, &ignored), 2);
+
+    // the closest xattr wins over the policies, invalid values are skipped
+    arbo.set_inode_xattr(10, REDUNDANCY_XATTR.to_owned(), b"1".to_vec())
+        .unwrap();
+    assert_eq!(target_redundancy(&arbo, &targets, 11).unwrap(), 1);
+    arbo.set_inode_xattr(11, REDUNDANCY_XATTR.to_owned(), b"many".to_vec())
+        .unwrap();
+    assert_eq!(target_redundancy(&arbo, &targets, 11).unwrap(), 1);
+    arbo.set_inode_xattr(ROOT, REDUNDANCY_XATTR.to_owned(), b"0".to_vec())
+        .unwrap();
+    assert_eq!(under_replicated(&arbo, &targets, &ignored), 1);
+}
+
+#[test]
+fn test_healable() {
+    let targets = RedundancyConfig {
+        number: 2,
+        ..Default::default()
+    }
+    .targets();
+    let ignored = IgnoreRules::new(&[]);
+    let pod = "10.0.0.1:8080".to_owned();
+
+    let mut arbo = Arbo::new();
+    arbo.add_inode_from_parameters(
+        "alone".to_owned(),
+        11,
+        ROOT,
+        FsEntry::File(vec![pod.clone()]),
+        0o644,
+    )
+    .unwrap();
+    arbo.add_inode_from_parameters("lost".to_owned(), 12, ROOT, FsEntry::File(vec![]), 0o644)
+        .unwrap();
+
+    assert_eq!(under_replicated(&arbo, &targets, &ignored), 2);
+    // a lone pod has nowhere to copy its files, and lost files have nowhere to be copied from
+    assert_eq!(healable(&arbo, &targets, &ignored, 1), 0);
+    assert_eq!(healable(&arbo, &targets, &ignored, 2), 1);
+}
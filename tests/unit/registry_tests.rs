@@ -0,0 +1,42 @@
+extern crate wormhole;
+use crate::wormhole::config::registry::{PodRegistry, RegisteredPod};
+
+fn pod(name: &str, path: &str, autostart: bool) -> RegisteredPod {
+    RegisteredPod {
+        name: name.to_owned(),
+        path: path.to_owned(),
+        address: "10.0.0.1:8080".to_owned(),
+        autostart,
+    }
+}
+
+#[test]
+fn test_registry_persistence() {
+    let dir = assert_fs::TempDir::new().expect("can't create temp dir");
+    // the service directory doesn't exist before the first save
+    let dir = dir.path().join("wormhole");
+
+    {
+        let mut registry = PodRegistry::load(&dir);
+        assert!(registry.pods.is_empty());
+        registry.register(pod("a", "/mnt/a", true));
+        registry.register(pod("b", "/mnt/b", true));
+        // same path, the pod was recreated under another name
+        registry.register(pod("c", "/mnt/b", true));
+        registry.set_autostart("a", false);
+        registry.save().unwrap();
+    }
+
+    let mut registry = PodRegistry::load(&dir);
+    assert_eq!(
+        registry.pods,
+        vec![pod("a", "/mnt/a", false), pod("c", "/mnt/b", true)]
+    );
+    registry.rename("c", "d");
+    assert_eq!(
+        registry.find_by_path("/mnt/b"),
+        Some(&pod("d", "/mnt/b", true))
+    );
+    registry.unregister("a");
+    assert!(registry.get("a").is_none());
+}
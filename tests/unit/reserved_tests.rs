@@ -0,0 +1,166 @@
+extern crate wormhole;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+use crate::wormhole::{
+    config::{GlobalConfig, LocalConfig},
+    network::message::{RedundancyMessage, ToNetworkMessage},
+    pods::{
+        arbo::{
+            Arbo, FsEntry, Inode, InodeId, ARBO_FILE_FNAME, INVITES_FNAME, JOURNAL_FNAME, ROOT,
+            SHARDS_DIR, STATIC_KEY_FNAME,
+        },
+        disk_managers::{dummy_disk_manager::DummyDiskManager, DiskManager},
+        filesystem::{
+            fs_interface::{FsInterface, SimpleFileType},
+            make_inode::MakeInodeError,
+            rename::RenameError,
+        },
+        network::{journal::Journal, network_interface::NetworkInterface},
+        whpath::WhPath,
+    },
+};
+
+const SELF: &str = "10.0.0.1:8080";
+const FILE: InodeId = 11;
+const KEY: &[u8] = b"static key";
+
+struct TestPod {
+    fs: FsInterface,
+    _outbox: UnboundedReceiver<ToNetworkMessage>,
+    _redundancy: UnboundedReceiver<RedundancyMessage>,
+}
+
+/// Pod with a `file.bin` in its arbo and its static key on the disk, next to it
+fn pod() -> TestPod {
+    let mut arbo = Arbo::new();
+    arbo.add_inode(Inode::new(
+        "file.bin".to_owned(),
+        ROOT,
+        FILE,
+        FsEntry::File(vec![SELF.to_owned()]),
+        0o644,
+    ))
+    .unwrap();
+
+    let disk = DummyDiskManager::new(&WhPath::from("/tmp/wormhole")).unwrap();
+    disk.new_file(&WhPath::from("/file.bin"), 0o644).unwrap();
+    disk.new_file(&key_path(), 0o600).unwrap();
+    disk.write_file(&key_path(), KEY, 0).unwrap();
+
+    let mut local_config = LocalConfig::default();
+    local_config.general.address = SELF.to_owned();
+    let (network_tx, outbox) = unbounded_channel();
+    let (redundancy_tx, redundancy) = unbounded_channel();
+    let arbo = Arc::new(RwLock::new(arbo));
+    let network_interface = NetworkInterface::new(
+        arbo.clone(),
+        WhPath::from("/tmp/wormhole"),
+        network_tx,
+        redundancy_tx,
+        Arbo::first_ino() + 1,
+        Arc::new(Journal::new(SELF.to_owned())),
+        Arc::new(RwLock::new(Vec::new())),
+        Arc::new(RwLock::new(local_config)),
+        Arc::new(RwLock::new(GlobalConfig::default())),
+    );
+    TestPod {
+        fs: FsInterface::new(Arc::new(network_interface), Box::new(disk), arbo),
+        _outbox: outbox,
+        _redundancy: redundancy,
+    }
+}
+
+fn key_path() -> WhPath {
+    WhPath::from(&format!("/{STATIC_KEY_FNAME}"))
+}
+
+/// The static key is still on the disk, untouched
+fn assert_key_kept(fs: &FsInterface) {
+    let mut key = vec![0; KEY.len()];
+    assert_eq!(
+        fs.disk.read_file(&key_path(), 0, &mut key).unwrap(),
+        KEY.len()
+    );
+    assert_eq!(key, KEY);
+}
+
+#[test]
+fn test_create_reserved_name() {
+    let pod = pod();
+
+    assert!(matches!(
+        pod.fs.make_inode(
+            ROOT,
+            STATIC_KEY_FNAME.to_owned(),
+            0o644,
+            SimpleFileType::File
+        ),
+        Err(MakeInodeError::AlreadyExist)
+    ));
+    assert!(matches!(
+        pod.fs.make_inode(
+            ROOT,
+            STATIC_KEY_FNAME.to_owned(),
+            0o755,
+            SimpleFileType::Directory
+        ),
+        Err(MakeInodeError::AlreadyExist)
+    ));
+    assert_key_kept(&pod.fs);
+}
+
+#[test]
+fn test_rename_onto_reserved_name() {
+    let pod = pod();
+    let name = "file.bin".to_owned();
+    let reserved = STATIC_KEY_FNAME.to_owned();
+
+    assert!(matches!(
+        pod.fs.rename(ROOT, ROOT, &name, &reserved, true),
+        Err(RenameError::DestinationExists)
+    ));
+    assert!(matches!(
+        pod.fs.recept_rename(ROOT, ROOT, &name, &reserved, true),
+        Err(RenameError::DestinationExists)
+    ));
+    assert_eq!(pod.fs.arbo.read().n_get_inode(FILE).unwrap().name, name);
+    assert_key_kept(&pod.fs);
+}
+
+#[test]
+fn test_receive_reserved_name() {
+    let pod = pod();
+
+    let inode = Inode::new(
+        STATIC_KEY_FNAME.to_owned(),
+        ROOT,
+        FILE + 1,
+        FsEntry::File(vec![SELF.to_owned()]),
+        0o644,
+    );
+    assert!(matches!(
+        pod.fs.recept_inode(inode),
+        Err(MakeInodeError::AlreadyExist)
+    ));
+    assert!(pod.fs.arbo.read().n_get_inode(FILE + 1).is_err());
+    assert_key_kept(&pod.fs);
+}
+
+#[test]
+fn test_reserved_names() {
+    for name in [
+        ARBO_FILE_FNAME,
+        STATIC_KEY_FNAME,
+        INVITES_FNAME,
+        JOURNAL_FNAME,
+        SHARDS_DIR,
+    ] {
+        assert!(Arbo::is_reserved(name, ROOT), "{name}");
+        // only the files of the root are the pod's
+        assert!(!Arbo::is_reserved(name, FILE), "{name}");
+    }
+    assert!(!Arbo::is_reserved("file.bin", ROOT));
+}
@@ -0,0 +1,255 @@
+extern crate wormhole;
+use std::sync::Arc;
+
+use crate::wormhole::network::{
+    invite::{join_network, InviteError, InviteToken, Invites},
+    secure::{
+        generate_network_key, handshake_initiator, handshake_responder, parse_network_key,
+        Decryptor, Encryptor, LinkSecurity, NetworkKey, PublicKey, SecureError, StaticKey,
+    },
+};
+
+use tokio::net::TcpListener;
+
+type Keys = Result<(Encryptor, Decryptor), SecureError>;
+
+fn security(network_key: Option<NetworkKey>, trusted_peers: Vec<PublicKey>) -> LinkSecurity {
+    LinkSecurity {
+        network_key,
+        static_key: StaticKey::generate().unwrap(),
+        trusted_peers,
+    }
+}
+
+async fn secure_link(initiator: LinkSecurity, responder: LinkSecurity) -> (Keys, Keys) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let responder = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws_stream = tokio_tungstenite::accept_async(stream).await.unwrap();
+        handshake_responder(&mut ws_stream, &responder, &Invites::default())
+            .await
+            .map(|keys| keys.expect("a link, not a join"))
+    });
+    let (mut ws_stream, _) = tokio_tungstenite::connect_async(format!("ws://{address}"))
+        .await
+        .unwrap();
+    let initiated = handshake_initiator(&mut ws_stream, &initiator).await;
+    drop(ws_stream);
+    (initiated, responder.await.unwrap())
+}
+
+/// Joins the network of `issuer` with `token`, returns what the joining pod and the issuer got
+async fn join(
+    issuer: LinkSecurity,
+    invites: Arc<Invites>,
+    token: &InviteToken,
+) -> (Result<NetworkKey, InviteError>, Result<(), SecureError>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let issuer = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws_stream = tokio_tungstenite::accept_async(stream).await.unwrap();
+        handshake_responder(&mut ws_stream, &issuer, &invites)
+            .await
+            .map(|keys| assert!(keys.is_none(), "a join, not a link"))
+    });
+    let (mut ws_stream, _) = tokio_tungstenite::connect_async(format!("ws://{address}"))
+        .await
+        .unwrap();
+    let joined = join_network(&mut ws_stream, token, &StaticKey::generate().unwrap()).await;
+    drop(ws_stream);
+    (joined, issuer.await.unwrap())
+}
+
+#[test]
+fn test_parse_network_key() {
+    assert!(parse_network_key(&"ab".repeat(32)).is_ok());
+    assert!(parse_network_key(&"ab".repeat(31)).is_err());
+    assert!(parse_network_key(&"zz".repeat(32)).is_err());
+}
+
+#[tokio::test]
+async fn test_secure_link_with_same_key() {
+    let key = Some(parse_network_key(&generate_network_key().unwrap()).unwrap());
+    let (initiator, responder) = secure_link(security(key, vec![]), security(key, vec![])).await;
+    let (mut encryptor, _) = initiator.unwrap();
+    let (_, mut decryptor) = responder.unwrap();
+
+    // bigger than a noise message, to be split
+    let message: Vec<u8> = (0..200_000).map(|i| i as u8).collect();
+    let sealed = encryptor.seal(&message).unwrap();
+    assert_eq!(decryptor.open(&sealed).unwrap(), message);
+    assert_eq!(
+        decryptor.open(&encryptor.seal(&[]).unwrap()).unwrap(),
+        Vec::<u8>::new()
+    );
+}
+
+#[tokio::test]
+async fn test_secure_link_refuses_wrong_key() {
+    let (initiator, responder) = secure_link(
+        security(Some([7; 32]), vec![]),
+        security(Some([8; 32]), vec![]),
+    )
+    .await;
+    assert!(initiator.is_err());
+    assert!(responder.is_err());
+
+    let initiator = security(None, vec![[1; 32]]);
+    let (initiator, responder) = secure_link(initiator, security(Some([8; 32]), vec![])).await;
+    assert!(initiator.is_err());
+    assert!(responder.is_err());
+}
+
+#[tokio::test]
+async fn test_secure_link_refuses_without_trust() {
+    let (initiator, _) = secure_link(security(None, vec![]), security(Some([7; 32]), vec![])).await;
+    assert!(matches!(initiator, Err(SecureError::NoTrust)));
+    let (_, responder) = secure_link(security(Some([7; 32]), vec![]), security(None, vec![])).await;
+    assert!(matches!(responder, Err(SecureError::NoTrust)));
+}
+
+#[tokio::test]
+async fn test_secure_link_with_trusted_peers() {
+    let mut initiator = security(None, vec![]);
+    let mut responder = security(None, vec![]);
+    initiator.trusted_peers = vec![responder.static_key.public];
+    responder.trusted_peers = vec![initiator.static_key.public];
+    let stranger = security(None, vec![initiator.static_key.public]);
+
+    let (initiated, responded) = secure_link(initiator.clone(), responder).await;
+    assert!(initiated.is_ok());
+    assert!(responded.is_ok());
+
+    // the initiator doesn't trust the responder, which isn't linked either
+    let (initiated, responded) = secure_link(initiator, stranger).await;
+    assert!(matches!(initiated, Err(SecureError::UntrustedPeer { .. })));
+    assert!(responded.is_err());
+}
+
+#[test]
+fn test_static_key_persistence() {
+    let dir = assert_fs::TempDir::new().expect("can't create temp dir");
+    let path = dir.path().join(".static_key");
+
+    let key = StaticKey::load_or_create(&path).unwrap();
+    assert_eq!(StaticKey::load_or_create(&path).unwrap().public, key.public);
+    assert_ne!(StaticKey::generate().unwrap().public, key.public);
+}
+
+#[tokio::test]
+async fn test_join_with_invite() {
+    let key = [7; 32];
+    let issuer = security(Some(key), vec![]);
+    let invites = Arc::new(Invites::default());
+    let token = invites.issue(issuer.static_key.public).unwrap();
+    assert_eq!(InviteToken::parse(&token.to_string()).unwrap(), token);
+    assert_eq!(invites.list().unwrap(), vec![token.id()]);
+
+    let (joined, served) = join(issuer.clone(), invites.clone(), &token).await;
+    assert_eq!(joined.unwrap(), key);
+    assert!(served.is_ok());
+    assert!(invites.list().unwrap().is_empty());
+
+    // an invitation is only used once
+    let (joined, served) = join(issuer, invites, &token).await;
+    assert!(matches!(
+        joined,
+        Err(InviteError::Secure {
+            source: SecureError::InviteRefused
+        })
+    ));
+    assert!(matches!(served, Err(SecureError::InviteRefused)));
+}
+
+#[tokio::test]
+async fn test_join_with_revoked_invite() {
+    let issuer = security(Some([7; 32]), vec![]);
+    let invites = Arc::new(Invites::default());
+    let token = invites.issue(issuer.static_key.public).unwrap();
+    let other = invites.issue(issuer.static_key.public).unwrap();
+
+    invites.revoke(&token.id()).unwrap();
+    assert!(invites.revoke(&token.id()).is_err());
+    assert_eq!(invites.list().unwrap(), vec![other.id()]);
+    let (joined, _) = join(issuer, invites, &token).await;
+    assert!(joined.is_err());
+}
+
+#[tokio::test]
+async fn test_join_checks_the_issuer() {
+    let issuer = security(Some([7; 32]), vec![]);
+    let invites = Arc::new(Invites::default());
+    // the token names another pod, the secret isn't sent
+    let mut token =
+        InviteToken::parse(&invites.issue(issuer.static_key.public).unwrap().to_string()).unwrap();
+    token.issuer = StaticKey::generate().unwrap().public;
+
+    let (joined, _) = join(issuer, invites.clone(), &token).await;
+    assert!(matches!(
+        joined,
+        Err(InviteError::Secure {
+            source: SecureError::UntrustedPeer { .. }
+        })
+    ));
+    assert_eq!(invites.list().unwrap().len(), 1);
+}
+
+#[test]
+fn test_invites_persistence() {
+    let dir = assert_fs::TempDir::new().expect("can't create temp dir");
+    let path = dir.path().join(".invites");
+
+    let token = Invites::open(&path).unwrap().issue([1; 32]).unwrap();
+    let invites = Invites::open(&path).unwrap();
+    assert_eq!(invites.list().unwrap(), vec![token.id()]);
+    assert!(InviteToken::parse(&token.to_string()[..100]).is_err());
+}
+
+#[test]
+fn test_revoke_invite_by_id() {
+    let path = std::env::temp_dir().join(format!("wormhole_invites_{}", std::process::id()));
+    let shared = "0123abcd";
+    std::fs::write(
+        &path,
+        format!(
+            "{shared}{}\n{shared}{}\n{}\n",
+            "0".repeat(56),
+            "1".repeat(56),
+            "f".repeat(64)
+        ),
+    )
+    .unwrap();
+    let invites = Invites::open(&path).unwrap();
+    let first = format!("{shared}{}", "0".repeat(56));
+
+    // the invitations sharing their short id are listed whole
+    assert_eq!(
+        invites.list().unwrap(),
+        vec![
+            first.clone(),
+            format!("{shared}{}", "1".repeat(56)),
+            "ffffffff".to_owned()
+        ]
+    );
+    assert!(matches!(
+        invites.revoke("fff"),
+        Err(InviteError::ShortInviteId { .. })
+    ));
+    assert!(matches!(
+        invites.revoke(shared),
+        Err(InviteError::AmbiguousInvite { .. })
+    ));
+    assert!(matches!(
+        invites.revoke("eeeeeeee"),
+        Err(InviteError::UnknownInvite { .. })
+    ));
+    invites.revoke(&first).unwrap();
+    invites.revoke(shared).unwrap();
+    assert_eq!(invites.list().unwrap(), vec!["ffffffff".to_owned()]);
+
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,310 @@
+extern crate wormhole;
+
+use std::{sync::Arc, time::Duration};
+
+use parking_lot::RwLock;
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    task::JoinHandle,
+};
+
+use crate::wormhole::{
+    config::{GlobalConfig, LocalConfig},
+    network::message::{
+        FileChunk, FromNetworkMessage, MessageContent, RedundancyMessage, ToNetworkMessage,
+    },
+    pods::{
+        arbo::{Arbo, FsEntry, Inode, InodeId, ROOT},
+        disk_managers::{dummy_disk_manager::DummyDiskManager, DiskManager},
+        filesystem::fs_interface::FsInterface,
+        network::{
+            callbacks::Callback,
+            journal::Journal,
+            network_interface::NetworkInterface,
+            transfer::{TransferError, TransferKind, CHUNK_SIZE},
+        },
+        whpath::WhPath,
+    },
+};
+
+const SENDER: &str = "10.0.0.1:8080";
+const RECEIVER: &str = "10.0.0.2:8080";
+/// Socket of the link the receiver opened to the sender
+const RECEIVER_SOCKET: &str = "10.0.0.2:51234";
+const FILE: InodeId = 11;
+
+struct TestPod {
+    fs: Arc<FsInterface>,
+    outbox: UnboundedReceiver<ToNetworkMessage>,
+    _redundancy: UnboundedReceiver<RedundancyMessage>,
+}
+
+fn pod(address: &str, content: Option<&[u8]>, quota: Option<u64>, size: u64) -> TestPod {
+    let mut arbo = Arbo::new();
+    let mut inode = Inode::new(
+        "file.bin".to_owned(),
+        ROOT,
+        FILE,
+        FsEntry::File(vec![SENDER.to_owned()]),
+        0o644,
+    );
+    inode.meta.size = size;
+    arbo.add_inode(inode).unwrap();
+    let path = arbo.n_get_path_from_inode_id(FILE).unwrap();
+
+    let disk = DummyDiskManager::new(&WhPath::from("/tmp/wormhole")).unwrap();
+    if let Some(content) = content {
+        disk.new_file(&path, 0o644).unwrap();
+        disk.write_file(&path, content, 0).unwrap();
+    }
+
+    let mut local_config = LocalConfig::default();
+    local_config.general.address = address.to_owned();
+    local_config.storage.quota = quota;
+
+    let (network_tx, outbox) = unbounded_channel();
+    let (redundancy_tx, redundancy) = unbounded_channel();
+    let arbo = Arc::new(RwLock::new(arbo));
+    let network_interface = NetworkInterface::new(
+        arbo.clone(),
+        WhPath::from("/tmp/wormhole"),
+        network_tx,
+        redundancy_tx,
+        Arbo::first_ino() + 1,
+        Arc::new(Journal::new(address.to_owned())),
+        Arc::new(RwLock::new(Vec::new())),
+        Arc::new(RwLock::new(local_config)),
+        Arc::new(RwLock::new(GlobalConfig::default())),
+    );
+    TestPod {
+        fs: Arc::new(FsInterface::new(
+            Arc::new(network_interface),
+            Box::new(disk),
+            arbo,
+        )),
+        outbox,
+        _redundancy: redundancy,
+    }
+}
+
+/// Streams [FILE] from `sender` to `receiver`, carrying the chunks and the acknowledgments
+/// between them as a link opened by the receiver would
+async fn relay(sender: &mut TestPod, receiver: &mut TestPod) -> Result<(), TransferError> {
+    // the sender knows the link by its socket until the receiver registers
+    sender
+        .fs
+        .network_interface
+        .edit_peer_ip(RECEIVER_SOCKET.to_owned(), RECEIVER.to_owned());
+    let (airport_tx, airport_rx): (UnboundedSender<FromNetworkMessage>, _) = unbounded_channel();
+    tokio::spawn(NetworkInterface::network_airport(
+        airport_rx,
+        sender.fs.clone(),
+    ));
+
+    let fs = sender.fs.clone();
+    let mut stream: JoinHandle<Result<(), TransferError>> = tokio::spawn(async move {
+        fs.stream_file(FILE, RECEIVER.to_owned(), 0, TransferKind::Redundancy)
+            .await
+    });
+
+    tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            tokio::select! {
+                status = &mut stream => return status.unwrap(),
+                message = sender.outbox.recv() => {
+                    let Some(ToNetworkMessage::SpecificMessage(
+                        (MessageContent::RedundancyFile(chunk), _),
+                        to,
+                    )) = message
+                    else {
+                        panic!("unexpected message from the sender: {message:?}");
+                    };
+                    assert_eq!(to, vec![RECEIVER.to_owned()]);
+                    let _ = receiver
+                        .fs
+                        .recept_chunk(chunk, SENDER.to_owned(), TransferKind::Redundancy);
+                }
+            }
+            while let Ok(message) = receiver.outbox.try_recv() {
+                let ToNetworkMessage::SpecificMessage(
+                    (content @ MessageContent::ChunkAck(..), _),
+                    to,
+                ) = message
+                else {
+                    panic!("unexpected message from the receiver: {message:?}");
+                };
+                assert_eq!(to, vec![SENDER.to_owned()]);
+                airport_tx
+                    .send(FromNetworkMessage {
+                        origin: RECEIVER_SOCKET.to_owned(),
+                        content,
+                    })
+                    .unwrap();
+            }
+        }
+    })
+    .await
+    .expect("the transfer stalled")
+}
+
+#[tokio::test]
+async fn test_stream_file_through_inbound_link() {
+    let data: Vec<u8> = (0..CHUNK_SIZE * 2 + 10).map(|i| i as u8).collect();
+    let size = data.len() as u64;
+    let mut sender = pod(SENDER, Some(&data), None, size);
+    let mut receiver = pod(RECEIVER, None, None, size);
+
+    relay(&mut sender, &mut receiver).await.unwrap();
+
+    let path = WhPath::from("/file.bin");
+    let mut received = vec![0; data.len()];
+    assert_eq!(
+        receiver.fs.disk.read_file(&path, 0, &mut received).unwrap(),
+        data.len()
+    );
+    assert_eq!(received, data);
+    assert_eq!(
+        receiver
+            .fs
+            .network_interface
+            .transfers
+            .received(FILE)
+            .unwrap(),
+        0
+    );
+    match &receiver.fs.arbo.read().n_get_inode(FILE).unwrap().entry {
+        FsEntry::File(hosts) => assert!(hosts.contains(&RECEIVER.to_owned())),
+        _ => panic!("not a file"),
+    };
+}
+
+#[tokio::test]
+async fn test_stream_file_refused() {
+    let data = vec![42; 10];
+    let mut sender = pod(SENDER, Some(&data), None, 10);
+    // a pod without space left refuses the first chunk
+    let mut receiver = pod(RECEIVER, None, Some(0), 10);
+
+    assert!(matches!(
+        relay(&mut sender, &mut receiver).await,
+        Err(TransferError::Refused)
+    ));
+}
+
+#[test]
+fn test_registered_address() {
+    let pod = pod(SENDER, None, None, 0);
+    let network_interface = &pod.fs.network_interface;
+    let socket = RECEIVER_SOCKET.to_owned();
+
+    assert_eq!(
+        network_interface.registered_address(&socket).unwrap(),
+        socket
+    );
+    network_interface.edit_peer_ip(socket.clone(), RECEIVER.to_owned());
+    assert_eq!(
+        network_interface.registered_address(&socket).unwrap(),
+        RECEIVER
+    );
+    // a new link from the same pod replaces the old one
+    network_interface.edit_peer_ip("10.0.0.2:60000".to_owned(), RECEIVER.to_owned());
+    assert_eq!(
+        network_interface.registered_address(&socket).unwrap(),
+        socket
+    );
+}
+
+#[test]
+fn test_concurrent_pulls_of_a_file() {
+    let data = vec![42; 10];
+    let mut receiver = pod(RECEIVER, None, None, data.len() as u64);
+    let network_interface = receiver.fs.network_interface.clone();
+
+    let readers: Vec<_> = (0..2)
+        .map(|_| {
+            let network_interface = network_interface.clone();
+            std::thread::spawn(move || {
+                let callback = network_interface.pull_file_sync(FILE).unwrap().unwrap();
+                network_interface.callbacks.n_wait_for(callback).unwrap()
+            })
+        })
+        .collect();
+
+    // only the first reader asks for the file, the second one waits for the same pull
+    let Some(ToNetworkMessage::SpecificMessage(
+        (MessageContent::RequestFile(FILE, _, 0), Some(status)),
+        to,
+    )) = receiver.outbox.blocking_recv()
+    else {
+        panic!("the file wasn't requested");
+    };
+    assert_eq!(to, vec![SENDER.to_owned()]);
+    status.send(Ok(())).unwrap();
+    while network_interface
+        .callbacks
+        .callbacks
+        .read()
+        .get(&Callback::Pull(FILE))
+        .map_or(0, |callback| callback.receiver_count())
+        < 2
+    {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert!(receiver.outbox.try_recv().is_err());
+
+    receiver
+        .fs
+        .recept_chunk(
+            FileChunk {
+                ino: FILE,
+                offset: 0,
+                total_size: data.len() as u64,
+                data: data.clone(),
+            },
+            SENDER.to_owned(),
+            TransferKind::Pull,
+        )
+        .unwrap();
+    for reader in readers {
+        assert!(reader.join().unwrap());
+    }
+    let mut received = vec![0; data.len()];
+    receiver
+        .fs
+        .disk
+        .read_file(&WhPath::from("/file.bin"), 0, &mut received)
+        .unwrap();
+    assert_eq!(received, data);
+}
+
+#[tokio::test]
+async fn test_stream_file_twice_to_a_pod() {
+    let data = vec![42; 10];
+    let sender = pod(SENDER, Some(&data), None, 10);
+
+    let fs = sender.fs.clone();
+    // never acknowledged, so it keeps running
+    let first = tokio::spawn(async move {
+        fs.stream_file(FILE, RECEIVER.to_owned(), 0, TransferKind::Redundancy)
+            .await
+    });
+    while sender.fs.network_interface.transfers.load().unwrap() == 0 {
+        tokio::task::yield_now().await;
+    }
+
+    assert!(matches!(
+        sender
+            .fs
+            .stream_file(FILE, RECEIVER.to_owned(), 0, TransferKind::Pull)
+            .await,
+        Err(TransferError::AlreadyRunning)
+    ));
+    // the running stream is still the one acknowledged
+    sender
+        .fs
+        .network_interface
+        .transfers
+        .acknowledge(&RECEIVER.to_owned(), FILE, 10, true)
+        .unwrap();
+    first.await.unwrap().unwrap();
+}